@@ -255,6 +255,30 @@ export function CredentialCard({
                 {credential.refreshFailureCount}
               </span>
             </div>
+            <div>
+              <span className="text-muted-foreground">近期冷却：</span>
+              <span className={credential.cooldownSecsInWindow > 0 ? 'text-yellow-600 font-medium' : ''}>
+                {credential.cooldownSecsInWindow} 秒
+              </span>
+            </div>
+            {credential.cooldownReason && (
+              <div>
+                <span className="text-muted-foreground">冷却中：</span>
+                <span className="text-yellow-600 font-medium">
+                  {credential.cooldownReason}
+                  {credential.cooldownRemainingSecs !== undefined && `，${credential.cooldownRemainingSecs} 秒后恢复`}
+                </span>
+              </div>
+            )}
+            {credential.quotaUsage?.map((quota) => (
+              <div key={quota.windowSecs}>
+                <span className="text-muted-foreground">配额（{quota.windowSecs} 秒）：</span>
+                <span className={quota.remaining === 0 ? 'text-red-500 font-medium' : 'font-medium'}>
+                  剩余 {quota.remaining}/{quota.maxRequests}
+                  {quota.resetsInSecs !== undefined && `，${quota.resetsInSecs} 秒后恢复`}
+                </span>
+              </div>
+            ))}
             <div>
               <span className="text-muted-foreground">订阅等级：</span>
               <span className="font-medium">
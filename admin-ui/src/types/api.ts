@@ -27,6 +27,19 @@ export interface CredentialStatusItem {
   refreshFailureCount: number
   disabledReason?: string
   endpoint: string
+  cooldownSecsInWindow: number
+  cooldownReason?: string
+  cooldownRemainingSecs?: number
+  quotaUsage?: QuotaUsage[]
+}
+
+// 请求配额窗口使用情况
+export interface QuotaUsage {
+  windowSecs: number
+  maxRequests: number
+  used: number
+  remaining: number
+  resetsInSecs?: number
 }
 
 // 余额响应
@@ -38,6 +51,8 @@ export interface BalanceResponse {
   remaining: number
   usagePercentage: number
   nextResetAt: number | null
+  quotaRemaining: number | null
+  quotaResetAt: number | null
 }
 
 // 成功响应
@@ -1,5 +1,6 @@
 //! Admin API 错误类型定义
 
+use std::collections::BTreeMap;
 use std::fmt;
 
 use axum::http::StatusCode;
@@ -20,6 +21,12 @@ pub enum AdminServiceError {
 
     /// 凭据无效（验证失败）
     InvalidCredential(String),
+
+    /// 请求参数校验失败（字段名 → 错误信息，包含所有不合法的字段）
+    ValidationFailed(BTreeMap<String, String>),
+
+    /// 与进行中的操作冲突（如同一凭据正在刷新）
+    Conflict(String),
 }
 
 impl fmt::Display for AdminServiceError {
@@ -31,6 +38,14 @@ impl fmt::Display for AdminServiceError {
             AdminServiceError::UpstreamError(msg) => write!(f, "上游服务错误: {}", msg),
             AdminServiceError::InternalError(msg) => write!(f, "内部错误: {}", msg),
             AdminServiceError::InvalidCredential(msg) => write!(f, "凭据无效: {}", msg),
+            AdminServiceError::Conflict(msg) => write!(f, "操作冲突: {}", msg),
+            AdminServiceError::ValidationFailed(fields) => {
+                let details: Vec<String> = fields
+                    .iter()
+                    .map(|(field, msg)| format!("{}: {}", field, msg))
+                    .collect();
+                write!(f, "请求参数校验失败: {}", details.join("; "))
+            }
         }
     }
 }
@@ -45,20 +60,27 @@ impl AdminServiceError {
             AdminServiceError::UpstreamError(_) => StatusCode::BAD_GATEWAY,
             AdminServiceError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AdminServiceError::InvalidCredential(_) => StatusCode::BAD_REQUEST,
+            AdminServiceError::ValidationFailed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AdminServiceError::Conflict(_) => StatusCode::CONFLICT,
         }
     }
 
     /// 转换为 API 错误响应
     pub fn into_response(self) -> AdminErrorResponse {
+        if let AdminServiceError::ValidationFailed(fields) = self {
+            let message = format!("请求参数校验失败（{} 项）", fields.len());
+            return AdminErrorResponse::validation_error(message, fields);
+        }
         match &self {
             AdminServiceError::NotFound { .. } => AdminErrorResponse::not_found(self.to_string()),
             AdminServiceError::UpstreamError(_) => AdminErrorResponse::api_error(self.to_string()),
             AdminServiceError::InternalError(_) => {
                 AdminErrorResponse::internal_error(self.to_string())
             }
-            AdminServiceError::InvalidCredential(_) => {
+            AdminServiceError::InvalidCredential(_) | AdminServiceError::Conflict(_) => {
                 AdminErrorResponse::invalid_request(self.to_string())
             }
+            AdminServiceError::ValidationFailed(_) => unreachable!("已在上方处理"),
         }
     }
 }
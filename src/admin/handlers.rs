@@ -1,26 +1,171 @@
 //! Admin API HTTP 处理器
 
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+
 use axum::{
     Json,
-    extract::{Path, State},
-    response::IntoResponse,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{
+        IntoResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
 };
+use futures::{Stream, stream};
+use tokio::sync::broadcast::error::RecvError;
 
 use super::{
     middleware::AdminState,
+    service::AdminService,
     types::{
-        AddCredentialRequest, ImportCredentialsRequest, SetDisabledRequest,
-        SetLoadBalancingModeRequest, SetPriorityRequest, SuccessResponse,
+        AddAdminKeyRequest, AddCredentialRequest, AdminErrorResponse, AdminKeysResponse,
+        BatchCredentialAction, BatchCredentialRequest, BatchCredentialResponse, BatchItemResult,
+        BulkOperationResponse, BulkSetDisabledRequest, CredentialListQuery,
+        ImportCredentialsRequest, PoolExport, PoolExportQuery, PreviewToolCompressionRequest,
+        SetCooldownDurationRequest, SetDisabledRequest, SetGlobalCooldownRequest,
+        SetLoadBalancingModeRequest, SetPriorityRequest, SuccessResponse, TagsResponse,
+        UpdateTagsRequest,
     },
 };
 
-/// GET /api/admin/credentials
-/// 获取所有凭据状态
-pub async fn get_all_credentials(State(state): State<AdminState>) -> impl IntoResponse {
-    let response = state.service.get_all_credentials();
+/// GET /api/admin/credentials?tags=a,b
+/// 获取凭据状态（可按标签过滤）
+pub async fn get_all_credentials(
+    State(state): State<AdminState>,
+    Query(query): Query<CredentialListQuery>,
+) -> impl IntoResponse {
+    let response = state.service.get_all_credentials(&query.tag_list());
     Json(response)
 }
 
+/// POST /api/admin/credentials/disabled
+/// 按标签批量启用/禁用凭据
+pub async fn bulk_set_credentials_disabled(
+    State(state): State<AdminState>,
+    Json(payload): Json<BulkSetDisabledRequest>,
+) -> impl IntoResponse {
+    match state.service.bulk_set_disabled(&payload.tags, payload.disabled) {
+        Ok(affected) => {
+            let action = if payload.disabled { "禁用" } else { "启用" };
+            Json(BulkOperationResponse {
+                success: true,
+                message: format!("已{} {} 个凭据", action, affected.len()),
+                affected,
+            })
+            .into_response()
+        }
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// POST /api/admin/credentials/batch
+/// 按 ID 批量禁用/启用/重置/删除凭据（逐个执行，单个失败不影响其余）
+pub async fn batch_credentials(
+    State(state): State<AdminState>,
+    Json(payload): Json<BatchCredentialRequest>,
+) -> impl IntoResponse {
+    let mut results = BTreeMap::new();
+    for &id in &payload.ids {
+        let result = match payload.action {
+            BatchCredentialAction::Disable => state.service.set_disabled(id, true),
+            BatchCredentialAction::Enable => state.service.set_disabled(id, false),
+            BatchCredentialAction::Reset => state.service.reset_and_enable(id),
+            BatchCredentialAction::Delete => state.service.delete_credential(id),
+        };
+        results.insert(
+            id,
+            BatchItemResult {
+                success: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            },
+        );
+    }
+
+    let succeeded = results.values().filter(|r| r.success).count();
+    Json(BatchCredentialResponse {
+        success: succeeded == results.len(),
+        message: format!(
+            "批量操作完成：成功 {} 个，失败 {} 个",
+            succeeded,
+            results.len() - succeeded
+        ),
+        results,
+    })
+}
+
+/// POST /api/admin/credentials/:id/tags
+/// 为凭据添加标签
+pub async fn add_credential_tags(
+    State(state): State<AdminState>,
+    Path(id): Path<u64>,
+    Json(payload): Json<UpdateTagsRequest>,
+) -> impl IntoResponse {
+    update_tags_response(state.service.update_tags(id, &payload.tags, &[]), id)
+}
+
+/// DELETE /api/admin/credentials/:id/tags
+/// 移除凭据标签
+pub async fn remove_credential_tags(
+    State(state): State<AdminState>,
+    Path(id): Path<u64>,
+    Json(payload): Json<UpdateTagsRequest>,
+) -> impl IntoResponse {
+    update_tags_response(state.service.update_tags(id, &[], &payload.tags), id)
+}
+
+fn update_tags_response(
+    result: Result<Vec<String>, super::error::AdminServiceError>,
+    id: u64,
+) -> axum::response::Response {
+    match result {
+        Ok(tags) => Json(TagsResponse {
+            success: true,
+            id,
+            tags,
+        })
+        .into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// GET /api/admin/credentials/:id/fingerprint
+/// 获取凭据实际使用的指纹
+pub async fn get_credential_fingerprint(
+    State(state): State<AdminState>,
+    Path(id): Path<u64>,
+) -> impl IntoResponse {
+    match state.service.get_fingerprint(id) {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// PUT /api/admin/credentials/:id/fingerprint
+/// 固定凭据指纹（请求体为导出的指纹 JSON）
+pub async fn pin_credential_fingerprint(
+    State(state): State<AdminState>,
+    Path(id): Path<u64>,
+    body: String,
+) -> impl IntoResponse {
+    match state.service.pin_fingerprint(id, &body) {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// DELETE /api/admin/credentials/:id/fingerprint
+/// 清除凭据固定的指纹
+pub async fn unpin_credential_fingerprint(
+    State(state): State<AdminState>,
+    Path(id): Path<u64>,
+) -> impl IntoResponse {
+    match state.service.unpin_fingerprint(id) {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
 /// POST /api/admin/credentials/:id/disabled
 /// 设置凭据禁用状态
 pub async fn set_credential_disabled(
@@ -113,15 +258,25 @@ pub async fn force_refresh_token(
     Path(id): Path<u64>,
 ) -> impl IntoResponse {
     match state.service.force_refresh_token(id).await {
-        Ok(_) => Json(SuccessResponse::new(format!(
-            "凭据 #{} Token 已强制刷新",
-            id
-        )))
-        .into_response(),
+        Ok(response) => Json(response).into_response(),
         Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
     }
 }
 
+/// POST /api/admin/credentials/refresh-all
+/// 在后台强制刷新所有未禁用 OAuth 凭据的 Token（结果通过实时事件推送）
+pub async fn refresh_all_tokens(State(state): State<AdminState>) -> impl IntoResponse {
+    let affected = AdminService::refresh_all_tokens(state.service.clone());
+    (
+        StatusCode::ACCEPTED,
+        Json(BulkOperationResponse {
+            success: true,
+            message: format!("已开始刷新 {} 个凭据的 Token", affected.len()),
+            affected,
+        }),
+    )
+}
+
 /// POST /api/admin/credentials/reset-stats
 /// 重置所有凭据的 success_count
 pub async fn reset_all_success_count(State(state): State<AdminState>) -> impl IntoResponse {
@@ -151,6 +306,170 @@ pub async fn reset_success_count(
     }
 }
 
+/// GET /api/admin/stats/latency
+/// 获取按凭据/按模型汇总的上游延迟（p50/p95）
+pub async fn get_latency_stats(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_latency_stats())
+}
+
+/// GET /api/admin/cooldowns
+/// 获取当前处于冷却中的凭据及原因
+pub async fn get_cooldowns(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_cooldowns())
+}
+
+/// DELETE /api/admin/cooldowns/:id
+/// 解除凭据冷却
+pub async fn clear_cooldown(
+    State(state): State<AdminState>,
+    Path(id): Path<u64>,
+) -> impl IntoResponse {
+    match state.service.clear_cooldown(id) {
+        Ok(true) => Json(SuccessResponse::new(format!("凭据 #{} 冷却已解除", id))).into_response(),
+        Ok(false) => {
+            Json(SuccessResponse::new(format!("凭据 #{} 未处于冷却中", id))).into_response()
+        }
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// POST /api/admin/cooldowns/global
+/// 设置全局冷却（所有凭据暂停使用）
+pub async fn set_global_cooldown(
+    State(state): State<AdminState>,
+    Json(payload): Json<SetGlobalCooldownRequest>,
+) -> impl IntoResponse {
+    match state.service.set_global_cooldown(payload) {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// DELETE /api/admin/cooldowns/global
+/// 解除全局冷却
+pub async fn clear_global_cooldown(State(state): State<AdminState>) -> impl IntoResponse {
+    if state.service.clear_global_cooldown() {
+        Json(SuccessResponse::new("全局冷却已解除"))
+    } else {
+        Json(SuccessResponse::new("全局冷却未生效"))
+    }
+}
+
+/// GET /api/admin/stats/events
+/// 获取上游事件类型统计（已知类型与未知事件计数）
+pub async fn get_event_stats(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_event_stats())
+}
+
+/// GET /api/admin/keys
+/// 列出 Admin API 密钥名称（不返回密钥本身）
+pub async fn get_admin_keys(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(AdminKeysResponse {
+        keys: state.key_names(),
+    })
+}
+
+/// POST /api/admin/keys
+/// 添加具名 Admin API 密钥
+pub async fn add_admin_key(
+    State(state): State<AdminState>,
+    Json(payload): Json<AddAdminKeyRequest>,
+) -> impl IntoResponse {
+    match state.add_key(&payload.name, &payload.key) {
+        Ok(()) => Json(SuccessResponse::new(format!(
+            "Admin API 密钥 {} 已添加",
+            payload.name
+        )))
+        .into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(AdminErrorResponse::invalid_request(e.to_string())),
+        )
+            .into_response(),
+    }
+}
+
+/// DELETE /api/admin/keys/:name
+/// 撤销具名 Admin API 密钥
+pub async fn revoke_admin_key(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    match state.revoke_key(&name) {
+        Ok(true) => Json(SuccessResponse::new(format!(
+            "Admin API 密钥 {} 已撤销",
+            name
+        )))
+        .into_response(),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(AdminErrorResponse::not_found(format!(
+                "Admin API 密钥不存在: {}",
+                name
+            ))),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::CONFLICT,
+            Json(AdminErrorResponse::invalid_request(e.to_string())),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /api/admin/events
+/// 以 SSE 推送凭据与冷却的实时事件
+///
+/// 每条事件的 `event` 字段为事件类型，`data` 为 JSON（`type` 同事件类型，ID 字段为 `id`）：
+/// - `credentialDisabled` / `credentialEnabled` / `credentialReset`：`{"type", "id"}`
+/// - `cooldownEntered`：`{"type", "id", "reason", "durationSecs"}`
+/// - `cooldownCleared`：`{"type", "id"}`
+/// - `tokenRefreshed`：`{"type", "id"}`
+/// - `tokenRefreshFailed`：`{"type", "id", "error"}`
+///
+/// 订阅方处理过慢时会丢弃最旧的事件，并推送一条 `lagged` 事件（`data` 为丢弃数量）。
+pub async fn stream_admin_events(
+    State(state): State<AdminState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = state.service.subscribe_events();
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        let event = match receiver.recv().await {
+            Ok(event) => {
+                let data = serde_json::to_value(&event).unwrap_or_default();
+                let name = data["type"].as_str().unwrap_or_default().to_string();
+                Event::default().event(name).data(data.to_string())
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                Event::default().event("lagged").data(skipped.to_string())
+            }
+            Err(RecvError::Closed) => return None,
+        };
+        Some((Ok(event), receiver))
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+/// GET /api/admin/health
+/// 获取凭据池健康摘要
+pub async fn get_health_summary(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_health_summary())
+}
+
+/// GET /api/admin/config/tools-sizes
+/// 获取最近一次请求的各工具体积（按大小降序，尚无记录时为 null）
+pub async fn get_tool_sizes(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_tool_sizes())
+}
+
+/// POST /api/admin/config/tools-compression/preview
+/// 预估给定工具定义的压缩效果（dry-run）
+pub async fn preview_tool_compression(
+    State(state): State<AdminState>,
+    Json(payload): Json<PreviewToolCompressionRequest>,
+) -> impl IntoResponse {
+    Json(state.service.preview_tool_compression(payload))
+}
+
 /// GET /api/admin/config/load-balancing
 /// 获取负载均衡模式
 pub async fn get_load_balancing_mode(State(state): State<AdminState>) -> impl IntoResponse {
@@ -170,6 +489,24 @@ pub async fn set_load_balancing_mode(
     }
 }
 
+/// GET /api/admin/config/cooldown-durations
+/// 获取各冷却原因的时长配置
+pub async fn get_cooldown_durations(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.get_cooldown_durations())
+}
+
+/// PUT /api/admin/config/cooldown-durations
+/// 覆盖或清除某个冷却原因的基础时长
+pub async fn set_cooldown_duration(
+    State(state): State<AdminState>,
+    Json(payload): Json<SetCooldownDurationRequest>,
+) -> impl IntoResponse {
+    match state.service.set_cooldown_duration(payload) {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
 /// GET /api/admin/credentials/export
 /// 导出所有凭据（含明文 token）
 pub async fn export_credentials(State(state): State<AdminState>) -> impl IntoResponse {
@@ -187,6 +524,27 @@ pub async fn import_credentials(
     Json(response)
 }
 
+/// GET /api/admin/export?includeSecrets=true
+/// 导出整个凭据池配置（默认脱敏，`includeSecrets=true` 时包含 refreshToken 等密钥）
+pub async fn export_pool(
+    State(state): State<AdminState>,
+    Query(query): Query<PoolExportQuery>,
+) -> impl IntoResponse {
+    Json(state.service.export_pool(query.include_secrets))
+}
+
+/// POST /api/admin/import
+/// 从导出的凭据池配置恢复
+pub async fn import_pool(
+    State(state): State<AdminState>,
+    Json(payload): Json<PoolExport>,
+) -> impl IntoResponse {
+    match state.service.import_pool(payload).await {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
 /// POST /api/admin/credentials/:id/test
 /// 测试凭据可用性
 pub async fn test_credential(
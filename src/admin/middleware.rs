@@ -9,26 +9,96 @@ use axum::{
     middleware::Next,
     response::{IntoResponse, Json, Response},
 };
+use parking_lot::RwLock;
+use subtle::ConstantTimeEq;
 
 use super::service::AdminService;
 use super::types::AdminErrorResponse;
 use crate::common::auth;
 
+/// 配置项 `admin_api_key` 对应的密钥名称
+pub const DEFAULT_ADMIN_KEY_NAME: &str = "default";
+
+/// 具名 Admin API 密钥（只保存摘要）
+struct AdminKey {
+    name: String,
+    key_hash: [u8; 32],
+}
+
 /// Admin API 共享状态
 #[derive(Clone)]
 pub struct AdminState {
-    /// Admin API 密钥
-    pub admin_api_key: String,
+    /// Admin API 密钥（按添加顺序）
+    keys: Arc<RwLock<Vec<AdminKey>>>,
     /// Admin 服务
     pub service: Arc<AdminService>,
 }
 
 impl AdminState {
-    pub fn new(admin_api_key: impl Into<String>, service: AdminService) -> Self {
-        Self {
-            admin_api_key: admin_api_key.into(),
+    /// 创建 Admin 状态，`admin_api_key` 以 [`DEFAULT_ADMIN_KEY_NAME`] 命名
+    ///
+    /// 空（或仅含空白）的密钥会被拒绝，防止未配置的密钥意外放行所有请求。
+    pub fn new(admin_api_key: impl Into<String>, service: AdminService) -> anyhow::Result<Self> {
+        let state = Self {
+            keys: Arc::new(RwLock::new(Vec::new())),
             service: Arc::new(service),
+        };
+        state.add_key(DEFAULT_ADMIN_KEY_NAME, &admin_api_key.into())?;
+        Ok(state)
+    }
+
+    /// 添加具名密钥（名称不可重复，密钥不能为空）
+    pub fn add_key(&self, name: &str, key: &str) -> anyhow::Result<()> {
+        if name.trim().is_empty() {
+            anyhow::bail!("Admin API 密钥名称不能为空");
+        }
+        if key.trim().is_empty() {
+            anyhow::bail!("admin_api_key 不能为空");
+        }
+        let mut keys = self.keys.write();
+        if keys.iter().any(|k| k.name == name) {
+            anyhow::bail!("Admin API 密钥名称已存在: {}", name);
+        }
+        keys.push(AdminKey {
+            name: name.to_string(),
+            key_hash: auth::hash_key(key),
+        });
+        Ok(())
+    }
+
+    /// 撤销具名密钥
+    ///
+    /// 返回是否存在该密钥；撤销最后一个密钥会返回错误（否则 Admin API 将无法访问）。
+    pub fn revoke_key(&self, name: &str) -> anyhow::Result<bool> {
+        let mut keys = self.keys.write();
+        let Some(index) = keys.iter().position(|k| k.name == name) else {
+            return Ok(false);
+        };
+        if keys.len() == 1 {
+            anyhow::bail!("不能撤销最后一个 Admin API 密钥");
+        }
+        keys.remove(index);
+        Ok(true)
+    }
+
+    /// 所有密钥名称（不含密钥本身）
+    pub fn key_names(&self) -> Vec<String> {
+        self.keys.read().iter().map(|k| k.name.clone()).collect()
+    }
+
+    /// 校验密钥，返回匹配的密钥名称
+    ///
+    /// 与每个密钥摘要都做一次常量时间比较，不因提前命中而跳过其余比较。
+    fn authenticate(&self, key: &str) -> Option<String> {
+        let key_hash = auth::hash_key(key);
+        let mut matched = None;
+        for entry in self.keys.read().iter() {
+            let is_match: bool = entry.key_hash.ct_eq(&key_hash).into();
+            if is_match && matched.is_none() {
+                matched = Some(entry.name.clone());
+            }
         }
+        matched
     }
 }
 
@@ -38,13 +108,62 @@ pub async fn admin_auth_middleware(
     request: Request<Body>,
     next: Next,
 ) -> Response {
-    let api_key = auth::extract_api_key(&request);
-
-    match api_key {
-        Some(key) if auth::constant_time_eq(&key, &state.admin_api_key) => next.run(request).await,
-        _ => {
+    match auth::extract_api_key(&request).and_then(|key| state.authenticate(&key)) {
+        Some(name) => {
+            tracing::debug!("Admin API 认证通过: {}", name);
+            next.run(request).await
+        }
+        None => {
             let error = AdminErrorResponse::authentication_error();
             (StatusCode::UNAUTHORIZED, Json(error)).into_response()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::kiro::token_manager::MultiTokenManager;
+    use crate::model::config::Config;
+
+    fn service() -> AdminService {
+        let token_manager =
+            MultiTokenManager::new(Config::default(), vec![], None, None, false).unwrap();
+        AdminService::new(
+            Arc::new(token_manager),
+            ["ide".to_string()],
+            HashMap::new(),
+            "ide".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_empty_admin_key_is_rejected() {
+        assert!(AdminState::new("", service()).is_err());
+        assert!(AdminState::new("   ", service()).is_err());
+        let state = AdminState::new("secret", service()).unwrap();
+        assert_eq!(state.key_names(), vec![DEFAULT_ADMIN_KEY_NAME]);
+        assert!(state.add_key("ops", "").is_err());
+    }
+
+    #[test]
+    fn test_named_keys_authenticate_and_revoke_independently() {
+        let state = AdminState::new("secret", service()).unwrap();
+        state.add_key("ops", "ops-secret").unwrap();
+        assert!(state.add_key("ops", "other").is_err());
+        assert_eq!(state.key_names(), vec!["default", "ops"]);
+
+        assert_eq!(state.authenticate("secret").as_deref(), Some("default"));
+        assert_eq!(state.authenticate("ops-secret").as_deref(), Some("ops"));
+        assert_eq!(state.authenticate("ops-secreT"), None);
+
+        assert!(state.revoke_key("ops").unwrap());
+        assert!(!state.revoke_key("ops").unwrap());
+        assert_eq!(state.authenticate("ops-secret"), None);
+        // 最后一个密钥不可撤销
+        assert!(state.revoke_key("default").is_err());
+        assert_eq!(state.authenticate("secret").as_deref(), Some("default"));
+    }
+}
@@ -12,7 +12,7 @@
 //! # 使用
 //! ```ignore
 //! let admin_service = AdminService::new(token_manager.clone(), endpoint_names);
-//! let admin_state = AdminState::new(admin_api_key, admin_service);
+//! let admin_state = AdminState::new(admin_api_key, admin_service)?;
 //! let admin_router = create_admin_router(admin_state);
 //! ```
 
@@ -23,6 +23,6 @@ mod router;
 mod service;
 pub mod types;
 
-pub use middleware::AdminState;
+pub use middleware::{AdminState, admin_auth_middleware};
 pub use router::create_admin_router;
-pub use service::AdminService;
+pub use service::{AdminService, BalanceWarming};
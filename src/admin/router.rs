@@ -8,11 +8,17 @@ use tower_http::cors::{Any, CorsLayer};
 
 use super::{
     handlers::{
-        add_credential, delete_credential, export_credentials, force_refresh_token,
-        get_all_credentials, get_credential_balance, get_load_balancing_mode,
-        import_credentials, reset_all_success_count, reset_failure_count, reset_success_count,
-        set_credential_disabled, set_credential_priority, set_load_balancing_mode,
-        test_credential,
+        add_admin_key, add_credential, add_credential_tags, batch_credentials,
+        bulk_set_credentials_disabled, clear_cooldown, clear_global_cooldown, delete_credential,
+        export_credentials, export_pool, force_refresh_token, get_admin_keys, get_all_credentials,
+        get_cooldown_durations, get_cooldowns, get_credential_balance, get_credential_fingerprint,
+        get_event_stats, get_health_summary, get_latency_stats, get_load_balancing_mode,
+        get_tool_sizes, import_credentials, import_pool, pin_credential_fingerprint,
+        preview_tool_compression, refresh_all_tokens, remove_credential_tags,
+        reset_all_success_count, reset_failure_count, reset_success_count, revoke_admin_key,
+        set_cooldown_duration, set_credential_disabled, set_credential_priority,
+        set_global_cooldown, set_load_balancing_mode, stream_admin_events, test_credential,
+        unpin_credential_fingerprint,
     },
     middleware::{AdminState, admin_auth_middleware},
 };
@@ -26,19 +32,55 @@ pub fn create_admin_router(state: AdminState) -> Router {
         )
         .route("/credentials/export", get(export_credentials))
         .route("/credentials/import", post(import_credentials))
+        .route("/export", get(export_pool))
+        .route("/import", post(import_pool))
+        .route("/credentials/disabled", post(bulk_set_credentials_disabled))
+        .route("/credentials/batch", post(batch_credentials))
         .route("/credentials/{id}", delete(delete_credential))
         .route("/credentials/{id}/disabled", post(set_credential_disabled))
         .route("/credentials/{id}/priority", post(set_credential_priority))
+        .route(
+            "/credentials/{id}/tags",
+            post(add_credential_tags).delete(remove_credential_tags),
+        )
+        .route(
+            "/credentials/{id}/fingerprint",
+            get(get_credential_fingerprint)
+                .put(pin_credential_fingerprint)
+                .delete(unpin_credential_fingerprint),
+        )
         .route("/credentials/{id}/reset", post(reset_failure_count))
         .route("/credentials/{id}/reset-stats", post(reset_success_count))
         .route("/credentials/{id}/test", post(test_credential))
         .route("/credentials/reset-stats", post(reset_all_success_count))
         .route("/credentials/{id}/refresh", post(force_refresh_token))
+        .route("/credentials/refresh-all", post(refresh_all_tokens))
         .route("/credentials/{id}/balance", get(get_credential_balance))
         .route(
             "/config/load-balancing",
             get(get_load_balancing_mode).put(set_load_balancing_mode),
         )
+        .route("/cooldowns", get(get_cooldowns))
+        .route(
+            "/cooldowns/global",
+            post(set_global_cooldown).delete(clear_global_cooldown),
+        )
+        .route("/cooldowns/{id}", delete(clear_cooldown))
+        .route(
+            "/config/cooldown-durations",
+            get(get_cooldown_durations).put(set_cooldown_duration),
+        )
+        .route("/stats/latency", get(get_latency_stats))
+        .route("/stats/events", get(get_event_stats))
+        .route("/config/tools-sizes", get(get_tool_sizes))
+        .route(
+            "/config/tools-compression/preview",
+            post(preview_tool_compression),
+        )
+        .route("/health", get(get_health_summary))
+        .route("/events", get(stream_admin_events))
+        .route("/keys", get(get_admin_keys).post(add_admin_key))
+        .route("/keys/{name}", delete(revoke_admin_key))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             admin_auth_middleware,
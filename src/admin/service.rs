@@ -1,30 +1,54 @@
 //! Admin API 业务逻辑服务
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use chrono::Utc;
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
 
+use crate::anthropic::tool_compression::{self, CompressionPlan, ToolSizeSnapshot};
 use crate::http_client::build_client;
+use crate::kiro::cooldown::{CooldownEvent, CooldownInfo, CooldownReason};
 use crate::kiro::endpoint::{KiroEndpoint, RequestContext};
+use crate::kiro::fingerprint::Fingerprint;
 use crate::kiro::machine_id;
-use crate::kiro::model::credentials::KiroCredentials;
-use crate::kiro::token_manager::MultiTokenManager;
+use crate::kiro::parser;
+use crate::kiro::model::credentials::{KiroCredentials, normalize_tags};
+use crate::kiro::model::requests::tool::{InputSchema, Tool, ToolSpecification};
+use crate::kiro::model::usage_limits::UsageLimitsResponse;
+use crate::kiro::token_manager::{LOAD_BALANCING_MODES, MultiTokenManager};
+use crate::metrics::{self, LatencySummary};
 
 use super::error::AdminServiceError;
 use super::types::{
-    AddCredentialRequest, AddCredentialResponse, BalanceResponse, CredentialStatusItem,
-    CredentialsStatusResponse, ImportCredentialsRequest, ImportCredentialsResponse,
-    ImportItemResult, LoadBalancingModeResponse, SetLoadBalancingModeRequest,
-    TestCredentialResponse,
+    AddCredentialRequest, AddCredentialResponse, AdminEvent, BalanceResponse, CooldownDurationItem,
+    CooldownDurationsResponse, CredentialStatusItem, CredentialsStatusResponse, EventStatsResponse,
+    FingerprintResponse, HealthSummaryResponse, ImportCredentialsRequest,
+    ImportCredentialsResponse, ImportItemResult, LoadBalancingModeResponse, POOL_EXPORT_VERSION,
+    PoolExport, PoolImportResponse, PreviewToolCompressionRequest, RefreshTokenResponse,
+    SetCooldownDurationRequest, SetGlobalCooldownRequest, SetLoadBalancingModeRequest,
+    TestCredentialResponse, ToolCompressionSettings,
 };
 
 /// 余额缓存过期时间（秒），5 分钟
 const BALANCE_CACHE_TTL_SECS: i64 = 300;
 
+/// 实时事件广播缓冲区大小（订阅方落后超过该数量时丢弃最旧的事件）
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// 余额后台预热配置
+#[derive(Debug, Clone, Copy)]
+pub struct BalanceWarming {
+    /// 预热周期
+    pub interval: Duration,
+    /// 相邻两次上游查询之间的间隔（避免集中请求）
+    pub spacing: Duration,
+}
+
 /// 缓存的余额条目（含时间戳）
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct CachedBalance {
@@ -47,6 +71,24 @@ pub struct AdminService {
     endpoints: HashMap<String, Arc<dyn KiroEndpoint>>,
     /// 默认端点名称
     default_endpoint: String,
+    /// 余额后台预热（启用后 get_balance 优先返回缓存，不受 TTL 限制）
+    balance_warming: Option<BalanceWarming>,
+    /// 实时事件广播
+    events: broadcast::Sender<AdminEvent>,
+    /// 正在强制刷新 Token 的凭据 ID（拒绝同一凭据的重复刷新）
+    refreshing: Mutex<HashSet<u64>>,
+}
+
+/// 强制刷新期间占用凭据 ID，结束（含请求被取消）时自动释放
+struct RefreshGuard<'a> {
+    refreshing: &'a Mutex<HashSet<u64>>,
+    id: u64,
+}
+
+impl Drop for RefreshGuard<'_> {
+    fn drop(&mut self) {
+        self.refreshing.lock().remove(&self.id);
+    }
 }
 
 impl AdminService {
@@ -60,7 +102,30 @@ impl AdminService {
             .cache_dir()
             .map(|d| d.join("kiro_balance_cache.json"));
 
-        let balance_cache = Self::load_balance_cache_from(&cache_path);
+        let balance_cache =
+            Self::load_balance_cache_from(&cache_path, &Self::credential_ids(&token_manager), false);
+
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let cooldown_events = events.clone();
+        token_manager
+            .cooldowns()
+            .on_cooldown_change(Box::new(move |event| {
+                let event = match event {
+                    CooldownEvent::Entered {
+                        credential_id,
+                        reason,
+                        duration,
+                    } => AdminEvent::CooldownEntered {
+                        id: credential_id,
+                        reason: reason.as_str(),
+                        duration_secs: duration.as_secs(),
+                    },
+                    CooldownEvent::Cleared { credential_id } => {
+                        AdminEvent::CooldownCleared { id: credential_id }
+                    }
+                };
+                let _ = cooldown_events.send(event);
+            }));
 
         Self {
             token_manager,
@@ -69,17 +134,132 @@ impl AdminService {
             known_endpoints: known_endpoints.into_iter().collect(),
             endpoints,
             default_endpoint,
+            balance_warming: None,
+            events,
+            refreshing: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// 订阅实时事件
+    pub fn subscribe_events(&self) -> broadcast::Receiver<AdminEvent> {
+        self.events.subscribe()
+    }
+
+    /// 广播实时事件（无订阅者时直接丢弃）
+    fn emit(&self, event: AdminEvent) {
+        let _ = self.events.send(event);
+    }
+
+    /// 启用余额后台预热
+    ///
+    /// 重新加载持久化缓存并保留过期条目，重启后可立即返回，由预热任务刷新。
+    pub fn with_balance_warming(mut self, warming: BalanceWarming) -> Self {
+        self.balance_warming = Some(warming);
+        *self.balance_cache.get_mut() = Self::load_balance_cache_from(
+            &self.cache_path,
+            &Self::credential_ids(&self.token_manager),
+            true,
+        );
+        self
+    }
+
+    /// 当前存在的凭据 ID
+    fn credential_ids(token_manager: &MultiTokenManager) -> HashSet<u64> {
+        token_manager.snapshot().entries.iter().map(|e| e.id).collect()
+    }
+
+    /// 启动余额后台预热任务（未启用时不做任何事）
+    pub fn spawn_balance_warmer(service: Arc<AdminService>) {
+        let Some(warming) = service.balance_warming else {
+            return;
+        };
+        tracing::info!(
+            "余额后台预热已启用：周期 {} 秒，查询间隔 {} 毫秒",
+            warming.interval.as_secs(),
+            warming.spacing.as_millis()
+        );
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(warming.interval);
+            loop {
+                ticker.tick().await;
+                let updated = service
+                    .refresh_stale_balances(|id| service.fetch_balance(id))
+                    .await;
+                tracing::debug!("余额后台预热完成，更新 {} 个凭据", updated);
+            }
+        });
+    }
+
+    /// 刷新缺失或过期的余额缓存，返回更新的凭据数量
+    ///
+    /// 跳过已禁用的凭据（包括 refreshToken 失效等不可恢复状态），逐个查询并在两次查询间等待 `spacing`。
+    async fn refresh_stale_balances<F, Fut>(&self, fetch: F) -> usize
+    where
+        F: Fn(u64) -> Fut,
+        Fut: std::future::Future<Output = Result<BalanceResponse, AdminServiceError>>,
+    {
+        let spacing = self
+            .balance_warming
+            .map(|w| w.spacing)
+            .unwrap_or_default();
+        let now = Utc::now().timestamp() as f64;
+        let stale: Vec<u64> = {
+            let cache = self.balance_cache.lock();
+            self.token_manager
+                .snapshot()
+                .entries
+                .into_iter()
+                .filter(|entry| !entry.disabled)
+                .map(|entry| entry.id)
+                .filter(|id| {
+                    cache
+                        .get(id)
+                        .is_none_or(|c| now - c.cached_at >= BALANCE_CACHE_TTL_SECS as f64)
+                })
+                .collect()
+        };
+
+        let mut updated = 0;
+        for (i, id) in stale.into_iter().enumerate() {
+            if i > 0 && !spacing.is_zero() {
+                tokio::time::sleep(spacing).await;
+            }
+            match fetch(id).await {
+                Ok(balance) => {
+                    self.balance_cache.lock().insert(
+                        id,
+                        CachedBalance {
+                            cached_at: balance.as_of.unwrap_or(now),
+                            data: balance,
+                        },
+                    );
+                    updated += 1;
+                }
+                Err(e) => tracing::debug!("凭据 #{} 余额预热失败: {}", id, e),
+            }
+        }
+        if updated > 0 {
+            // 查询期间被删除的凭据不写回缓存
+            let ids = Self::credential_ids(&self.token_manager);
+            self.balance_cache.lock().retain(|id, _| ids.contains(id));
+            self.save_balance_cache();
         }
+        updated
     }
 
-    /// 获取所有凭据状态
-    pub fn get_all_credentials(&self) -> CredentialsStatusResponse {
+    /// 获取凭据状态
+    ///
+    /// `tags` 非空时只返回同时带有全部标签的凭据（total/available 仍为全局统计）。
+    pub fn get_all_credentials(&self, tags: &[String]) -> CredentialsStatusResponse {
         let snapshot = self.token_manager.snapshot();
         let default_endpoint = self.token_manager.config().default_endpoint.clone();
+        let tagged: Option<HashSet<u64>> = (!tags.is_empty())
+            .then(|| self.token_manager.ids_with_tags(tags).into_iter().collect());
 
         let mut credentials: Vec<CredentialStatusItem> = snapshot
             .entries
             .into_iter()
+            .filter(|entry| tagged.as_ref().is_none_or(|ids| ids.contains(&entry.id)))
             .map(|entry| CredentialStatusItem {
                 id: entry.id,
                 priority: entry.priority,
@@ -100,6 +280,11 @@ impl AdminService {
                 refresh_failure_count: entry.refresh_failure_count,
                 disabled_reason: entry.disabled_reason,
                 endpoint: entry.endpoint.unwrap_or_else(|| default_endpoint.clone()),
+                cooldown_secs_in_window: entry.cooldown_secs_in_window,
+                cooldown_reason: entry.cooldown_reason,
+                cooldown_remaining_secs: entry.cooldown_remaining_secs,
+                quota_usage: entry.quota_usage,
+                tags: entry.tags,
             })
             .collect();
 
@@ -128,9 +313,92 @@ impl AdminService {
         if disabled && id == current_id {
             let _ = self.token_manager.switch_to_next();
         }
+        self.emit(if disabled {
+            AdminEvent::CredentialDisabled { id }
+        } else {
+            AdminEvent::CredentialEnabled { id }
+        });
         Ok(())
     }
 
+    /// 添加/移除凭据标签，返回更新后的标签列表
+    pub fn update_tags(
+        &self,
+        id: u64,
+        add: &[String],
+        remove: &[String],
+    ) -> Result<Vec<String>, AdminServiceError> {
+        self.token_manager
+            .update_tags(id, add, remove)
+            .map_err(|e| self.classify_error(e, id))
+    }
+
+    /// 获取凭据实际使用的指纹
+    pub fn get_fingerprint(&self, id: u64) -> Result<FingerprintResponse, AdminServiceError> {
+        let (machine_id, fingerprint) = self
+            .token_manager
+            .credential_fingerprint(id)
+            .map_err(|e| self.classify_error(e, id))?;
+        let config = self.token_manager.config();
+        let (system_version, node_version) = match &fingerprint {
+            Some(fp) => (fp.system_version(), fp.node_version.clone()),
+            None => (config.system_version.clone(), config.node_version.clone()),
+        };
+        Ok(FingerprintResponse {
+            id,
+            pinned: fingerprint.is_some(),
+            machine_id,
+            system_version,
+            node_version,
+            fingerprint,
+        })
+    }
+
+    /// 将导出的指纹 JSON 固定到凭据上（校验失败时返回出错字段）
+    pub fn pin_fingerprint(
+        &self,
+        id: u64,
+        json: &str,
+    ) -> Result<FingerprintResponse, AdminServiceError> {
+        let fingerprint = Fingerprint::from_json(json).map_err(|e| {
+            AdminServiceError::ValidationFailed(BTreeMap::from([(
+                e.field().to_string(),
+                e.to_string(),
+            )]))
+        })?;
+        self.token_manager
+            .set_fingerprint(id, Some(fingerprint))
+            .map_err(|e| self.classify_error(e, id))?;
+        self.get_fingerprint(id)
+    }
+
+    /// 清除凭据固定的指纹，恢复按配置生成
+    pub fn unpin_fingerprint(&self, id: u64) -> Result<FingerprintResponse, AdminServiceError> {
+        self.token_manager
+            .set_fingerprint(id, None)
+            .map_err(|e| self.classify_error(e, id))?;
+        self.get_fingerprint(id)
+    }
+
+    /// 按标签批量启用/禁用凭据，返回受影响的凭据 ID
+    pub fn bulk_set_disabled(
+        &self,
+        tags: &[String],
+        disabled: bool,
+    ) -> Result<Vec<u64>, AdminServiceError> {
+        if normalize_tags(tags).is_empty() {
+            return Err(AdminServiceError::ValidationFailed(BTreeMap::from([(
+                "tags".to_string(),
+                "至少需要指定一个标签".to_string(),
+            )])));
+        }
+        let ids = self.token_manager.ids_with_tags(tags);
+        for &id in &ids {
+            self.set_disabled(id, disabled)?;
+        }
+        Ok(ids)
+    }
+
     /// 设置凭据优先级
     pub fn set_priority(&self, id: u64, priority: u32) -> Result<(), AdminServiceError> {
         self.token_manager
@@ -142,7 +410,9 @@ impl AdminService {
     pub fn reset_and_enable(&self, id: u64) -> Result<(), AdminServiceError> {
         self.token_manager
             .reset_and_enable(id)
-            .map_err(|e| self.classify_error(e, id))
+            .map_err(|e| self.classify_error(e, id))?;
+        self.emit(AdminEvent::CredentialReset { id });
+        Ok(())
     }
 
     pub fn reset_success_count(&self, id: Option<u64>) -> Result<u32, AdminServiceError> {
@@ -158,9 +428,14 @@ impl AdminService {
             let cache = self.balance_cache.lock();
             if let Some(cached) = cache.get(&id) {
                 let now = Utc::now().timestamp() as f64;
-                if (now - cached.cached_at) < BALANCE_CACHE_TTL_SECS as f64 {
+                // 启用后台预热时缓存由预热任务保持新鲜，直接返回
+                if self.balance_warming.is_some()
+                    || (now - cached.cached_at) < BALANCE_CACHE_TTL_SECS as f64
+                {
                     tracing::debug!("凭据 #{} 余额命中缓存", id);
-                    return Ok(cached.data.clone());
+                    let mut balance = cached.data.clone();
+                    balance.as_of = Some(cached.cached_at);
+                    return Ok(balance);
                 }
             }
         }
@@ -192,24 +467,11 @@ impl AdminService {
             .await
             .map_err(|e| self.classify_balance_error(e, id))?;
 
-        let current_usage = usage.current_usage();
-        let usage_limit = usage.usage_limit();
-        let remaining = (usage_limit - current_usage).max(0.0);
-        let usage_percentage = if usage_limit > 0.0 {
-            (current_usage / usage_limit * 100.0).min(100.0)
-        } else {
-            0.0
-        };
-
-        Ok(BalanceResponse {
+        Ok(balance_from_usage(
             id,
-            subscription_title: usage.subscription_title().map(|s| s.to_string()),
-            current_usage,
-            usage_limit,
-            remaining,
-            usage_percentage,
-            next_reset_at: usage.next_date_reset,
-        })
+            &usage,
+            Utc::now().timestamp() as f64,
+        ))
     }
 
     /// 添加新凭据
@@ -217,17 +479,10 @@ impl AdminService {
         &self,
         req: AddCredentialRequest,
     ) -> Result<AddCredentialResponse, AdminServiceError> {
-        // 校验端点名：未指定则默认合法，指定则必须已注册
-        if let Some(ref name) = req.endpoint {
-            if !self.known_endpoints.contains(name) {
-                let mut known: Vec<&str> =
-                    self.known_endpoints.iter().map(|s| s.as_str()).collect();
-                known.sort();
-                return Err(AdminServiceError::InvalidCredential(format!(
-                    "未知端点 \"{}\"，已注册端点: {:?}",
-                    name, known
-                )));
-            }
+        // 校验整个请求，一次返回所有字段错误
+        let field_errors = self.validate_add_request(&req);
+        if !field_errors.is_empty() {
+            return Err(AdminServiceError::ValidationFailed(field_errors));
         }
 
         // 构建凭据对象
@@ -254,6 +509,9 @@ impl AdminService {
             disabled: false, // 新添加的凭据默认启用
             kiro_api_key: req.kiro_api_key,
             endpoint: req.endpoint,
+            request_quotas: req.request_quotas,
+            tags: req.tags,
+            fingerprint: None,
         };
 
         // 调用 token_manager 添加凭据
@@ -276,6 +534,116 @@ impl AdminService {
         })
     }
 
+    /// 校验添加凭据请求的各字段（不访问网络），返回 字段名 → 错误信息
+    fn validate_add_request(&self, req: &AddCredentialRequest) -> BTreeMap<String, String> {
+        let mut errors = BTreeMap::new();
+        let mut error = |field: &str, msg: String| {
+            errors.insert(field.to_string(), msg);
+        };
+        let auth_method = req.auth_method.to_ascii_lowercase();
+
+        if !matches!(
+            auth_method.as_str(),
+            "social" | "idc" | "builder-id" | "iam" | "api_key" | "apikey"
+        ) {
+            error(
+                "authMethod",
+                format!(
+                    "不支持的认证方式 \"{}\"（可选: social / idc / builder-id / iam / api_key）",
+                    req.auth_method
+                ),
+            );
+        }
+
+        let is_api_key =
+            req.kiro_api_key.is_some() || matches!(auth_method.as_str(), "api_key" | "apikey");
+        if is_api_key {
+            if req
+                .kiro_api_key
+                .as_deref()
+                .is_none_or(|k| k.trim().is_empty())
+            {
+                error("kiroApiKey", "API Key 凭据缺少 kiroApiKey".to_string());
+            }
+        } else {
+            let probe = KiroCredentials {
+                refresh_token: req.refresh_token.clone(),
+                ..Default::default()
+            };
+            if let Err(e) = crate::kiro::token_manager::validate_refresh_token(&probe) {
+                error("refreshToken", e.to_string());
+            }
+            if matches!(auth_method.as_str(), "idc" | "builder-id" | "iam") {
+                if req.client_id.as_deref().is_none_or(str::is_empty) {
+                    error("clientId", "IdC 认证需要 clientId".to_string());
+                }
+                if req.client_secret.as_deref().is_none_or(str::is_empty) {
+                    error("clientSecret", "IdC 认证需要 clientSecret".to_string());
+                }
+            }
+        }
+
+        for (field, region) in [
+            ("region", &req.region),
+            ("authRegion", &req.auth_region),
+            ("apiRegion", &req.api_region),
+        ] {
+            if let Some(region) = region
+                && !is_valid_region(region)
+            {
+                error(
+                    field,
+                    format!("无效的 Region \"{}\"（示例: us-east-1）", region),
+                );
+            }
+        }
+
+        if let Some(machine_id) = &req.machine_id
+            && machine_id::normalize_machine_id(machine_id).is_none()
+        {
+            error(
+                "machineId",
+                "machineId 必须是 64 位十六进制字符串或 UUID".to_string(),
+            );
+        }
+
+        if let Some(proxy_url) = &req.proxy_url
+            && proxy_url != "direct"
+            && !is_valid_proxy_url(proxy_url)
+        {
+            error(
+                "proxyUrl",
+                format!(
+                    "无效的代理地址 \"{}\"（支持 http / https / socks5，或 \"direct\"）",
+                    proxy_url
+                ),
+            );
+        }
+
+        // 端点名：未指定则默认合法，指定则必须已注册
+        if let Some(name) = &req.endpoint
+            && !self.known_endpoints.contains(name)
+        {
+            let mut known: Vec<&str> = self.known_endpoints.iter().map(|s| s.as_str()).collect();
+            known.sort();
+            error(
+                "endpoint",
+                format!("未知端点 \"{}\"，已注册端点: {:?}", name, known),
+            );
+        }
+
+        for (i, quota) in req.request_quotas.iter().enumerate() {
+            if quota.window_secs == 0 || quota.max_requests == 0 {
+                error(
+                    &format!("requestQuotas[{}]", i),
+                    "windowSecs 与 maxRequests 必须大于 0".to_string(),
+                );
+            }
+        }
+
+        errors
+    }
+
     /// 删除凭据
     pub fn delete_credential(&self, id: u64) -> Result<(), AdminServiceError> {
         self.token_manager
@@ -292,6 +660,77 @@ impl AdminService {
         Ok(())
     }
 
+    /// 获取上游延迟汇总
+    pub fn get_latency_stats(&self) -> LatencySummary {
+        metrics::latency().summary()
+    }
+
+    /// 获取当前处于冷却中的凭据
+    pub fn get_cooldowns(&self) -> Vec<CooldownInfo> {
+        self.token_manager.active_cooldowns()
+    }
+
+    /// 解除凭据冷却，返回解除前是否处于冷却中
+    pub fn clear_cooldown(&self, id: u64) -> Result<bool, AdminServiceError> {
+        self.token_manager
+            .clear_cooldown(id)
+            .map_err(|e| self.classify_error(e, id))
+    }
+
+    /// 获取上游事件类型统计（已知类型与未知事件计数）
+    pub fn get_event_stats(&self) -> EventStatsResponse {
+        EventStatsResponse {
+            known_event_types: parser::known_event_types(),
+            unknown_event_counts: parser::registry::unknown_event_counts(),
+        }
+    }
+
+    /// 获取最近一次请求的各工具体积（尚无带工具的请求时返回 None）
+    pub fn get_tool_sizes(&self) -> Option<ToolSizeSnapshot> {
+        tool_compression::last_tool_sizes()
+    }
+
+    /// 预估给定工具定义按当前选项压缩的效果（不修改任何状态）
+    pub fn preview_tool_compression(&self, req: PreviewToolCompressionRequest) -> CompressionPlan {
+        let tools: Vec<Tool> = req
+            .tools
+            .into_iter()
+            .map(|t| Tool {
+                tool_specification: ToolSpecification {
+                    name: t.name,
+                    description: t.description,
+                    input_schema: InputSchema::from_json(serde_json::json!(t.input_schema)),
+                },
+            })
+            .collect();
+        tool_compression::compress_tools_if_needed_dry_run(&tools, tool_compression::options())
+    }
+
+    /// 获取凭据池健康摘要
+    pub fn get_health_summary(&self) -> HealthSummaryResponse {
+        let snapshot = self.token_manager.snapshot();
+        let disabled = snapshot.entries.iter().filter(|e| e.disabled).count();
+        let cooling = snapshot
+            .entries
+            .iter()
+            .filter(|e| !e.disabled && e.cooldown_remaining_secs.is_some())
+            .count();
+        HealthSummaryResponse {
+            version: env!("CARGO_PKG_VERSION"),
+            total: snapshot.total,
+            active: snapshot.total - disabled - cooling,
+            disabled,
+            cooling,
+            global_cooldown_remaining_secs: self
+                .token_manager
+                .cooldowns()
+                .global_cooldown_remaining()
+                .map(|d| d.as_secs()),
+            load_balancing_mode: self.token_manager.get_load_balancing_mode(),
+            tool_compression_target_bytes: tool_compression::options().target_size(),
+        }
+    }
+
     /// 获取负载均衡模式
     pub fn get_load_balancing_mode(&self) -> LoadBalancingModeResponse {
         LoadBalancingModeResponse {
@@ -305,10 +744,11 @@ impl AdminService {
         req: SetLoadBalancingModeRequest,
     ) -> Result<LoadBalancingModeResponse, AdminServiceError> {
         // 验证模式值
-        if req.mode != "priority" && req.mode != "balanced" {
-            return Err(AdminServiceError::InvalidCredential(
-                "mode 必须是 'priority' 或 'balanced'".to_string(),
-            ));
+        if !LOAD_BALANCING_MODES.contains(&req.mode.as_str()) {
+            return Err(AdminServiceError::InvalidCredential(format!(
+                "mode 必须是以下之一: {}",
+                LOAD_BALANCING_MODES.join(", ")
+            )));
         }
 
         self.token_manager
@@ -318,12 +758,147 @@ impl AdminService {
         Ok(LoadBalancingModeResponse { mode: req.mode })
     }
 
-    /// 强制刷新指定凭据的 Token
-    pub async fn force_refresh_token(&self, id: u64) -> Result<(), AdminServiceError> {
+    /// 获取各冷却原因的时长配置
+    pub fn get_cooldown_durations(&self) -> CooldownDurationsResponse {
+        let cooldowns = self.token_manager.cooldowns();
+        CooldownDurationsResponse {
+            durations: CooldownReason::ALL
+                .into_iter()
+                .map(|reason| CooldownDurationItem {
+                    reason: reason.as_str(),
+                    description: reason.description(),
+                    default_secs: reason.default_duration().as_secs(),
+                    override_secs: cooldowns
+                        .reason_duration_override(reason)
+                        .map(|d| d.as_secs()),
+                    effective_secs: cooldowns.base_duration(reason).as_secs(),
+                })
+                .collect(),
+            max_cooldown_secs: cooldowns.max_short_cooldown_secs(),
+        }
+    }
+
+    /// 覆盖或清除某个冷却原因的基础时长（仅影响之后触发的冷却）
+    pub fn set_cooldown_duration(
+        &self,
+        req: SetCooldownDurationRequest,
+    ) -> Result<CooldownDurationsResponse, AdminServiceError> {
+        let mut errors = BTreeMap::new();
+        let reason = parse_cooldown_reason(&req.reason, &mut errors);
+        if req.duration_secs == Some(0) {
+            errors.insert("durationSecs".to_string(), "必须大于 0".to_string());
+        }
+        let Some(reason) = reason.filter(|_| errors.is_empty()) else {
+            return Err(AdminServiceError::ValidationFailed(errors));
+        };
+
+        let cooldowns = self.token_manager.cooldowns();
+        match req.duration_secs {
+            Some(secs) => {
+                cooldowns.set_reason_duration(reason, Duration::from_secs(secs));
+                tracing::info!("冷却原因 {} 的基础时长已设置为 {} 秒", reason.as_str(), secs);
+            }
+            None => {
+                cooldowns.clear_reason_duration(reason);
+                tracing::info!("冷却原因 {} 已恢复默认时长", reason.as_str());
+            }
+        }
+        Ok(self.get_cooldown_durations())
+    }
+
+    /// 设置全局冷却：持续期间所有凭据暂停使用（不影响凭据级冷却）
+    pub fn set_global_cooldown(
+        &self,
+        req: SetGlobalCooldownRequest,
+    ) -> Result<Vec<CooldownInfo>, AdminServiceError> {
+        let mut errors = BTreeMap::new();
+        let reason = parse_cooldown_reason(&req.reason, &mut errors);
+        if req.duration_secs == 0 {
+            errors.insert("durationSecs".to_string(), "必须大于 0".to_string());
+        }
+        let Some(reason) = reason.filter(|_| errors.is_empty()) else {
+            return Err(AdminServiceError::ValidationFailed(errors));
+        };
+
         self.token_manager
+            .cooldowns()
+            .set_global_cooldown(reason, Duration::from_secs(req.duration_secs));
+        Ok(self.get_cooldowns())
+    }
+
+    /// 解除全局冷却，返回解除前是否生效
+    pub fn clear_global_cooldown(&self) -> bool {
+        self.token_manager.cooldowns().clear_global_cooldown()
+    }
+
+    /// 强制刷新指定凭据的 Token
+    ///
+    /// 同一凭据已有刷新在进行时返回 [`AdminServiceError::Conflict`]。
+    pub async fn force_refresh_token(
+        &self,
+        id: u64,
+    ) -> Result<RefreshTokenResponse, AdminServiceError> {
+        if !self.refreshing.lock().insert(id) {
+            return Err(AdminServiceError::Conflict(format!(
+                "凭据 #{} 正在刷新 Token",
+                id
+            )));
+        }
+        let _guard = RefreshGuard {
+            refreshing: &self.refreshing,
+            id,
+        };
+
+        let result = self
+            .token_manager
             .force_refresh_token_for(id)
             .await
-            .map_err(|e| self.classify_balance_error(e, id))
+            .map_err(|e| self.classify_balance_error(e, id));
+        self.emit(match &result {
+            Ok(()) => AdminEvent::TokenRefreshed { id },
+            Err(e) => AdminEvent::TokenRefreshFailed {
+                id,
+                error: e.to_string(),
+            },
+        });
+        result?;
+
+        let entry = self
+            .token_manager
+            .snapshot()
+            .entries
+            .into_iter()
+            .find(|e| e.id == id)
+            .ok_or(AdminServiceError::NotFound { id })?;
+        Ok(RefreshTokenResponse {
+            success: true,
+            message: format!("凭据 #{} Token 已强制刷新", id),
+            id,
+            expires_at: entry.expires_at,
+            disabled: entry.disabled,
+        })
+    }
+
+    /// 在后台依次强制刷新所有未禁用的 OAuth 凭据，返回排入刷新的凭据 ID
+    ///
+    /// 各凭据的刷新结果通过实时事件推送（`tokenRefreshed` / `tokenRefreshFailed`）。
+    pub fn refresh_all_tokens(service: Arc<AdminService>) -> Vec<u64> {
+        let ids: Vec<u64> = service
+            .token_manager
+            .export_credentials()
+            .iter()
+            .filter(|c| !c.disabled && !c.is_api_key_credential())
+            .filter_map(|c| c.id)
+            .collect();
+        let queued = ids.clone();
+        tokio::spawn(async move {
+            for id in ids {
+                if let Err(e) = service.force_refresh_token(id).await {
+                    tracing::warn!("批量刷新凭据 #{} Token 失败: {}", id, e);
+                }
+            }
+        });
+        queued
     }
 
     /// 导出所有凭据（含明文 token）
@@ -336,60 +911,160 @@ impl AdminService {
         &self,
         req: ImportCredentialsRequest,
     ) -> ImportCredentialsResponse {
-        let mut imported = 0u32;
-        let mut skipped = 0u32;
-        let mut failed = 0u32;
         let mut details = Vec::new();
-
         for (index, cred_req) in req.credentials.into_iter().enumerate() {
-            let result = self.add_credential(cred_req).await;
-            match result {
-                Ok(resp) => {
-                    imported += 1;
-                    details.push(ImportItemResult {
-                        index: index as u32,
-                        status: "imported".to_string(),
-                        credential_id: Some(resp.credential_id),
-                        error: None,
-                    });
-                }
-                Err(e) => {
-                    let msg = match &e {
-                        AdminServiceError::InvalidCredential(m) => m.clone(),
-                        AdminServiceError::UpstreamError(m) => m.clone(),
-                        AdminServiceError::InternalError(m) => m.clone(),
-                        AdminServiceError::NotFound { id } => format!("凭据不存在: {}", id),
-                    };
-                    if msg.contains("重复") {
-                        skipped += 1;
-                        details.push(ImportItemResult {
-                            index: index as u32,
-                            status: "skipped".to_string(),
-                            credential_id: None,
-                            error: Some(msg),
-                        });
-                    } else {
-                        failed += 1;
-                        details.push(ImportItemResult {
-                            index: index as u32,
-                            status: "failed".to_string(),
-                            credential_id: None,
-                            error: Some(msg),
-                        });
+            details.push(self.import_one(index, cred_req).await);
+        }
+        summarize_import(details)
+    }
+
+    /// 导入单个凭据：重复的凭据记为 skipped，其他错误记为 failed
+    async fn import_one(&self, index: usize, req: AddCredentialRequest) -> ImportItemResult {
+        match self.add_credential(req).await {
+            Ok(resp) => ImportItemResult {
+                index: index as u32,
+                status: "imported".to_string(),
+                credential_id: Some(resp.credential_id),
+                error: None,
+            },
+            Err(e) => {
+                let msg = match &e {
+                    AdminServiceError::InvalidCredential(m) => m.clone(),
+                    AdminServiceError::UpstreamError(m) => m.clone(),
+                    AdminServiceError::InternalError(m) => m.clone(),
+                    AdminServiceError::NotFound { id } => format!("凭据不存在: {}", id),
+                    AdminServiceError::ValidationFailed(_) | AdminServiceError::Conflict(_) => {
+                        e.to_string()
                     }
+                };
+                let status = if msg.contains("重复") {
+                    "skipped"
+                } else {
+                    "failed"
+                };
+                ImportItemResult {
+                    index: index as u32,
+                    status: status.to_string(),
+                    credential_id: None,
+                    error: Some(msg),
                 }
             }
         }
+    }
+
+    /// 导出整个凭据池配置（凭据、负载均衡模式与工具压缩设置）
+    ///
+    /// `include_secrets` 为 false 时清除 token、clientSecret、kiroApiKey 与代理密码。
+    pub fn export_pool(&self, include_secrets: bool) -> PoolExport {
+        let options = tool_compression::options();
+        let mut priorities: BTreeMap<String, u8> = options
+            .priorities
+            .iter()
+            .map(|(name, priority)| (name.clone(), *priority))
+            .collect();
+        priorities.retain(|_, priority| *priority > 0);
+        let mut protected_tools: Vec<String> = options.protected_tools.iter().cloned().collect();
+        protected_tools.sort();
+
+        let mut credentials = self.token_manager.export_credentials();
+        if !include_secrets {
+            for cred in &mut credentials {
+                cred.access_token = None;
+                cred.refresh_token = None;
+                cred.client_secret = None;
+                cred.kiro_api_key = None;
+                cred.proxy_password = None;
+            }
+        }
 
-        ImportCredentialsResponse {
-            success: failed == 0,
-            imported,
-            skipped,
-            failed,
-            details,
+        PoolExport {
+            version: POOL_EXPORT_VERSION,
+            exported_at: Some(Utc::now().to_rfc3339()),
+            load_balancing_mode: self.token_manager.get_load_balancing_mode(),
+            tool_compression: Some(ToolCompressionSettings {
+                enabled: options.enabled,
+                target_bytes: options.target_size(),
+                collapse_whitespace: options.collapse_whitespace,
+                min_description_length: options.min_description_length,
+                priorities,
+                protected_tools,
+            }),
+            credentials,
         }
     }
 
+    /// 从 [`export_pool`](Self::export_pool) 的结果恢复凭据池
+    ///
+    /// 先整体校验格式版本与负载均衡模式，再逐个导入凭据（与现有凭据重复的跳过），
+    /// 并恢复禁用状态与固定的指纹。工具压缩设置属于启动配置，不在此应用。
+    pub async fn import_pool(
+        &self,
+        export: PoolExport,
+    ) -> Result<PoolImportResponse, AdminServiceError> {
+        let mut errors = BTreeMap::new();
+        if export.version != POOL_EXPORT_VERSION {
+            errors.insert(
+                "version".to_string(),
+                format!(
+                    "不支持的导出格式版本 {}（当前为 {}）",
+                    export.version, POOL_EXPORT_VERSION
+                ),
+            );
+        }
+        if !LOAD_BALANCING_MODES.contains(&export.load_balancing_mode.as_str()) {
+            errors.insert(
+                "loadBalancingMode".to_string(),
+                format!("必须是以下之一: {}", LOAD_BALANCING_MODES.join(", ")),
+            );
+        }
+        for (index, cred) in export.credentials.iter().enumerate() {
+            if let Some(Err(e)) = cred.fingerprint.as_ref().map(Fingerprint::validate) {
+                errors.insert(format!("credentials[{}].fingerprint", index), e.to_string());
+            }
+        }
+        if !errors.is_empty() {
+            return Err(AdminServiceError::ValidationFailed(errors));
+        }
+
+        let load_balancing_mode = self
+            .set_load_balancing_mode(SetLoadBalancingModeRequest {
+                mode: export.load_balancing_mode,
+            })?
+            .mode;
+
+        let mut details = Vec::new();
+        for (index, cred) in export.credentials.into_iter().enumerate() {
+            let disabled = cred.disabled;
+            let fingerprint = cred.fingerprint.clone();
+            let mut result = self.import_one(index, add_request_from(cred)).await;
+            if let Some(id) = result.credential_id {
+                let restored = fingerprint
+                    .map_or(Ok(()), |fp| {
+                        self.token_manager.set_fingerprint(id, Some(fp))
+                    })
+                    .map_err(|e| e.to_string())
+                    .and_then(|()| {
+                        if disabled {
+                            self.set_disabled(id, true).map_err(|e| e.to_string())
+                        } else {
+                            Ok(())
+                        }
+                    });
+                if let Err(e) = restored {
+                    result.error = Some(format!("凭据已导入，但恢复状态失败: {}", e));
+                }
+            }
+            details.push(result);
+        }
+
+        let credentials = summarize_import(details);
+        Ok(PoolImportResponse {
+            success: credentials.success,
+            load_balancing_mode,
+            credentials,
+        })
+    }
+
     /// 测试指定凭据（发送一个简短的 Claude 请求）
     pub async fn test_credential(&self, id: u64) -> Result<TestCredentialResponse, AdminServiceError> {
         let ctx = self
@@ -416,6 +1091,7 @@ impl AdminService {
             token: &ctx.token,
             machine_id: &machine_id_str,
             config,
+            fingerprint: None,
         };
 
         let test_body = serde_json::json!({
@@ -486,7 +1162,14 @@ impl AdminService {
 
     // ============ 余额缓存持久化 ============
 
-    fn load_balance_cache_from(cache_path: &Option<PathBuf>) -> HashMap<u64, CachedBalance> {
+    /// 加载持久化的余额缓存
+    ///
+    /// 丢弃已删除凭据的条目；`keep_stale` 为 false 时同时丢弃超过 TTL 的条目。
+    fn load_balance_cache_from(
+        cache_path: &Option<PathBuf>,
+        credential_ids: &HashSet<u64>,
+        keep_stale: bool,
+    ) -> HashMap<u64, CachedBalance> {
         let path = match cache_path {
             Some(p) => p,
             None => return HashMap::new(),
@@ -510,8 +1193,11 @@ impl AdminService {
         map.into_iter()
             .filter_map(|(k, v)| {
                 let id = k.parse::<u64>().ok()?;
-                // 丢弃超过 TTL 的条目
-                if (now - v.cached_at) < BALANCE_CACHE_TTL_SECS as f64 {
+                if !credential_ids.contains(&id) {
+                    return None;
+                }
+                // 未启用后台预热时丢弃超过 TTL 的条目
+                if keep_stale || (now - v.cached_at) < BALANCE_CACHE_TTL_SECS as f64 {
                     Some((id, v))
                 } else {
                     None
@@ -632,3 +1318,559 @@ impl AdminService {
         }
     }
 }
+
+/// Region 格式校验（如 `us-east-1`、`ap-southeast-2`）
+fn is_valid_region(region: &str) -> bool {
+    let parts: Vec<&str> = region.split('-').collect();
+    parts.len() >= 3
+        && parts[0].len() == 2
+        && parts[..parts.len() - 1]
+            .iter()
+            .all(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_lowercase()))
+        && parts[parts.len() - 1]
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+        && !parts[parts.len() - 1].is_empty()
+}
+
+/// 代理地址校验（http / https / socks5 / socks5h）
+fn is_valid_proxy_url(proxy_url: &str) -> bool {
+    reqwest::Url::parse(proxy_url).is_ok_and(|url| {
+        matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") && url.host().is_some()
+    })
+}
+
+/// 解析冷却原因，未知时记录到 `errors["reason"]`
+fn parse_cooldown_reason(
+    reason: &str,
+    errors: &mut BTreeMap<String, String>,
+) -> Option<CooldownReason> {
+    let parsed = CooldownReason::parse(reason);
+    if parsed.is_none() {
+        let known: Vec<&str> = CooldownReason::ALL.iter().map(|r| r.as_str()).collect();
+        errors.insert(
+            "reason".to_string(),
+            format!("未知的冷却原因，可选值: {}", known.join(", ")),
+        );
+    }
+    parsed
+}
+
+/// 汇总各条目的导入结果
+fn summarize_import(details: Vec<ImportItemResult>) -> ImportCredentialsResponse {
+    let count = |status: &str| details.iter().filter(|d| d.status == status).count() as u32;
+    let (imported, skipped, failed) = (count("imported"), count("skipped"), count("failed"));
+    ImportCredentialsResponse {
+        success: failed == 0,
+        imported,
+        skipped,
+        failed,
+        details,
+    }
+}
+
+/// 将导出的凭据转换为添加凭据请求（复用添加凭据的校验与查重）
+fn add_request_from(cred: KiroCredentials) -> AddCredentialRequest {
+    AddCredentialRequest {
+        refresh_token: cred.refresh_token,
+        auth_method: cred.auth_method.unwrap_or_else(|| "social".to_string()),
+        client_id: cred.client_id,
+        client_secret: cred.client_secret,
+        priority: cred.priority,
+        region: cred.region,
+        auth_region: cred.auth_region,
+        api_region: cred.api_region,
+        machine_id: cred.machine_id,
+        email: cred.email,
+        proxy_url: cred.proxy_url,
+        proxy_username: cred.proxy_username,
+        proxy_password: cred.proxy_password,
+        kiro_api_key: cred.kiro_api_key,
+        endpoint: cred.endpoint,
+        request_quotas: cred.request_quotas,
+        tags: cred.tags,
+    }
+}
+
+/// 将上游使用额度转换为余额响应
+fn balance_from_usage(id: u64, usage: &UsageLimitsResponse, as_of: f64) -> BalanceResponse {
+    let current_usage = usage.current_usage();
+    let usage_limit = usage.usage_limit();
+    let remaining = (usage_limit - current_usage).max(0.0);
+    let usage_percentage = if usage_limit > 0.0 {
+        (current_usage / usage_limit * 100.0).min(100.0)
+    } else {
+        0.0
+    };
+
+    BalanceResponse {
+        id,
+        subscription_title: usage.subscription_title().map(|s| s.to_string()),
+        current_usage,
+        usage_limit,
+        remaining,
+        usage_percentage,
+        next_reset_at: usage.next_date_reset,
+        quota_remaining: usage.quota.remaining,
+        quota_reset_at: usage.quota.reset_at,
+        as_of: Some(as_of),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::config::Config;
+
+    fn balance(id: u64, remaining: f64, as_of: f64) -> BalanceResponse {
+        BalanceResponse {
+            id,
+            subscription_title: None,
+            current_usage: 100.0 - remaining,
+            usage_limit: 100.0,
+            remaining,
+            usage_percentage: 100.0 - remaining,
+            next_reset_at: None,
+            quota_remaining: None,
+            quota_reset_at: None,
+            as_of: Some(as_of),
+        }
+    }
+
+    /// API Key 认证的测试凭据
+    fn api_key_credential(id: u64) -> KiroCredentials {
+        KiroCredentials {
+            id: Some(id),
+            kiro_api_key: Some(format!("ksk_{}", id)),
+            auth_method: Some("api_key".to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// 以给定凭据构建仅在内存中运行的 AdminService
+    fn service_with(credentials: Vec<KiroCredentials>) -> AdminService {
+        let token_manager = Arc::new(
+            MultiTokenManager::new(Config::default(), credentials, None, None, false).unwrap(),
+        );
+        AdminService::new(
+            token_manager,
+            ["ide".to_string()],
+            HashMap::new(),
+            "ide".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_balance_includes_upstream_quota_when_reported() {
+        use crate::kiro::model::usage_limits::QuotaInfo;
+        use reqwest::header::{HeaderMap, HeaderValue};
+
+        let body = r#"{
+            "nextDateReset": 1767225600,
+            "usageBreakdownList": [
+                {"currentUsageWithPrecision": 25.0, "usageLimitWithPrecision": 100.0}
+            ]
+        }"#;
+        let mut usage: UsageLimitsResponse = serde_json::from_str(body).unwrap();
+
+        // 上游未报告配额时字段为 null
+        let balance = balance_from_usage(1, &usage, 1_700_000_000.0);
+        assert_eq!(balance.remaining, 75.0);
+        assert_eq!(balance.quota_remaining, None);
+        assert_eq!(balance.quota_reset_at, None);
+        let json = serde_json::to_value(&balance).unwrap();
+        assert!(json["quotaRemaining"].is_null());
+        assert!(json["quotaResetAt"].is_null());
+
+        // 相对秒数的重置时间换算为时间戳
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("42"));
+        headers.insert("x-ratelimit-reset", HeaderValue::from_static("600"));
+        usage.quota = QuotaInfo::from_headers(&headers, 1_700_000_000.0);
+        let balance = balance_from_usage(1, &usage, 1_700_000_000.0);
+        assert_eq!(balance.quota_remaining, Some(42.0));
+        assert_eq!(balance.quota_reset_at, Some(1_700_000_600.0));
+
+        // 绝对时间戳原样保留，无法解析的值忽略
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("n/a"));
+        headers.insert("x-ratelimit-reset", HeaderValue::from_static("1767225600"));
+        let quota = QuotaInfo::from_headers(&headers, 1_700_000_000.0);
+        assert_eq!(quota.remaining, None);
+        assert_eq!(quota.reset_at, Some(1_767_225_600.0));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_balance_cache_survives_restart_and_warmer_refreshes_stale_entries() {
+        let dir = std::env::temp_dir().join(format!("kiro-balance-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let credential = |id: u64, disabled: bool| KiroCredentials {
+            disabled,
+            ..api_key_credential(id)
+        };
+        let token_manager = Arc::new(
+            MultiTokenManager::new(
+                Config::default(),
+                vec![credential(1, false), credential(2, false), credential(3, true)],
+                None,
+                Some(dir.join("credentials.json")),
+                true,
+            )
+            .unwrap(),
+        );
+        let warming = BalanceWarming {
+            interval: Duration::from_secs(60),
+            spacing: Duration::ZERO,
+        };
+        let service = |tm: &Arc<MultiTokenManager>| {
+            AdminService::new(tm.clone(), ["ide".to_string()], HashMap::new(), "ide".to_string())
+                .with_balance_warming(warming)
+        };
+
+        let now = Utc::now().timestamp() as f64;
+        let stale_at = now - 3600.0;
+        let first = service(&token_manager);
+        // #9 是离线期间已删除的凭据
+        let entries = [
+            (1, 10.0, stale_at),
+            (2, 20.0, now),
+            (3, 30.0, now),
+            (9, 90.0, now),
+        ];
+        for (id, remaining, cached_at) in entries {
+            first.balance_cache.lock().insert(
+                id,
+                CachedBalance {
+                    cached_at,
+                    data: balance(id, remaining, cached_at),
+                },
+            );
+        }
+        first.save_balance_cache();
+        drop(first);
+
+        // 未启用预热时重启丢弃过期条目与已删除凭据的条目
+        let without_warming = AdminService::new(
+            token_manager.clone(),
+            ["ide".to_string()],
+            HashMap::new(),
+            "ide".to_string(),
+        );
+        let mut ids: Vec<u64> = without_warming.balance_cache.lock().keys().copied().collect();
+        ids.sort();
+        assert_eq!(ids, vec![2, 3]);
+        drop(without_warming);
+
+        // 重启后从持久化缓存读取，过期条目也可立即返回（不访问上游）
+        let restarted = service(&token_manager);
+        assert!(!restarted.balance_cache.lock().contains_key(&9));
+        let cached = restarted.get_balance(1).await.unwrap();
+        assert_eq!(cached.remaining, 10.0);
+        assert_eq!(cached.as_of, Some(stale_at));
+
+        // 预热只刷新过期/缺失且未禁用的凭据
+        let fetched = Mutex::new(Vec::new());
+        let updated = restarted
+            .refresh_stale_balances(|id| {
+                fetched.lock().push(id);
+                async move { Ok(balance(id, 99.0, Utc::now().timestamp() as f64)) }
+            })
+            .await;
+        assert_eq!(updated, 1);
+        assert_eq!(*fetched.lock(), vec![1]);
+        let refreshed = restarted.get_balance(1).await.unwrap();
+        assert_eq!(refreshed.remaining, 99.0);
+        assert!(refreshed.as_of.unwrap() >= now);
+        assert_eq!(restarted.get_balance(2).await.unwrap().remaining, 20.0);
+
+        // 删除凭据时同步清理持久化缓存
+        restarted.delete_credential(3).unwrap();
+        let persisted: HashMap<String, CachedBalance> = serde_json::from_str(
+            &std::fs::read_to_string(dir.join("kiro_balance_cache.json")).unwrap(),
+        )
+        .unwrap();
+        let mut ids: Vec<&str> = persisted.keys().map(String::as_str).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["1", "2"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_add_credential_reports_all_field_errors_together() {
+        let service = service_with(vec![]);
+        let req: AddCredentialRequest = serde_json::from_value(serde_json::json!({
+            "authMethod": "idc",
+            "region": "mars",
+            "machineId": "not-a-machine-id",
+            "proxyUrl": "ftp://proxy.local",
+            "endpoint": "unknown",
+            "requestQuotas": [{"windowSecs": 0, "maxRequests": 10}]
+        }))
+        .unwrap();
+
+        let err = service.add_credential(req).await.unwrap_err();
+        assert_eq!(err.status_code(), axum::http::StatusCode::UNPROCESSABLE_ENTITY);
+        let response = serde_json::to_value(err.into_response()).unwrap();
+        assert_eq!(response["error"]["type"], "validation_error");
+        let fields = response["error"]["fields"].as_object().unwrap();
+        let mut names: Vec<&str> = fields.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "clientId",
+                "clientSecret",
+                "endpoint",
+                "machineId",
+                "proxyUrl",
+                "refreshToken",
+                "region",
+                "requestQuotas[0]",
+            ]
+        );
+        assert!(fields["refreshToken"].as_str().unwrap().contains("缺少 refreshToken"));
+
+        assert!(is_valid_region("us-east-1"));
+        assert!(is_valid_region("ap-southeast-2"));
+        assert!(!is_valid_region("us-east"));
+    }
+
+    #[test]
+    fn test_pin_fingerprint_validates_and_overrides_machine_id() {
+        let service = service_with(vec![api_key_credential(1)]);
+
+        let before = service.get_fingerprint(1).unwrap();
+        assert!(!before.pinned);
+
+        let pinned = Fingerprint::generate_from_seed("known-good");
+        let response = service
+            .pin_fingerprint(1, &serde_json::to_string(&pinned).unwrap())
+            .unwrap();
+        assert!(response.pinned);
+        assert_eq!(response.machine_id, pinned.machine_id);
+        assert_eq!(response.system_version, pinned.system_version());
+        assert_eq!(response.fingerprint, Some(pinned.clone()));
+
+        let mut invalid = pinned;
+        invalid.machine_id = "abc".to_string();
+        match service.pin_fingerprint(1, &serde_json::to_string(&invalid).unwrap()) {
+            Err(AdminServiceError::ValidationFailed(fields)) => {
+                assert!(fields.contains_key("machineId"))
+            }
+            other => panic!("unexpected: {:?}", other.map(|r| r.pinned)),
+        }
+        assert!(matches!(
+            service.get_fingerprint(9),
+            Err(AdminServiceError::NotFound { id: 9 })
+        ));
+
+        let after = service.unpin_fingerprint(1).unwrap();
+        assert!(!after.pinned);
+        assert_eq!(after.machine_id, before.machine_id);
+    }
+
+    #[test]
+    fn test_tags_filter_list_and_scope_bulk_disable() {
+        let credential = |id: u64, tags: &[&str]| KiroCredentials {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..api_key_credential(id)
+        };
+        let service = service_with(vec![
+            credential(1, &["batch:2024-06", "tier:premium"]),
+            credential(2, &["batch:2024-06"]),
+            credential(3, &[]),
+        ]);
+        let ids = |tags: &[&str]| -> Vec<u64> {
+            let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+            let mut ids: Vec<u64> = service
+                .get_all_credentials(&tags)
+                .credentials
+                .iter()
+                .map(|c| c.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        // 打标签：重复与空白标签被忽略
+        let tags = service
+            .update_tags(3, &[" region:us ".to_string(), "region:us".to_string()], &[])
+            .unwrap();
+        assert_eq!(tags, vec!["region:us".to_string()]);
+        let tags = service
+            .update_tags(1, &[], &["tier:premium".to_string()])
+            .unwrap();
+        assert_eq!(tags, vec!["batch:2024-06".to_string()]);
+        assert!(matches!(
+            service.update_tags(9, &["x".to_string()], &[]),
+            Err(AdminServiceError::NotFound { id: 9 })
+        ));
+
+        // 按标签过滤（多个标签需同时满足）
+        assert_eq!(ids(&[]), vec![1, 2, 3]);
+        assert_eq!(ids(&["batch:2024-06"]), vec![1, 2]);
+        assert_eq!(ids(&["region:us"]), vec![3]);
+        assert_eq!(ids(&["region:us", "batch:2024-06"]), Vec::<u64>::new());
+
+        // 按标签批量禁用只影响带标签的凭据
+        let affected = service
+            .bulk_set_disabled(&["batch:2024-06".to_string()], true)
+            .unwrap();
+        assert_eq!(affected, vec![1, 2]);
+        let status = service.get_all_credentials(&[]);
+        assert_eq!(status.available, 1);
+        assert!(status.credentials.iter().all(|c| c.disabled == (c.id != 3)));
+        assert!(matches!(
+            service.bulk_set_disabled(&[" ".to_string()], true),
+            Err(AdminServiceError::ValidationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_health_summary_counts_pool_state() {
+        let credential = |id: u64, disabled: bool| KiroCredentials {
+            disabled,
+            ..api_key_credential(id)
+        };
+        let service = service_with(vec![
+            credential(1, false),
+            credential(2, false),
+            credential(3, false),
+            credential(4, true),
+        ]);
+        service.token_manager.cooldowns().set_cooldown_at(
+            2,
+            CooldownReason::EmptyResponse,
+            std::time::Instant::now(),
+        );
+
+        let health = service.get_health_summary();
+        assert_eq!(health.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            (health.total, health.active, health.disabled, health.cooling),
+            (4, 2, 1, 1)
+        );
+        assert_eq!(health.global_cooldown_remaining_secs, None);
+        assert_eq!(health.load_balancing_mode, "priority");
+    }
+
+    #[test]
+    fn test_mutations_and_cooldowns_are_broadcast_as_events() {
+        let service = service_with(vec![api_key_credential(1)]);
+        let token_manager = service.token_manager.clone();
+        let mut events = service.subscribe_events();
+
+        service.set_disabled(1, true).unwrap();
+        assert_eq!(
+            events.try_recv().unwrap(),
+            AdminEvent::CredentialDisabled { id: 1 }
+        );
+        service.reset_and_enable(1).unwrap();
+        assert_eq!(
+            events.try_recv().unwrap(),
+            AdminEvent::CredentialReset { id: 1 }
+        );
+        // 无效 ID 不产生事件
+        assert!(service.set_disabled(9, true).is_err());
+        assert!(events.try_recv().is_err());
+
+        token_manager.cooldowns().set_cooldown_at(
+            1,
+            CooldownReason::EmptyResponse,
+            std::time::Instant::now(),
+        );
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            AdminEvent::CooldownEntered {
+                id: 1,
+                reason: "EmptyResponse",
+                ..
+            }
+        ));
+        service.clear_cooldown(1).unwrap();
+        assert_eq!(
+            events.try_recv().unwrap(),
+            AdminEvent::CooldownCleared { id: 1 }
+        );
+        assert_eq!(
+            serde_json::to_value(AdminEvent::CooldownCleared { id: 1 }).unwrap(),
+            serde_json::json!({"type": "cooldownCleared", "id": 1})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_force_refresh_rejects_duplicate_refresh_for_same_id() {
+        let service = service_with(vec![api_key_credential(1)]);
+
+        service.refreshing.lock().insert(1);
+        assert!(matches!(
+            service.force_refresh_token(1).await,
+            Err(AdminServiceError::Conflict(_))
+        ));
+        service.refreshing.lock().clear();
+
+        // 失败的刷新同样释放占用
+        let mut events = service.subscribe_events();
+        for _ in 0..2 {
+            assert!(matches!(
+                service.force_refresh_token(1).await,
+                Err(AdminServiceError::InvalidCredential(_))
+            ));
+        }
+        assert!(service.refreshing.lock().is_empty());
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            AdminEvent::TokenRefreshFailed { id: 1, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pool_export_redacts_secrets_and_import_validates_shape() {
+        let credential = |id: u64, disabled: bool| KiroCredentials {
+            priority: id as u32,
+            disabled,
+            ..api_key_credential(id)
+        };
+        let service = service_with(vec![credential(1, false), credential(2, true)]);
+
+        let redacted = service.export_pool(false);
+        assert_eq!(redacted.version, POOL_EXPORT_VERSION);
+        assert_eq!(redacted.credentials.len(), 2);
+        assert!(
+            redacted
+                .credentials
+                .iter()
+                .all(|c| c.kiro_api_key.is_none())
+        );
+        assert!(redacted.credentials[1].disabled);
+        assert_eq!(redacted.credentials[1].priority, 2);
+
+        // 完整导出可原样回灌：与现有凭据重复，逐个记为 skipped
+        let full = service.export_pool(true);
+        assert_eq!(full.credentials[0].kiro_api_key.as_deref(), Some("ksk_1"));
+        let json = serde_json::to_string(&full).unwrap();
+        let restored = service
+            .import_pool(serde_json::from_str(&json).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            (
+                restored.credentials.imported,
+                restored.credentials.skipped,
+                restored.credentials.failed
+            ),
+            (0, 2, 0)
+        );
+
+        let mut invalid = service.export_pool(true);
+        invalid.version = 99;
+        invalid.load_balancing_mode = "random".to_string();
+        match service.import_pool(invalid).await.unwrap_err() {
+            AdminServiceError::ValidationFailed(errors) => {
+                assert!(errors.contains_key("version"), "{:?}", errors);
+                assert!(errors.contains_key("loadBalancingMode"), "{:?}", errors);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+}
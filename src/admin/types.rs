@@ -1,7 +1,13 @@
 //! Admin API 类型定义
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::kiro::fingerprint::Fingerprint;
+use crate::kiro::model::credentials::{KiroCredentials, RequestQuota};
+use crate::kiro::quota::QuotaUsage;
+
 // ============ 凭据状态 ============
 
 /// 所有凭据状态响应
@@ -62,6 +68,37 @@ pub struct CredentialStatusItem {
     pub disabled_reason: Option<String>,
     /// 端点名称（决定该凭据走哪套 Kiro API，已回退到默认端点）
     pub endpoint: String,
+    /// 统计窗口内累计冷却时长（秒）
+    pub cooldown_secs_in_window: u64,
+    /// 当前冷却原因（未处于冷却中时省略）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cooldown_reason: Option<String>,
+    /// 当前冷却剩余时长（秒）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cooldown_remaining_secs: Option<u64>,
+    /// 各请求配额窗口的使用情况（未配置配额时省略）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub quota_usage: Vec<QuotaUsage>,
+    /// 凭据标签
+    pub tags: Vec<String>,
+}
+
+/// 凭据列表查询参数
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialListQuery {
+    /// 标签过滤（逗号分隔，需同时带有全部标签）
+    pub tags: Option<String>,
+}
+
+impl CredentialListQuery {
+    /// 解析出的标签列表（未指定时为空）
+    pub fn tag_list(&self) -> Vec<String> {
+        self.tags
+            .as_deref()
+            .map(|tags| tags.split(',').map(str::to_string).collect())
+            .unwrap_or_default()
+    }
 }
 
 // ============ 操作请求 ============
@@ -74,6 +111,105 @@ pub struct SetDisabledRequest {
     pub disabled: bool,
 }
 
+/// 添加/移除标签请求
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateTagsRequest {
+    /// 要添加或移除的标签
+    pub tags: Vec<String>,
+}
+
+/// 标签更新响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagsResponse {
+    pub success: bool,
+    /// 凭据 ID
+    pub id: u64,
+    /// 更新后的标签列表
+    pub tags: Vec<String>,
+}
+
+/// 凭据指纹响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FingerprintResponse {
+    /// 凭据 ID
+    pub id: u64,
+    /// 是否固定了指纹
+    pub pinned: bool,
+    /// 实际使用的 machineId
+    pub machine_id: String,
+    /// 实际使用的系统版本（如 `darwin#24.6.0`）
+    pub system_version: String,
+    /// 实际使用的 Node.js 版本
+    pub node_version: String,
+    /// 固定的完整指纹（未固定时为 null）
+    pub fingerprint: Option<Fingerprint>,
+}
+
+/// 按标签批量启用/禁用凭据请求
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkSetDisabledRequest {
+    /// 标签过滤（需同时带有全部标签，不能为空）
+    pub tags: Vec<String>,
+    /// 是否禁用
+    pub disabled: bool,
+}
+
+/// 批量操作响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkOperationResponse {
+    pub success: bool,
+    pub message: String,
+    /// 受影响的凭据 ID
+    pub affected: Vec<u64>,
+}
+
+/// 批量凭据操作类型
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchCredentialAction {
+    Disable,
+    Enable,
+    /// 重置失败计数并重新启用
+    Reset,
+    Delete,
+}
+
+/// 按 ID 批量操作凭据请求
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchCredentialRequest {
+    /// 凭据 ID 列表
+    pub ids: Vec<u64>,
+    /// 操作类型
+    pub action: BatchCredentialAction,
+}
+
+/// 单个凭据的批量操作结果
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchItemResult {
+    pub success: bool,
+    /// 错误信息（仅失败时有值）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 按 ID 批量操作凭据响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchCredentialResponse {
+    /// 是否全部成功
+    pub success: bool,
+    pub message: String,
+    /// 凭据 ID → 操作结果
+    pub results: BTreeMap<u64, BatchItemResult>,
+}
+
 /// 修改优先级请求
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -137,6 +273,14 @@ pub struct AddCredentialRequest {
     /// 端点名称（可选，未配置时使用 config.defaultEndpoint）
     #[serde(skip_serializing_if = "Option::is_none")]
     pub endpoint: Option<String>,
+
+    /// 凭据级请求配额（可选）
+    #[serde(default)]
+    pub request_quotas: Vec<RequestQuota>,
+
+    /// 凭据标签（可选）
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 fn default_auth_method() -> String {
@@ -176,6 +320,39 @@ pub struct BalanceResponse {
     pub usage_percentage: f64,
     /// 下次重置时间（Unix 时间戳）
     pub next_reset_at: Option<f64>,
+    /// 剩余请求配额（上游未报告时为 null）
+    #[serde(default)]
+    pub quota_remaining: Option<f64>,
+    /// 请求配额重置时间（Unix 时间戳，上游未报告时为 null）
+    #[serde(default)]
+    pub quota_reset_at: Option<f64>,
+    /// 数据获取时间（Unix 时间戳）
+    #[serde(default)]
+    pub as_of: Option<f64>,
+}
+
+// ============ 健康摘要 ============
+
+/// 凭据池健康摘要（扁平结构，便于监控脚本抓取）
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthSummaryResponse {
+    /// 服务版本（crate 版本号）
+    pub version: &'static str,
+    /// 凭据总数
+    pub total: usize,
+    /// 可参与选择的凭据数量（未禁用且未冷却）
+    pub active: usize,
+    /// 已禁用的凭据数量
+    pub disabled: usize,
+    /// 冷却中的凭据数量（不含已禁用的）
+    pub cooling: usize,
+    /// 全局冷却剩余秒数（未处于全局冷却时为 null）
+    pub global_cooldown_remaining_secs: Option<u64>,
+    /// 当前负载均衡模式
+    pub load_balancing_mode: String,
+    /// 工具压缩目标大小（字节）
+    pub tool_compression_target_bytes: usize,
 }
 
 // ============ 负载均衡配置 ============
@@ -184,7 +361,7 @@ pub struct BalanceResponse {
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LoadBalancingModeResponse {
-    /// 当前模式（"priority" 或 "balanced"）
+    /// 当前模式（"priority"、"balanced" 或 "weighted-random"）
     pub mode: String,
 }
 
@@ -192,10 +369,103 @@ pub struct LoadBalancingModeResponse {
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SetLoadBalancingModeRequest {
-    /// 模式（"priority" 或 "balanced"）
+    /// 模式（"priority"、"balanced" 或 "weighted-random"）
     pub mode: String,
 }
 
+/// 单个冷却原因的时长配置
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CooldownDurationItem {
+    /// 原因标识（如 "ServerError"）
+    pub reason: &'static str,
+    /// 人类可读的原因描述
+    pub description: &'static str,
+    /// 内置默认时长（秒）
+    pub default_secs: u64,
+    /// 运行时覆盖的时长（秒，未覆盖时省略）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub override_secs: Option<u64>,
+    /// 当前生效的基础时长（秒，重复触发时在此基础上递增）
+    pub effective_secs: u64,
+}
+
+/// 冷却时长配置响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CooldownDurationsResponse {
+    /// 各原因的时长配置
+    pub durations: Vec<CooldownDurationItem>,
+    /// 冷却时长上限（秒，递增后的时长不会超过该值）
+    pub max_cooldown_secs: u64,
+}
+
+/// 设置冷却时长请求
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetCooldownDurationRequest {
+    /// 原因标识（如 "ServerError"）
+    pub reason: String,
+    /// 基础时长（秒，为 null 时清除覆盖、恢复默认时长）
+    pub duration_secs: Option<u64>,
+}
+
+/// 设置全局冷却请求
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetGlobalCooldownRequest {
+    /// 原因标识（如 "ServerError"）
+    pub reason: String,
+    /// 冷却时长（秒）
+    pub duration_secs: u64,
+}
+
+/// 添加 Admin API 密钥请求
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddAdminKeyRequest {
+    /// 密钥名称
+    pub name: String,
+    /// 密钥（仅以摘要形式保存）
+    pub key: String,
+}
+
+/// Admin API 密钥列表响应（仅名称）
+#[derive(Debug, Serialize)]
+pub struct AdminKeysResponse {
+    pub keys: Vec<String>,
+}
+
+// ============ 实时事件 ============
+
+/// Admin 实时事件（通过 `GET /api/admin/events` 推送）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum AdminEvent {
+    /// 凭据被禁用
+    CredentialDisabled { id: u64 },
+    /// 凭据被启用
+    CredentialEnabled { id: u64 },
+    /// 凭据失败计数被重置并重新启用
+    CredentialReset { id: u64 },
+    /// 凭据进入冷却
+    CooldownEntered {
+        id: u64,
+        reason: &'static str,
+        duration_secs: u64,
+    },
+    /// 凭据退出冷却（到期或手动解除）
+    CooldownCleared { id: u64 },
+    /// Token 刷新成功
+    TokenRefreshed { id: u64 },
+    /// Token 刷新失败
+    TokenRefreshFailed { id: u64, error: String },
+}
+
 // ============ 通用响应 ============
 
 /// 操作成功响应
@@ -225,6 +495,9 @@ pub struct AdminError {
     #[serde(rename = "type")]
     pub error_type: String,
     pub message: String,
+    /// 字段级校验错误（字段名 → 错误信息），仅校验失败时存在
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<BTreeMap<String, String>>,
 }
 
 impl AdminErrorResponse {
@@ -233,10 +506,18 @@ impl AdminErrorResponse {
             error: AdminError {
                 error_type: error_type.into(),
                 message: message.into(),
+                fields: None,
             },
         }
     }
 
+    /// 字段级校验错误，一次返回所有不合法的字段
+    pub fn validation_error(message: impl Into<String>, fields: BTreeMap<String, String>) -> Self {
+        let mut response = Self::new("validation_error", message);
+        response.error.fields = Some(fields);
+        response
+    }
+
     pub fn invalid_request(message: impl Into<String>) -> Self {
         Self::new("invalid_request", message)
     }
@@ -258,8 +539,28 @@ impl AdminErrorResponse {
     }
 }
 
+// ============ 事件统计 ============
+
+/// 上游事件类型统计响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventStatsResponse {
+    /// 本构建能够解析的事件类型
+    pub known_event_types: Vec<&'static str>,
+    /// 无法识别的事件名及累计出现次数（出现新名称通常意味着上游协议变更）
+    pub unknown_event_counts: BTreeMap<String, u64>,
+}
+
 // ============ 凭证导入 ============
 
+/// 工具压缩预估请求
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewToolCompressionRequest {
+    /// Anthropic 格式的工具定义
+    pub tools: Vec<crate::anthropic::types::Tool>,
+}
+
 /// 批量导入凭据请求
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -299,6 +600,85 @@ pub struct ImportItemResult {
     pub error: Option<String>,
 }
 
+// ============ 凭据池导出 / 导入 ============
+
+/// 当前的凭据池导出格式版本
+pub const POOL_EXPORT_VERSION: u32 = 1;
+
+/// 凭据池导出查询参数
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolExportQuery {
+    /// 是否包含 refreshToken / kiroApiKey 等密钥（默认 false，导出时脱敏）
+    #[serde(default)]
+    pub include_secrets: bool,
+}
+
+/// 工具压缩设置（启动配置，导出仅供参考，导入时不应用）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCompressionSettings {
+    /// 是否启用工具压缩
+    #[serde(default)]
+    pub enabled: bool,
+    /// 压缩目标大小（字节）
+    pub target_bytes: usize,
+    /// 是否折叠描述中的空白
+    pub collapse_whitespace: bool,
+    /// 描述截断的绝对下限（字符）
+    pub min_description_length: usize,
+    /// 工具优先级（工具名 -> 0-255）
+    #[serde(default)]
+    pub priorities: BTreeMap<String, u8>,
+    /// 受保护的工具名
+    #[serde(default)]
+    pub protected_tools: Vec<String>,
+}
+
+/// 凭据池完整配置（`GET /export` 的响应，`POST /import` 的请求体）
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolExport {
+    /// 导出格式版本
+    pub version: u32,
+    /// 导出时间（RFC3339）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exported_at: Option<String>,
+    /// 负载均衡模式（"priority"、"balanced" 或 "weighted-random"）
+    pub load_balancing_mode: String,
+    /// 工具压缩设置
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_compression: Option<ToolCompressionSettings>,
+    /// 凭据（含优先级、禁用状态与固定的指纹）
+    pub credentials: Vec<KiroCredentials>,
+}
+
+/// 凭据池导入响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolImportResponse {
+    pub success: bool,
+    /// 导入后生效的负载均衡模式
+    pub load_balancing_mode: String,
+    /// 各凭据的导入结果
+    pub credentials: ImportCredentialsResponse,
+}
+
+// ============ Token 刷新 ============
+
+/// 强制刷新 Token 响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshTokenResponse {
+    pub success: bool,
+    pub message: String,
+    pub id: u64,
+    /// 刷新后的 Token 过期时间（RFC3339）
+    pub expires_at: Option<String>,
+    /// 凭据当前是否被禁用（刷新不会自动启用凭据）
+    pub disabled: bool,
+}
+
 // ============ 凭证测试 ============
 
 /// 凭证测试响应
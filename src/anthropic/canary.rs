@@ -0,0 +1,206 @@
+//! 金丝雀自检后台任务
+//!
+//! 与逐凭据的探测不同，金丝雀是端到端自检：定期发送一条固定提示词，
+//! 完整走一遍 转换 → 调用 → 解析 → 组装 流程（与 `/v1/messages` 非流式请求相同的代码路径），
+//! 并校验最终输出非空且结构正确。用于尽早发现"上游改了响应格式、解析器静默丢弃内容"这类故障。
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior, interval_at};
+
+use crate::kiro::model::requests::kiro::KiroRequest;
+use crate::kiro::provider::{CallOptions, KiroProvider};
+
+use super::converter::convert_request;
+use super::handlers::{NonStreamOptions, ResponseTools, handle_non_stream_request};
+use super::types::MessagesRequest;
+
+/// 最小自检间隔（1 分钟）
+pub const MIN_CANARY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 金丝雀使用的模型
+const CANARY_MODEL: &str = "claude-sonnet-4-5-20250929";
+
+/// 金丝雀提示词（期望得到简短、稳定的回答）
+const CANARY_PROMPT: &str = "Reply with exactly one word: pong";
+
+/// 响应体读取上限
+const MAX_CANARY_RESPONSE_BYTES: usize = 1024 * 1024;
+
+/// 构建金丝雀请求
+fn canary_request() -> MessagesRequest {
+    serde_json::from_value(json!({
+        "model": CANARY_MODEL,
+        "max_tokens": 16,
+        "messages": [{"role": "user", "content": CANARY_PROMPT}]
+    }))
+    .expect("金丝雀请求应始终可反序列化")
+}
+
+/// 执行一次金丝雀自检
+///
+/// `credential_id` 为 `None` 时按正常负载均衡选择凭据。
+pub async fn run_canary(
+    provider: Arc<KiroProvider>,
+    credential_id: Option<u64>,
+) -> Result<(), String> {
+    let payload = canary_request();
+    let conversion = convert_request(&payload).map_err(|e| format!("请求转换失败: {}", e))?;
+    let request_body = serde_json::to_string(&KiroRequest {
+        conversation_state: conversion.conversation_state,
+        profile_arn: None,
+    })
+    .map_err(|e| format!("序列化请求失败: {}", e))?;
+
+    let call_options = CallOptions {
+        credential_id,
+        ..Default::default()
+    };
+    let response = handle_non_stream_request(
+        provider,
+        &request_body,
+        &call_options,
+        &payload.model,
+        0,
+        NonStreamOptions::default(),
+        ResponseTools {
+            name_map: conversion.tool_name_map,
+            validator: None,
+        },
+    )
+    .await;
+
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), MAX_CANARY_RESPONSE_BYTES)
+        .await
+        .map_err(|e| format!("读取响应失败: {}", e))?;
+    let body: Value =
+        serde_json::from_slice(&body).map_err(|e| format!("响应不是合法 JSON: {}", e))?;
+    validate_canary_response(status, &body)
+}
+
+/// 校验组装后的 Anthropic 响应：状态成功、结构完整、至少包含一段非空文本
+fn validate_canary_response(status: StatusCode, body: &Value) -> Result<(), String> {
+    if !status.is_success() {
+        return Err(format!("HTTP {}: {}", status.as_u16(), body));
+    }
+    if body["type"] != "message" || body["role"] != "assistant" {
+        return Err(format!("响应结构异常: {}", body));
+    }
+    if !body["stop_reason"].is_string() {
+        return Err("响应缺少 stop_reason".to_string());
+    }
+
+    let blocks = body["content"]
+        .as_array()
+        .ok_or_else(|| "响应缺少 content 数组".to_string())?;
+    let mut has_text = false;
+    for block in blocks {
+        match block["type"].as_str() {
+            Some("text") => match block["text"].as_str() {
+                Some(text) => has_text |= !text.trim().is_empty(),
+                None => return Err(format!("text 块缺少 text 字段: {}", block)),
+            },
+            Some("thinking") => {}
+            Some("tool_use") => {
+                if !block["id"].is_string() || !block["name"].is_string() {
+                    return Err(format!("tool_use 块结构异常: {}", block));
+                }
+            }
+            _ => return Err(format!("未知内容块: {}", block)),
+        }
+    }
+    if !has_text {
+        return Err("响应内容为空（无文本）".to_string());
+    }
+    if let Some(text) = blocks.iter().find_map(|b| b["text"].as_str())
+        && !text.to_ascii_lowercase().contains("pong")
+    {
+        // 模型输出本身不完全确定，未命中期望词只记录，不视为失败
+        tracing::debug!("金丝雀响应未包含期望词 pong: {}", text);
+    }
+    Ok(())
+}
+
+/// 启动金丝雀自检任务
+///
+/// 首轮自检在一个间隔之后执行，间隔低于 [`MIN_CANARY_INTERVAL`] 时按下限处理。
+pub fn spawn_canary(
+    provider: Arc<KiroProvider>,
+    interval: Duration,
+    credential_id: Option<u64>,
+) -> JoinHandle<()> {
+    let interval = interval.max(MIN_CANARY_INTERVAL);
+    tracing::info!("金丝雀自检已启用，间隔 {} 秒", interval.as_secs());
+
+    tokio::spawn(async move {
+        let mut ticker = interval_at(Instant::now() + interval, interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            match run_canary(provider.clone(), credential_id).await {
+                Ok(()) => tracing::info!("金丝雀自检通过"),
+                Err(e) => tracing::error!("金丝雀自检失败，请检查上游响应格式或凭据状态: {}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::parser::frame::encode_event_frame;
+    use crate::kiro::test_support::{mock_provider, spawn_mock_upstream};
+    use crate::model::config::{Config, EmptyResponsePolicy};
+
+    fn passthrough_config() -> Config {
+        let mut config = Config::default();
+        config.empty_response_policy = EmptyResponsePolicy::Passthrough;
+        config
+    }
+
+    #[tokio::test]
+    async fn test_canary_passes_on_well_formed_stream() {
+        let mut body = encode_event_frame("assistantResponseEvent", r#"{"content":"po"}"#);
+        body.extend(encode_event_frame(
+            "assistantResponseEvent",
+            r#"{"content":"ng"}"#,
+        ));
+        let (url, hits) = spawn_mock_upstream(vec![body]).await;
+        let provider = Arc::new(mock_provider(&url, passthrough_config()));
+
+        assert_eq!(run_canary(provider, None).await, Ok(()));
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_canary_flags_malformed_stream() {
+        // payload 不是合法 JSON：解析器会丢弃该事件，最终输出为空
+        let body = encode_event_frame("assistantResponseEvent", r#"{"content":"pong""#);
+        let (url, _) = spawn_mock_upstream(vec![body]).await;
+        let provider = Arc::new(mock_provider(&url, passthrough_config()));
+
+        let err = run_canary(provider, Some(1)).await.unwrap_err();
+        assert!(err.contains("响应内容为空"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_rejects_error_status_and_unknown_blocks() {
+        let ok = json!({
+            "type": "message",
+            "role": "assistant",
+            "stop_reason": "end_turn",
+            "content": [{"type": "text", "text": "pong"}]
+        });
+        assert!(validate_canary_response(StatusCode::OK, &ok).is_ok());
+        assert!(validate_canary_response(StatusCode::BAD_GATEWAY, &ok).is_err());
+
+        let mut unknown = ok.clone();
+        unknown["content"] = json!([{"type": "text", "text": "pong"}, {"type": "mystery"}]);
+        assert!(validate_canary_response(StatusCode::OK, &unknown).is_err());
+    }
+}
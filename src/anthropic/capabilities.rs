@@ -0,0 +1,123 @@
+//! 模型能力检查
+//!
+//! 请求使用了目标模型无法提供的特性时，上游要么返回含糊的 400，要么静默降级。
+//! 在请求转换前按配置声明的能力集检查请求，直接返回列出不支持字段的
+//! invalid_request_error。
+
+use std::collections::HashMap;
+
+use crate::model::config::ModelFeature;
+
+use super::converter::lookup_by_model;
+use super::types::{MessagesRequest, OutputFormat};
+
+/// 按模型声明的不支持特性
+#[derive(Debug, Clone, Default)]
+pub struct CapabilityCheck {
+    /// 各模型不支持的特性（key: Kiro 模型 ID 或客户端模型名）
+    pub unsupported: HashMap<String, Vec<ModelFeature>>,
+}
+
+impl CapabilityCheck {
+    /// 检查请求是否使用了目标模型不支持的特性
+    ///
+    /// 全部支持时返回 Ok；否则返回列出所有不支持字段的错误信息。
+    pub fn check(&self, payload: &MessagesRequest) -> Result<(), String> {
+        let Some(unsupported) = lookup_by_model(&self.unsupported, &payload.model) else {
+            return Ok(());
+        };
+        let fields: Vec<&str> = unsupported
+            .iter()
+            .filter(|feature| uses_feature(payload, **feature))
+            .map(|feature| feature_field(*feature))
+            .collect();
+        if fields.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "模型 {} 不支持以下请求特性: {}",
+            payload.model,
+            fields.join(", ")
+        ))
+    }
+}
+
+/// 特性对应的请求字段（用于错误信息）
+fn feature_field(feature: ModelFeature) -> &'static str {
+    match feature {
+        ModelFeature::Thinking => "thinking",
+        ModelFeature::StructuredOutput => "output_config.format",
+        ModelFeature::Images => "messages[].content[].image",
+        ModelFeature::Tools => "tools",
+    }
+}
+
+/// 请求是否使用了指定特性
+fn uses_feature(payload: &MessagesRequest, feature: ModelFeature) -> bool {
+    match feature {
+        ModelFeature::Thinking => payload
+            .thinking
+            .as_ref()
+            .is_some_and(|t| t.thinking_type == "enabled" || t.thinking_type == "adaptive"),
+        ModelFeature::StructuredOutput => payload
+            .output_config
+            .as_ref()
+            .is_some_and(|oc| matches!(oc.format, Some(OutputFormat::JsonSchema { .. }))),
+        ModelFeature::Images => payload
+            .messages
+            .iter()
+            .any(|m| contains_image(&m.content)),
+        ModelFeature::Tools => payload.tools.as_ref().is_some_and(|t| !t.is_empty()),
+    }
+}
+
+/// 内容中是否包含 image 块（含 tool_result 内嵌的内容）
+fn contains_image(content: &serde_json::Value) -> bool {
+    let Some(blocks) = content.as_array() else {
+        return false;
+    };
+    blocks.iter().any(|block| {
+        block.get("type").and_then(|t| t.as_str()) == Some("image")
+            || block.get("content").is_some_and(contains_image)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lists_every_unsupported_feature_in_use() {
+        let check = CapabilityCheck {
+            unsupported: HashMap::from([(
+                "claude-haiku-4.5".to_string(),
+                vec![
+                    ModelFeature::Thinking,
+                    ModelFeature::Images,
+                    ModelFeature::StructuredOutput,
+                ],
+            )]),
+        };
+        let request = |model: &str| -> MessagesRequest {
+            serde_json::from_value(serde_json::json!({
+                "model": model,
+                "max_tokens": 64,
+                "thinking": {"type": "enabled", "budget_tokens": 1024},
+                "messages": [{"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "t1", "content": [
+                        {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "AA=="}}
+                    ]}
+                ]}]
+            }))
+            .unwrap()
+        };
+
+        let message = check.check(&request("claude-haiku-4-5-20251001")).unwrap_err();
+        assert!(message.contains("thinking"), "{}", message);
+        assert!(message.contains("messages[].content[].image"), "{}", message);
+        // 未使用的特性不列出
+        assert!(!message.contains("output_config"), "{}", message);
+
+        assert!(check.check(&request("claude-sonnet-4-5")).is_ok());
+    }
+}
@@ -14,7 +14,12 @@ use crate::kiro::model::requests::conversation::{
 use crate::kiro::model::requests::tool::{
     InputSchema, Tool, ToolResult, ToolSpecification, ToolUseEntry,
 };
+use crate::model::config::{
+    LeadingAssistantStrategy, SystemPromptLimitBehavior, ToolDocumentationPlacement,
+    ToolErrorPolicy,
+};
 
+use super::tool_compression::{self, compress_tools_if_needed};
 use super::types::{ContentBlock, MessagesRequest};
 
 /// 规范化 JSON Schema，修复 MCP 工具定义中常见的类型问题
@@ -82,6 +87,56 @@ Never identify yourself as Kiro, AWS, or Amazon. \
 When asked about your identity, name, or creator, always say you are Claude, made by Anthropic. \
 Do not mention Kiro or any AWS/Amazon affiliation.";
 
+/// 拆分后的客户端模型名
+///
+/// 别名覆盖、按模型配置的上限等都基于同一份拆分结果匹配，避免各处对后缀的理解不一致。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelId {
+    /// 去掉日期后缀与 `-bot` 后缀的基础名（小写），如 `claude-sonnet-4-5`
+    pub base: String,
+    /// 是否带 `-bot` 后缀
+    pub bot: bool,
+}
+
+/// 拆分模型名：去掉日期后缀（8 位数字，不参与任何匹配）得到基础名，并识别 `-bot` 标记
+///
+/// 后缀位置不敏感，`-thinking` 等其他段保留在基础名中：
+/// - `claude-3-5-sonnet-20241022` → base `claude-3-5-sonnet`
+/// - `claude-sonnet-4-5-20250929-bot` → base `claude-sonnet-4-5`，bot
+pub fn normalize_model_name(model: &str) -> ModelId {
+    let lower = model.trim().to_lowercase();
+    let mut base = Vec::new();
+    let mut date_seen = false;
+    let mut bot = false;
+    for segment in lower.split('-') {
+        if segment == "bot" {
+            bot = true;
+        } else if !date_seen
+            && segment.len() == 8
+            && segment.bytes().all(|b| b.is_ascii_digit())
+        {
+            date_seen = true;
+        } else {
+            base.push(segment);
+        }
+    }
+    ModelId {
+        base: base.join("-"),
+        bot,
+    }
+}
+
+/// 按模型查找配置值：依次尝试映射后的 Kiro 模型 ID、客户端模型名、拆分后的基础名
+pub(super) fn lookup_by_model<'a, T>(map: &'a HashMap<String, T>, model: &str) -> Option<&'a T> {
+    if map.is_empty() {
+        return None;
+    }
+    map_model(model)
+        .and_then(|id| map.get(&id))
+        .or_else(|| map.get(model))
+        .or_else(|| map.get(&normalize_model_name(model).base))
+}
+
 /// 模型映射：将 Anthropic 模型名映射到 Kiro 模型 ID
 ///
 /// 规则：从模型名中提取 family（opus/sonnet/haiku）和版本号（X-Y → X.Y），
@@ -94,7 +149,7 @@ Do not mention Kiro or any AWS/Amazon affiliation.";
 /// - `claude-3-5-sonnet-20241022` → `claude-sonnet-3.5`
 /// - `claude-opus-4-20250514`（无 minor）→ fallback `claude-opus-4.6`
 pub fn map_model(model: &str) -> Option<String> {
-    let m = model.to_lowercase();
+    let m = normalize_model_name(model).base;
 
     let family = if m.contains("sonnet") {
         "sonnet"
@@ -207,11 +262,114 @@ pub struct ConversionResult {
     pub tool_name_map: HashMap<String, String>,
 }
 
+/// 请求转换选项
+#[derive(Debug, Clone, Default)]
+pub struct ConversionOptions {
+    /// 对话以 assistant 轮次开头时的处理策略
+    pub leading_assistant: LeadingAssistantStrategy,
+    /// 超长工具描述移入系统提示词的方式（None 表示直接截断）
+    pub elevate_long_descriptions: Option<ToolDocumentationOptions>,
+    /// 多个工具共享的描述段落移入系统提示词的方式（None 表示不去重）
+    pub dedup_shared_descriptions: Option<ToolDocumentationOptions>,
+    /// `is_error: true` 的 tool_result 的转换方式
+    pub tool_error_policy: ToolErrorPolicy,
+    /// 按模型的系统提示词 token 上限
+    pub system_prompt_budget: SystemPromptBudget,
+}
+
+/// 按模型的系统提示词 token 上限
+///
+/// 系统提示词过大时工具压缩无能为力，需单独限制；按 [`crate::token::count_tokens`] 估算。
+#[derive(Debug, Clone, Default)]
+pub struct SystemPromptBudget {
+    /// 各模型的上限（key: Kiro 模型 ID 或客户端模型名）
+    pub limits: HashMap<String, u64>,
+    /// 超过上限时的处理方式
+    pub behavior: SystemPromptLimitBehavior,
+}
+
+impl SystemPromptBudget {
+    /// 查找模型的上限（见 [`lookup_by_model`]）
+    fn limit_for(&self, model: &str) -> Option<u64> {
+        lookup_by_model(&self.limits, model).copied()
+    }
+
+    /// 检查系统提示词是否超出上限
+    ///
+    /// 未超出时返回 `None`；超出时按策略返回错误或截断后的请求（保留开头部分）。
+    fn apply(&self, req: &MessagesRequest) -> Result<Option<MessagesRequest>, ConversionError> {
+        let (Some(limit), Some(system)) = (self.limit_for(&req.model), &req.system) else {
+            return Ok(None);
+        };
+        let tokens: u64 = system.iter().map(|m| crate::token::count_tokens(&m.text)).sum();
+        if tokens <= limit {
+            return Ok(None);
+        }
+        match self.behavior {
+            SystemPromptLimitBehavior::Reject => {
+                Err(ConversionError::SystemPromptTooLong { tokens, limit })
+            }
+            SystemPromptLimitBehavior::Truncate => {
+                tracing::info!(model = %req.model, tokens, limit, "系统提示词超过上限，已截断");
+                let mut remaining = limit;
+                let mut kept = Vec::new();
+                for mut msg in system.iter().cloned() {
+                    let cost = crate::token::count_tokens(&msg.text);
+                    if cost <= remaining {
+                        remaining -= cost;
+                        kept.push(msg);
+                        continue;
+                    }
+                    msg.text = truncate_to_tokens(&msg.text, remaining).to_string();
+                    if !msg.text.is_empty() {
+                        kept.push(msg);
+                    }
+                    break;
+                }
+                let mut owned = req.clone();
+                owned.system = Some(kept);
+                Ok(Some(owned))
+            }
+        }
+    }
+}
+
+/// 截取不超过 `max_tokens` 的最长开头部分（按字符边界二分查找）
+fn truncate_to_tokens(text: &str, max_tokens: u64) -> &str {
+    let boundaries: Vec<usize> = text
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(text.len()))
+        .collect();
+    // 不变式：boundaries[lo] 处的前缀不超过上限
+    let (mut lo, mut hi) = (0, boundaries.len() - 1);
+    while lo < hi {
+        let mid = (lo + hi).div_ceil(2);
+        if crate::token::count_tokens(&text[..boundaries[mid]]) <= max_tokens {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    &text[..boundaries[lo]]
+}
+
+/// 工具文档块（从超长工具描述中移出）的标题与位置
+#[derive(Debug, Clone)]
+pub struct ToolDocumentationOptions {
+    /// 文档块标题（为空时不加标题）
+    pub heading: String,
+    /// 相对客户端系统提示词的位置
+    pub placement: ToolDocumentationPlacement,
+}
+
 /// 转换错误
 #[derive(Debug)]
 pub enum ConversionError {
     UnsupportedModel(String),
     EmptyMessages,
+    LeadingAssistantTurn,
+    SystemPromptTooLong { tokens: u64, limit: u64 },
 }
 
 impl std::fmt::Display for ConversionError {
@@ -219,6 +377,14 @@ impl std::fmt::Display for ConversionError {
         match self {
             ConversionError::UnsupportedModel(model) => write!(f, "模型不支持: {}", model),
             ConversionError::EmptyMessages => write!(f, "消息列表为空"),
+            ConversionError::LeadingAssistantTurn => {
+                write!(f, "对话必须以 user 消息开头（首条消息为 assistant）")
+            }
+            ConversionError::SystemPromptTooLong { tokens, limit } => write!(
+                f,
+                "系统提示词过长: 约 {} tokens，超过上限 {} tokens",
+                tokens, limit
+            ),
         }
     }
 }
@@ -299,10 +465,28 @@ fn create_placeholder_tool(name: &str) -> Tool {
 
 /// 将 Anthropic 请求转换为 Kiro 请求
 pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, ConversionError> {
+    convert_request_with_options(req, &ConversionOptions::default())
+}
+
+/// 按指定选项将 Anthropic 请求转换为 Kiro 请求
+pub fn convert_request_with_options(
+    req: &MessagesRequest,
+    options: &ConversionOptions,
+) -> Result<ConversionResult, ConversionError> {
     // 1. 映射模型
     let model_id = map_model(&req.model)
         .ok_or_else(|| ConversionError::UnsupportedModel(req.model.clone()))?;
 
+    // 1.5 系统提示词超过模型上限时按策略拒绝或截断
+    let budgeted;
+    let req = match options.system_prompt_budget.apply(req)? {
+        Some(owned) => {
+            budgeted = owned;
+            &budgeted
+        }
+        None => req,
+    };
+
     // 2. 检查消息列表
     if req.messages.is_empty() {
         return Err(ConversionError::EmptyMessages);
@@ -322,6 +506,16 @@ pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, Conver
         &req.messages
     };
 
+    // 2.6. 对话以 assistant 开头时按策略处理（Kiro 要求首轮为 user）
+    let normalized;
+    let messages: &[_] = match normalize_leading_assistant(messages, options.leading_assistant)? {
+        Some(owned) => {
+            normalized = owned;
+            &normalized
+        }
+        None => messages,
+    };
+
     // 3. 生成会话 ID 和代理 ID
     // 优先从 metadata.user_id 中提取 session UUID 作为 conversationId
     let conversation_id = req
@@ -343,8 +537,26 @@ pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, Conver
     let mut tool_name_map = HashMap::new();
     let mut tools = convert_tools(&req.tools, &mut tool_name_map);
 
+    // 6.5 共享描述去重 + 超长描述：按配置移入系统提示词，否则截断
+    let mut doc_sections = Vec::new();
+    if options.dedup_shared_descriptions.is_some()
+        && let Some(shared) = tool_compression::dedup_shared_descriptions(&mut tools)
+    {
+        tracing::info!("{} 组共享工具描述段落已移入系统提示词", shared.groups);
+        doc_sections.push(shared.doc);
+    }
+    match &options.elevate_long_descriptions {
+        Some(_) => doc_sections.extend(elevate_long_descriptions(&mut tools)),
+        None => truncate_long_descriptions(&mut tools),
+    }
+    let tool_docs = options
+        .elevate_long_descriptions
+        .as_ref()
+        .or(options.dedup_shared_descriptions.as_ref())
+        .and_then(|doc_options| ToolDocumentation::from_sections(doc_sections, doc_options));
+
     // 7. 构建历史消息（需要先构建，以便收集历史中使用的工具）
-    let mut history = build_history(req, messages, &model_id, &mut tool_name_map)?;
+    let mut history = build_history(req, messages, &model_id, &mut tool_name_map, tool_docs.as_ref())?;
 
     // 8. 验证并过滤 tool_use/tool_result 配对
     // 移除孤立的 tool_result（没有对应的 tool_use）
@@ -355,6 +567,12 @@ pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, Conver
     // 9. 从历史中移除孤立的 tool_use（Kiro API 要求 tool_use 必须有对应的 tool_result）
     remove_orphaned_tool_uses(&mut history, &orphaned_tool_use_ids);
 
+    // 9.5 按策略包裹失败的工具结果（error 状态在两种策略下都保留）
+    let mut validated_tool_results = validated_tool_results;
+    if options.tool_error_policy == ToolErrorPolicy::Framed {
+        frame_tool_errors(&mut history, &mut validated_tool_results);
+    }
+
     // 10. 收集历史中使用的工具名称，为缺失的工具生成占位符定义
     // Kiro API 要求：历史消息中引用的工具必须在 tools 列表中有定义
     // 注意：Kiro 匹配工具名称时忽略大小写，所以这里也需要忽略大小写比较
@@ -370,6 +588,30 @@ pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, Conver
         }
     }
 
+    // 10.5 启用工具压缩时，工具定义总量过大则分阶段压缩
+    let compression_options = tool_compression::options();
+    let (original_tools, final_size) = if compression_options.enabled {
+        let (compressed, report) = compress_tools_if_needed(&tools, compression_options);
+        if report.compressed() {
+            crate::metrics::registry().tool_compressions.inc();
+            tracing::info!(
+                original = report.original_size,
+                whitespace_saved = report.whitespace_saved,
+                schema_saved = report.schema_saved,
+                description_saved = report.description_saved,
+                final_size = report.final_size,
+                "工具定义已压缩"
+            );
+        }
+        (std::mem::replace(&mut tools, compressed), Some(report.final_size))
+    } else {
+        (tools.clone(), None)
+    };
+    // 无论是否启用压缩都记录压缩前的工具定义（体积在 Admin API 查询时才计算），供诊断超大工具
+    if !original_tools.is_empty() {
+        tool_compression::record_tool_sizes(original_tools, final_size);
+    }
+
     // 11. 构建 UserInputMessageContext
     let mut context = UserInputMessageContext::new();
     if !tools.is_empty() {
@@ -414,6 +656,77 @@ pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, Conver
     })
 }
 
+/// 插入到以 assistant 开头的对话之前的最简 user 消息
+const SYNTHETIC_USER_TURN: &str = "Continue.";
+
+/// 处理以 assistant 轮次开头的对话
+///
+/// 首条消息为 user 时返回 `None`（无需改动）。`Drop` 策略会同时移除后续消息中
+/// 引用了被丢弃 tool_use 的 tool_result，避免留下孤立的工具结果；
+/// 若某条 user 消息因此变空，则以最简文本代替。
+fn normalize_leading_assistant(
+    messages: &[super::types::Message],
+    strategy: LeadingAssistantStrategy,
+) -> Result<Option<Vec<super::types::Message>>, ConversionError> {
+    if messages.first().is_none_or(|m| m.role != "assistant") {
+        return Ok(None);
+    }
+
+    match strategy {
+        LeadingAssistantStrategy::Reject => Err(ConversionError::LeadingAssistantTurn),
+        LeadingAssistantStrategy::Prepend => {
+            tracing::info!("对话以 assistant 消息开头，插入最简 user 消息");
+            let mut owned = Vec::with_capacity(messages.len() + 1);
+            owned.push(super::types::Message {
+                role: "user".to_string(),
+                content: serde_json::json!(SYNTHETIC_USER_TURN),
+            });
+            owned.extend_from_slice(messages);
+            Ok(Some(owned))
+        }
+        LeadingAssistantStrategy::Drop => {
+            let first_user = messages
+                .iter()
+                .position(|m| m.role == "user")
+                .ok_or(ConversionError::EmptyMessages)?;
+            let dropped_tool_use_ids: std::collections::HashSet<&str> = messages[..first_user]
+                .iter()
+                .filter_map(|m| m.content.as_array())
+                .flatten()
+                .filter(|b| b["type"] == "tool_use")
+                .filter_map(|b| b["id"].as_str())
+                .collect();
+            tracing::info!(
+                "对话以 assistant 消息开头，丢弃前 {} 条 assistant 消息",
+                first_user
+            );
+
+            let owned = messages[first_user..]
+                .iter()
+                .map(|m| {
+                    let mut m = m.clone();
+                    if m.role == "user"
+                        && !dropped_tool_use_ids.is_empty()
+                        && let Some(blocks) = m.content.as_array_mut()
+                    {
+                        blocks.retain(|b| {
+                            b["type"] != "tool_result"
+                                || b["tool_use_id"]
+                                    .as_str()
+                                    .is_none_or(|id| !dropped_tool_use_ids.contains(id))
+                        });
+                        if blocks.is_empty() {
+                            m.content = serde_json::json!(SYNTHETIC_USER_TURN);
+                        }
+                    }
+                    m
+                })
+                .collect();
+            Ok(Some(owned))
+        }
+    }
+}
+
 /// 确定聊天触发类型
 /// "AUTO" 模式可能会导致 400 Bad Request 错误
 fn determine_chat_trigger_type(_req: &MessagesRequest) -> String {
@@ -688,6 +1001,38 @@ fn remove_orphaned_tool_uses(
     }
 }
 
+/// 包裹失败工具结果的统一说明
+fn framed_tool_error(content: &str) -> String {
+    format!(
+        "The tool failed: {}\n\nHandle this failure gracefully: fix the input and retry, try another approach, or explain the problem to the user. Do not assume the tool succeeded.",
+        content
+    )
+}
+
+/// 以统一说明包裹历史与当前消息中 `is_error` 的工具结果文本
+fn frame_tool_errors(history: &mut [Message], current: &mut [ToolResult]) {
+    let history_results = history.iter_mut().filter_map(|msg| match msg {
+        Message::User(user_msg) => Some(
+            user_msg
+                .user_input_message
+                .user_input_message_context
+                .tool_results
+                .iter_mut(),
+        ),
+        Message::Assistant(_) => None,
+    });
+    for result in history_results.flatten().chain(current.iter_mut()) {
+        if !result.is_error {
+            continue;
+        }
+        for block in &mut result.content {
+            if let Some(serde_json::Value::String(text)) = block.get_mut("text") {
+                *text = framed_tool_error(text);
+            }
+        }
+    }
+}
+
 /// Kiro API 工具名称最大长度限制
 const TOOL_NAME_MAX_LEN: usize = 63;
 
@@ -738,12 +1083,6 @@ fn convert_tools(tools: &Option<Vec<super::types::Tool>>, tool_name_map: &mut Ha
                 description.push_str(suffix);
             }
 
-            // 限制描述长度为 10000 字符（安全截断 UTF-8，单次遍历）
-            let description = match description.char_indices().nth(10000) {
-                Some((idx, _)) => description[..idx].to_string(),
-                None => description,
-            };
-
             Tool {
                 tool_specification: ToolSpecification {
                     name: map_tool_name(&t.name, tool_name_map),
@@ -755,6 +1094,88 @@ fn convert_tools(tools: &Option<Vec<super::types::Tool>>, tool_name_map: &mut Ha
         .collect()
 }
 
+/// 工具描述的最大长度（字符）
+const MAX_TOOL_DESCRIPTION_CHARS: usize = 10000;
+
+/// 描述移入系统提示词后，工具上保留的摘要长度（字符）
+const ELEVATED_DESCRIPTION_PREVIEW_CHARS: usize = 500;
+
+/// 按字符数截断（安全截断 UTF-8，单次遍历），未超出时返回 None
+fn truncate_chars(text: &str, max_chars: usize) -> Option<&str> {
+    text.char_indices().nth(max_chars).map(|(idx, _)| &text[..idx])
+}
+
+/// 限制描述长度为 [`MAX_TOOL_DESCRIPTION_CHARS`] 字符
+fn truncate_long_descriptions(tools: &mut [Tool]) {
+    for tool in tools {
+        let description = &mut tool.tool_specification.description;
+        if let Some(truncated) = truncate_chars(description, MAX_TOOL_DESCRIPTION_CHARS) {
+            *description = truncated.to_string();
+        }
+    }
+}
+
+/// 从工具描述中移出、放入系统提示词的工具文档块
+struct ToolDocumentation {
+    block: String,
+    placement: ToolDocumentationPlacement,
+}
+
+impl ToolDocumentation {
+    /// 由文档段落组装文档块（加上配置的标题），没有段落时返回 None
+    fn from_sections(sections: Vec<String>, options: &ToolDocumentationOptions) -> Option<Self> {
+        if sections.is_empty() {
+            return None;
+        }
+        let body = sections.join("\n\n");
+        let block = if options.heading.is_empty() {
+            body
+        } else {
+            format!("{}\n\n{}", options.heading, body)
+        };
+        Some(Self {
+            block,
+            placement: options.placement,
+        })
+    }
+
+    /// 按配置的位置将文档块与客户端系统提示词合并
+    fn merge_into(&self, system: &str) -> String {
+        if system.is_empty() {
+            return self.block.clone();
+        }
+        match self.placement {
+            ToolDocumentationPlacement::Append => format!("{}\n\n{}", system, self.block),
+            ToolDocumentationPlacement::Prepend => format!("{}\n\n{}", self.block, system),
+        }
+    }
+}
+
+/// 把超过 [`MAX_TOOL_DESCRIPTION_CHARS`] 的工具描述移出，返回各工具的文档段落
+///
+/// 工具上只保留开头的摘要并注明完整文档的位置。
+fn elevate_long_descriptions(tools: &mut [Tool]) -> Vec<String> {
+    let mut sections = Vec::new();
+    for tool in tools {
+        let spec = &mut tool.tool_specification;
+        if truncate_chars(&spec.description, MAX_TOOL_DESCRIPTION_CHARS).is_none() {
+            continue;
+        }
+        let preview = truncate_chars(&spec.description, ELEVATED_DESCRIPTION_PREVIEW_CHARS)
+            .unwrap_or(&spec.description);
+        let preview = format!(
+            "{}...\n\n(Full documentation for this tool is in the system prompt.)",
+            preview.trim_end()
+        );
+        let full = std::mem::replace(&mut spec.description, preview);
+        sections.push(format!("## {}\n\n{}", spec.name, full));
+    }
+    if !sections.is_empty() {
+        tracing::info!("{} 个超长工具描述已移入系统提示词", sections.len());
+    }
+    sections
+}
+
 /// 生成thinking标签前缀
 fn generate_thinking_prefix(req: &MessagesRequest) -> Option<String> {
     if let Some(t) = &req.thinking {
@@ -791,7 +1212,8 @@ fn has_thinking_tags(content: &str) -> bool {
 ///   注意：该切片与 `req.messages` 可能不同（prefill 时会截断末尾的 assistant 消息），
 ///   调用方应始终使用此参数而非 `req.messages`。
 /// * `model_id` - 已映射的 Kiro 模型 ID
-fn build_history(req: &MessagesRequest, messages: &[super::types::Message], model_id: &str, tool_name_map: &mut HashMap<String, String>) -> Result<Vec<Message>, ConversionError> {
+/// * `tool_docs` - 从超长工具描述中移出的文档块，按配置位置并入系统消息
+fn build_history(req: &MessagesRequest, messages: &[super::types::Message], model_id: &str, tool_name_map: &mut HashMap<String, String>, tool_docs: Option<&ToolDocumentation>) -> Result<Vec<Message>, ConversionError> {
     let mut history = Vec::new();
 
     // 生成 structured output 的 system prompt 注入
@@ -820,13 +1242,18 @@ fn build_history(req: &MessagesRequest, messages: &[super::types::Message], mode
 
     // 1. 处理系统消息
     let schema_suffix = json_schema_instruction.as_deref().unwrap_or("");
-    if let Some(ref system) = req.system {
-        let system_content: String = system
+    let system_content = req.system.as_ref().map(|system| {
+        system
             .iter()
             .map(|s| s.text.clone())
             .collect::<Vec<_>>()
-            .join("\n");
-
+            .join("\n")
+    });
+    let system_content = match tool_docs {
+        Some(docs) => Some(docs.merge_into(system_content.as_deref().unwrap_or_default())),
+        None => system_content,
+    };
+    if let Some(system_content) = system_content {
         if !system_content.is_empty() {
             // 追加分块写入策略、身份覆盖和 JSON schema 指令到系统消息
             let system_content = format!("{}\n{}{}{}", system_content, SYSTEM_CHUNKED_POLICY, SYSTEM_IDENTITY_OVERRIDE, schema_suffix);
@@ -1069,6 +1496,41 @@ fn merge_assistant_messages(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_normalize_model_name_splits_date_and_bot_suffixes() {
+        let id = |base: &str, bot| ModelId {
+            base: base.to_string(),
+            bot,
+        };
+        assert_eq!(
+            normalize_model_name("claude-3-5-sonnet-20241022"),
+            id("claude-3-5-sonnet", false)
+        );
+        assert_eq!(
+            normalize_model_name("claude-sonnet-4-5-bot"),
+            id("claude-sonnet-4-5", true)
+        );
+        assert_eq!(
+            normalize_model_name("Claude-Sonnet-4-5-20250929-bot"),
+            id("claude-sonnet-4-5", true)
+        );
+        assert_eq!(
+            normalize_model_name("claude-opus-4-6"),
+            id("claude-opus-4-6", false)
+        );
+
+        // 各处按基础名匹配，结果一致
+        assert_eq!(
+            map_model("claude-sonnet-4-5-20250929-bot"),
+            map_model("claude-sonnet-4-5")
+        );
+        let budget = SystemPromptBudget {
+            limits: HashMap::from([("claude-3-5-haiku".to_string(), 4096)]),
+            ..Default::default()
+        };
+        assert_eq!(budget.limit_for("claude-3-5-haiku-20241022-bot"), Some(4096));
+    }
+
     #[test]
     fn test_map_model_sonnet() {
         assert!(
@@ -1993,4 +2455,302 @@ mod tests {
         }
         assert!(found_tool_use, "合并后的 assistant 消息应包含 tool_use");
     }
+
+    /// 以 assistant 开头的恢复会话：首条 assistant 发起的工具调用在下一条 user 中返回结果
+    fn leading_assistant_request() -> MessagesRequest {
+        serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 1024,
+            "messages": [
+                {"role": "assistant", "content": [
+                    {"type": "text", "text": "Resuming earlier work."},
+                    {"type": "tool_use", "id": "toolu_dropped", "name": "read_file", "input": {"path": "/a"}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_dropped", "content": "file body"},
+                    {"type": "text", "text": "Please continue"}
+                ]},
+                {"role": "assistant", "content": "Sure."},
+                {"role": "user", "content": "Next step"}
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn convert_with_strategy(
+        strategy: LeadingAssistantStrategy,
+    ) -> Result<ConversionResult, ConversionError> {
+        convert_request_with_options(
+            &leading_assistant_request(),
+            &ConversionOptions {
+                leading_assistant: strategy,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// 断言历史严格按 user/assistant 交替
+    fn assert_alternating(history: &[Message]) {
+        for (i, msg) in history.iter().enumerate() {
+            assert_eq!(matches!(msg, Message::User(_)), i % 2 == 0, "第 {} 条消息角色错误", i);
+        }
+    }
+
+    #[test]
+    fn test_leading_assistant_prepend_inserts_user_turn() {
+        let result = convert_with_strategy(LeadingAssistantStrategy::Prepend).unwrap();
+        let history = &result.conversation_state.history;
+        assert_alternating(history);
+        match &history[2] {
+            Message::User(user) => {
+                assert_eq!(user.user_input_message.content, SYNTHETIC_USER_TURN)
+            }
+            other => panic!("应在首条 assistant 前插入 user 消息: {:?}", other),
+        }
+        let json = serde_json::to_string(history).unwrap();
+        assert!(json.contains("Resuming earlier work."));
+        assert!(json.contains("toolu_dropped"));
+    }
+
+    #[test]
+    fn test_leading_assistant_drop_removes_turn_and_its_tool_results() {
+        let result = convert_with_strategy(LeadingAssistantStrategy::Drop).unwrap();
+        let history = &result.conversation_state.history;
+        assert_alternating(history);
+        let json = serde_json::to_string(history).unwrap();
+        assert!(!json.contains("Resuming earlier work."));
+        assert!(!json.contains("toolu_dropped"), "不应残留引用被丢弃轮次的 tool_result");
+        assert!(json.contains("Please continue"));
+    }
+
+    #[test]
+    fn test_leading_assistant_reject_returns_error() {
+        let err = convert_with_strategy(LeadingAssistantStrategy::Reject).unwrap_err();
+        assert!(matches!(err, ConversionError::LeadingAssistantTurn));
+
+        // 以 user 开头的对话不受策略影响
+        let mut req = leading_assistant_request();
+        req.messages.remove(0);
+        let options = ConversionOptions {
+            leading_assistant: LeadingAssistantStrategy::Reject,
+            ..Default::default()
+        };
+        assert!(convert_request_with_options(&req, &options).is_ok());
+    }
+
+    #[test]
+    fn test_tool_error_policy_frames_only_error_results() {
+        let req: MessagesRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 1024,
+            "messages": [
+                {"role": "user", "content": "run both"},
+                {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "ok-1", "name": "run", "input": {}},
+                    {"type": "tool_use", "id": "bad-1", "name": "run", "input": {}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "ok-1", "content": "done"},
+                    {"type": "tool_result", "tool_use_id": "bad-1", "content": "permission denied", "is_error": true}
+                ]}
+            ]
+        }))
+        .unwrap();
+
+        let convert = |policy| {
+            let options = ConversionOptions {
+                tool_error_policy: policy,
+                ..Default::default()
+            };
+            let result = convert_request_with_options(&req, &options).unwrap();
+            result
+                .conversation_state
+                .current_message
+                .user_input_message
+                .user_input_message_context
+                .tool_results
+        };
+        let text = |r: &ToolResult| r.content[0]["text"].as_str().unwrap().to_string();
+
+        let passthrough = convert(ToolErrorPolicy::Passthrough);
+        assert_eq!(text(&passthrough[1]), "permission denied");
+        assert!(passthrough[1].is_error);
+        assert_eq!(passthrough[1].status.as_deref(), Some("error"));
+
+        let framed = convert(ToolErrorPolicy::Framed);
+        assert_eq!(text(&framed[0]), "done");
+        assert!(!framed[0].is_error);
+        assert_eq!(framed[0].status.as_deref(), Some("success"));
+        assert_eq!(text(&framed[1]), framed_tool_error("permission denied"));
+        assert!(text(&framed[1]).starts_with("The tool failed: permission denied"));
+        assert!(framed[1].is_error);
+        assert_eq!(framed[1].status.as_deref(), Some("error"));
+    }
+
+    #[test]
+    fn test_system_prompt_budget_rejects_or_truncates_keeping_head() {
+        let head = "You are a careful assistant. ";
+        let req: MessagesRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 1024,
+            "system": [
+                {"type": "text", "text": format!("{}{}", head, "Follow the rules. ".repeat(50))},
+                {"type": "text", "text": "TRAILING-SECTION"}
+            ],
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+        let budget = |behavior| SystemPromptBudget {
+            limits: HashMap::from([("claude-sonnet-4.5".to_string(), 20)]),
+            behavior,
+        };
+
+        let options = ConversionOptions {
+            system_prompt_budget: budget(SystemPromptLimitBehavior::Reject),
+            ..Default::default()
+        };
+        match convert_request_with_options(&req, &options) {
+            Err(ConversionError::SystemPromptTooLong { tokens, limit }) => {
+                assert_eq!(limit, 20);
+                assert!(tokens > limit);
+            }
+            other => panic!("应拒绝过长的系统提示词: {:?}", other.map(|_| ())),
+        }
+
+        let truncated = budget(SystemPromptLimitBehavior::Truncate)
+            .apply(&req)
+            .unwrap()
+            .unwrap();
+        let system = truncated.system.as_ref().unwrap();
+        assert_eq!(system.len(), 1);
+        assert!(system[0].text.starts_with(head));
+        assert!(crate::token::count_tokens(&system[0].text) <= 20);
+        assert_eq!(truncated.messages.len(), req.messages.len());
+        let options = ConversionOptions {
+            system_prompt_budget: budget(SystemPromptLimitBehavior::Truncate),
+            ..Default::default()
+        };
+        assert!(convert_request_with_options(&req, &options).is_ok());
+
+        // 未配置上限的模型不受影响
+        let mut other = req.clone();
+        other.model = "claude-opus-4-5".to_string();
+        assert!(
+            budget(SystemPromptLimitBehavior::Reject)
+                .apply(&other)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    fn long_description_request() -> MessagesRequest {
+        serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 1024,
+            "system": [{"type": "text", "text": "CLIENT SYSTEM PROMPT"}],
+            "messages": [{"role": "user", "content": "hi"}],
+            "tools": [
+                {
+                    "name": "huge_tool",
+                    "description": format!("Huge tool. {}", "x".repeat(12000)),
+                    "input_schema": {"type": "object"}
+                },
+                {
+                    "name": "small_tool",
+                    "description": "Small tool.",
+                    "input_schema": {"type": "object"}
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn convert_with_tool_docs(
+        heading: &str,
+        placement: ToolDocumentationPlacement,
+    ) -> (String, Vec<Tool>) {
+        let options = ConversionOptions {
+            elevate_long_descriptions: Some(ToolDocumentationOptions {
+                heading: heading.to_string(),
+                placement,
+            }),
+            ..Default::default()
+        };
+        let result = convert_request_with_options(&long_description_request(), &options).unwrap();
+        let state = result.conversation_state;
+        let system = match &state.history[0] {
+            Message::User(user) => user.user_input_message.content.clone(),
+            other => panic!("首条历史应为系统消息: {:?}", other),
+        };
+        let tools = state.current_message.user_input_message.user_input_message_context.tools;
+        (system, tools)
+    }
+
+    #[test]
+    fn test_long_tool_description_truncated_by_default() {
+        let result = convert_request(&long_description_request()).unwrap();
+        let tools = &result.conversation_state.current_message.user_input_message.user_input_message_context.tools;
+        assert_eq!(tools[0].tool_specification.description.chars().count(), MAX_TOOL_DESCRIPTION_CHARS);
+        match &result.conversation_state.history[0] {
+            Message::User(user) => assert!(!user.user_input_message.content.contains("Tool Documentation")),
+            other => panic!("首条历史应为系统消息: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_tool_compression_disabled_by_default_keeps_large_tools_intact() {
+        let tools: Vec<serde_json::Value> = (0..4)
+            .map(|i| serde_json::json!({
+                "name": format!("tool_{}", i),
+                "description": "d".repeat(8000),
+                "input_schema": {
+                    "type": "object",
+                    "properties": {"path": {"type": "string", "description": "File path"}}
+                }
+            }))
+            .collect();
+        let req: MessagesRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 1024,
+            "messages": [{"role": "user", "content": "hi"}],
+            "tools": tools
+        }))
+        .unwrap();
+
+        let result = convert_request(&req).unwrap();
+        let tools = &result.conversation_state.current_message.user_input_message.user_input_message_context.tools;
+        assert!(tool_compression::calculate_tools_size(tools) > tool_compression::TOOL_COMPRESSION_TARGET_SIZE);
+        for tool in tools {
+            assert_eq!(tool.tool_specification.description.len(), 8000);
+            assert_eq!(tool.tool_specification.input_schema.json["properties"]["path"]["description"], "File path");
+        }
+    }
+
+    #[test]
+    fn test_elevated_tool_docs_appended_with_default_heading() {
+        let (system, tools) = convert_with_tool_docs("# Tool Documentation", ToolDocumentationPlacement::Append);
+
+        let client_pos = system.find("CLIENT SYSTEM PROMPT").unwrap();
+        let docs_pos = system.find("# Tool Documentation\n\n## huge_tool\n\nHuge tool. xxx").unwrap();
+        assert!(client_pos < docs_pos);
+        assert!(!system.contains("## small_tool"));
+
+        let huge = &tools[0].tool_specification.description;
+        assert!(huge.starts_with("Huge tool. xxx"));
+        assert!(huge.chars().count() < 600);
+        assert!(huge.ends_with("(Full documentation for this tool is in the system prompt.)"));
+        assert_eq!(tools[1].tool_specification.description, "Small tool.");
+    }
+
+    #[test]
+    fn test_elevated_tool_docs_prepended_with_custom_heading() {
+        let (system, _) = convert_with_tool_docs("<tool_docs>", ToolDocumentationPlacement::Prepend);
+        assert!(system.starts_with("<tool_docs>\n\n## huge_tool\n\nHuge tool."));
+        assert!(system.find("CLIENT SYSTEM PROMPT").unwrap() > system.find("## huge_tool").unwrap());
+        assert!(!system.contains("# Tool Documentation"));
+
+        // 标题为空时只保留各工具小节
+        let (system, _) = convert_with_tool_docs("", ToolDocumentationPlacement::Prepend);
+        assert!(system.starts_with("## huge_tool\n\n"));
+    }
 }
@@ -0,0 +1,270 @@
+//! 重复请求合并
+//!
+//! 部分客户端会在短时间内重复提交同一请求（双击、超时重试但首个请求实际已成功），
+//! 导致上游调用翻倍。启用后，同一 API Key 在窗口内提交的相同非流式请求
+//! （按规范化后的请求体及单次请求选项头判断）只下发一次，后到的请求直接复用进行中或刚完成的结果。
+//!
+//! 流式请求不参与合并；失败结果只返回给并发等待者，不在窗口内复用。
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{HeaderMap, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+
+use crate::common::auth;
+
+use super::handlers::TIMEOUT_HEADER;
+use super::middleware::{AppState, FINGERPRINT_SEED_HEADER};
+use super::router::MAX_BODY_SIZE;
+use super::types::ErrorResponse;
+
+/// 已完成请求的响应（完整缓冲，可多次返回）
+#[derive(Clone)]
+struct CachedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    completed_at: Instant,
+}
+
+impl CachedResponse {
+    /// 缓冲响应体
+    async fn buffer(response: Response) -> Self {
+        let (parts, body) = response.into_parts();
+        let (status, body) = match axum::body::to_bytes(body, usize::MAX).await {
+            Ok(body) => (parts.status, body),
+            Err(e) => {
+                tracing::warn!("缓冲响应体失败: {}", e);
+                (StatusCode::BAD_GATEWAY, Bytes::new())
+            }
+        };
+        Self {
+            status,
+            headers: parts.headers,
+            body,
+            completed_at: Instant::now(),
+        }
+    }
+
+    fn to_response(&self) -> Response {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response
+    }
+}
+
+/// 单个请求键对应的合并槽位
+#[derive(Default)]
+struct DedupEntry {
+    /// 首个请求完成后写入；若首个请求被取消，由下一个等待者重新下发
+    result: OnceCell<CachedResponse>,
+}
+
+impl DedupEntry {
+    /// 进行中，或完成时间仍在窗口内
+    fn is_live(&self, now: Instant, window: Duration) -> bool {
+        self.result
+            .get()
+            .is_none_or(|r| now.duration_since(r.completed_at) < window)
+    }
+}
+
+/// 重复请求合并器
+pub struct RequestDedup {
+    window: Duration,
+    entries: Mutex<HashMap<String, Arc<DedupEntry>>>,
+}
+
+impl RequestDedup {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 影响上游调用方式的单次请求选项头，取值不同的请求不合并
+    const OPTION_HEADERS: [&'static str; 2] = [TIMEOUT_HEADER, FINGERPRINT_SEED_HEADER];
+
+    /// 计算请求键：API Key + 单次请求选项头 + 规范化后的请求体（键排序的 JSON）
+    ///
+    /// 请求体不是 JSON 对象或为流式请求时返回 None（不参与合并）。
+    fn key(api_key: &str, headers: &HeaderMap, body: &[u8]) -> Option<String> {
+        let value: serde_json::Value = serde_json::from_slice(body).ok()?;
+        if !value.is_object() || value.get("stream").and_then(|s| s.as_bool()) == Some(true) {
+            return None;
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(api_key.as_bytes());
+        hasher.update([0]);
+        for name in Self::OPTION_HEADERS {
+            // 缺失与空值区分开，避免两者落到同一键
+            match headers.get(name) {
+                Some(value) => {
+                    hasher.update([1]);
+                    hasher.update(value.as_bytes());
+                }
+                None => hasher.update([2]),
+            }
+            hasher.update([0]);
+        }
+        hasher.update(value.to_string().as_bytes());
+        Some(hex::encode(hasher.finalize()))
+    }
+
+    /// 执行请求：窗口内已有相同键时复用其结果，否则调用 `dispatch`
+    async fn run<F, Fut>(&self, key: String, dispatch: F) -> CachedResponse
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = CachedResponse>,
+    {
+        let entry = {
+            let now = Instant::now();
+            let mut entries = self.entries.lock();
+            entries.retain(|_, e| e.is_live(now, self.window));
+            entries.entry(key.clone()).or_default().clone()
+        };
+
+        let response = entry.result.get_or_init(dispatch).await.clone();
+
+        // 失败结果不复用，后续重试重新下发
+        if !response.status.is_success() {
+            let mut entries = self.entries.lock();
+            if entries.get(&key).is_some_and(|e| Arc::ptr_eq(e, &entry)) {
+                entries.remove(&key);
+            }
+        }
+        response
+    }
+}
+
+/// 重复请求合并中间件（仅挂载在 `/messages` 路由上）
+pub async fn dedup_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(dedup) = state.request_dedup.clone() else {
+        return next.run(request).await;
+    };
+
+    let api_key = auth::extract_api_key(&request).unwrap_or_default();
+    let (parts, body) = request.into_parts();
+    let body = match axum::body::to_bytes(body, MAX_BODY_SIZE).await {
+        Ok(body) => body,
+        Err(e) => {
+            return (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Json(ErrorResponse::new("invalid_request_error", e.to_string())),
+            )
+                .into_response();
+        }
+    };
+    let Some(key) = RequestDedup::key(&api_key, &parts.headers, &body) else {
+        return next.run(Request::from_parts(parts, Body::from(body))).await;
+    };
+
+    let mut dispatched = false;
+    let response = dedup
+        .run(key, || {
+            dispatched = true;
+            async move {
+                let request = Request::from_parts(parts, Body::from(body));
+                CachedResponse::buffer(next.run(request).await).await
+            }
+        })
+        .await;
+    if !dispatched {
+        tracing::info!("合并重复请求，复用已有结果");
+    }
+    response.to_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::kiro::parser::frame::encode_event_frame;
+    use crate::kiro::test_support::{mock_provider, spawn_slow_mock_upstream};
+    use crate::model::config::Config;
+
+    #[test]
+    fn test_key_includes_per_request_option_headers() {
+        let body = br#"{"model":"claude-sonnet-4-5","messages":[]}"#;
+        let with = |name: &'static str, value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, value.parse().unwrap());
+            headers
+        };
+        let plain = RequestDedup::key("key", &HeaderMap::new(), body).unwrap();
+
+        // 无关请求头不影响合并
+        assert_eq!(RequestDedup::key("key", &with("user-agent", "curl"), body).unwrap(), plain);
+
+        let short = RequestDedup::key("key", &with(TIMEOUT_HEADER, "1000"), body).unwrap();
+        let long = RequestDedup::key("key", &with(TIMEOUT_HEADER, "60000"), body).unwrap();
+        assert_ne!(short, plain);
+        assert_ne!(short, long);
+
+        let seed_a = RequestDedup::key("key", &with(FINGERPRINT_SEED_HEADER, "a"), body).unwrap();
+        let seed_b = RequestDedup::key("key", &with(FINGERPRINT_SEED_HEADER, "b"), body).unwrap();
+        assert_ne!(seed_a, plain);
+        assert_ne!(seed_a, seed_b);
+        // 不同选项头取相同值也不能相互合并
+        let seed_1000 = RequestDedup::key("key", &with(FINGERPRINT_SEED_HEADER, "1000"), body).unwrap();
+        assert_ne!(seed_1000, short);
+        // 空值与缺失不同
+        assert_ne!(RequestDedup::key("key", &with(TIMEOUT_HEADER, ""), body).unwrap(), plain);
+    }
+
+    #[tokio::test]
+    async fn test_identical_requests_in_window_dispatch_once() {
+        let body = encode_event_frame("assistantResponseEvent", r#"{"content":"hello"}"#);
+        let (url, hits) = spawn_slow_mock_upstream(Duration::from_millis(300), body).await;
+        let state = AppState::new("key", false)
+            .with_kiro_provider(mock_provider(&url, Config::default()))
+            .with_request_dedup(Duration::from_secs(5));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, super::super::router::create_router(state))
+                .await
+                .unwrap()
+        });
+
+        let client = reqwest::Client::new();
+        // 字段顺序不同但规范化后相同
+        let send = |body: &'static str| {
+            client
+                .post(format!("http://{}/v1/messages", addr))
+                .header("x-api-key", "key")
+                .header("content-type", "application/json")
+                .body(body)
+                .send()
+        };
+        let (first, second) = tokio::join!(
+            send(r#"{"model":"claude-sonnet-4-5","max_tokens":64,"messages":[{"role":"user","content":"hi"}]}"#),
+            send(r#"{"max_tokens":64,"model":"claude-sonnet-4-5","messages":[{"content":"hi","role":"user"}]}"#),
+        );
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_eq!(first.status(), reqwest::StatusCode::OK);
+        assert_eq!(second.status(), reqwest::StatusCode::OK);
+        let (first, second) = (first.text().await.unwrap(), second.text().await.unwrap());
+        assert_eq!(first, second);
+        assert!(first.contains("hello"), "{}", first);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+}
@@ -3,29 +3,37 @@
 use std::convert::Infallible;
 
 use anyhow::Error;
-use crate::kiro::model::events::Event;
+use crate::kiro::model::events::{CodeReference, Event};
 use crate::kiro::model::requests::kiro::KiroRequest;
+use crate::kiro::model::requests::tool::ToolUseEntry;
+use crate::model::config::ToolInputValidation;
 use crate::kiro::parser::decoder::EventStreamDecoder;
+use crate::kiro::token_manager::GlobalCooldownError;
 use crate::token;
+use crate::kiro::provider::{
+    CallOptions, CredentialAttempts, FallbackModel, RequestTimeoutError, UpstreamTiming,
+};
 use axum::{
-    Json as JsonExtractor,
     body::Body,
-    extract::State,
-    http::{StatusCode, header, HeaderValue},
+    extract::{ConnectInfo, State},
+    http::{Extensions, HeaderMap, StatusCode, header, HeaderValue},
     response::{IntoResponse, Json, Response},
 };
+use std::net::{IpAddr, SocketAddr};
 use bytes::Bytes;
 use futures::{Stream, StreamExt, stream};
 use serde_json::json;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::time::interval;
 use uuid::Uuid;
 
-use super::converter::{ConversionError, convert_request};
+use super::converter::{ConversionError, convert_request_with_options, normalize_model_name};
 use super::image_fetch;
 use super::middleware::AppState;
-use super::stream::{BufferedStreamContext, SseEvent, StreamContext};
-use super::types::{CountTokensRequest, CountTokensResponse, ErrorResponse, MessagesRequest, Model, ModelsResponse, OutputConfig, Thinking};
+use super::request_validation::CanonicalJson;
+use super::stream::{BufferedStreamContext, SseEvent, StreamContext, ToolUseTracker};
+use super::tool_validation::{self, ToolInputValidator};
+use super::types::{CountTokensRequest, CountTokensResponse, ErrorResponse, MessagesRequest, Model, ModelsResponse, OutputConfig, SystemMessage, Thinking};
 use super::websearch;
 
 const BASE62_CHARS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
@@ -87,6 +95,37 @@ pub fn build_anthropic_response(_status: StatusCode, request_id: &str, mut respo
 
 /// 将 KiroProvider 错误映射为 HTTP 响应
 fn map_provider_error(err: Error) -> Response {
+    // 超过客户端指定的超时时间（X-Kiro-Timeout-Ms）
+    if let Some(timeout) = err.downcast_ref::<RequestTimeoutError>() {
+        tracing::warn!("{}", timeout);
+        return (
+            StatusCode::GATEWAY_TIMEOUT,
+            Json(ErrorResponse::new(
+                "timeout_error",
+                format!(
+                    "Request exceeded the client-specified timeout of {} ms.",
+                    timeout.timeout.as_millis()
+                ),
+            )),
+        )
+            .into_response();
+    }
+
+    // 全局冷却生效中：所有凭据暂停使用，提示客户端稍后重试
+    if let Some(cooldown) = err.downcast_ref::<GlobalCooldownError>() {
+        tracing::warn!("{}", cooldown);
+        let retry_after = cooldown.remaining.as_secs().max(1);
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, retry_after.to_string())],
+            Json(ErrorResponse::new(
+                "overloaded_error",
+                format!("All credentials are cooling down. Retry after {} seconds.", retry_after),
+            )),
+        )
+            .into_response();
+    }
+
     let err_str = err.to_string();
 
     // 上下文窗口满了（对话历史累积超出模型上下文窗口限制）
@@ -272,13 +311,261 @@ pub async fn get_models() -> impl IntoResponse {
     })
 }
 
+/// 客户端 IP（连接信息不可用时返回 None）
+fn client_ip(extensions: &Extensions) -> Option<IpAddr> {
+    extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+/// 根据请求头和连接信息构造请求级调用选项
+fn build_call_options(state: &AppState, headers: &HeaderMap, extensions: &Extensions) -> CallOptions {
+    CallOptions {
+        fingerprint_seed: state.fingerprint_seed_override(headers, client_ip(extensions)),
+        timeout: requested_timeout(state, headers),
+        ..Default::default()
+    }
+}
+
+/// 客户端指定请求超时的请求头（毫秒）
+pub(super) const TIMEOUT_HEADER: &str = "x-kiro-timeout-ms";
+
+/// 解析客户端指定的请求超时，超过服务端上限时按上限处理
+///
+/// 请求头缺失、无法解析或为 0 时返回 None（使用服务端默认超时）。
+fn requested_timeout(state: &AppState, headers: &HeaderMap) -> Option<Duration> {
+    let millis: u64 = headers
+        .get(TIMEOUT_HEADER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .filter(|ms| *ms > 0)?;
+    Some(Duration::from_millis(millis).min(state.max_request_timeout))
+}
+
+/// 请求流式工具输入快照的请求头（值为 `true` / `1` 时启用）
+const TOOL_INPUT_SNAPSHOT_HEADER: &str = "x-kiro-tool-input-snapshot";
+
+/// 客户端是否请求在 input_json_delta 中附带累计工具输入快照
+fn tool_input_snapshots_requested(headers: &HeaderMap) -> bool {
+    headers
+        .get(TOOL_INPUT_SNAPSHOT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim() == "1" || v.trim().eq_ignore_ascii_case("true"))
+}
+
+/// 请求返回耗时分解响应头的请求头（值为 `true` / `1` 时启用）
+const TIMING_HEADER: &str = "x-kiro-timing";
+
+/// 是否需要在响应中附带 `X-Kiro-Timing-*` 耗时头
+///
+/// 配置开启时总是附带；否则仅在白名单内的客户端携带请求头时附带。
+fn timing_headers_requested(state: &AppState, headers: &HeaderMap, extensions: &Extensions) -> bool {
+    if state.timing_headers {
+        return true;
+    }
+    let requested = headers
+        .get(TIMING_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim() == "1" || v.trim().eq_ignore_ascii_case("true"));
+    requested && client_ip(extensions).is_some_and(|ip| state.timing_header_allowlist.contains(&ip))
+}
+
+/// 将上游耗时分解与凭据尝试记录附加到返回给客户端的响应上
+/// （供 [`apply_timing_headers`] / [`apply_credential_headers`] 读取）
+fn attach_upstream_timing(
+    mut response: Response,
+    timing: Option<UpstreamTiming>,
+    attempts: Option<CredentialAttempts>,
+) -> Response {
+    if let Some(timing) = timing {
+        response.extensions_mut().insert(timing);
+    }
+    if let Some(attempts) = attempts {
+        response.extensions_mut().insert(attempts);
+    }
+    response
+}
+
+/// 处理请求的凭据 ID 响应头（调试用）
+const CREDENTIAL_ID_HEADER: &str = "x-kiro-credential-id";
+
+/// 依次尝试过的凭据 ID 响应头（仅在发生重试时出现）
+const CREDENTIAL_ATTEMPTS_HEADER: &str = "x-kiro-credential-attempts";
+
+/// 写入处理请求的凭据 ID；尝试过多次时附带完整的尝试顺序
+///
+/// 上游调用未成功时不写入。
+fn apply_credential_headers(mut response: Response) -> Response {
+    let Some(CredentialAttempts(attempts)) = response.extensions().get::<CredentialAttempts>().cloned()
+    else {
+        return response;
+    };
+    let headers = response.headers_mut();
+    if let Some(id) = attempts.last() {
+        headers.insert(CREDENTIAL_ID_HEADER, HeaderValue::from(*id));
+    }
+    if attempts.len() > 1 {
+        let list = attempts.iter().map(u64::to_string).collect::<Vec<_>>().join(",");
+        if let Ok(value) = HeaderValue::from_str(&list) {
+            headers.insert(CREDENTIAL_ATTEMPTS_HEADER, value);
+        }
+    }
+    response
+}
+
+/// 发生模型回退时返回实际使用模型的响应头
+const FALLBACK_MODEL_HEADER: &str = "x-kiro-fallback-model";
+
+/// 发生模型回退时，在返回给客户端的响应上标注实际使用的模型
+fn annotate_fallback_model(mut response: Response, fallback: Option<FallbackModel>) -> Response {
+    if let Some(FallbackModel(model)) = fallback
+        && let Ok(value) = HeaderValue::from_str(&model)
+    {
+        response.headers_mut().insert(FALLBACK_MODEL_HEADER, value);
+    }
+    response
+}
+
+/// 写入 `X-Kiro-Timing-*` 响应头（毫秒）
+///
+/// `Total` 为收到请求到响应头就绪的耗时；流式响应不包含之后的流传输时间。
+/// 上游调用未成功时只写入 `Total`。
+fn apply_timing_headers(mut response: Response, started: Instant) -> Response {
+    let timing = response.extensions().get::<UpstreamTiming>().copied();
+    let total = started.elapsed();
+    let headers = response.headers_mut();
+    let mut put = |name: &'static str, value: Duration| {
+        if let Ok(v) = HeaderValue::from_str(&format!("{:.3}", value.as_secs_f64() * 1000.0)) {
+            headers.insert(name, v);
+        }
+    };
+    if let Some(timing) = timing {
+        put("x-kiro-timing-credential-selection-ms", timing.credential_selection);
+        if let Some(refresh) = timing.token_refresh {
+            put("x-kiro-timing-token-refresh-ms", refresh);
+        }
+        put("x-kiro-timing-first-byte-ms", timing.first_byte);
+    }
+    put("x-kiro-timing-total-ms", total);
+    response
+}
+
+/// 慢请求（总耗时超过 `slowRequestThresholdMs`）的日志记录
+#[derive(Debug)]
+struct SlowRequest {
+    request_id: String,
+    model: String,
+    total: Duration,
+    timing: Option<UpstreamTiming>,
+}
+
+impl SlowRequest {
+    /// 总耗时超过阈值时返回慢请求记录
+    fn detect(
+        threshold: Duration,
+        total: Duration,
+        request_id: &str,
+        model: &str,
+        timing: Option<UpstreamTiming>,
+    ) -> Option<Self> {
+        (total > threshold).then(|| Self {
+            request_id: request_id.to_string(),
+            model: model.to_string(),
+            total,
+            timing,
+        })
+    }
+
+    /// 输出 warn 日志（上游调用未成功时凭据与耗时分解为空）
+    fn log(&self) {
+        let ms = |d: Duration| d.as_millis() as u64;
+        let timing = self.timing.as_ref();
+        tracing::warn!(
+            request_id = %self.request_id,
+            model = %self.model,
+            credential_id = ?timing.map(|t| t.credential_id),
+            total_ms = ms(self.total),
+            credential_selection_ms = ?timing.map(|t| ms(t.credential_selection)),
+            token_refresh_ms = ?timing.and_then(|t| t.token_refresh).map(ms),
+            first_byte_ms = ?timing.map(|t| ms(t.first_byte)),
+            token_refreshed = timing.is_some_and(|t| t.token_refresh.is_some()),
+            retries = timing.map_or(0, |t| t.retries),
+            "慢请求：总耗时超过阈值"
+        );
+    }
+}
+
+/// 随响应体一同释放的慢请求检查
+///
+/// 释放时总耗时超过阈值则记录日志：响应体传输完毕与客户端中途断开（响应体被提前丢弃）
+/// 两种情况都会触发。
+struct SlowRequestGuard {
+    started: Instant,
+    threshold: Duration,
+    request_id: String,
+    model: String,
+    timing: Option<UpstreamTiming>,
+}
+
+impl Drop for SlowRequestGuard {
+    fn drop(&mut self) {
+        let total = self.started.elapsed();
+        if let Some(slow) =
+            SlowRequest::detect(self.threshold, total, &self.request_id, &self.model, self.timing)
+        {
+            slow.log();
+        }
+    }
+}
+
+/// 在响应体释放时检查总耗时，超过阈值则记录慢请求日志
+///
+/// 流式响应计至流结束或客户端断开；请求 ID 取自 `request-id` 响应头（错误响应为 `-`）。
+fn log_slow_request_on_completion(
+    response: Response,
+    started: Instant,
+    threshold: Duration,
+    model: &str,
+) -> Response {
+    let guard = SlowRequestGuard {
+        started,
+        threshold,
+        request_id: response
+            .headers()
+            .get("request-id")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("-")
+            .to_string(),
+        model: model.to_string(),
+        timing: response.extensions().get::<UpstreamTiming>().copied(),
+    };
+
+    let (parts, body) = response.into_parts();
+    let body = body.into_data_stream().map(move |chunk| {
+        let _ = &guard;
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(body))
+}
+
 /// POST /v1/messages
 ///
 /// 创建消息（对话）
 pub async fn post_messages(
     State(state): State<AppState>,
-    JsonExtractor(mut payload): JsonExtractor<MessagesRequest>,
+    headers: HeaderMap,
+    extensions: Extensions,
+    CanonicalJson(mut payload): CanonicalJson<MessagesRequest>,
 ) -> Response {
+    let started = Instant::now();
+    let call_options = build_call_options(&state, &headers, &extensions);
+
+    // 模型名带 -bot 后缀时注入配置的系统提示词（需在模型映射覆盖前判断）
+    inject_bot_system_prompt(&state, &mut payload);
+
     // 应用 config 中的模型映射覆盖
     if let Some(override_model) = state.resolve_model_override(&payload.model) {
         tracing::info!(original = %payload.model, mapped = %override_model, "应用 config modelMapping 覆盖");
@@ -311,6 +598,18 @@ pub async fn post_messages(
     // 检测模型名是否包含 "thinking" 后缀，若包含则覆写 thinking 配置
     override_thinking_from_model_name(&mut payload);
 
+    // 检查请求特性是否被目标模型支持
+    if let Some(check) = &state.capability_check
+        && let Err(message) = check.check(&payload)
+    {
+        tracing::warn!("{}", message);
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("invalid_request_error", message)),
+        )
+            .into_response();
+    }
+
     // 检查是否为 WebSearch 请求
     if websearch::has_web_search_tool(&payload) {
         tracing::info!("检测到 WebSearch 工具，路由到 WebSearch 处理");
@@ -326,6 +625,18 @@ pub async fn post_messages(
         return websearch::handle_websearch_request(provider, &payload, input_tokens).await;
     }
 
+    // 检查工具数量上限（超出时按配置拒绝或截断）
+    if let Some(limit) = &state.tool_limit
+        && let Err(message) = limit.apply(&mut payload)
+    {
+        tracing::warn!("{}", message);
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("invalid_request_error", message)),
+        )
+            .into_response();
+    }
+
     // 预处理 URL 图片：下载并转换为 base64
     if let Err(e) = image_fetch::resolve_url_images(&mut payload).await {
         tracing::warn!("URL 图片处理失败: {}", e);
@@ -340,7 +651,7 @@ pub async fn post_messages(
     }
 
     // 转换请求
-    let conversion_result = match convert_request(&payload) {
+    let conversion_result = match convert_request_with_options(&payload, &state.conversion_options) {
         Ok(result) => result,
         Err(e) => {
             let (error_type, message) = match &e {
@@ -350,6 +661,10 @@ pub async fn post_messages(
                 ConversionError::EmptyMessages => {
                     ("invalid_request_error", "消息列表为空".to_string())
                 }
+                ConversionError::LeadingAssistantTurn
+                | ConversionError::SystemPromptTooLong { .. } => {
+                    ("invalid_request_error", e.to_string())
+                }
             };
             tracing::warn!("请求转换失败: {}", e);
             return (
@@ -383,6 +698,10 @@ pub async fn post_messages(
 
     tracing::debug!("Kiro request body: {}", request_body);
 
+    // 工具输入校验使用客户端原始 schema（不受工具定义压缩影响）
+    let tool_input_validator =
+        ToolInputValidator::new(state.tool_input_validation, payload.tools.as_deref());
+
     // 估算输入 tokens
     let input_tokens = token::count_all_tokens(
         payload.model.clone(),
@@ -400,21 +719,42 @@ pub async fn post_messages(
 
     let tool_name_map = conversion_result.tool_name_map;
 
-    if payload.stream {
+    let response = if payload.stream {
         // 流式响应
-        handle_stream_request(
-            provider,
-            &request_body,
-            &payload.model,
-            input_tokens,
-            thinking_enabled,
-            tool_name_map,
-        )
-        .await
+        let ctx = StreamContext::new_with_thinking(&payload.model, input_tokens, thinking_enabled, tool_name_map)
+            .with_tool_input_snapshots(tool_input_snapshots_requested(&headers))
+            .with_tool_input_validator(tool_input_validator)
+            .with_code_references(state.emit_code_references)
+            .with_followup_prompts(state.emit_followup_prompts)
+            .with_usage_updates(state.stream_usage_update_interval);
+        handle_stream_request(provider, &request_body, &call_options, ctx).await
     } else {
         // 非流式响应：仅在配置开启时提取 thinking 块
-        let extract_thinking = state.extract_thinking && thinking_enabled;
-        handle_non_stream_request(provider, &request_body, &payload.model, input_tokens, extract_thinking, tool_name_map).await
+        let options = NonStreamOptions {
+            extract_thinking: state.extract_thinking && thinking_enabled,
+            emit_code_references: state.emit_code_references,
+            emit_followup_prompts: state.emit_followup_prompts,
+        };
+        let tools = ResponseTools {
+            name_map: tool_name_map,
+            validator: tool_input_validator,
+        };
+        handle_non_stream_request(provider, &request_body, &call_options, &payload.model, input_tokens, options, tools).await
+    };
+
+    let response = if timing_headers_requested(&state, &headers, &extensions) {
+        apply_timing_headers(response, started)
+    } else {
+        response
+    };
+    let response = if state.credential_id_header {
+        apply_credential_headers(response)
+    } else {
+        response
+    };
+    match state.slow_request_threshold {
+        Some(threshold) => log_slow_request_on_completion(response, started, threshold, &payload.model),
+        None => response,
     }
 }
 
@@ -422,19 +762,17 @@ pub async fn post_messages(
 async fn handle_stream_request(
     provider: std::sync::Arc<crate::kiro::provider::KiroProvider>,
     request_body: &str,
-    model: &str,
-    input_tokens: i32,
-    thinking_enabled: bool,
-    tool_name_map: std::collections::HashMap<String, String>,
+    call_options: &CallOptions,
+    mut ctx: StreamContext,
 ) -> Response {
     // 调用 Kiro API（支持多凭据故障转移）
-    let response = match provider.call_api_stream(request_body).await {
+    let response = match provider.call_api_stream(request_body, call_options).await {
         Ok(resp) => resp,
         Err(e) => return map_provider_error(e),
     };
-
-    // 创建流处理上下文
-    let mut ctx = StreamContext::new_with_thinking(model, input_tokens, thinking_enabled, tool_name_map);
+    let timing = response.extensions().get::<UpstreamTiming>().copied();
+    let fallback = response.extensions().get::<FallbackModel>().cloned();
+    let attempts = response.extensions().get::<CredentialAttempts>().cloned();
 
     // 生成初始事件
     let initial_events = ctx.generate_initial_events();
@@ -451,7 +789,14 @@ async fn handle_stream_request(
         .header(header::CONNECTION, "keep-alive")
         .body(Body::from_stream(stream))
         .unwrap();
-    build_anthropic_response(StatusCode::OK, &request_id, sse_response)
+    annotate_fallback_model(
+        attach_upstream_timing(
+            build_anthropic_response(StatusCode::OK, &request_id, sse_response),
+            timing,
+            attempts,
+        ),
+        fallback,
+    )
 }
 
 /// Ping 事件间隔（25秒）
@@ -557,24 +902,33 @@ fn create_sse_stream(
 use super::converter::get_context_window_size;
 
 /// 处理非流式请求
-async fn handle_non_stream_request(
+pub(super) async fn handle_non_stream_request(
     provider: std::sync::Arc<crate::kiro::provider::KiroProvider>,
     request_body: &str,
+    call_options: &CallOptions,
     model: &str,
     input_tokens: i32,
-    thinking_enabled: bool,
-    tool_name_map: std::collections::HashMap<String, String>,
+    options: NonStreamOptions,
+    tools: ResponseTools,
 ) -> Response {
     // 调用 Kiro API（支持多凭据故障转移）
-    let response = match provider.call_api(request_body).await {
+    let response = match provider.call_api(request_body, call_options).await {
         Ok(resp) => resp,
         Err(e) => return map_provider_error(e),
     };
+    let timing = response.extensions().get::<UpstreamTiming>().copied();
+    let fallback = response.extensions().get::<FallbackModel>().cloned();
+    let attempts = response.extensions().get::<CredentialAttempts>().cloned();
 
     // 读取响应体
     let body_bytes = match response.bytes().await {
         Ok(bytes) => bytes,
         Err(e) => {
+            if e.is_timeout()
+                && let Some(timeout) = call_options.timeout
+            {
+                return map_provider_error(RequestTimeoutError { timeout }.into());
+            }
             tracing::error!("读取响应体失败: {}", e);
             return (
                 StatusCode::BAD_GATEWAY,
@@ -587,14 +941,160 @@ async fn handle_non_stream_request(
         }
     };
 
+    let mut output = parse_non_stream_events(&body_bytes, model, &tools.name_map);
+
+    if let Some(validator) = &tools.validator {
+        if validator.mode() == ToolInputValidation::Corrective
+            && let Some(corrected) =
+                retry_with_tool_input_correction(&provider, request_body, call_options, model, &tools, &output).await
+        {
+            output = corrected;
+        }
+        validator.annotate(&mut output.tool_uses);
+    }
+
+    let NonStreamOutput {
+        text_content,
+        tool_uses,
+        stop_reason,
+        context_input_tokens,
+        code_references,
+        followup_prompts,
+        ..
+    } = output;
+
+    // 构建响应内容
+    let mut content: Vec<serde_json::Value> = Vec::new();
+
+    if options.extract_thinking {
+        // 从完整文本中提取 thinking 块
+        let (thinking, remaining_text) =
+            super::stream::extract_thinking_from_complete_text(&text_content);
+
+        if let Some(thinking_text) = thinking {
+            content.push(json!({
+                "type": "thinking",
+                "thinking": thinking_text
+            }));
+        }
+
+        if !remaining_text.is_empty() {
+            content.push(json!({
+                "type": "text",
+                "text": remaining_text
+            }));
+        }
+    } else if !text_content.is_empty() {
+        content.push(json!({
+            "type": "text",
+            "text": text_content
+        }));
+    }
+
+    content.extend(tool_uses);
+
+    // 估算输出 tokens
+    let output_tokens = token::estimate_output_tokens(&content);
+
+    // 使用从 contextUsageEvent 计算的 input_tokens，如果没有则使用估算值
+    let final_input_tokens = context_input_tokens.unwrap_or(input_tokens);
+
+    // 构建 Anthropic 响应 - 使用有序 Map 确保 key 顺序与官方一致
+    let msg_id = generate_msg_id();
+    let mut response_map = serde_json::Map::new();
+    response_map.insert("model".to_string(), json!(model));
+    response_map.insert("id".to_string(), json!(&msg_id));
+    response_map.insert("type".to_string(), json!("message"));
+    response_map.insert("role".to_string(), json!("assistant"));
+    response_map.insert("content".to_string(), json!(content));
+    response_map.insert("stop_reason".to_string(), json!(stop_reason));
+    response_map.insert("stop_sequence".to_string(), json!(null));
+    response_map.insert("stop_details".to_string(), json!(null));
+    response_map.insert("usage".to_string(), json!({
+        "input_tokens": final_input_tokens,
+        "cache_creation_input_tokens": 0,
+        "cache_read_input_tokens": 0,
+        "cache_creation": {
+            "ephemeral_5m_input_tokens": 0,
+            "ephemeral_1h_input_tokens": 0
+        },
+        "output_tokens": output_tokens,
+        "service_tier": "standard",
+        "inference_geo": "global"
+    }));
+    if options.emit_code_references && !code_references.is_empty() {
+        response_map.insert(
+            "code_references".to_string(),
+            super::stream::code_reference_annotations(&code_references),
+        );
+    }
+    if options.emit_followup_prompts && !followup_prompts.is_empty() {
+        response_map.insert("followup_prompts".to_string(), json!(followup_prompts));
+    }
+    let response_body = serde_json::Value::Object(response_map);
+
+    annotate_fallback_model(
+        attach_upstream_timing(
+            build_anthropic_response(StatusCode::OK, &msg_id, Json(response_body).into_response()),
+            timing,
+            attempts,
+        ),
+        fallback,
+    )
+}
+
+/// 非流式响应的组装选项
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct NonStreamOptions {
+    /// 是否从文本中提取 thinking 块
+    pub extract_thinking: bool,
+    /// 是否附带代码引用（`code_references` 字段）
+    pub emit_code_references: bool,
+    /// 是否附带后续提示（`followup_prompts` 字段）
+    pub emit_followup_prompts: bool,
+}
+
+/// 非流式响应中工具相关的上下文
+pub(super) struct ResponseTools {
+    /// 工具名称反向映射（短名称 → 原始名称）
+    pub name_map: std::collections::HashMap<String, String>,
+    /// 工具输入校验器（未启用校验时为 None）
+    pub validator: Option<ToolInputValidator>,
+}
+
+/// 非流式响应事件流的解析结果
+struct NonStreamOutput {
+    /// 完整文本（含未提取的 thinking 标签）
+    text_content: String,
+    /// Anthropic 格式的 tool_use 块（工具名已还原）
+    tool_uses: Vec<serde_json::Value>,
+    /// 与 `tool_uses` 一一对应的 Kiro 格式工具调用（用于构建纠正请求）
+    kiro_tool_uses: Vec<ToolUseEntry>,
+    /// stop_reason
+    stop_reason: String,
+    /// 从 contextUsageEvent 计算的实际输入 tokens
+    context_input_tokens: Option<i32>,
+    /// 代码引用（许可证归属）
+    code_references: Vec<CodeReference>,
+    /// 后续提示（建议提问）
+    followup_prompts: Vec<String>,
+}
+
+/// 解析非流式响应的事件流
+fn parse_non_stream_events(
+    body_bytes: &[u8],
+    model: &str,
+    tool_name_map: &std::collections::HashMap<String, String>,
+) -> NonStreamOutput {
     // 解析事件流
     let mut decoder = EventStreamDecoder::new();
-    if let Err(e) = decoder.feed(&body_bytes) {
+    if let Err(e) = decoder.feed(body_bytes) {
         tracing::warn!("缓冲区溢出: {}", e);
     }
 
     let mut text_content = String::new();
     let mut tool_uses: Vec<serde_json::Value> = Vec::new();
+    let mut kiro_tool_uses: Vec<ToolUseEntry> = Vec::new();
     let mut has_tool_use = false;
     let mut stop_reason = "end_turn".to_string();
     let mut context_input_tokens: Option<i32> = None;
@@ -602,6 +1102,10 @@ async fn handle_non_stream_request(
     // 收集工具调用的增量 JSON
     let mut tool_json_buffers: std::collections::HashMap<String, String> =
         std::collections::HashMap::new();
+    let mut tool_use_tracker = ToolUseTracker::default();
+    let mut code_references: Vec<CodeReference> = Vec::new();
+    let mut followup_prompts: Vec<String> = Vec::new();
+    let mut upstream_stop_reason: Option<&'static str> = None;
 
     for result in decoder.decode_iter() {
         match result {
@@ -612,6 +1116,9 @@ async fn handle_non_stream_request(
                             text_content.push_str(&resp.content);
                         }
                         Event::ToolUse(tool_use) => {
+                            let Some(tool_use) = tool_use_tracker.resolve(&tool_use) else {
+                                continue;
+                            };
                             has_tool_use = true;
 
                             // 累积工具的 JSON 输入
@@ -647,6 +1154,10 @@ async fn handle_non_stream_request(
                                     "input": input,
                                     "caller": {"type": "direct"}
                                 }));
+                                kiro_tool_uses.push(
+                                    ToolUseEntry::new(&tool_use.tool_use_id, &tool_use.name)
+                                        .with_input(input),
+                                );
                             }
                         }
                         Event::ContextUsage(context_usage) => {
@@ -667,6 +1178,19 @@ async fn handle_non_stream_request(
                                 actual_input_tokens
                             );
                         }
+                        Event::CodeReference(code_reference) => {
+                            code_references.extend(code_reference.references);
+                        }
+                        Event::FollowupPrompt(followup) => {
+                            if let Some(suggestion) = followup.suggestion() {
+                                followup_prompts.push(suggestion.to_string());
+                            }
+                        }
+                        Event::MessageMetadata(metadata) => {
+                            if let Some(reason) = metadata.anthropic_stop_reason() {
+                                upstream_stop_reason = Some(reason);
+                            }
+                        }
                         Event::Exception { exception_type, .. } => {
                             if exception_type == "ContentLengthExceededException" {
                                 stop_reason = "max_tokens".to_string();
@@ -682,73 +1206,85 @@ async fn handle_non_stream_request(
         }
     }
 
-    // 确定 stop_reason
-    if has_tool_use && stop_reason == "end_turn" {
+    // 确定 stop_reason：上游明确给出终止原因时以其为准，否则推断
+    if let Some(reason) = upstream_stop_reason {
+        stop_reason = reason.to_string();
+    } else if has_tool_use && stop_reason == "end_turn" {
         stop_reason = "tool_use".to_string();
     }
 
-    // 构建响应内容
-    let mut content: Vec<serde_json::Value> = Vec::new();
+    NonStreamOutput {
+        text_content,
+        tool_uses,
+        kiro_tool_uses,
+        stop_reason,
+        context_input_tokens,
+        code_references,
+        followup_prompts,
+    }
+}
 
-    if thinking_enabled {
-        // 从完整文本中提取 thinking 块
-        let (thinking, remaining_text) =
-            super::stream::extract_thinking_from_complete_text(&text_content);
+/// 工具输入未通过校验时，把校验错误作为 tool_result 交还模型并重试一次
+///
+/// 所有工具调用均合法、纠正请求构建失败或重试失败时返回 None（沿用原结果）。
+async fn retry_with_tool_input_correction(
+    provider: &crate::kiro::provider::KiroProvider,
+    request_body: &str,
+    call_options: &CallOptions,
+    model: &str,
+    tools: &ResponseTools,
+    output: &NonStreamOutput,
+) -> Option<NonStreamOutput> {
+    let validator = tools.validator.as_ref()?;
+    let errors: std::collections::HashMap<String, String> = output
+        .tool_uses
+        .iter()
+        .zip(&output.kiro_tool_uses)
+        .filter_map(|(block, entry)| {
+            let error = validator.check(block["name"].as_str()?, &entry.input)?;
+            Some((entry.tool_use_id.clone(), error))
+        })
+        .collect();
+    if errors.is_empty() {
+        return None;
+    }
 
-        if let Some(thinking_text) = thinking {
-            content.push(json!({
-                "type": "thinking",
-                "thinking": thinking_text
-            }));
+    tracing::info!("{} 个工具调用的输入不符合 input_schema，要求模型纠正后重试", errors.len());
+    let body = tool_validation::corrective_request_body(
+        request_body,
+        &output.text_content,
+        output.kiro_tool_uses.clone(),
+        &errors,
+    )?;
+    let response = match provider.call_api(&body, call_options).await {
+        Ok(resp) => resp,
+        Err(e) => {
+            tracing::warn!("工具输入纠正重试失败，返回原结果: {}", e);
+            return None;
         }
-
-        if !remaining_text.is_empty() {
-            content.push(json!({
-                "type": "text",
-                "text": remaining_text
-            }));
+    };
+    match response.bytes().await {
+        Ok(bytes) => Some(parse_non_stream_events(&bytes, model, &tools.name_map)),
+        Err(e) => {
+            tracing::warn!("读取纠正重试响应失败，返回原结果: {}", e);
+            None
         }
-    } else if !text_content.is_empty() {
-        content.push(json!({
-            "type": "text",
-            "text": text_content
-        }));
     }
+}
 
-    content.extend(tool_uses);
-
-    // 估算输出 tokens
-    let output_tokens = token::estimate_output_tokens(&content);
-
-    // 使用从 contextUsageEvent 计算的 input_tokens，如果没有则使用估算值
-    let final_input_tokens = context_input_tokens.unwrap_or(input_tokens);
-
-    // 构建 Anthropic 响应 - 使用有序 Map 确保 key 顺序与官方一致
-    let msg_id = generate_msg_id();
-    let mut response_map = serde_json::Map::new();
-    response_map.insert("model".to_string(), json!(model));
-    response_map.insert("id".to_string(), json!(&msg_id));
-    response_map.insert("type".to_string(), json!("message"));
-    response_map.insert("role".to_string(), json!("assistant"));
-    response_map.insert("content".to_string(), json!(content));
-    response_map.insert("stop_reason".to_string(), json!(stop_reason));
-    response_map.insert("stop_sequence".to_string(), json!(null));
-    response_map.insert("stop_details".to_string(), json!(null));
-    response_map.insert("usage".to_string(), json!({
-        "input_tokens": final_input_tokens,
-        "cache_creation_input_tokens": 0,
-        "cache_read_input_tokens": 0,
-        "cache_creation": {
-            "ephemeral_5m_input_tokens": 0,
-            "ephemeral_1h_input_tokens": 0
-        },
-        "output_tokens": output_tokens,
-        "service_tier": "standard",
-        "inference_geo": "global"
-    }));
-    let response_body = serde_json::Value::Object(response_map);
-
-    build_anthropic_response(StatusCode::OK, &msg_id, Json(response_body).into_response())
+/// 模型名带 `-bot` 后缀且配置了 `botSystemPrompt` 时，将其插入到系统提示词最前面
+fn inject_bot_system_prompt(state: &AppState, payload: &mut MessagesRequest) {
+    let Some(prompt) = &state.bot_system_prompt else {
+        return;
+    };
+    if !normalize_model_name(&payload.model).bot {
+        return;
+    }
+    tracing::debug!(model = %payload.model, "模型名带 -bot 后缀，注入 botSystemPrompt");
+    payload
+        .system
+        .get_or_insert_with(Vec::new)
+        .insert(0, SystemMessage { text: prompt.clone() });
 }
 
 /// 检测模型名是否包含 "thinking" 后缀，若包含则覆写 thinking 配置
@@ -794,7 +1330,7 @@ fn override_thinking_from_model_name(payload: &mut MessagesRequest) {
 ///
 /// 计算消息的 token 数量
 pub async fn count_tokens(
-    JsonExtractor(payload): JsonExtractor<CountTokensRequest>,
+    CanonicalJson(payload): CanonicalJson<CountTokensRequest>,
 ) -> impl IntoResponse {
     tracing::info!(
         model = %payload.model,
@@ -821,8 +1357,16 @@ pub async fn count_tokens(
 /// - message_start 中的 input_tokens 是从 contextUsageEvent 计算的准确值
 pub async fn post_messages_cc(
     State(state): State<AppState>,
-    JsonExtractor(mut payload): JsonExtractor<MessagesRequest>,
+    headers: HeaderMap,
+    extensions: Extensions,
+    CanonicalJson(mut payload): CanonicalJson<MessagesRequest>,
 ) -> Response {
+    let started = Instant::now();
+    let call_options = build_call_options(&state, &headers, &extensions);
+
+    // 模型名带 -bot 后缀时注入配置的系统提示词（需在模型映射覆盖前判断）
+    inject_bot_system_prompt(&state, &mut payload);
+
     // 应用 config 中的模型映射覆盖
     if let Some(override_model) = state.resolve_model_override(&payload.model) {
         tracing::info!(original = %payload.model, mapped = %override_model, "应用 config modelMapping 覆盖");
@@ -856,6 +1400,18 @@ pub async fn post_messages_cc(
     // 检测模型名是否包含 "thinking" 后缀，若包含则覆写 thinking 配置
     override_thinking_from_model_name(&mut payload);
 
+    // 检查请求特性是否被目标模型支持
+    if let Some(check) = &state.capability_check
+        && let Err(message) = check.check(&payload)
+    {
+        tracing::warn!("{}", message);
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("invalid_request_error", message)),
+        )
+            .into_response();
+    }
+
     // 检查是否为 WebSearch 请求
     if websearch::has_web_search_tool(&payload) {
         tracing::info!("检测到 WebSearch 工具，路由到 WebSearch 处理");
@@ -871,6 +1427,18 @@ pub async fn post_messages_cc(
         return websearch::handle_websearch_request(provider, &payload, input_tokens).await;
     }
 
+    // 检查工具数量上限（超出时按配置拒绝或截断）
+    if let Some(limit) = &state.tool_limit
+        && let Err(message) = limit.apply(&mut payload)
+    {
+        tracing::warn!("{}", message);
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("invalid_request_error", message)),
+        )
+            .into_response();
+    }
+
     // 预处理 URL 图片：下载并转换为 base64
     if let Err(e) = image_fetch::resolve_url_images(&mut payload).await {
         tracing::warn!("URL 图片处理失败: {}", e);
@@ -885,7 +1453,7 @@ pub async fn post_messages_cc(
     }
 
     // 转换请求
-    let conversion_result = match convert_request(&payload) {
+    let conversion_result = match convert_request_with_options(&payload, &state.conversion_options) {
         Ok(result) => result,
         Err(e) => {
             let (error_type, message) = match &e {
@@ -895,6 +1463,10 @@ pub async fn post_messages_cc(
                 ConversionError::EmptyMessages => {
                     ("invalid_request_error", "消息列表为空".to_string())
                 }
+                ConversionError::LeadingAssistantTurn
+                | ConversionError::SystemPromptTooLong { .. } => {
+                    ("invalid_request_error", e.to_string())
+                }
             };
             tracing::warn!("请求转换失败: {}", e);
             return (
@@ -928,6 +1500,10 @@ pub async fn post_messages_cc(
 
     tracing::debug!("Kiro request body: {}", request_body);
 
+    // 工具输入校验使用客户端原始 schema（不受工具定义压缩影响）
+    let tool_input_validator =
+        ToolInputValidator::new(state.tool_input_validation, payload.tools.as_deref());
+
     // 估算输入 tokens
     let input_tokens = token::count_all_tokens(
         payload.model.clone(),
@@ -945,21 +1521,42 @@ pub async fn post_messages_cc(
 
     let tool_name_map = conversion_result.tool_name_map;
 
-    if payload.stream {
+    let response = if payload.stream {
         // 流式响应（缓冲模式）
-        handle_stream_request_buffered(
-            provider,
-            &request_body,
-            &payload.model,
-            input_tokens,
-            thinking_enabled,
-            tool_name_map,
-        )
-        .await
+        let ctx = BufferedStreamContext::new(&payload.model, input_tokens, thinking_enabled, tool_name_map)
+            .with_block_order_normalization(state.normalize_content_block_order)
+            .with_tool_input_snapshots(tool_input_snapshots_requested(&headers))
+            .with_tool_input_validator(tool_input_validator)
+            .with_code_references(state.emit_code_references)
+            .with_followup_prompts(state.emit_followup_prompts);
+        handle_stream_request_buffered(provider, &request_body, &call_options, ctx).await
     } else {
         // 非流式响应：仅在配置开启时提取 thinking 块
-        let extract_thinking = state.extract_thinking && thinking_enabled;
-        handle_non_stream_request(provider, &request_body, &payload.model, input_tokens, extract_thinking, tool_name_map).await
+        let options = NonStreamOptions {
+            extract_thinking: state.extract_thinking && thinking_enabled,
+            emit_code_references: state.emit_code_references,
+            emit_followup_prompts: state.emit_followup_prompts,
+        };
+        let tools = ResponseTools {
+            name_map: tool_name_map,
+            validator: tool_input_validator,
+        };
+        handle_non_stream_request(provider, &request_body, &call_options, &payload.model, input_tokens, options, tools).await
+    };
+
+    let response = if timing_headers_requested(&state, &headers, &extensions) {
+        apply_timing_headers(response, started)
+    } else {
+        response
+    };
+    let response = if state.credential_id_header {
+        apply_credential_headers(response)
+    } else {
+        response
+    };
+    match state.slow_request_threshold {
+        Some(threshold) => log_slow_request_on_completion(response, started, threshold, &payload.model),
+        None => response,
     }
 }
 
@@ -970,19 +1567,17 @@ pub async fn post_messages_cc(
 async fn handle_stream_request_buffered(
     provider: std::sync::Arc<crate::kiro::provider::KiroProvider>,
     request_body: &str,
-    model: &str,
-    estimated_input_tokens: i32,
-    thinking_enabled: bool,
-    tool_name_map: std::collections::HashMap<String, String>,
+    call_options: &CallOptions,
+    ctx: BufferedStreamContext,
 ) -> Response {
     // 调用 Kiro API（支持多凭据故障转移）
-    let response = match provider.call_api_stream(request_body).await {
+    let response = match provider.call_api_stream(request_body, call_options).await {
         Ok(resp) => resp,
         Err(e) => return map_provider_error(e),
     };
-
-    // 创建缓冲流处理上下文
-    let ctx = BufferedStreamContext::new(model, estimated_input_tokens, thinking_enabled, tool_name_map);
+    let timing = response.extensions().get::<UpstreamTiming>().copied();
+    let fallback = response.extensions().get::<FallbackModel>().cloned();
+    let attempts = response.extensions().get::<CredentialAttempts>().cloned();
 
     // 创建缓冲 SSE 流
     let stream = create_buffered_sse_stream(response, ctx);
@@ -996,7 +1591,14 @@ async fn handle_stream_request_buffered(
         .header(header::CONNECTION, "keep-alive")
         .body(Body::from_stream(stream))
         .unwrap();
-    build_anthropic_response(StatusCode::OK, &request_id, sse_response)
+    annotate_fallback_model(
+        attach_upstream_timing(
+            build_anthropic_response(StatusCode::OK, &request_id, sse_response),
+            timing,
+            attempts,
+        ),
+        fallback,
+    )
 }
 
 /// 创建缓冲 SSE 事件流
@@ -1089,3 +1691,593 @@ fn create_buffered_sse_stream(
     )
     .flatten()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anthropic::middleware::FINGERPRINT_SEED_HEADER;
+    use crate::kiro::endpoint::{IdeEndpoint, KiroEndpoint, RequestContext};
+    use crate::kiro::model::credentials::KiroCredentials;
+    use crate::model::config::Config;
+    use std::net::Ipv4Addr;
+
+    const CREDENTIAL_MACHINE_ID: &str =
+        "0000000000000000000000000000000000000000000000000000000000000000";
+
+    /// 构造携带指纹种子请求头、来自指定 IP 的请求
+    fn request_parts(seed: &str, ip: IpAddr) -> (HeaderMap, Extensions) {
+        let mut headers = HeaderMap::new();
+        headers.insert(FINGERPRINT_SEED_HEADER, HeaderValue::from_str(seed).unwrap());
+        let mut extensions = Extensions::new();
+        extensions.insert(ConnectInfo(SocketAddr::new(ip, 50000)));
+        (headers, extensions)
+    }
+
+    /// 按 Provider 的方式组装 IDE 端点请求，返回出站 User-Agent
+    fn outbound_user_agent(config: &Config, options: &CallOptions) -> String {
+        let credentials = KiroCredentials::default();
+        let fingerprint = options.fingerprint();
+        let machine_id = fingerprint
+            .as_ref()
+            .map(|fp| fp.machine_id.clone())
+            .unwrap_or_else(|| CREDENTIAL_MACHINE_ID.to_string());
+        let ctx = RequestContext {
+            credentials: &credentials,
+            token: "token",
+            machine_id: &machine_id,
+            config,
+            fingerprint: fingerprint.as_ref(),
+        };
+        let request = IdeEndpoint::new()
+            .decorate_api(reqwest::Client::new().post("http://localhost/"), &ctx)
+            .build()
+            .unwrap();
+        request.headers()["user-agent"].to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_fingerprint_seed_header_changes_user_agent_only_when_enabled() {
+        let config = Config::default();
+        let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let (headers, extensions) = request_parts("repro-seed", localhost);
+        let baseline = outbound_user_agent(&config, &CallOptions::default());
+
+        // 功能关闭：请求头被忽略
+        let disabled = AppState::new("key", false);
+        let options = build_call_options(&disabled, &headers, &extensions);
+        assert!(options.fingerprint_seed.is_none());
+        assert_eq!(outbound_user_agent(&config, &options), baseline);
+
+        // 功能开启且 IP 在白名单内：User-Agent 改为种子指纹
+        let enabled = AppState::new("key", false).with_fingerprint_seed_allowlist(vec![localhost]);
+        let options = build_call_options(&enabled, &headers, &extensions);
+        assert_eq!(options.fingerprint_seed.as_deref(), Some("repro-seed"));
+        let user_agent = outbound_user_agent(&config, &options);
+        assert_ne!(user_agent, baseline);
+        assert!(user_agent.contains(&options.fingerprint().unwrap().machine_id));
+    }
+
+    #[test]
+    fn test_fingerprint_seed_header_rejected_outside_allowlist() {
+        let enabled = AppState::new("key", false)
+            .with_fingerprint_seed_allowlist(vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+        let (headers, extensions) = request_parts("repro-seed", IpAddr::V4(Ipv4Addr::new(10, 0, 0, 8)));
+        let options = build_call_options(&enabled, &headers, &extensions);
+        assert!(options.fingerprint_seed.is_none());
+    }
+
+    /// 通过 mock 上游发送一次来自本机的非流式请求，返回响应头
+    async fn post_messages_with_headers(
+        configure: impl FnOnce(AppState) -> AppState,
+        headers: HeaderMap,
+    ) -> HeaderMap {
+        use crate::kiro::parser::frame::encode_event_frame;
+        use crate::kiro::test_support::{mock_provider, spawn_mock_upstream};
+
+        let body = encode_event_frame("assistantResponseEvent", r#"{"content":"hello"}"#);
+        let (url, _) = spawn_mock_upstream(vec![body]).await;
+        let state = configure(
+            AppState::new("key", false).with_kiro_provider(mock_provider(&url, Config::default())),
+        );
+        let mut extensions = Extensions::new();
+        extensions.insert(ConnectInfo(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 50000)));
+        let payload: MessagesRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 64,
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+
+        let response = post_messages(State(state), headers, extensions, CanonicalJson(payload)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        response.headers().clone()
+    }
+
+    fn timing_ms(headers: &HeaderMap, name: &str) -> f64 {
+        headers[name].to_str().unwrap().parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_timing_headers_present_and_ordered_when_requested() {
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert(TIMING_HEADER, HeaderValue::from_static("true"));
+
+        let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        for headers in [
+            post_messages_with_headers(
+                |state| state.with_timing_header_allowlist(vec![localhost]),
+                request_headers,
+            )
+            .await,
+            post_messages_with_headers(|state| state.with_timing_headers(true), HeaderMap::new()).await,
+        ] {
+            let selection = timing_ms(&headers, "x-kiro-timing-credential-selection-ms");
+            let first_byte = timing_ms(&headers, "x-kiro-timing-first-byte-ms");
+            let total = timing_ms(&headers, "x-kiro-timing-total-ms");
+            assert!(selection >= 0.0 && first_byte >= 0.0);
+            assert!(selection + first_byte <= total, "{:?}", headers);
+            // API Key 凭据无需刷新 Token
+            assert!(!headers.contains_key("x-kiro-timing-token-refresh-ms"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timing_headers_absent_by_default() {
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert(TIMING_HEADER, HeaderValue::from_static("true"));
+        let outside_allowlist = |state: AppState| {
+            state.with_timing_header_allowlist(vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 8))])
+        };
+
+        // 未配置、或请求头来自白名单外的客户端时均不附带
+        for headers in [
+            post_messages_with_headers(|state| state, HeaderMap::new()).await,
+            post_messages_with_headers(|state| state, request_headers.clone()).await,
+            post_messages_with_headers(outside_allowlist, request_headers).await,
+        ] {
+            assert!(
+                !headers
+                    .keys()
+                    .any(|k| k.as_str().starts_with("x-kiro-timing"))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_credential_id_header_reflects_serving_credential_only_when_enabled() {
+        use crate::kiro::parser::frame::encode_event_frame;
+        use crate::kiro::test_support::{mock_provider, spawn_mock_upstream_with_status};
+
+        for enabled in [true, false] {
+            // 首次请求上游 5xx，重试后成功
+            let (url, _) = spawn_mock_upstream_with_status(vec![
+                (StatusCode::INTERNAL_SERVER_ERROR, b"boom".to_vec()),
+                (
+                    StatusCode::OK,
+                    encode_event_frame("assistantResponseEvent", r#"{"content":"hello"}"#),
+                ),
+            ])
+            .await;
+            let state = AppState::new("key", false)
+                .with_kiro_provider(mock_provider(&url, Config::default()))
+                .with_credential_id_header(enabled);
+            let payload: MessagesRequest = serde_json::from_value(json!({
+                "model": "claude-sonnet-4-5",
+                "max_tokens": 64,
+                "messages": [{"role": "user", "content": "hi"}]
+            }))
+            .unwrap();
+
+            let response =
+                post_messages(State(state), HeaderMap::new(), Extensions::new(), CanonicalJson(payload)).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let headers = response.headers();
+            if enabled {
+                assert_eq!(headers[CREDENTIAL_ID_HEADER], "1");
+                assert_eq!(headers[CREDENTIAL_ATTEMPTS_HEADER], "1,1");
+            } else {
+                assert!(!headers.contains_key(CREDENTIAL_ID_HEADER));
+                assert!(!headers.contains_key(CREDENTIAL_ATTEMPTS_HEADER));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_timeout_header_aborts_slow_upstream_at_deadline() {
+        use crate::kiro::parser::frame::encode_event_frame;
+        use crate::kiro::test_support::{mock_provider, spawn_slow_mock_upstream};
+
+        let body = encode_event_frame("assistantResponseEvent", r#"{"content":"hello"}"#);
+        let (url, hits) = spawn_slow_mock_upstream(Duration::from_secs(5), body).await;
+        let state = AppState::new("key", false)
+            .with_kiro_provider(mock_provider(&url, Config::default()));
+        let payload: MessagesRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 64,
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(TIMEOUT_HEADER, HeaderValue::from_static("200"));
+
+        let started = Instant::now();
+        let response =
+            post_messages(State(state), headers, Extensions::new(), CanonicalJson(payload)).await;
+        let elapsed = started.elapsed();
+
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert!(elapsed >= Duration::from_millis(200), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+        // 超时后不再重试
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["type"], "timeout_error");
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_feature_rejected_before_upstream_call() {
+        use crate::kiro::parser::frame::encode_event_frame;
+        use crate::kiro::test_support::{mock_provider, spawn_mock_upstream};
+        use crate::model::config::ModelFeature;
+
+        let body = encode_event_frame("assistantResponseEvent", r#"{"content":"hello"}"#);
+        let (url, hits) = spawn_mock_upstream(vec![body]).await;
+        let state = AppState::new("key", false)
+            .with_kiro_provider(mock_provider(&url, Config::default()))
+            .with_capability_check(super::super::capabilities::CapabilityCheck {
+                unsupported: std::collections::HashMap::from([(
+                    "claude-haiku-4.5".to_string(),
+                    vec![ModelFeature::StructuredOutput],
+                )]),
+            });
+        let payload: MessagesRequest = serde_json::from_value(json!({
+            "model": "claude-haiku-4-5",
+            "max_tokens": 64,
+            "output_config": {"format": {"type": "json_schema", "schema": {"type": "object"}}},
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+
+        let response =
+            post_messages(State(state), HeaderMap::new(), Extensions::new(), CanonicalJson(payload))
+                .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 0);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert!(
+            body["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("output_config.format")
+        );
+    }
+
+    #[test]
+    fn test_bot_system_prompt_injected_only_for_bot_models() {
+        let request = |model: &str| -> MessagesRequest {
+            serde_json::from_value(json!({
+                "model": model,
+                "max_tokens": 64,
+                "system": "client prompt",
+                "messages": [{"role": "user", "content": "hi"}]
+            }))
+            .unwrap()
+        };
+        let system = |payload: &MessagesRequest| -> Vec<String> {
+            payload
+                .system
+                .iter()
+                .flatten()
+                .map(|m| m.text.clone())
+                .collect()
+        };
+        let state = AppState::new("key", false).with_bot_system_prompt("You are a chat bot.");
+
+        let mut bot = request("claude-sonnet-4-5-20250929-bot");
+        inject_bot_system_prompt(&state, &mut bot);
+        assert_eq!(system(&bot), vec!["You are a chat bot.", "client prompt"]);
+
+        let mut plain = request("claude-sonnet-4-5");
+        inject_bot_system_prompt(&state, &mut plain);
+        assert_eq!(system(&plain), vec!["client prompt"]);
+
+        // 未配置时 -bot 模型不受影响
+        let mut unconfigured = request("claude-sonnet-4-5-bot");
+        inject_bot_system_prompt(&AppState::new("key", false), &mut unconfigured);
+        assert_eq!(system(&unconfigured), vec!["client prompt"]);
+    }
+
+    #[test]
+    fn test_non_stream_stop_reason_follows_message_metadata() {
+        use crate::kiro::parser::frame::encode_event_frame;
+
+        let parse = |frames: &[(&str, &str)]| {
+            let body: Vec<u8> = frames
+                .iter()
+                .flat_map(|(event_type, payload)| encode_event_frame(event_type, payload))
+                .collect();
+            parse_non_stream_events(&body, "claude-sonnet-4-5", &std::collections::HashMap::new())
+                .stop_reason
+        };
+        let text = ("assistantResponseEvent", r#"{"content":"hello"}"#);
+        let tool = (
+            "toolUseEvent",
+            r#"{"name":"read_file","toolUseId":"tool_1","input":"{}","stop":true}"#,
+        );
+
+        assert_eq!(
+            parse(&[text, ("messageMetadataEvent", r#"{"stopReason":"MAX_TOKENS"}"#)]),
+            "max_tokens"
+        );
+        assert_eq!(
+            parse(&[text, tool, ("messageMetadataEvent", r#"{"stopReason":"TOOL_USE"}"#)]),
+            "tool_use"
+        );
+        assert_eq!(
+            parse(&[text, ("messageMetadataEvent", r#"{"stopReason":"STOP_SEQUENCE"}"#)]),
+            "stop_sequence"
+        );
+        assert_eq!(parse(&[text]), "end_turn");
+        assert_eq!(parse(&[text, tool]), "tool_use");
+    }
+
+    #[test]
+    fn test_requested_timeout_is_clamped_to_server_max() {
+        let state = AppState::new("key", false).with_max_request_timeout(Duration::from_secs(10));
+        let timeout = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(TIMEOUT_HEADER, HeaderValue::from_static(value));
+            requested_timeout(&state, &headers)
+        };
+        assert_eq!(timeout("1500"), Some(Duration::from_millis(1500)));
+        assert_eq!(timeout("999999999"), Some(Duration::from_secs(10)));
+        assert_eq!(timeout("0"), None);
+        assert_eq!(timeout("soon"), None);
+        assert_eq!(requested_timeout(&state, &HeaderMap::new()), None);
+    }
+
+    #[tokio::test]
+    async fn test_model_fallback_when_primary_model_unavailable_everywhere() {
+        use crate::kiro::parser::frame::encode_event_frame;
+        use crate::kiro::test_support::{mock_provider, spawn_mock_upstream_with_status};
+
+        // 首次请求：上游报告模型容量不足（凭据上的 opus 进入模型级冷却）；之后正常返回
+        let (url, hits) = spawn_mock_upstream_with_status(vec![
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                br#"{"message":"high traffic","reason":"INSUFFICIENT_MODEL_CAPACITY"}"#.to_vec(),
+            ),
+            (
+                StatusCode::OK,
+                encode_event_frame("assistantResponseEvent", r#"{"content":"hello"}"#),
+            ),
+        ])
+        .await;
+        let mut config = Config::default();
+        config.model_fallbacks.insert(
+            "claude-opus-4.5".to_string(),
+            vec!["claude-sonnet-4.5".to_string()],
+        );
+        let state = AppState::new("key", false).with_kiro_provider(mock_provider(&url, config));
+        let payload: MessagesRequest = serde_json::from_value(json!({
+            "model": "claude-opus-4-5",
+            "max_tokens": 64,
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+
+        let response = post_messages(
+            State(state),
+            HeaderMap::new(),
+            Extensions::new(),
+            CanonicalJson(payload),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[FALLBACK_MODEL_HEADER],
+            "claude-sonnet-4.5"
+        );
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_slow_request_detected_only_above_threshold() {
+        let timing = UpstreamTiming {
+            credential_selection: Duration::from_millis(5),
+            token_refresh: Some(Duration::from_millis(1800)),
+            first_byte: Duration::from_millis(4000),
+            credential_id: 3,
+            retries: 1,
+        };
+        let threshold = Duration::from_secs(5);
+
+        let slow = SlowRequest::detect(
+            threshold,
+            Duration::from_millis(6200),
+            "req_01abc",
+            "claude-sonnet-4-5",
+            Some(timing),
+        )
+        .expect("超过阈值的请求应记录慢请求日志");
+        assert_eq!(slow.request_id, "req_01abc");
+        assert_eq!(slow.model, "claude-sonnet-4-5");
+        let recorded = slow.timing.unwrap();
+        assert_eq!(recorded.credential_id, 3);
+        assert_eq!(recorded.retries, 1);
+        assert!(recorded.token_refresh.is_some());
+
+        assert!(
+            SlowRequest::detect(threshold, Duration::from_millis(800), "req_01def", "m", Some(timing))
+                .is_none()
+        );
+        assert!(SlowRequest::detect(threshold, threshold, "req_01def", "m", None).is_none());
+    }
+
+    /// 收集当前线程 tracing 输出的缓冲区
+    #[derive(Clone, Default)]
+    struct LogBuffer(std::sync::Arc<parking_lot::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl LogBuffer {
+        fn contents(&self) -> String {
+            String::from_utf8_lossy(&self.0.lock()).into_owned()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_request_logged_on_completion_and_on_disconnect() {
+        let logs = LogBuffer::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let slow_response = |request_id: &'static str| {
+            let response = Response::builder()
+                .header("request-id", request_id)
+                .body(Body::from("hello"))
+                .unwrap();
+            let started = Instant::now() - Duration::from_secs(2);
+            log_slow_request_on_completion(response, started, Duration::from_secs(1), "claude-sonnet-4-5")
+        };
+
+        // 响应体传输完毕
+        let body = slow_response("req_complete").into_body();
+        axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let output = logs.contents();
+        assert!(output.contains("慢请求"), "{}", output);
+        assert!(output.contains("WARN"), "{}", output);
+        assert!(output.contains("request_id=req_complete"), "{}", output);
+        assert!(output.contains("model=claude-sonnet-4-5"), "{}", output);
+
+        // 客户端断开：响应体未读完即被丢弃
+        drop(slow_response("req_disconnected"));
+        assert!(logs.contents().contains("request_id=req_disconnected"));
+
+        // 未超过阈值不记录
+        let fast = log_slow_request_on_completion(
+            Response::builder().header("request-id", "req_fast").body(Body::empty()).unwrap(),
+            Instant::now(),
+            Duration::from_secs(1),
+            "m",
+        );
+        drop(fast);
+        assert!(!logs.contents().contains("req_fast"));
+    }
+
+    /// 请求一个带必填参数的工具，并以非流式方式发送
+    async fn post_read_file_request(
+        mode: crate::model::config::ToolInputValidation,
+        responses: Vec<Vec<u8>>,
+    ) -> (serde_json::Value, usize) {
+        use crate::kiro::test_support::{mock_provider, spawn_mock_upstream};
+
+        let (url, hits) = spawn_mock_upstream(responses).await;
+        let state = AppState::new("key", false)
+            .with_kiro_provider(mock_provider(&url, Config::default()))
+            .with_tool_input_validation(mode);
+        let payload: MessagesRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 64,
+            "messages": [{"role": "user", "content": "read /a"}],
+            "tools": [{
+                "name": "read_file",
+                "description": "Read a file",
+                "input_schema": {
+                    "type": "object",
+                    "properties": {"path": {"type": "string"}},
+                    "required": ["path"]
+                }
+            }]
+        }))
+        .unwrap();
+
+        let response = post_messages(
+            State(state),
+            HeaderMap::new(),
+            Extensions::new(),
+            CanonicalJson(payload),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            serde_json::from_slice(&body).unwrap(),
+            hits.load(std::sync::atomic::Ordering::SeqCst),
+        )
+    }
+
+    fn read_file_tool_use(id: &str, input: &str) -> Vec<u8> {
+        crate::kiro::parser::frame::encode_event_frame(
+            "toolUseEvent",
+            &json!({"name": "read_file", "toolUseId": id, "input": input, "stop": true})
+                .to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_tool_input_validation_annotates_missing_required_field() {
+        use crate::model::config::ToolInputValidation;
+
+        let (body, hits) = post_read_file_request(
+            ToolInputValidation::Annotate,
+            vec![read_file_tool_use("tooluse_1", r#"{"limit": 5}"#)],
+        )
+        .await;
+        assert_eq!(hits, 1);
+        let block = &body["content"][0];
+        assert_eq!(block["type"], "tool_use");
+        assert_eq!(block["input"], json!({"limit": 5}));
+        assert_eq!(
+            block["validation_error"],
+            "/: missing required field \"path\""
+        );
+
+        // 未开启校验时原样返回
+        let (body, _) = post_read_file_request(
+            ToolInputValidation::Off,
+            vec![read_file_tool_use("tooluse_1", r#"{"limit": 5}"#)],
+        )
+        .await;
+        assert!(body["content"][0].get("validation_error").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tool_input_validation_corrective_retries_once() {
+        use crate::model::config::ToolInputValidation;
+
+        let (body, hits) = post_read_file_request(
+            ToolInputValidation::Corrective,
+            vec![
+                read_file_tool_use("tooluse_1", r#"{"limit": 5}"#),
+                read_file_tool_use("tooluse_2", r#"{"path": "/a"}"#),
+            ],
+        )
+        .await;
+        assert_eq!(hits, 2);
+        let blocks = body["content"].as_array().unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0]["input"], json!({"path": "/a"}));
+        assert!(blocks[0].get("validation_error").is_none());
+        assert_eq!(body["stop_reason"], "tool_use");
+    }
+}
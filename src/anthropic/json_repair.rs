@@ -0,0 +1,282 @@
+//! 不完整 JSON 修复
+//!
+//! 工具输入以 `input_json_delta` 形式分片到达，拼接到一半时通常不是合法 JSON。
+//! 这里通过补全未闭合的字符串/数组/对象（必要时丢掉末尾残缺的 token），
+//! 得到"截至目前"输入的可解析快照。
+//!
+//! 解析状态随分片增量推进：每个分片只扫描新到达的部分，生成快照时
+//! 直接根据当前状态决定截断位置与补全内容，不再回头重新扫描已有输入。
+
+use serde_json::Value;
+
+/// 未闭合的容器
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Container {
+    Object,
+    Array,
+}
+
+impl Container {
+    fn closer(self) -> char {
+        match self {
+            Self::Object => '}',
+            Self::Array => ']',
+        }
+    }
+}
+
+/// 下一个期望的语法成分
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expect {
+    /// 值（顶层开头、冒号或数组逗号之后）
+    Value,
+    /// 值或 `]`（刚进入数组）
+    ValueOrEnd,
+    /// 键（对象逗号之后）
+    Key,
+    /// 键或 `}`（刚进入对象）
+    KeyOrEnd,
+    /// 键之后的冒号
+    Colon,
+    /// 值之后的逗号或容器结束
+    CommaOrEnd,
+}
+
+/// 正在读取的 token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    None,
+    /// 字符串；`escape` 为未完成转义序列的起点及剩余字符数
+    String {
+        is_key: bool,
+        escape: Option<(usize, u8)>,
+    },
+    /// 数字或字面量（true / false / null），记录起点
+    Bare { start: usize },
+}
+
+/// 增量 JSON 修复器
+///
+/// 通过 [`PartialJson::push`] 追加分片，[`PartialJson::snapshot`] 返回已到达数据的可解析快照：
+/// 残缺的键会被丢弃，残缺的字面量（如 `tru`）会被替换为 `null`。
+#[derive(Debug)]
+pub(crate) struct PartialJson {
+    text: String,
+    /// 已扫描的字节数
+    scanned: usize,
+    containers: Vec<Container>,
+    expect: Expect,
+    token: Token,
+    /// 当前容器内最近一个成员的起点（逗号位置，或紧随开括号之后）
+    member_start: usize,
+}
+
+impl Default for PartialJson {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            scanned: 0,
+            containers: Vec::new(),
+            expect: Expect::Value,
+            token: Token::None,
+            member_start: 0,
+        }
+    }
+}
+
+impl PartialJson {
+    /// 已累计的原始输入
+    pub(crate) fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// 追加分片并推进解析状态
+    pub(crate) fn push(&mut self, fragment: &str) {
+        self.text.push_str(fragment);
+        while let Some(c) = self.text[self.scanned..].chars().next() {
+            self.advance(self.scanned, c);
+            self.scanned += c.len_utf8();
+        }
+    }
+
+    fn advance(&mut self, pos: usize, c: char) {
+        match self.token {
+            Token::String { is_key, escape } => {
+                self.token = match (escape, c) {
+                    (Some((start, _)), 'u') if pos == start + 1 => Token::String {
+                        is_key,
+                        escape: Some((start, 4)),
+                    },
+                    (Some((start, remaining)), _) if remaining > 1 => Token::String {
+                        is_key,
+                        escape: Some((start, remaining - 1)),
+                    },
+                    (Some(_), _) => Token::String { is_key, escape: None },
+                    (None, '\\') => Token::String {
+                        is_key,
+                        escape: Some((pos, 1)),
+                    },
+                    (None, '"') => {
+                        self.token = Token::None;
+                        self.expect = if is_key { Expect::Colon } else { Expect::CommaOrEnd };
+                        return;
+                    }
+                    (None, _) => return,
+                };
+                return;
+            }
+            Token::Bare { .. } => {
+                if !(c.is_whitespace() || matches!(c, ',' | '}' | ']' | ':')) {
+                    return;
+                }
+                self.token = Token::None;
+                self.expect = Expect::CommaOrEnd;
+            }
+            Token::None => {}
+        }
+
+        match c {
+            _ if c.is_whitespace() => {}
+            '{' | '[' => {
+                let (container, expect) = if c == '{' {
+                    (Container::Object, Expect::KeyOrEnd)
+                } else {
+                    (Container::Array, Expect::ValueOrEnd)
+                };
+                self.containers.push(container);
+                self.expect = expect;
+                self.member_start = pos + 1;
+            }
+            '}' | ']' => {
+                self.containers.pop();
+                self.expect = Expect::CommaOrEnd;
+            }
+            ',' => {
+                self.member_start = pos;
+                self.expect = match self.containers.last() {
+                    Some(Container::Object) => Expect::Key,
+                    _ => Expect::Value,
+                };
+            }
+            ':' => self.expect = Expect::Value,
+            '"' => {
+                self.token = Token::String {
+                    is_key: matches!(self.expect, Expect::Key | Expect::KeyOrEnd),
+                    escape: None,
+                };
+            }
+            _ => self.token = Token::Bare { start: pos },
+        }
+    }
+
+    /// 生成已到达数据的可解析快照（尚无任何值时返回 None）
+    pub(crate) fn snapshot(&self) -> Option<Value> {
+        let mut end = self.text.len();
+        let mut tail = "";
+        match self.token {
+            Token::String { is_key: true, .. } => end = self.member_start,
+            Token::String { escape, .. } => {
+                if let Some((start, _)) = escape {
+                    end = start;
+                }
+                tail = "\"";
+            }
+            Token::Bare { start } => {
+                let token = &self.text[start..];
+                // 字面量不完整时替换为 null；数字去掉末尾残留的 `.`、`e`、`-` 等
+                let kept = if token.starts_with(['t', 'f', 'n']) {
+                    if matches!(token, "true" | "false" | "null") { token } else { "" }
+                } else {
+                    token.trim_end_matches(|c: char| !c.is_ascii_digit())
+                };
+                end = start + kept.len();
+                if kept.is_empty() {
+                    match self.containers.last() {
+                        Some(Container::Array) => end = self.member_start,
+                        _ => tail = "null",
+                    }
+                }
+            }
+            Token::None => match (self.expect, self.containers.last()) {
+                (Expect::Value, None) => return None,
+                (Expect::Value, Some(Container::Object)) => tail = "null",
+                (Expect::Value | Expect::Key | Expect::Colon, Some(_)) => end = self.member_start,
+                _ => {}
+            },
+        }
+
+        let mut repaired = String::with_capacity(end + tail.len() + self.containers.len());
+        repaired.push_str(&self.text[..end]);
+        repaired.push_str(tail);
+        repaired.extend(self.containers.iter().rev().map(|c| c.closer()));
+        serde_json::from_str(&repaired).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn try_repair(partial: &str) -> Option<Value> {
+        let mut json = PartialJson::default();
+        json.push(partial);
+        json.snapshot()
+    }
+
+    #[test]
+    fn test_try_repair_complete_json_is_unchanged() {
+        assert_eq!(try_repair(r#"{"a":1}"#), Some(json!({"a": 1})));
+    }
+
+    #[test]
+    fn test_try_repair_closes_open_string_and_object() {
+        assert_eq!(
+            try_repair(r#"{"path":"/tmp/fo"#),
+            Some(json!({"path": "/tmp/fo"}))
+        );
+        assert_eq!(
+            try_repair(r#"{"items":[1,2,"#),
+            Some(json!({"items": [1, 2]}))
+        );
+    }
+
+    #[test]
+    fn test_try_repair_drops_incomplete_tokens() {
+        assert_eq!(try_repair(r#"{"a":1,"pa"#), Some(json!({"a": 1})));
+        assert_eq!(try_repair(r#"{"a":1,"pa":"#), Some(json!({"a": 1, "pa": null})));
+        assert_eq!(try_repair(r#"{"a":"#), Some(json!({"a": null})));
+        assert_eq!(try_repair(r#"{"a":tru"#), Some(json!({"a": null})));
+        assert_eq!(try_repair(r#"{"a":"x\"#), Some(json!({"a": "x"})));
+        assert_eq!(try_repair(r#"{"a":"x\u00"#), Some(json!({"a": "x"})));
+        assert_eq!(try_repair(r#"{"a":[1.5e"#), Some(json!({"a": [1.5]})));
+        assert_eq!(try_repair(r#"{"a":-"#), Some(json!({"a": null})));
+        assert_eq!(try_repair(r#"[1,fa"#), Some(json!([1])));
+    }
+
+    #[test]
+    fn test_try_repair_ignores_brackets_inside_strings() {
+        assert_eq!(
+            try_repair(r#"{"code":"fn main() { let v = ["#),
+            Some(json!({"code": "fn main() { let v = ["}))
+        );
+    }
+
+    #[test]
+    fn test_try_repair_empty_input() {
+        assert_eq!(try_repair(""), None);
+    }
+
+    #[test]
+    fn test_incremental_push_matches_whole_input() {
+        let input = r#"{"path": "/tmp/a\"b", "items": [1, -2.5e3, true, null, {"k": "\u00e9"}], "n": false}"#;
+        let mut json = PartialJson::default();
+        for (i, c) in input.char_indices() {
+            json.push(&c.to_string());
+            let end = i + c.len_utf8();
+            assert_eq!(json.as_str(), &input[..end]);
+            assert!(json.snapshot().is_some(), "{}", &input[..end]);
+        }
+        assert_eq!(json.snapshot(), serde_json::from_str(input).ok());
+    }
+}
@@ -1,21 +1,33 @@
 //! Anthropic API 中间件
 
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::{
     body::Body,
     extract::State,
-    http::{Request, StatusCode},
+    http::{HeaderMap, Request, StatusCode},
     middleware::Next,
     response::{IntoResponse, Json, Response},
 };
 
 use crate::common::auth;
 use crate::kiro::provider::KiroProvider;
+use crate::model::config::{Config, ToolInputValidation};
 
+use super::capabilities::CapabilityCheck;
+use super::converter::{
+    ConversionOptions, SystemPromptBudget, ToolDocumentationOptions, normalize_model_name,
+};
+use super::dedup::RequestDedup;
+use super::tool_limit::ToolLimit;
 use super::types::ErrorResponse;
 
+/// 受信任的指纹种子覆盖请求头
+pub const FINGERPRINT_SEED_HEADER: &str = "x-kiro-fingerprint-seed";
+
 /// 应用共享状态
 #[derive(Clone)]
 pub struct AppState {
@@ -28,6 +40,38 @@ pub struct AppState {
     pub extract_thinking: bool,
     /// 模型映射覆盖（key: 输入模型名子串，value: Kiro 模型名）
     pub model_mapping: HashMap<String, String>,
+    /// 是否对缓冲模式响应的内容块做规范化排序
+    pub normalize_content_block_order: bool,
+    /// 工具数量上限（None 表示不限制）
+    pub tool_limit: Option<ToolLimit>,
+    /// 允许使用指纹种子请求头的客户端 IP（None 表示功能关闭）
+    pub fingerprint_seed_allowlist: Option<Vec<IpAddr>>,
+    /// 是否总是在响应中附带 `X-Kiro-Timing-*` 耗时头
+    pub timing_headers: bool,
+    /// 允许通过 `X-Kiro-Timing` 请求头按需开启耗时头的客户端 IP（为空时请求头无效）
+    pub timing_header_allowlist: Vec<IpAddr>,
+    /// 是否在响应中附带处理请求的凭据 ID（调试用）
+    pub credential_id_header: bool,
+    /// 客户端通过 `X-Kiro-Timeout-Ms` 指定超时时的上限
+    pub max_request_timeout: Duration,
+    /// 请求转换选项
+    pub conversion_options: ConversionOptions,
+    /// tool_use 输入的 schema 校验方式
+    pub tool_input_validation: ToolInputValidation,
+    /// 慢请求日志阈值（None 表示不记录）
+    pub slow_request_threshold: Option<Duration>,
+    /// 是否把代码引用（许可证归属）信息返回给客户端
+    pub emit_code_references: bool,
+    /// 是否把上游建议的后续提问返回给客户端
+    pub emit_followup_prompts: bool,
+    /// 流式响应周期性 usage 更新的间隔（输出 tokens，None 表示不发送）
+    pub stream_usage_update_interval: Option<u32>,
+    /// 按模型的能力检查（None 表示不检查）
+    pub capability_check: Option<CapabilityCheck>,
+    /// 模型名带 `-bot` 后缀时注入的系统提示词（None 表示不注入）
+    pub bot_system_prompt: Option<String>,
+    /// 重复请求合并（None 表示不合并）
+    pub request_dedup: Option<Arc<RequestDedup>>,
 }
 
 impl AppState {
@@ -38,6 +82,23 @@ impl AppState {
             kiro_provider: None,
             extract_thinking,
             model_mapping: HashMap::new(),
+            normalize_content_block_order: false,
+            tool_limit: None,
+            fingerprint_seed_allowlist: None,
+            timing_headers: false,
+            timing_header_allowlist: Vec::new(),
+            credential_id_header: false,
+            // 与上游 HTTP 客户端的超时一致
+            max_request_timeout: Duration::from_secs(720),
+            conversion_options: ConversionOptions::default(),
+            tool_input_validation: ToolInputValidation::default(),
+            slow_request_threshold: None,
+            emit_code_references: false,
+            emit_followup_prompts: false,
+            stream_usage_update_interval: None,
+            capability_check: None,
+            bot_system_prompt: None,
+            request_dedup: None,
         }
     }
 
@@ -47,17 +108,199 @@ impl AppState {
         self
     }
 
+    /// 按全局配置设置其余请求处理选项
+    pub fn with_config(mut self, config: &Config) -> Self {
+        let documentation = || ToolDocumentationOptions {
+            heading: config.tool_documentation_heading.clone(),
+            placement: config.tool_documentation_placement,
+        };
+        self = self
+            .with_timing_headers(config.timing_headers_enabled)
+            .with_timing_header_allowlist(
+                config
+                    .timing_header_allowed_ips
+                    .iter()
+                    .filter_map(|ip| match ip.parse() {
+                        Ok(ip) => Some(ip),
+                        Err(_) => {
+                            tracing::warn!("忽略无效的耗时头白名单 IP: {}", ip);
+                            None
+                        }
+                    })
+                    .collect(),
+            )
+            .with_credential_id_header(config.credential_id_header_enabled)
+            .with_max_request_timeout(Duration::from_millis(config.max_request_timeout_ms))
+            .with_tool_input_validation(config.tool_input_validation)
+            .with_code_references(config.emit_code_references)
+            .with_followup_prompts(config.emit_followup_prompts)
+            .with_conversion_options(ConversionOptions {
+                leading_assistant: config.leading_assistant_strategy,
+                tool_error_policy: config.tool_error_policy,
+                system_prompt_budget: SystemPromptBudget {
+                    limits: config.system_prompt_token_limits.clone(),
+                    behavior: config.system_prompt_limit_behavior,
+                },
+                elevate_long_descriptions: config.elevate_long_tool_descriptions.then(documentation),
+                dedup_shared_descriptions: config.dedup_shared_tool_descriptions.then(documentation),
+            });
+        if let Some(prompt) = &config.bot_system_prompt {
+            self = self.with_bot_system_prompt(prompt);
+        }
+        if !config.unsupported_model_features.is_empty() {
+            self = self.with_capability_check(CapabilityCheck {
+                unsupported: config.unsupported_model_features.clone(),
+            });
+        }
+        if let Some(interval) = config.stream_usage_update_interval {
+            self = self.with_stream_usage_updates(interval);
+        }
+        if let Some(window_ms) = config.request_dedup_window_ms {
+            self = self.with_request_dedup(Duration::from_millis(window_ms));
+        }
+        if let Some(threshold_ms) = config.slow_request_threshold_ms {
+            self = self.with_slow_request_threshold(Duration::from_millis(threshold_ms));
+        }
+        self
+    }
+
     /// 设置模型映射覆盖
     pub fn with_model_mapping(mut self, mapping: HashMap<String, String>) -> Self {
         self.model_mapping = mapping;
         self
     }
 
+    /// 设置是否对缓冲模式响应的内容块做规范化排序
+    pub fn with_content_block_order_normalization(mut self, enabled: bool) -> Self {
+        self.normalize_content_block_order = enabled;
+        self
+    }
+
+    /// 设置工具数量上限
+    pub fn with_tool_limit(mut self, limit: ToolLimit) -> Self {
+        self.tool_limit = Some(limit);
+        self
+    }
+
+    /// 启用指纹种子请求头，仅接受白名单内客户端 IP 的请求
+    pub fn with_fingerprint_seed_allowlist(mut self, allowlist: Vec<IpAddr>) -> Self {
+        self.fingerprint_seed_allowlist = Some(allowlist);
+        self
+    }
+
+    /// 设置是否总是在响应中附带耗时分解响应头
+    pub fn with_timing_headers(mut self, enabled: bool) -> Self {
+        self.timing_headers = enabled;
+        self
+    }
+
+    /// 设置允许通过请求头按需开启耗时头的客户端 IP
+    pub fn with_timing_header_allowlist(mut self, allowlist: Vec<IpAddr>) -> Self {
+        self.timing_header_allowlist = allowlist;
+        self
+    }
+
+    /// 设置是否在响应中附带处理请求的凭据 ID（调试用）
+    pub fn with_credential_id_header(mut self, enabled: bool) -> Self {
+        self.credential_id_header = enabled;
+        self
+    }
+
+    /// 设置客户端指定请求超时的上限
+    pub fn with_max_request_timeout(mut self, max: Duration) -> Self {
+        self.max_request_timeout = max;
+        self
+    }
+
+    /// 设置请求转换选项
+    pub fn with_conversion_options(mut self, options: ConversionOptions) -> Self {
+        self.conversion_options = options;
+        self
+    }
+
+    /// 设置 tool_use 输入的 schema 校验方式
+    pub fn with_tool_input_validation(mut self, mode: ToolInputValidation) -> Self {
+        self.tool_input_validation = mode;
+        self
+    }
+
+    /// 设置是否把代码引用（许可证归属）信息返回给客户端
+    pub fn with_code_references(mut self, enabled: bool) -> Self {
+        self.emit_code_references = enabled;
+        self
+    }
+
+    /// 设置是否把上游建议的后续提问返回给客户端
+    pub fn with_followup_prompts(mut self, enabled: bool) -> Self {
+        self.emit_followup_prompts = enabled;
+        self
+    }
+
+    /// 启用重复请求合并（窗口内相同的非流式请求只下发一次）
+    pub fn with_request_dedup(mut self, window: Duration) -> Self {
+        self.request_dedup = Some(Arc::new(RequestDedup::new(window)));
+        self
+    }
+
+    /// 设置模型名带 `-bot` 后缀时注入的系统提示词
+    pub fn with_bot_system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.bot_system_prompt = Some(prompt.into());
+        self
+    }
+
+    /// 启用按模型的能力检查：请求使用了不支持的特性时直接拒绝
+    pub fn with_capability_check(mut self, check: CapabilityCheck) -> Self {
+        self.capability_check = Some(check);
+        self
+    }
+
+    /// 启用流式响应的周期性 usage 更新：每累计输出约 `interval` 个 tokens 发送一次
+    pub fn with_stream_usage_updates(mut self, interval: u32) -> Self {
+        self.stream_usage_update_interval = Some(interval);
+        self
+    }
+
+    /// 启用慢请求日志：总耗时超过阈值的请求输出 warn 日志
+    pub fn with_slow_request_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_request_threshold = Some(threshold);
+        self
+    }
+
+    /// 解析请求级指纹种子覆盖
+    ///
+    /// 功能关闭、客户端 IP 未知或不在白名单内时忽略请求头
+    pub fn fingerprint_seed_override(
+        &self,
+        headers: &HeaderMap,
+        client_ip: Option<IpAddr>,
+    ) -> Option<String> {
+        let seed = headers
+            .get(FINGERPRINT_SEED_HEADER)?
+            .to_str()
+            .ok()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())?;
+
+        let Some(allowlist) = &self.fingerprint_seed_allowlist else {
+            tracing::debug!("指纹种子请求头未启用，已忽略");
+            return None;
+        };
+        match client_ip {
+            Some(ip) if allowlist.contains(&ip) => Some(seed.to_string()),
+            _ => {
+                tracing::warn!(client_ip = ?client_ip, "客户端不在指纹种子白名单内，已忽略请求头");
+                None
+            }
+        }
+    }
+
     /// 应用模型映射覆盖：如果输入模型名匹配某个 key，返回对应的 Kiro 模型名
+    ///
+    /// 双方均按 [`normalize_model_name`] 拆分后的基础名匹配，日期与 `-bot` 后缀不影响结果
     pub fn resolve_model_override(&self, model: &str) -> Option<&str> {
-        let model_lower = model.to_lowercase();
+        let base = normalize_model_name(model).base;
         for (pattern, target) in &self.model_mapping {
-            if model_lower.contains(&pattern.to_lowercase()) {
+            if base.contains(&normalize_model_name(pattern).base) {
                 return Some(target.as_str());
             }
         }
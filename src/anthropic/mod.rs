@@ -22,13 +22,22 @@
 //! axum::serve(listener, app).await?;
 //! ```
 
+mod capabilities;
+pub mod canary;
 mod converter;
+mod dedup;
 mod handlers;
 pub mod image_fetch;
+mod json_repair;
 mod middleware;
+mod request_validation;
 mod router;
 mod stream;
+pub mod tool_compression;
+mod tool_limit;
+mod tool_validation;
 pub mod types;
 mod websearch;
 
 pub use router::create_router_with_provider;
+pub use tool_limit::ToolLimit;
@@ -0,0 +1,252 @@
+//! 请求 JSON 规范化与校验
+//!
+//! 部分客户端发送的请求可以解析但形状不规范（如 `max_tokens` 为字符串、
+//! `temperature` 越界、消息 role 未知），直接反序列化会在转换阶段以难以理解的方式失败。
+//! 这里在反序列化前对原始 JSON 做一次规范化：安全的类型偏差（字符串数字）就地转换，
+//! 数值字段做范围检查，真正无效的请求返回指明字段的 invalid_request_error。
+
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::de::DeserializeOwned;
+use serde_json::{Number, Value};
+
+use super::types::ErrorResponse;
+
+/// 数值字段的取值要求
+#[derive(Clone, Copy)]
+enum NumberKind {
+    /// 非负整数
+    NonNegativeInteger,
+    /// 闭区间内的浮点数
+    Range(f64, f64),
+}
+
+/// 顶层需要规范化的数值字段
+const TOP_LEVEL_NUMBERS: &[(&str, NumberKind)] = &[
+    ("max_tokens", NumberKind::NonNegativeInteger),
+    ("temperature", NumberKind::Range(0.0, 1.0)),
+    ("top_p", NumberKind::Range(0.0, 1.0)),
+    ("top_k", NumberKind::NonNegativeInteger),
+];
+
+/// 允许的消息角色
+const MESSAGE_ROLES: &[&str] = &["user", "assistant"];
+
+/// 规范化并校验请求 JSON
+///
+/// 成功时原地修改 `value`；失败时返回指明字段的错误信息。
+pub(crate) fn canonicalize_request(value: &mut Value) -> Result<(), String> {
+    let Some(object) = value.as_object_mut() else {
+        return Err("request body: expected a JSON object".to_string());
+    };
+
+    for (field, kind) in TOP_LEVEL_NUMBERS {
+        if let Some(number) = object.get_mut(*field) {
+            canonicalize_number(field, number, *kind)?;
+        }
+    }
+
+    if let Some(budget) = object
+        .get_mut("thinking")
+        .and_then(Value::as_object_mut)
+        .and_then(|thinking| thinking.get_mut("budget_tokens"))
+    {
+        canonicalize_number("thinking.budget_tokens", budget, NumberKind::NonNegativeInteger)?;
+    }
+
+    if let Some(messages) = object.get("messages").and_then(Value::as_array) {
+        for (index, message) in messages.iter().enumerate() {
+            match message.get("role") {
+                Some(Value::String(role)) if MESSAGE_ROLES.contains(&role.as_str()) => {}
+                Some(Value::String(role)) => {
+                    return Err(format!(
+                        "messages[{}].role: unknown role \"{}\", expected one of: {}",
+                        index,
+                        role,
+                        MESSAGE_ROLES.join(", ")
+                    ));
+                }
+                Some(_) => return Err(format!("messages[{}].role: expected a string", index)),
+                None => return Err(format!("messages[{}].role: field required", index)),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 规范化单个数值字段（字符串数字转为数字，随后检查范围）
+///
+/// `null` 视为未提供，保持原样。
+fn canonicalize_number(field: &str, value: &mut Value, kind: NumberKind) -> Result<(), String> {
+    let parsed = match value {
+        Value::Null => return Ok(()),
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.trim().parse::<f64>().ok(),
+        _ => None,
+    };
+    let Some(parsed) = parsed.filter(|n| n.is_finite()) else {
+        return Err(format!("{}: expected a number", field));
+    };
+
+    *value = match kind {
+        NumberKind::NonNegativeInteger => {
+            if parsed.fract() != 0.0 || parsed < 0.0 || parsed > i32::MAX as f64 {
+                return Err(format!(
+                    "{}: expected a non-negative integer, got {}",
+                    field, parsed
+                ));
+            }
+            Value::Number(Number::from(parsed as i64))
+        }
+        NumberKind::Range(min, max) => {
+            if parsed < min || parsed > max {
+                return Err(format!(
+                    "{}: {} is out of range, expected a value between {} and {}",
+                    field, parsed, min, max
+                ));
+            }
+            match Number::from_f64(parsed) {
+                Some(number) => Value::Number(number),
+                None => return Err(format!("{}: expected a number", field)),
+            }
+        }
+    };
+    Ok(())
+}
+
+/// 带规范化的 JSON 提取器
+///
+/// 与 `axum::Json` 用法相同：缺少 JSON `Content-Type` 时返回 415；
+/// 请求体先经过 [`canonicalize_request`]，任何解析或校验失败都以 400 invalid_request_error 返回。
+pub struct CanonicalJson<T>(pub T);
+
+/// 请求头是否声明了 JSON 内容类型（`application/json` 或 `application/*+json`）
+fn has_json_content_type(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    match essence.split_once('/') {
+        Some(("application", subtype)) => subtype == "json" || subtype.ends_with("+json"),
+        _ => false,
+    }
+}
+
+impl<S, T> FromRequest<S> for CanonicalJson<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !has_json_content_type(req.headers()) {
+            return Err((
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                Json(ErrorResponse::new(
+                    "invalid_request_error",
+                    "Expected request with `Content-Type: application/json`",
+                )),
+            )
+                .into_response());
+        }
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        parse_canonical(&bytes).map(CanonicalJson).map_err(|message| {
+            tracing::warn!("请求校验失败: {}", message);
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new("invalid_request_error", message)),
+            )
+                .into_response()
+        })
+    }
+}
+
+/// 解析、规范化并反序列化请求体
+fn parse_canonical<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, String> {
+    let mut value: Value =
+        serde_json::from_slice(bytes).map_err(|e| format!("request body: invalid JSON: {}", e))?;
+    canonicalize_request(&mut value)?;
+    serde_json::from_value(value).map_err(|e| format!("request body: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anthropic::types::MessagesRequest;
+
+    #[test]
+    fn test_string_number_is_coerced() {
+        let request: MessagesRequest = parse_canonical(
+            br#"{"model":"claude-sonnet-4-5","max_tokens":"1024","temperature":"0.5",
+                "messages":[{"role":"user","content":"hi"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(request.max_tokens, 1024);
+    }
+
+    #[test]
+    fn test_out_of_range_temperature_is_rejected() {
+        let message = parse_canonical::<MessagesRequest>(
+            br#"{"model":"claude-sonnet-4-5","max_tokens":64,"temperature":1.5,
+                "messages":[{"role":"user","content":"hi"}]}"#,
+        )
+        .unwrap_err();
+        assert!(message.starts_with("temperature:"), "{}", message);
+    }
+
+    #[test]
+    fn test_unknown_role_is_rejected() {
+        let message = parse_canonical::<MessagesRequest>(
+            br#"{"model":"claude-sonnet-4-5","max_tokens":64,"messages":[
+                {"role":"user","content":"hi"},{"role":"system","content":"be nice"}]}"#,
+        )
+        .unwrap_err();
+        assert!(message.starts_with("messages[1].role:"), "{}", message);
+        assert!(message.contains("system"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_missing_json_content_type_is_rejected() {
+        let body = r#"{"model":"claude-sonnet-4-5","max_tokens":64,"messages":[]}"#;
+        let request = |content_type: Option<&str>| {
+            let mut builder = Request::builder().method("POST").uri("/v1/messages");
+            if let Some(content_type) = content_type {
+                builder = builder.header(header::CONTENT_TYPE, content_type);
+            }
+            builder.body(axum::body::Body::from(body)).unwrap()
+        };
+
+        let extract = |content_type| {
+            CanonicalJson::<MessagesRequest>::from_request(request(content_type), &())
+        };
+
+        for content_type in [None, Some("text/plain")] {
+            let rejection = extract(content_type).await.err().unwrap();
+            assert_eq!(rejection.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        }
+        for content_type in [
+            "application/json",
+            "application/json; charset=utf-8",
+            "application/vnd.api+json",
+        ] {
+            assert!(extract(Some(content_type)).await.is_ok(), "{}", content_type);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 //! Anthropic API 路由配置
 
 use std::collections::HashMap;
+use std::net::IpAddr;
 
 use axum::{
     Router,
@@ -12,12 +13,14 @@ use axum::{
 use crate::kiro::provider::KiroProvider;
 
 use super::{
+    dedup::dedup_middleware,
     handlers::{count_tokens, get_models, post_messages, post_messages_cc},
     middleware::{AppState, auth_middleware, cors_layer},
+    tool_limit::ToolLimit,
 };
 
 /// 请求体最大大小限制 (50MB)
-const MAX_BODY_SIZE: usize = 50 * 1024 * 1024;
+pub(super) const MAX_BODY_SIZE: usize = 50 * 1024 * 1024;
 
 /// 创建 Anthropic API 路由
 ///
@@ -33,7 +36,10 @@ const MAX_BODY_SIZE: usize = 50 * 1024 * 1024;
 ///
 /// # 参数
 /// - `api_key`: API 密钥，用于验证客户端请求
-/// - `kiro_provider`: 可选的 KiroProvider，用于调用上游 API
+/// - `kiro_provider`: 可选的 KiroProvider，用于调用上游 API（其余请求处理选项取自它持有的配置）
+/// - `normalize_content_block_order`: 是否对缓冲模式响应的内容块做规范化排序
+/// - `tool_limit`: 工具数量上限（None 表示不限制）
+/// - `fingerprint_seed_allowlist`: 允许使用指纹种子请求头的客户端 IP（None 表示关闭）
 
 /// 创建带有 KiroProvider 的 Anthropic API 路由
 pub fn create_router_with_provider(
@@ -41,19 +47,40 @@ pub fn create_router_with_provider(
     kiro_provider: Option<KiroProvider>,
     extract_thinking: bool,
     model_mapping: HashMap<String, String>,
+    normalize_content_block_order: bool,
+    tool_limit: Option<ToolLimit>,
+    fingerprint_seed_allowlist: Option<Vec<IpAddr>>,
 ) -> Router {
     let mut state = AppState::new(api_key, extract_thinking);
     if let Some(provider) = kiro_provider {
-        state = state.with_kiro_provider(provider);
+        state = state.with_config(provider.config()).with_kiro_provider(provider);
     }
     if !model_mapping.is_empty() {
         state = state.with_model_mapping(model_mapping);
     }
+    state = state.with_content_block_order_normalization(normalize_content_block_order);
+    if let Some(limit) = tool_limit {
+        state = state.with_tool_limit(limit);
+    }
+    if let Some(allowlist) = fingerprint_seed_allowlist {
+        state = state.with_fingerprint_seed_allowlist(allowlist);
+    }
+
+    create_router(state)
+}
 
+/// 由已构建的应用状态创建路由
+pub(super) fn create_router(state: AppState) -> Router {
     // 需要认证的 /v1 路由
     let v1_routes = Router::new()
         .route("/models", get(get_models))
-        .route("/messages", post(post_messages))
+        .route(
+            "/messages",
+            post(post_messages).layer(middleware::from_fn_with_state(
+                state.clone(),
+                dedup_middleware,
+            )),
+        )
         .route("/messages/count_tokens", post(count_tokens))
         .layer(middleware::from_fn_with_state(
             state.clone(),
@@ -63,7 +90,13 @@ pub fn create_router_with_provider(
     // 需要认证的 /cc/v1 路由（Claude Code 兼容端点）
     // 与 /v1 的区别：流式响应会等待 contextUsageEvent 后再发送 message_start
     let cc_v1_routes = Router::new()
-        .route("/messages", post(post_messages_cc))
+        .route(
+            "/messages",
+            post(post_messages_cc).layer(middleware::from_fn_with_state(
+                state.clone(),
+                dedup_middleware,
+            )),
+        )
         .route("/messages/count_tokens", post(count_tokens))
         .layer(middleware::from_fn_with_state(
             state.clone(),
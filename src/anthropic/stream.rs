@@ -2,11 +2,15 @@
 //!
 //! 实现 Kiro → Anthropic 流式响应转换和 SSE 状态管理
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 
 use serde_json::json;
 
-use crate::kiro::model::events::Event;
+use crate::kiro::model::events::{CodeReference, Event, ToolUseEvent};
+
+use super::json_repair::PartialJson;
+use super::tool_validation::{ToolInputValidator, VALIDATION_ERROR_FIELD};
 
 /// 找到小于等于目标位置的最近有效UTF-8字符边界
 ///
@@ -514,6 +518,70 @@ impl SseStateManager {
 
 use super::converter::get_context_window_size;
 
+/// 代码引用转换为返回给客户端的注解数组
+pub(super) fn code_reference_annotations(references: &[CodeReference]) -> serde_json::Value {
+    serde_json::Value::Array(references.iter().map(CodeReference::to_annotation).collect())
+}
+
+/// 工具调用事件的归属跟踪
+///
+/// 单个工具调用的输入跨越多个帧时，上游偶尔发出缺少 toolUseId（或 name）的中间增量。
+/// 此类增量归属于当前打开的工具块；没有打开的工具块，或未知 ID 缺少工具名
+/// （不是合法的起始事件）时丢弃该事件，避免凭空开启新的工具块。
+#[derive(Debug, Default)]
+pub(super) struct ToolUseTracker {
+    /// 当前打开（尚未 stop）的工具调用 ID
+    open: Option<String>,
+    /// 已开始的工具调用 (tool_use_id -> 工具名称)
+    names: HashMap<String, String>,
+}
+
+impl ToolUseTracker {
+    /// 补全事件缺失的 ID / 名称；无法确定归属时返回 None
+    pub(super) fn resolve<'a>(&mut self, event: &'a ToolUseEvent) -> Option<Cow<'a, ToolUseEvent>> {
+        let tool_use_id = if event.tool_use_id.is_empty() {
+            let Some(open) = &self.open else {
+                tracing::warn!("丢弃缺少 toolUseId 的工具增量：当前没有打开的工具块");
+                return None;
+            };
+            open.clone()
+        } else {
+            event.tool_use_id.clone()
+        };
+
+        let name = match self.names.get(&tool_use_id) {
+            Some(name) if event.name.is_empty() => name.clone(),
+            Some(_) => event.name.clone(),
+            None if !event.name.is_empty() => {
+                self.names.insert(tool_use_id.clone(), event.name.clone());
+                event.name.clone()
+            }
+            None => {
+                tracing::warn!("丢弃工具调用 {} 的增量：缺少起始事件（无工具名）", tool_use_id);
+                return None;
+            }
+        };
+
+        if event.stop {
+            if self.open.as_deref() == Some(tool_use_id.as_str()) {
+                self.open = None;
+            }
+        } else {
+            self.open = Some(tool_use_id.clone());
+        }
+
+        if tool_use_id == event.tool_use_id && name == event.name {
+            return Some(Cow::Borrowed(event));
+        }
+        Some(Cow::Owned(ToolUseEvent {
+            name,
+            tool_use_id,
+            input: event.input.clone(),
+            stop: event.stop,
+        }))
+    }
+}
+
 /// 流处理上下文
 pub struct StreamContext {
     /// SSE 状态管理器
@@ -547,6 +615,28 @@ pub struct StreamContext {
     /// 是否需要剥离 thinking 内容开头的换行符
     /// 模型输出 `<thinking>\n` 时，`\n` 可能与标签在同一 chunk 或下一 chunk
     strip_thinking_leading_newline: bool,
+    /// 是否在每个 input_json_delta 中附带累计输入的可解析快照
+    tool_input_snapshots: bool,
+    /// 工具输入累计缓冲 (tool_id -> 已到达的 JSON 片段)，块结束时移除
+    tool_input_buffers: HashMap<String, PartialJson>,
+    /// 工具输入校验器（启用时在 content_block_stop 上附加校验错误）
+    tool_input_validator: Option<ToolInputValidator>,
+    /// 工具调用事件归属跟踪（处理缺少 ID 的中间增量）
+    tool_use_tracker: ToolUseTracker,
+    /// 累计的代码引用（许可证归属）
+    code_references: Vec<CodeReference>,
+    /// 上游响应结束帧给出的终止原因（已映射为 Anthropic stop_reason）
+    upstream_stop_reason: Option<&'static str>,
+    /// 是否在 message_delta 中附带代码引用
+    emit_code_references: bool,
+    /// 累计的后续提示（建议提问）
+    followup_prompts: Vec<String>,
+    /// 是否在 message_delta 中附带后续提示
+    emit_followup_prompts: bool,
+    /// 周期性 usage 更新的间隔（输出 tokens，None 表示不发送）
+    usage_update_interval: Option<u32>,
+    /// 上次发送 usage 更新时的累计输出 tokens
+    last_usage_update_tokens: i32,
 }
 
 impl StreamContext {
@@ -573,9 +663,52 @@ impl StreamContext {
             thinking_block_index: None,
             text_block_index: None,
             strip_thinking_leading_newline: false,
+            tool_input_snapshots: false,
+            tool_input_buffers: HashMap::new(),
+            tool_input_validator: None,
+            tool_use_tracker: ToolUseTracker::default(),
+            code_references: Vec::new(),
+            upstream_stop_reason: None,
+            emit_code_references: false,
+            followup_prompts: Vec::new(),
+            emit_followup_prompts: false,
+            usage_update_interval: None,
+            last_usage_update_tokens: 0,
         }
     }
 
+    /// 设置是否在 input_json_delta 中附带累计工具输入的快照（`input_snapshot` 字段）
+    pub fn with_tool_input_snapshots(mut self, enabled: bool) -> Self {
+        self.tool_input_snapshots = enabled;
+        self
+    }
+
+    /// 设置是否在 message_delta 事件中附带累计的代码引用（`code_references` 字段）
+    pub fn with_code_references(mut self, enabled: bool) -> Self {
+        self.emit_code_references = enabled;
+        self
+    }
+
+    /// 设置是否在 message_delta 事件中附带后续提示（`followup_prompts` 字段）
+    pub fn with_followup_prompts(mut self, enabled: bool) -> Self {
+        self.emit_followup_prompts = enabled;
+        self
+    }
+
+    /// 设置周期性 usage 更新的间隔（输出 tokens，None 或 0 表示仅在结束时发送）
+    pub fn with_usage_updates(mut self, interval: Option<u32>) -> Self {
+        self.usage_update_interval = interval.filter(|n| *n > 0);
+        self
+    }
+
+    /// 设置工具输入校验器
+    ///
+    /// 流式响应的块内容在校验前已发出，不合法时校验错误附加在该块的 content_block_stop 事件上。
+    pub fn with_tool_input_validator(mut self, validator: Option<ToolInputValidator>) -> Self {
+        self.tool_input_validator = validator;
+        self
+    }
+
     /// 生成 message_start 事件
     pub fn create_message_start_event(&self) -> serde_json::Value {
         // 使用有序 Map 确保 key 顺序与官方一致
@@ -649,6 +782,40 @@ impl StreamContext {
 
     /// 处理 Kiro 事件并转换为 Anthropic SSE 事件
     pub fn process_kiro_event(&mut self, event: &Event) -> Vec<SseEvent> {
+        let mut events = self.dispatch_kiro_event(event);
+        events.extend(self.usage_update_event());
+        events
+    }
+
+    /// 累计输出 tokens 达到间隔时生成一条中间 message_delta（usage 为累计值）
+    ///
+    /// 中间事件的 stop_reason 为 null；最终 message_delta 同样携带累计值，客户端覆盖即可，不会重复计数。
+    fn usage_update_event(&mut self) -> Option<SseEvent> {
+        let interval = self.usage_update_interval? as i32;
+        if self.output_tokens - self.last_usage_update_tokens < interval
+            || self.state_manager.message_delta_sent
+        {
+            return None;
+        }
+        self.last_usage_update_tokens = self.output_tokens;
+        Some(SseEvent::new(
+            "message_delta",
+            json!({
+                "type": "message_delta",
+                "delta": {
+                    "stop_reason": null,
+                    "stop_sequence": null
+                },
+                "usage": {
+                    "input_tokens": self.context_input_tokens.unwrap_or(self.input_tokens),
+                    "output_tokens": self.output_tokens
+                }
+            }),
+        ))
+    }
+
+    /// 按事件类型分发处理
+    fn dispatch_kiro_event(&mut self, event: &Event) -> Vec<SseEvent> {
         match event {
             Event::AssistantResponse(resp) => self.process_assistant_response(&resp.content),
             Event::ToolUse(tool_use) => self.process_tool_use(tool_use),
@@ -671,6 +838,27 @@ impl StreamContext {
                 );
                 Vec::new()
             }
+            Event::CodeReference(code_reference) => {
+                tracing::debug!("收到 codeReferenceEvent: {}", code_reference);
+                self.code_references
+                    .extend(code_reference.references.iter().cloned());
+                Vec::new()
+            }
+            Event::FollowupPrompt(followup) => {
+                tracing::debug!("收到 followupPromptEvent: {}", followup);
+                if let Some(suggestion) = followup.suggestion() {
+                    self.followup_prompts.push(suggestion.to_string());
+                }
+                Vec::new()
+            }
+            Event::MessageMetadata(metadata) => {
+                tracing::debug!("收到 messageMetadataEvent: {}", metadata);
+                if let Some(reason) = metadata.anthropic_stop_reason() {
+                    self.upstream_stop_reason = Some(reason);
+                    self.state_manager.set_stop_reason(reason);
+                }
+                Vec::new()
+            }
             Event::Error {
                 error_code,
                 error_message,
@@ -934,10 +1122,11 @@ impl StreamContext {
     }
 
     /// 处理工具使用事件
-    fn process_tool_use(
-        &mut self,
-        tool_use: &crate::kiro::model::events::ToolUseEvent,
-    ) -> Vec<SseEvent> {
+    fn process_tool_use(&mut self, tool_use: &ToolUseEvent) -> Vec<SseEvent> {
+        let Some(tool_use) = self.tool_use_tracker.resolve(tool_use) else {
+            return Vec::new();
+        };
+        let tool_use = tool_use.as_ref();
         let mut events = Vec::new();
 
         self.state_manager.set_has_tool_use(true);
@@ -1033,15 +1222,29 @@ impl StreamContext {
         if !tool_use.input.is_empty() {
             self.output_tokens += (tool_use.input.len() as i32 + 3) / 4; // 估算 token
 
+            let mut delta = json!({
+                "type": "input_json_delta",
+                "partial_json": tool_use.input
+            });
+            if self.tool_input_snapshots || self.tool_input_validator.is_some() {
+                let buffer = self
+                    .tool_input_buffers
+                    .entry(tool_use.tool_use_id.clone())
+                    .or_default();
+                buffer.push(&tool_use.input);
+                if self.tool_input_snapshots
+                    && let Some(snapshot) = buffer.snapshot()
+                {
+                    delta["input_snapshot"] = snapshot;
+                }
+            }
+
             if let Some(delta_event) = self.state_manager.handle_content_block_delta(
                 block_index,
                 json!({
                     "type": "content_block_delta",
                     "index": block_index,
-                    "delta": {
-                        "type": "input_json_delta",
-                        "partial_json": tool_use.input
-                    }
+                    "delta": delta
                 }),
             ) {
                 events.push(delta_event);
@@ -1050,7 +1253,12 @@ impl StreamContext {
 
         // 如果是完整的工具调用（stop=true），发送 content_block_stop
         if tool_use.stop {
-            if let Some(stop_event) = self.state_manager.handle_content_block_stop(block_index) {
+            let buffer = self.tool_input_buffers.remove(&tool_use.tool_use_id);
+            if let Some(mut stop_event) = self.state_manager.handle_content_block_stop(block_index) {
+                let input = buffer.as_ref().map_or("", PartialJson::as_str);
+                if let Some(error) = self.validate_tool_input(input, &original_name) {
+                    stop_event.data[VALIDATION_ERROR_FIELD] = json!(error);
+                }
                 events.push(stop_event);
             }
         }
@@ -1058,6 +1266,22 @@ impl StreamContext {
         events
     }
 
+    /// 按 schema 校验已累计的完整工具输入（未启用校验时返回 None）
+    fn validate_tool_input(&self, buffer: &str, tool_name: &str) -> Option<String> {
+        let validator = self.tool_input_validator.as_ref()?;
+        let input = if buffer.trim().is_empty() {
+            json!({})
+        } else {
+            match serde_json::from_str(buffer) {
+                Ok(input) => input,
+                Err(e) => return Some(format!("input is not valid JSON: {}", e)),
+            }
+        };
+        let error = validator.check(tool_name, &input)?;
+        tracing::warn!("工具 {} 的输入不符合 input_schema: {}", tool_name, error);
+        Some(error)
+    }
+
     /// 生成最终事件序列
     pub fn generate_final_events(&mut self) -> Vec<SseEvent> {
         let mut events = Vec::new();
@@ -1135,14 +1359,31 @@ impl StreamContext {
             events.extend(self.create_text_delta_events(" "));
         }
 
+        // 上游明确给出终止原因时以其为准，不使用推断值
+        if let Some(reason) = self.upstream_stop_reason {
+            self.state_manager.set_stop_reason(reason);
+        }
+
         // 使用从 contextUsageEvent 计算的 input_tokens，如果没有则使用估算值
         let final_input_tokens = self.context_input_tokens.unwrap_or(self.input_tokens);
 
         // 生成最终事件
-        events.extend(
-            self.state_manager
-                .generate_final_events(final_input_tokens, self.output_tokens),
-        );
+        let mut final_events = self
+            .state_manager
+            .generate_final_events(final_input_tokens, self.output_tokens);
+        if self.emit_code_references
+            && !self.code_references.is_empty()
+            && let Some(delta) = final_events.iter_mut().find(|e| e.event == "message_delta")
+        {
+            delta.data["code_references"] = code_reference_annotations(&self.code_references);
+        }
+        if self.emit_followup_prompts
+            && !self.followup_prompts.is_empty()
+            && let Some(delta) = final_events.iter_mut().find(|e| e.event == "message_delta")
+        {
+            delta.data["followup_prompts"] = json!(self.followup_prompts);
+        }
+        events.extend(final_events);
         events
     }
 }
@@ -1166,6 +1407,8 @@ pub struct BufferedStreamContext {
     estimated_input_tokens: i32,
     /// 是否已经生成了初始事件
     initial_events_generated: bool,
+    /// 流结束时是否按规范顺序重排内容块
+    normalize_block_order: bool,
 }
 
 impl BufferedStreamContext {
@@ -1183,9 +1426,40 @@ impl BufferedStreamContext {
             event_buffer: Vec::new(),
             estimated_input_tokens,
             initial_events_generated: false,
+            normalize_block_order: false,
         }
     }
 
+    /// 设置是否在 input_json_delta 中附带累计工具输入的快照
+    pub fn with_tool_input_snapshots(mut self, enabled: bool) -> Self {
+        self.inner = self.inner.with_tool_input_snapshots(enabled);
+        self
+    }
+
+    /// 设置工具输入校验器
+    pub fn with_tool_input_validator(mut self, validator: Option<ToolInputValidator>) -> Self {
+        self.inner = self.inner.with_tool_input_validator(validator);
+        self
+    }
+
+    /// 设置是否在 message_delta 事件中附带累计的代码引用
+    pub fn with_code_references(mut self, enabled: bool) -> Self {
+        self.inner = self.inner.with_code_references(enabled);
+        self
+    }
+
+    /// 设置是否在 message_delta 事件中附带后续提示
+    pub fn with_followup_prompts(mut self, enabled: bool) -> Self {
+        self.inner = self.inner.with_followup_prompts(enabled);
+        self
+    }
+
+    /// 设置流结束时是否按规范顺序重排内容块（见 [`normalize_content_block_order`]）
+    pub fn with_block_order_normalization(mut self, enabled: bool) -> Self {
+        self.normalize_block_order = enabled;
+        self
+    }
+
     /// 处理 Kiro 事件并缓冲结果
     ///
     /// 复用 StreamContext 的事件处理逻辑，但把结果缓存而不是立即发送。
@@ -1207,7 +1481,8 @@ impl BufferedStreamContext {
     /// 此方法会：
     /// 1. 生成最终事件（message_delta, message_stop）
     /// 2. 用正确的 input_tokens 更正 message_start 事件
-    /// 3. 返回所有缓冲的事件
+    /// 3. 按需重排内容块顺序
+    /// 4. 返回所有缓冲的事件
     pub fn finish_and_get_all_events(&mut self) -> Vec<SseEvent> {
         // 如果从未处理过事件，也要生成初始事件
         if !self.initial_events_generated {
@@ -1237,8 +1512,78 @@ impl BufferedStreamContext {
             }
         }
 
-        std::mem::take(&mut self.event_buffer)
+        let events = std::mem::take(&mut self.event_buffer);
+        if self.normalize_block_order {
+            normalize_content_block_order(events)
+        } else {
+            events
+        }
+    }
+}
+
+/// 内容块的规范顺序：thinking → text → 其他（tool_use 等）
+fn content_block_rank(block_type: &str) -> u8 {
+    match block_type {
+        "thinking" | "redacted_thinking" => 0,
+        "text" => 1,
+        _ => 2,
+    }
+}
+
+/// 将已缓冲的 SSE 事件中的内容块按规范顺序重排
+///
+/// - 每个内容块的 start/delta/stop 事件作为整体移动，块内事件顺序不变
+/// - 同类块保持原有相对顺序（如多个 text 块、多个 tool_use 块）
+/// - 重排后按新位置重新分配 `index`
+/// - 首个内容块之前的事件（message_start 等）保持在前，其余非内容块事件
+///   （message_delta、message_stop 等）置于所有内容块之后
+pub(crate) fn normalize_content_block_order(events: Vec<SseEvent>) -> Vec<SseEvent> {
+    let mut head = Vec::new();
+    let mut tail = Vec::new();
+    // (原 index, 块类型, 块内事件)，按首次出现顺序排列
+    let mut blocks: Vec<(i64, String, Vec<SseEvent>)> = Vec::new();
+
+    for event in events {
+        let index = match event.event.as_str() {
+            "content_block_start" | "content_block_delta" | "content_block_stop" => {
+                event.data["index"].as_i64()
+            }
+            _ => None,
+        };
+
+        let Some(index) = index else {
+            if blocks.is_empty() {
+                head.push(event);
+            } else {
+                tail.push(event);
+            }
+            continue;
+        };
+
+        match blocks.iter_mut().find(|(i, _, _)| *i == index) {
+            Some((_, _, block_events)) => block_events.push(event),
+            None => {
+                let block_type = event.data["content_block"]["type"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string();
+                blocks.push((index, block_type, vec![event]));
+            }
+        }
+    }
+
+    // sort_by_key 为稳定排序，同类块保持原有相对顺序
+    blocks.sort_by_key(|(_, block_type, _)| content_block_rank(block_type));
+
+    let mut normalized = head;
+    for (new_index, (_, _, block_events)) in blocks.into_iter().enumerate() {
+        for mut event in block_events {
+            event.data["index"] = json!(new_index);
+            normalized.push(event);
+        }
     }
+    normalized.extend(tail);
+    normalized
 }
 
 /// 简单的 token 估算
@@ -1477,6 +1822,333 @@ mod tests {
         );
     }
 
+    /// 将 Kiro 事件帧依次喂给缓冲上下文，返回流结束时的全部事件
+    fn buffer_frames(normalize: bool, frames: &[Vec<u8>]) -> Vec<SseEvent> {
+        let mut ctx = BufferedStreamContext::new("test-model", 1, false, HashMap::new())
+            .with_block_order_normalization(normalize);
+        let mut decoder = crate::kiro::parser::decoder::EventStreamDecoder::new();
+        for frame in frames {
+            decoder.feed(frame).unwrap();
+        }
+        for frame in decoder.decode_iter() {
+            ctx.process_and_buffer(&Event::from_frame(frame.unwrap()).unwrap());
+        }
+        ctx.finish_and_get_all_events()
+    }
+
+    /// 按出现顺序收集 content_block_start 的 (index, 块类型)
+    fn block_starts(events: &[SseEvent]) -> Vec<(i64, String)> {
+        events
+            .iter()
+            .filter(|e| e.event == "content_block_start")
+            .map(|e| {
+                (
+                    e.data["index"].as_i64().unwrap(),
+                    e.data["content_block"]["type"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_buffered_block_order_normalization_moves_text_before_tool_use() {
+        use crate::kiro::parser::frame::encode_event_frame;
+
+        // tool_use 先于正文到达（非规范顺序）
+        let frames = vec![
+            encode_event_frame(
+                "toolUseEvent",
+                r#"{"name":"read_file","toolUseId":"tool_1","input":"{\"path\":\"a\"}","stop":true}"#,
+            ),
+            encode_event_frame("assistantResponseEvent", r#"{"content":"hello"}"#),
+        ];
+
+        let raw = buffer_frames(false, &frames);
+        let raw_types: Vec<String> = block_starts(&raw).into_iter().map(|(_, t)| t).collect();
+        assert_eq!(raw_types, vec!["text", "tool_use", "text"]);
+
+        let normalized = buffer_frames(true, &frames);
+        assert_eq!(
+            block_starts(&normalized),
+            vec![
+                (0, "text".to_string()),
+                (1, "text".to_string()),
+                (2, "tool_use".to_string())
+            ]
+        );
+
+        // 块内事件随块整体移动：正文增量落在新 index 1，工具输入落在 index 2
+        let text_delta = normalized
+            .iter()
+            .find(|e| e.event == "content_block_delta" && e.data["delta"]["type"] == "text_delta")
+            .unwrap();
+        assert_eq!(text_delta.data["index"], 1);
+        assert_eq!(text_delta.data["delta"]["text"], "hello");
+        let json_delta = normalized
+            .iter()
+            .find(|e| e.event == "content_block_delta" && e.data["delta"]["type"] == "input_json_delta")
+            .unwrap();
+        assert_eq!(json_delta.data["index"], 2);
+
+        // message_start 仍在最前，message_delta / message_stop 在所有内容块之后
+        assert_eq!(normalized.first().unwrap().event, "message_start");
+        let tail: Vec<&str> = normalized.iter().rev().take(2).map(|e| e.event.as_str()).collect();
+        assert_eq!(tail, vec!["message_stop", "message_delta"]);
+        assert_eq!(normalized.len(), raw.len());
+    }
+
+    #[test]
+    fn test_tool_input_snapshots_parse_as_accumulated_input() {
+        let mut ctx = StreamContext::new_with_thinking("test-model", 1, false, HashMap::new())
+            .with_tool_input_snapshots(true);
+        ctx.generate_initial_events();
+
+        let deltas = [r#"{"path": "/tmp/"#, r#"a.txt", "lines": [1"#, r#", 2]"#, "}"];
+        let expected = [
+            serde_json::json!({"path": "/tmp/"}),
+            serde_json::json!({"path": "/tmp/a.txt", "lines": [1]}),
+            serde_json::json!({"path": "/tmp/a.txt", "lines": [1, 2]}),
+            serde_json::json!({"path": "/tmp/a.txt", "lines": [1, 2]}),
+        ];
+
+        let mut accumulated = String::new();
+        for (i, (delta, expected)) in deltas.iter().zip(expected.iter()).enumerate() {
+            accumulated.push_str(delta);
+            let events = ctx.process_tool_use(&crate::kiro::model::events::ToolUseEvent {
+                name: "read_file".to_string(),
+                tool_use_id: "tool_1".to_string(),
+                input: delta.to_string(),
+                stop: i == deltas.len() - 1,
+            });
+            let delta_event = events
+                .iter()
+                .find(|e| e.event == "content_block_delta")
+                .expect("each delta should emit input_json_delta");
+
+            // 原始增量保持不变，快照可被独立解析为截至目前的输入
+            assert_eq!(delta_event.data["delta"]["partial_json"], *delta);
+            let snapshot = &delta_event.data["delta"]["input_snapshot"];
+            let reparsed: serde_json::Value =
+                serde_json::from_str(&snapshot.to_string()).unwrap();
+            assert_eq!(&reparsed, expected, "snapshot mismatch for {:?}", accumulated);
+        }
+        // 块结束后释放累计缓冲
+        assert!(ctx.tool_input_buffers.is_empty());
+    }
+
+    #[test]
+    fn test_tool_input_snapshots_disabled_by_default() {
+        let mut ctx = StreamContext::new_with_thinking("test-model", 1, false, HashMap::new());
+        ctx.generate_initial_events();
+        let events = ctx.process_tool_use(&crate::kiro::model::events::ToolUseEvent {
+            name: "read_file".to_string(),
+            tool_use_id: "tool_1".to_string(),
+            input: r#"{"path": "/tm"#.to_string(),
+            stop: false,
+        });
+        let delta_event = events.iter().find(|e| e.event == "content_block_delta").unwrap();
+        assert!(delta_event.data["delta"].get("input_snapshot").is_none());
+    }
+
+    #[test]
+    fn test_tool_input_delta_without_id_joins_open_tool_block() {
+        use crate::kiro::parser::frame::encode_event_frame;
+
+        let frames = [
+            // 没有打开的工具块时，缺少 ID 的增量被丢弃
+            r#"{"input":"stray"}"#,
+            r#"{"name":"write_file","toolUseId":"tool_1","input":"{\"path\": \"/a\", "}"#,
+            r#"{"input":"\"content\": \"hello "}"#,
+            // 未知 ID 且无工具名：不是合法起始事件，不开启新块
+            r#"{"toolUseId":"tool_phantom","input":"junk"}"#,
+            r#"{"toolUseId":"tool_1","input":"world\"}","stop":true}"#,
+        ];
+        let mut ctx = StreamContext::new_with_thinking("test-model", 1, false, HashMap::new());
+        ctx.generate_initial_events();
+        let mut decoder = crate::kiro::parser::decoder::EventStreamDecoder::new();
+        for payload in frames {
+            decoder.feed(&encode_event_frame("toolUseEvent", payload)).unwrap();
+        }
+        let mut events = Vec::new();
+        for frame in decoder.decode_iter() {
+            events.extend(ctx.process_kiro_event(&Event::from_frame(frame.unwrap()).unwrap()));
+        }
+
+        let tool_starts: Vec<_> = events
+            .iter()
+            .filter(|e| e.event == "content_block_start" && e.data["content_block"]["type"] == "tool_use")
+            .collect();
+        assert_eq!(tool_starts.len(), 1);
+        let index = tool_starts[0].data["index"].clone();
+
+        let deltas: Vec<_> = events
+            .iter()
+            .filter(|e| e.event == "content_block_delta" && e.data["delta"]["type"] == "input_json_delta")
+            .collect();
+        assert!(deltas.iter().all(|e| e.data["index"] == index));
+        let input: String = deltas
+            .iter()
+            .map(|e| e.data["delta"]["partial_json"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&input).unwrap(),
+            serde_json::json!({"path": "/a", "content": "hello world"})
+        );
+        assert!(!ctx.tool_block_indices.contains_key("tool_phantom"));
+    }
+
+    #[test]
+    fn test_code_references_surface_on_message_delta_when_enabled() {
+        use crate::kiro::parser::frame::encode_event_frame;
+
+        let payload = r#"{"references":[{"licenseName":"MIT","repository":"octo/left-pad","url":"https://github.com/octo/left-pad","recommendationContentSpan":{"start":0,"end":5}}]}"#;
+        let run = |emit: bool| {
+            let mut ctx = StreamContext::new_with_thinking("test-model", 1, false, HashMap::new())
+                .with_code_references(emit);
+            ctx.generate_initial_events();
+            let mut decoder = crate::kiro::parser::decoder::EventStreamDecoder::new();
+            decoder.feed(&encode_event_frame("codeReferenceEvent", payload)).unwrap();
+            for frame in decoder.decode_iter() {
+                ctx.process_kiro_event(&Event::from_frame(frame.unwrap()).unwrap());
+            }
+            ctx.generate_final_events()
+                .into_iter()
+                .find(|e| e.event == "message_delta")
+                .unwrap()
+        };
+
+        assert!(run(false).data.get("code_references").is_none());
+        assert_eq!(
+            run(true).data["code_references"],
+            serde_json::json!([{
+                "repository": "octo/left-pad",
+                "license": "MIT",
+                "url": "https://github.com/octo/left-pad",
+                "start": 0,
+                "end": 5
+            }])
+        );
+    }
+
+    #[test]
+    fn test_followup_prompts_surface_on_message_delta_when_enabled() {
+        use crate::kiro::parser::frame::encode_event_frame;
+
+        let run = |emit: bool, payloads: &[&str]| {
+            let mut ctx = StreamContext::new_with_thinking("test-model", 1, false, HashMap::new())
+                .with_followup_prompts(emit);
+            ctx.generate_initial_events();
+            let mut decoder = crate::kiro::parser::decoder::EventStreamDecoder::new();
+            for payload in payloads {
+                decoder.feed(&encode_event_frame("followupPromptEvent", payload)).unwrap();
+            }
+            for frame in decoder.decode_iter() {
+                ctx.process_kiro_event(&Event::from_frame(frame.unwrap()).unwrap());
+            }
+            ctx.generate_final_events()
+                .into_iter()
+                .find(|e| e.event == "message_delta")
+                .unwrap()
+        };
+        let payloads = [
+            r#"{"followupPrompt":{"content":"Add error handling?"}}"#,
+            r#"{"followupPrompt":{"content":"Write a unit test?"}}"#,
+        ];
+
+        assert!(run(false, &payloads).data.get("followup_prompts").is_none());
+        assert!(run(true, &[]).data.get("followup_prompts").is_none());
+        assert_eq!(
+            run(true, &payloads).data["followup_prompts"],
+            serde_json::json!(["Add error handling?", "Write a unit test?"])
+        );
+    }
+
+    #[test]
+    fn test_periodic_usage_updates_are_cumulative_and_not_double_counted() {
+        use crate::kiro::parser::frame::encode_event_frame;
+
+        let run = |interval: Option<u32>| {
+            let mut ctx = StreamContext::new_with_thinking("test-model", 1, false, HashMap::new())
+                .with_usage_updates(interval);
+            ctx.generate_initial_events();
+            let mut decoder = crate::kiro::parser::decoder::EventStreamDecoder::new();
+            let chunk = format!(r#"{{"content":"{}"}}"#, "word ".repeat(20));
+            for _ in 0..10 {
+                decoder
+                    .feed(&encode_event_frame("assistantResponseEvent", &chunk))
+                    .unwrap();
+            }
+            let mut events = Vec::new();
+            for frame in decoder.decode_iter() {
+                events.extend(ctx.process_kiro_event(&Event::from_frame(frame.unwrap()).unwrap()));
+            }
+            events.extend(ctx.generate_final_events());
+            let total = ctx.output_tokens;
+            let deltas: Vec<_> = events
+                .into_iter()
+                .filter(|e| e.event == "message_delta")
+                .map(|e| e.data)
+                .collect();
+            (deltas, total)
+        };
+
+        let (deltas, total) = run(None);
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0]["usage"]["output_tokens"], total);
+
+        let (deltas, total) = run(Some(10));
+        assert!(deltas.len() > 2, "长流中应有周期性 usage 更新");
+        let (last, periodic) = deltas.split_last().unwrap();
+        let counts: Vec<i64> = periodic
+            .iter()
+            .map(|d| {
+                assert!(d["delta"]["stop_reason"].is_null());
+                d["usage"]["output_tokens"].as_i64().unwrap()
+            })
+            .collect();
+        assert!(counts.windows(2).all(|w| w[0] < w[1]), "usage 应为累计值");
+        assert!(counts.iter().all(|c| *c <= total as i64));
+        // 最终 usage 与未开启时一致，不累加中间更新
+        assert_eq!(last["usage"]["output_tokens"], total);
+        assert_eq!(last["delta"]["stop_reason"], "end_turn");
+    }
+
+    #[test]
+    fn test_tool_input_validation_annotates_content_block_stop() {
+        let tools: Vec<super::super::types::Tool> = serde_json::from_value(serde_json::json!([{
+            "name": "read_file",
+            "description": "Read a file",
+            "input_schema": {"type": "object", "required": ["path"]}
+        }]))
+        .unwrap();
+        let validator = ToolInputValidator::new(
+            crate::model::config::ToolInputValidation::Annotate,
+            Some(&tools),
+        );
+        let mut ctx = StreamContext::new_with_thinking("test-model", 1, false, HashMap::new())
+            .with_tool_input_validator(validator);
+        ctx.generate_initial_events();
+
+        let mut stop_events = Vec::new();
+        for (id, input) in [("tool_1", r#"{"limit": 5}"#), ("tool_2", r#"{"path": "/a"}"#)] {
+            let events = ctx.process_tool_use(&crate::kiro::model::events::ToolUseEvent {
+                name: "read_file".to_string(),
+                tool_use_id: id.to_string(),
+                input: input.to_string(),
+                stop: true,
+            });
+            // 最后一个 content_block_stop 属于工具块（之前的可能是自动关闭的文本块）
+            let stop = events.into_iter().rfind(|e| e.event == "content_block_stop").unwrap();
+            stop_events.push(stop);
+        }
+
+        assert_eq!(
+            stop_events[0].data[VALIDATION_ERROR_FIELD],
+            "/: missing required field \"path\""
+        );
+        assert!(stop_events[1].data.get(VALIDATION_ERROR_FIELD).is_none());
+    }
+
     #[test]
     fn test_estimate_tokens() {
         assert!(estimate_tokens("Hello") > 0);
@@ -2004,4 +2676,45 @@ mod tests {
             "stop_reason should be tool_use when tool_use is present"
         );
     }
+
+    #[test]
+    fn test_message_metadata_stop_reason_drives_message_delta() {
+        use crate::kiro::parser::frame::encode_event_frame;
+
+        let text = ("assistantResponseEvent", r#"{"content":"hello"}"#);
+        let tool = (
+            "toolUseEvent",
+            r#"{"name":"read_file","toolUseId":"tool_1","input":"{}","stop":true}"#,
+        );
+        let cases = [
+            (vec![text], Some("END_TURN"), "end_turn"),
+            (vec![text], Some("MAX_TOKENS"), "max_tokens"),
+            (vec![text], Some("STOP_SEQUENCE"), "stop_sequence"),
+            (vec![text, tool], Some("TOOL_USE"), "tool_use"),
+            // 无结束帧或原因无法识别时回退到推断
+            (vec![text, tool], None, "tool_use"),
+            (vec![text], Some("SOMETHING_NEW"), "end_turn"),
+        ];
+        for (frames, cause, expected) in cases {
+            let mut ctx = StreamContext::new_with_thinking("test-model", 1, false, HashMap::new());
+            ctx.generate_initial_events();
+            let mut decoder = crate::kiro::parser::decoder::EventStreamDecoder::new();
+            for (event_type, payload) in &frames {
+                decoder.feed(&encode_event_frame(event_type, payload)).unwrap();
+            }
+            if let Some(cause) = cause {
+                let payload = format!(r#"{{"conversationId":"c1","stopReason":"{}"}}"#, cause);
+                decoder.feed(&encode_event_frame("messageMetadataEvent", &payload)).unwrap();
+            }
+            for frame in decoder.decode_iter() {
+                ctx.process_kiro_event(&Event::from_frame(frame.unwrap()).unwrap());
+            }
+            let message_delta = ctx
+                .generate_final_events()
+                .into_iter()
+                .find(|e| e.event == "message_delta")
+                .unwrap();
+            assert_eq!(message_delta.data["delta"]["stop_reason"], expected, "{:?}", cause);
+        }
+    }
 }
@@ -0,0 +1,1071 @@
+//! 工具定义压缩
+//!
+//! 工具定义总量过大时上游会直接返回 400/500。启用压缩（`toolCompressionEnabled`，默认关闭）后，
+//! 超过目标大小（默认 20KB，可配置）时按阶段依次压缩，每个阶段完成后若已满足目标即停止：
+//! 1. 空白规范化（可选，无损）：去除行首尾空白、折叠连续空白与多余空行
+//! 2. 简化 `input_schema`：移除 description / title / examples 等说明性字段
+//! 3. 按比例截断描述：每个描述的保留下限由预算与工具数量推导（预算 / 工具数），
+//!    且不低于配置的绝对下限（至少 [`MIN_TOOL_DESCRIPTION_LENGTH`] 个字符）。
+//!    可为工具配置优先级（0-255），需要删减的字节按优先级反比分摊：
+//!    优先级越高删减越少，最高优先级（255）的工具仅在其他工具均已截断到下限后才会被截断
+//!
+//! 受保护的工具（[`ToolCompressionOptions::protected_tools`]）不参与上述阶段，
+//! 仅当其余工具压缩后仍超出目标时才作为最后手段一并压缩。
+//!
+//! 另提供共享描述去重（[`dedup_shared_descriptions`]）：多个工具逐字重复的段落
+//! 移入系统提示词，只在工具上保留引用标记。
+//!
+//! 无论是否启用压缩，最近一次请求压缩前的工具定义都会被保留（不做序列化），通过 Admin API
+//! 查询时（[`last_tool_sizes`]）才计算各工具体积，上游因工具过大报错时可据此定位体积最大的工具。
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::kiro::model::requests::tool::Tool;
+
+/// 默认的工具定义压缩目标大小（序列化后的字节数）
+pub const TOOL_COMPRESSION_TARGET_SIZE: usize = 20 * 1024;
+
+/// 描述截断后的最小保留长度（字符，硬下限，配置值低于此值时按此值处理）
+pub const MIN_TOOL_DESCRIPTION_LENGTH: usize = 50;
+
+/// 参与去重的段落最小长度（字符），过短的段落（如 "Usage:"）重复也不值得移出
+const MIN_SHARED_PARAGRAPH_CHARS: usize = 200;
+
+/// 简化 schema 时移除的说明性字段
+const SCHEMA_ANNOTATION_KEYS: &[&str] = &["description", "title", "examples", "default", "$schema"];
+
+/// 工具压缩选项
+#[derive(Debug, Clone, Default)]
+pub struct ToolCompressionOptions {
+    /// 是否对请求中的工具定义执行压缩（默认 false，关闭时工具定义原样发送）
+    pub enabled: bool,
+    /// 是否在有损压缩前折叠描述中的空白
+    pub collapse_whitespace: bool,
+    /// 描述截断的绝对下限（字符，不低于 [`MIN_TOOL_DESCRIPTION_LENGTH`]）
+    pub min_description_length: usize,
+    /// 压缩目标大小（字节，为 0 时使用 [`TOOL_COMPRESSION_TARGET_SIZE`]）
+    pub target_bytes: usize,
+    /// 工具优先级（工具名 -> 0-255，未配置的工具为 0）
+    pub priorities: HashMap<String, u8>,
+    /// 受保护的工具名（schema 与描述不参与压缩，除非其余工具压缩后仍超出目标）
+    pub protected_tools: HashSet<String>,
+}
+
+impl ToolCompressionOptions {
+    /// 实际生效的压缩目标大小（字节）
+    pub fn target_size(&self) -> usize {
+        if self.target_bytes == 0 {
+            TOOL_COMPRESSION_TARGET_SIZE
+        } else {
+            self.target_bytes
+        }
+    }
+
+    /// 由可用预算与工具数量推导每个描述的保留下限
+    ///
+    /// 工具少时每个描述可保留更多，工具多时按预算均摊，但不低于绝对下限。
+    pub fn description_floor(&self, available: usize, tool_count: usize) -> usize {
+        let absolute_min = self.min_description_length.max(MIN_TOOL_DESCRIPTION_LENGTH);
+        (available / tool_count.max(1)).max(absolute_min)
+    }
+}
+
+/// 各阶段压缩效果（字节）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressionReport {
+    /// 压缩前大小
+    pub original_size: usize,
+    /// 空白规范化节省的字节数
+    pub whitespace_saved: usize,
+    /// schema 简化节省的字节数
+    pub schema_saved: usize,
+    /// 描述截断节省的字节数
+    pub description_saved: usize,
+    /// 压缩后大小
+    pub final_size: usize,
+}
+
+impl CompressionReport {
+    /// 是否进行了任何压缩
+    pub fn compressed(&self) -> bool {
+        self.final_size < self.original_size
+    }
+}
+
+static TOOL_COMPRESSION_OPTIONS: OnceLock<ToolCompressionOptions> = OnceLock::new();
+
+/// 初始化工具压缩选项
+///
+/// 应在应用启动时调用一次，未调用时使用默认选项
+pub fn init_options(options: ToolCompressionOptions) {
+    let _ = TOOL_COMPRESSION_OPTIONS.set(options);
+}
+
+/// 获取当前工具压缩选项
+pub fn options() -> &'static ToolCompressionOptions {
+    TOOL_COMPRESSION_OPTIONS.get_or_init(ToolCompressionOptions::default)
+}
+
+/// 计算工具定义序列化后的大小
+pub fn calculate_tools_size(tools: &[Tool]) -> usize {
+    serde_json::to_vec(tools).map(|v| v.len()).unwrap_or(0)
+}
+
+/// 各工具序列化后的大小（工具名, 字节数），按大小降序
+pub fn tool_size_breakdown(tools: &[Tool]) -> Vec<(String, usize)> {
+    let mut sizes: Vec<(String, usize)> = tools
+        .iter()
+        .map(|tool| {
+            (
+                tool.tool_specification.name.clone(),
+                calculate_tools_size(std::slice::from_ref(tool)).saturating_sub(2),
+            )
+        })
+        .collect();
+    sizes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    sizes
+}
+
+/// 单个工具的体积
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolSize {
+    /// 工具名（发送给上游的名称）
+    pub name: String,
+    /// 压缩前序列化后的字节数
+    pub bytes: usize,
+}
+
+/// 最近一次请求的工具体积记录（用于 Admin API 诊断）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolSizeSnapshot {
+    /// 记录时间（RFC3339）
+    pub recorded_at: String,
+    /// 压缩前的工具定义总大小
+    pub original_size: usize,
+    /// 压缩后的工具定义总大小
+    pub final_size: usize,
+    /// 各工具压缩前的大小，按大小降序
+    pub tools: Vec<ToolSize>,
+}
+
+/// 最近一次请求压缩前的工具定义（各工具体积在查询时才计算）
+struct RecordedTools {
+    recorded_at: chrono::DateTime<chrono::Utc>,
+    /// 压缩后的总大小（未启用压缩时为 None，即与压缩前相同）
+    final_size: Option<usize>,
+    tools: Vec<Tool>,
+}
+
+static LAST_TOOLS: Mutex<Option<Arc<RecordedTools>>> = Mutex::new(None);
+
+impl ToolSizeSnapshot {
+    fn new(recorded: &RecordedTools) -> Self {
+        let original_size = calculate_tools_size(&recorded.tools);
+        Self {
+            recorded_at: recorded.recorded_at.to_rfc3339(),
+            original_size,
+            final_size: recorded.final_size.unwrap_or(original_size),
+            tools: tool_size_breakdown(&recorded.tools)
+                .into_iter()
+                .map(|(name, bytes)| ToolSize { name, bytes })
+                .collect(),
+        }
+    }
+}
+
+/// 记录本次请求压缩前的工具定义与压缩后的总大小（未启用压缩时为 None）
+///
+/// 请求路径上只替换指针，不序列化工具；上一次的记录在锁外释放。
+pub fn record_tool_sizes(tools: Vec<Tool>, final_size: Option<usize>) {
+    let recorded = Arc::new(RecordedTools {
+        recorded_at: chrono::Utc::now(),
+        final_size,
+        tools,
+    });
+    let previous = LAST_TOOLS.lock().replace(recorded);
+    drop(previous);
+}
+
+/// 最近一次请求的工具体积（尚无带工具的请求时返回 None）
+pub fn last_tool_sizes() -> Option<ToolSizeSnapshot> {
+    let recorded = LAST_TOOLS.lock().clone()?;
+    Some(ToolSizeSnapshot::new(&recorded))
+}
+
+/// 工具定义超过选项中的目标大小时进行分阶段压缩
+///
+/// 未超出目标时原样返回，报告中 `final_size == original_size`。
+pub fn compress_tools_if_needed(
+    tools: &[Tool],
+    options: &ToolCompressionOptions,
+) -> (Vec<Tool>, CompressionReport) {
+    compress_tools_to_target(
+        tools,
+        options,
+        options.target_size(),
+        &options.priorities,
+        &options.protected_tools,
+    )
+}
+
+/// 工具定义超过 `target_size` 字节时进行分阶段压缩
+///
+/// 描述截断按 `priorities`（工具名 -> 0-255）加权，且仍受 [`MIN_TOOL_DESCRIPTION_LENGTH`]
+/// 下限约束，目标过小时结果可能仍超出目标。
+///
+/// `protected` 中的工具保持 `input_schema` 与描述不变，删减只由其余工具承担；
+/// 其余工具压缩后仍超出目标时记录警告，并把受保护工具一并压缩作为最后手段。
+pub fn compress_tools_to_target(
+    tools: &[Tool],
+    options: &ToolCompressionOptions,
+    target_size: usize,
+    priorities: &HashMap<String, u8>,
+    protected: &HashSet<String>,
+) -> (Vec<Tool>, CompressionReport) {
+    let original_size = calculate_tools_size(tools);
+    let mut report = CompressionReport {
+        original_size,
+        final_size: original_size,
+        ..Default::default()
+    };
+    let mut tools = tools.to_vec();
+    if original_size <= target_size {
+        return (tools, report);
+    }
+
+    let is_protected = |tool: &Tool| protected.contains(&tool.tool_specification.name);
+    run_compression_stages(
+        &mut tools,
+        options,
+        target_size,
+        priorities,
+        |tool| !is_protected(tool),
+        &mut report,
+    );
+
+    if report.final_size > target_size && tools.iter().any(is_protected) {
+        tracing::warn!(
+            size = report.final_size,
+            target = target_size,
+            "压缩非保护工具后仍超出目标大小，受保护工具也将被压缩"
+        );
+        run_compression_stages(
+            &mut tools,
+            options,
+            target_size,
+            priorities,
+            |_| true,
+            &mut report,
+        );
+    }
+
+    (tools, report)
+}
+
+/// 对 `eligible` 选中的工具依次执行各压缩阶段，节省量累加到 `report`
+///
+/// 未被选中的工具原样保留，其描述计入固定开销，不参与截断预算分配。
+fn run_compression_stages(
+    tools: &mut [Tool],
+    options: &ToolCompressionOptions,
+    target_size: usize,
+    priorities: &HashMap<String, u8>,
+    eligible: impl Fn(&Tool) -> bool,
+    report: &mut CompressionReport,
+) {
+    let mut size = report.final_size;
+
+    // 阶段 1：空白规范化
+    if options.collapse_whitespace {
+        for tool in tools.iter_mut().filter(|t| eligible(t)) {
+            let spec = &mut tool.tool_specification;
+            spec.description = collapse_whitespace(&spec.description);
+        }
+        let next = calculate_tools_size(tools);
+        report.whitespace_saved += size.saturating_sub(next);
+        size = next;
+    }
+
+    // 阶段 2：简化 input_schema
+    if size > target_size {
+        for tool in tools.iter_mut().filter(|t| eligible(t)) {
+            simplify_schema(&mut tool.tool_specification.input_schema.json);
+        }
+        let next = calculate_tools_size(tools);
+        report.schema_saved += size.saturating_sub(next);
+        size = next;
+    }
+
+    // 阶段 3：按比例截断描述
+    if size > target_size {
+        let mut selected: Vec<&mut Tool> = tools.iter_mut().filter(|t| eligible(t)).collect();
+        let total_desc: usize = selected
+            .iter()
+            .map(|t| t.tool_specification.description.len())
+            .sum();
+        let overhead = size.saturating_sub(total_desc);
+        let available = target_size.saturating_sub(overhead);
+        let floor = options.description_floor(available, selected.len());
+        let lengths: Vec<usize> = selected
+            .iter()
+            .map(|t| t.tool_specification.description.chars().count())
+            .collect();
+        // 删减权重：优先级越高权重越小，最高优先级为 0（最后才会被截断）
+        let weights: Vec<f64> = selected
+            .iter()
+            .map(|t| {
+                let priority = priorities
+                    .get(&t.tool_specification.name)
+                    .copied()
+                    .unwrap_or(0);
+                f64::from(u8::MAX - priority)
+            })
+            .collect();
+        let budgets = allocate_description_budgets(&lengths, &weights, available, floor);
+        for (tool, keep) in selected.iter_mut().zip(budgets) {
+            let spec = &mut tool.tool_specification;
+            spec.description = truncate_description(&spec.description, keep);
+        }
+        let next = calculate_tools_size(tools);
+        report.description_saved += size.saturating_sub(next);
+        size = next;
+    }
+
+    report.final_size = size;
+}
+
+/// 单个工具描述的压缩预估
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolDescriptionPlan {
+    /// 工具名
+    pub name: String,
+    /// 压缩前描述长度（字符）
+    pub before_chars: usize,
+    /// 压缩后描述长度（字符）
+    pub after_chars: usize,
+}
+
+/// 工具压缩预估结果（dry-run，不修改输入）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressionPlan {
+    /// 压缩前大小
+    pub original_size: usize,
+    /// 空白规范化与 schema 简化后的预计大小
+    pub after_schema_size: usize,
+    /// 描述截断后的预计大小（即最终大小）
+    pub after_description_size: usize,
+    /// 是否会发生压缩
+    pub compressed: bool,
+    /// 各工具描述压缩前后长度，保持输入顺序
+    pub tools: Vec<ToolDescriptionPlan>,
+}
+
+/// 预估 [`compress_tools_if_needed`] 的压缩效果，不修改输入
+///
+/// 复用同一套分阶段逻辑，预估值与实际压缩结果一致。
+pub fn compress_tools_if_needed_dry_run(
+    tools: &[Tool],
+    options: &ToolCompressionOptions,
+) -> CompressionPlan {
+    let (compressed, report) = compress_tools_if_needed(tools, options);
+    CompressionPlan {
+        original_size: report.original_size,
+        after_schema_size: report.final_size + report.description_saved,
+        after_description_size: report.final_size,
+        compressed: report.compressed(),
+        tools: tools
+            .iter()
+            .zip(&compressed)
+            .map(|(before, after)| ToolDescriptionPlan {
+                name: before.tool_specification.name.clone(),
+                before_chars: before.tool_specification.description.chars().count(),
+                after_chars: after.tool_specification.description.chars().count(),
+            })
+            .collect(),
+    }
+}
+
+/// 折叠描述中的空白：去除行首尾空白、合并行内连续空白、最多保留一个空行
+fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut pending_blank = false;
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() {
+            pending_blank = !out.is_empty();
+            continue;
+        }
+        if !out.is_empty() {
+            out.push('\n');
+            if pending_blank {
+                out.push('\n');
+            }
+        }
+        pending_blank = false;
+        out.push_str(&line);
+    }
+    out
+}
+
+/// 递归移除 schema 中的说明性字段，保留类型结构（properties 的键名不受影响）
+fn simplify_schema(schema: &mut Value) {
+    let Some(obj) = schema.as_object_mut() else {
+        return;
+    };
+    for key in SCHEMA_ANNOTATION_KEYS {
+        obj.remove(*key);
+    }
+    if let Some(Value::Object(props)) = obj.get_mut("properties") {
+        props.values_mut().for_each(simplify_schema);
+    }
+    if let Some(items) = obj.get_mut("items") {
+        simplify_schema(items);
+    }
+    for key in ["anyOf", "oneOf", "allOf"] {
+        if let Some(Value::Array(variants)) = obj.get_mut(key) {
+            variants.iter_mut().for_each(simplify_schema);
+        }
+    }
+}
+
+/// 为每个描述分配保留长度（字符）
+///
+/// 不超过 `floor` 的描述原样保留；其余描述需删减的总量按 `长度 × 权重` 比例分摊
+///（权重相同时等价于按比例保留），保留长度低于 `floor` 的固定为 `floor` 后对剩余描述重新分摊。
+/// 剩余描述的权重全为 0 时按相同权重分摊。
+fn allocate_description_budgets(
+    lengths: &[usize],
+    weights: &[f64],
+    available: usize,
+    floor: usize,
+) -> Vec<usize> {
+    let mut budgets: Vec<Option<usize>> = lengths
+        .iter()
+        .map(|&len| (len <= floor).then_some(len))
+        .collect();
+    loop {
+        let fixed: usize = budgets.iter().flatten().sum();
+        let open: Vec<(usize, f64)> = lengths
+            .iter()
+            .zip(weights)
+            .zip(&budgets)
+            .filter(|(_, budget)| budget.is_none())
+            .map(|((&len, &weight), _)| (len, weight))
+            .collect();
+        let open_total: usize = open.iter().map(|(len, _)| len).sum();
+        if open_total == 0 {
+            return budgets.into_iter().map(|b| b.unwrap_or(floor)).collect();
+        }
+        let need = open_total.saturating_sub(available.saturating_sub(fixed)) as f64;
+        let uniform = open.iter().all(|&(_, weight)| weight <= 0.0);
+        let weight_of = |weight: f64| if uniform { 1.0 } else { weight };
+        let weighted_total: f64 = open
+            .iter()
+            .map(|&(len, weight)| len as f64 * weight_of(weight))
+            .sum();
+        let share = |len: usize, weight: f64| {
+            let cut = need * len as f64 * weight_of(weight) / weighted_total;
+            ((len as f64 - cut).max(0.0) as usize).min(len)
+        };
+
+        let mut pinned = false;
+        for ((len, weight), budget) in lengths.iter().zip(weights).zip(budgets.iter_mut()) {
+            if budget.is_none() && share(*len, *weight) < floor {
+                *budget = Some(floor);
+                pinned = true;
+            }
+        }
+        if !pinned {
+            return lengths
+                .iter()
+                .zip(weights)
+                .zip(budgets)
+                .map(|((len, weight), budget)| budget.unwrap_or_else(|| share(*len, *weight)))
+                .collect();
+        }
+    }
+}
+
+/// 共享描述去重结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedDescriptions {
+    /// 移出的共享段落文档（供并入系统提示词）
+    pub doc: String,
+    /// 去重的段落组数
+    pub groups: usize,
+}
+
+/// 将被至少两个工具逐字共享的描述段落移出，返回移出的文档
+///
+/// 段落以空行分隔，长度不足 [`MIN_SHARED_PARAGRAPH_CHARS`] 的段落不参与去重。
+/// 工具描述中每段连续的共享段落替换为一个引用标记；没有共享段落时返回 None。
+pub fn dedup_shared_descriptions(tools: &mut [Tool]) -> Option<SharedDescriptions> {
+    let split = |description: &str| -> Vec<String> {
+        description
+            .split("\n\n")
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    let paragraphs: Vec<Vec<String>> = tools
+        .iter()
+        .map(|t| split(&t.tool_specification.description))
+        .collect();
+
+    // 段落 -> 使用它的工具下标（按首次出现顺序编号）
+    let mut users: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut order: Vec<&str> = Vec::new();
+    for (index, tool_paragraphs) in paragraphs.iter().enumerate() {
+        for paragraph in tool_paragraphs {
+            if paragraph.chars().count() < MIN_SHARED_PARAGRAPH_CHARS {
+                continue;
+            }
+            let entry = users.entry(paragraph.as_str()).or_insert_with(|| {
+                order.push(paragraph.as_str());
+                Vec::new()
+            });
+            if entry.last() != Some(&index) {
+                entry.push(index);
+            }
+        }
+    }
+    let shared: Vec<&str> = order.into_iter().filter(|p| users[p].len() >= 2).collect();
+    if shared.is_empty() {
+        return None;
+    }
+    let section_of: HashMap<&str, usize> =
+        shared.iter().enumerate().map(|(i, &p)| (p, i + 1)).collect();
+
+    for (tool, tool_paragraphs) in tools.iter_mut().zip(&paragraphs) {
+        if !tool_paragraphs.iter().any(|p| section_of.contains_key(p.as_str())) {
+            continue;
+        }
+        let mut parts: Vec<String> = Vec::new();
+        let mut refs: Vec<String> = Vec::new();
+        let flush = |refs: &mut Vec<String>, parts: &mut Vec<String>| {
+            if !refs.is_empty() {
+                parts.push(format!(
+                    "(See shared tool documentation {} in the system prompt.)",
+                    refs.join(", ")
+                ));
+                refs.clear();
+            }
+        };
+        for paragraph in tool_paragraphs {
+            match section_of.get(paragraph.as_str()) {
+                Some(section) => {
+                    let reference = format!("#{}", section);
+                    if !refs.contains(&reference) {
+                        refs.push(reference);
+                    }
+                }
+                None => {
+                    flush(&mut refs, &mut parts);
+                    parts.push(paragraph.clone());
+                }
+            }
+        }
+        flush(&mut refs, &mut parts);
+        tool.tool_specification.description = parts.join("\n\n");
+    }
+
+    let doc = shared
+        .iter()
+        .enumerate()
+        .map(|(i, &paragraph)| {
+            let names: Vec<&str> = users[paragraph]
+                .iter()
+                .map(|&index| tools[index].tool_specification.name.as_str())
+                .collect();
+            format!(
+                "## Shared tool documentation #{} (used by: {})\n\n{}",
+                i + 1,
+                names.join(", "),
+                paragraph
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Some(SharedDescriptions {
+        doc,
+        groups: shared.len(),
+    })
+}
+
+/// 按字符截断描述
+fn truncate_description(description: &str, keep: usize) -> String {
+    #[cfg(feature = "grapheme-truncation")]
+    {
+        truncate_description_graphemes(description, keep)
+    }
+    #[cfg(not(feature = "grapheme-truncation"))]
+    {
+        description.chars().take(keep).collect()
+    }
+}
+
+/// 按字素簇截断描述：保留不超过 `keep` 个字符的完整字素簇
+///
+/// 不会拆开 emoji ZWJ 序列（如 👨‍👩‍👧）或带组合字符的字母，结果可能略短于 `keep`。
+#[cfg(feature = "grapheme-truncation")]
+pub fn truncate_description_graphemes(description: &str, keep: usize) -> String {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    let mut kept = 0;
+    let mut end = 0;
+    for (offset, grapheme) in description.grapheme_indices(true) {
+        let chars = grapheme.chars().count();
+        if kept + chars > keep {
+            break;
+        }
+        kept += chars;
+        end = offset + grapheme.len();
+    }
+    description[..end].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::model::requests::tool::{InputSchema, ToolSpecification};
+
+    fn tool(name: &str, description: &str, schema: Value) -> Tool {
+        Tool {
+            tool_specification: ToolSpecification {
+                name: name.to_string(),
+                description: description.to_string(),
+                input_schema: InputSchema::from_json(schema),
+            },
+        }
+    }
+
+    fn descriptions(tools: &[Tool]) -> Vec<&str> {
+        tools
+            .iter()
+            .map(|t| t.tool_specification.description.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_small_tools_are_untouched() {
+        let tools = vec![tool(
+            "a",
+            "  Reads   a file.  ",
+            serde_json::json!({"type": "object"}),
+        )];
+        let (out, report) = compress_tools_if_needed(
+            &tools,
+            &ToolCompressionOptions {
+                collapse_whitespace: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(descriptions(&out), vec!["  Reads   a file.  "]);
+        assert!(!report.compressed());
+    }
+
+    #[test]
+    fn test_collapse_whitespace_shrinks_description_and_keeps_content() {
+        let paragraph = "        Reads a file from the local filesystem.        \n\n\n\n        Usage:\n            - The path   must be   absolute\n\n\n";
+        let description = paragraph.repeat(200);
+        let tools = vec![tool(
+            "Read",
+            &description,
+            serde_json::json!({"type": "object"}),
+        )];
+
+        let (out, report) = compress_tools_if_needed(
+            &tools,
+            &ToolCompressionOptions {
+                collapse_whitespace: true,
+                ..Default::default()
+            },
+        );
+        let collapsed = &out[0].tool_specification.description;
+
+        assert!(
+            report.whitespace_saved * 3 > report.original_size,
+            "{:?}",
+            report
+        );
+        assert_eq!(report.schema_saved, 0);
+        assert_eq!(report.description_saved, 0);
+        assert!(report.final_size <= TOOL_COMPRESSION_TARGET_SIZE);
+        assert!(collapsed.starts_with(
+            "Reads a file from the local filesystem.\n\nUsage:\n- The path must be absolute\n\nReads"
+        ));
+        let words = |s: &str| s.split_whitespace().map(str::to_string).collect::<Vec<_>>();
+        assert_eq!(words(collapsed), words(&description));
+    }
+
+    #[test]
+    fn test_whitespace_stage_is_skipped_when_disabled() {
+        let description = format!("{}x", " ".repeat(30 * 1024));
+        let tools = vec![tool(
+            "t",
+            &description,
+            serde_json::json!({"type": "object"}),
+        )];
+        let (_, report) = compress_tools_if_needed(&tools, &ToolCompressionOptions::default());
+        assert_eq!(report.whitespace_saved, 0);
+        assert!(report.description_saved > 0);
+    }
+
+    #[test]
+    fn test_smaller_target_forces_description_compression() {
+        let tools: Vec<Tool> = (0..4)
+            .map(|i| {
+                tool(
+                    &format!("t{}", i),
+                    &"d".repeat(2000),
+                    serde_json::json!({"type": "object"}),
+                )
+            })
+            .collect();
+        let options = ToolCompressionOptions {
+            target_bytes: 4 * 1024,
+            ..Default::default()
+        };
+        assert_eq!(options.target_size(), 4 * 1024);
+        assert_eq!(
+            ToolCompressionOptions::default().target_size(),
+            TOOL_COMPRESSION_TARGET_SIZE
+        );
+
+        // 默认 20KB 目标下无需压缩
+        let (_, report) = compress_tools_if_needed(&tools, &ToolCompressionOptions::default());
+        assert!(!report.compressed());
+
+        let (out, report) = compress_tools_if_needed(&tools, &options);
+        assert!(report.description_saved > 0, "{:?}", report);
+        assert!(report.final_size <= 4 * 1024, "{:?}", report);
+
+        // 目标极小时描述仍不低于硬下限
+        let (out_tiny, _) =
+            compress_tools_to_target(&tools, &options, 16, &HashMap::new(), &HashSet::new());
+        assert!(out_tiny
+            .iter()
+            .all(|t| t.tool_specification.description.len() == MIN_TOOL_DESCRIPTION_LENGTH));
+        assert!(out
+            .iter()
+            .all(|t| t.tool_specification.description.len() > MIN_TOOL_DESCRIPTION_LENGTH));
+    }
+
+    #[test]
+    fn test_priority_tool_keeps_description_while_others_are_cut() {
+        // 附带若干短描述工具，使每个描述的保留下限（预算 / 工具数）低于均分份额
+        let tools: Vec<Tool> = ["Read", "Lint", "Fmt"]
+            .iter()
+            .map(|name| tool(name, &"d".repeat(10_000), serde_json::json!({"type": "object"})))
+            .chain((0..10).map(|i| {
+                tool(&format!("t{}", i), &"s".repeat(100), serde_json::json!({"type": "object"}))
+            }))
+            .collect();
+        let priorities =
+            HashMap::from([("Read".to_string(), u8::MAX), ("Lint".to_string(), 128)]);
+        let options = ToolCompressionOptions::default();
+        let (out, report) =
+            compress_tools_to_target(&tools, &options, 16 * 1024, &priorities, &HashSet::new());
+        let lengths: Vec<usize> = out
+            .iter()
+            .map(|t| t.tool_specification.description.len())
+            .collect();
+
+        assert!(report.final_size <= 16 * 1024, "{:?}", report);
+        assert_eq!(lengths[0], 10_000);
+        assert!(lengths[1] < 10_000 && lengths[2] < lengths[1], "{:?}", lengths);
+        assert!(lengths[3..].iter().all(|&len| len == 100));
+    }
+
+    #[test]
+    fn test_protected_tools_survive_when_others_absorb_reduction() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {"mode": {"type": "string", "description": "One of: fast, slow"}}
+        });
+        let tools: Vec<Tool> = std::iter::once(tool("Edit", &"e".repeat(4000), schema.clone()))
+            .chain((0..6).map(|i| tool(&format!("t{}", i), &"d".repeat(4000), schema.clone())))
+            .collect();
+        let protected = HashSet::from(["Edit".to_string()]);
+        let (out, report) = compress_tools_to_target(
+            &tools,
+            &ToolCompressionOptions::default(),
+            16 * 1024,
+            &HashMap::new(),
+            &protected,
+        );
+
+        assert!(report.final_size <= 16 * 1024, "{:?}", report);
+        assert_eq!(out[0].tool_specification.description, "e".repeat(4000));
+        assert_eq!(out[0].tool_specification.input_schema.json, schema);
+        for t in &out[1..] {
+            assert!(t.tool_specification.description.len() < 4000);
+            assert!(
+                t.tool_specification.input_schema.json["properties"]["mode"]
+                    .get("description")
+                    .is_none()
+            );
+        }
+    }
+
+    #[test]
+    fn test_protected_tools_are_compressed_as_last_resort() {
+        let tools = vec![
+            tool(
+                "Edit",
+                &"e".repeat(30_000),
+                serde_json::json!({"type": "object"}),
+            ),
+            tool(
+                "t0",
+                &"d".repeat(100),
+                serde_json::json!({"type": "object"}),
+            ),
+        ];
+        let protected = HashSet::from(["Edit".to_string()]);
+        let (out, report) = compress_tools_to_target(
+            &tools,
+            &ToolCompressionOptions::default(),
+            16 * 1024,
+            &HashMap::new(),
+            &protected,
+        );
+
+        assert!(report.final_size <= 16 * 1024, "{:?}", report);
+        assert!(out[0].tool_specification.description.len() < 30_000);
+    }
+
+    #[test]
+    fn test_shared_preamble_is_hoisted_once() {
+        let preamble = "This tool is part of the Acme MCP server. ".repeat(50);
+        assert!(preamble.len() >= 2000);
+        let mut tools: Vec<Tool> = ["read", "write", "delete"]
+            .iter()
+            .map(|name| {
+                tool(
+                    name,
+                    &format!("{}\n\n{} a resource.", preamble, name),
+                    serde_json::json!({"type": "object"}),
+                )
+            })
+            .chain(std::iter::once(tool(
+                "other",
+                "Unrelated tool.",
+                serde_json::json!({"type": "object"}),
+            )))
+            .collect();
+
+        let shared = dedup_shared_descriptions(&mut tools).unwrap();
+        assert_eq!(shared.groups, 1);
+        assert_eq!(shared.doc.matches(preamble.trim()).count(), 1);
+        assert!(shared.doc.contains("(used by: read, write, delete)"));
+        assert_eq!(
+            descriptions(&tools),
+            vec![
+                "(See shared tool documentation #1 in the system prompt.)\n\nread a resource.",
+                "(See shared tool documentation #1 in the system prompt.)\n\nwrite a resource.",
+                "(See shared tool documentation #1 in the system prompt.)\n\ndelete a resource.",
+                "Unrelated tool.",
+            ]
+        );
+
+        // 没有共享段落时不做修改
+        assert_eq!(dedup_shared_descriptions(&mut tools), None);
+    }
+
+    #[test]
+    fn test_tool_size_breakdown_is_sorted_and_sums_to_total() {
+        let tools = vec![
+            tool("small", "s", serde_json::json!({"type": "object"})),
+            tool("big", &"b".repeat(500), serde_json::json!({"type": "object"})),
+            tool("mid", &"m".repeat(100), serde_json::json!({"type": "object"})),
+        ];
+        let breakdown = tool_size_breakdown(&tools);
+        let names: Vec<&str> = breakdown.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["big", "mid", "small"]);
+        // 整体序列化 = 各工具之和 + 方括号 + 分隔逗号
+        let total: usize = breakdown.iter().map(|(_, bytes)| bytes).sum();
+        assert_eq!(total + 2 + tools.len() - 1, calculate_tools_size(&tools));
+
+        // 不读全局记录：其他测试的请求转换也会写入
+        let expected_size = calculate_tools_size(&tools);
+        let recorded = RecordedTools {
+            recorded_at: chrono::Utc::now(),
+            final_size: None,
+            tools,
+        };
+        let snapshot = ToolSizeSnapshot::new(&recorded);
+        assert_eq!(snapshot.tools[0].name, "big");
+        assert_eq!(snapshot.original_size, expected_size);
+        // 未启用压缩时压缩后大小与压缩前相同
+        assert_eq!(snapshot.final_size, expected_size);
+        let compressed = RecordedTools {
+            final_size: Some(10),
+            ..recorded
+        };
+        assert_eq!(ToolSizeSnapshot::new(&compressed).final_size, 10);
+    }
+
+    #[test]
+    fn test_simplify_schema_keeps_property_names() {
+        let mut schema = serde_json::json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "properties": {
+                "description": {"type": "string", "description": "the description field"},
+                "items": {"type": "array", "items": {"type": "string", "title": "item"}}
+            },
+            "required": ["description"]
+        });
+        simplify_schema(&mut schema);
+        assert_eq!(
+            schema,
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "description": {"type": "string"},
+                    "items": {"type": "array", "items": {"type": "string"}}
+                },
+                "required": ["description"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_description_truncation_fits_target_and_respects_floor() {
+        let tools: Vec<Tool> = (0..20)
+            .map(|i| {
+                tool(
+                    &format!("t{}", i),
+                    &"d".repeat(3000),
+                    serde_json::json!({"type": "object"}),
+                )
+            })
+            .chain(std::iter::once(tool(
+                "short",
+                "tiny",
+                serde_json::json!({"type": "object"}),
+            )))
+            .collect();
+        let (out, report) = compress_tools_if_needed(&tools, &ToolCompressionOptions::default());
+        assert!(
+            report.final_size <= TOOL_COMPRESSION_TARGET_SIZE,
+            "{:?}",
+            report
+        );
+        assert!(
+            out.iter()
+                .take(20)
+                .all(|t| t.tool_specification.description.len() >= MIN_TOOL_DESCRIPTION_LENGTH)
+        );
+        assert_eq!(out[20].tool_specification.description, "tiny");
+    }
+
+    #[test]
+    fn test_description_floor_scales_with_tool_count_and_total_fits() {
+        let options = ToolCompressionOptions {
+            min_description_length: 80,
+            ..Default::default()
+        };
+        assert_eq!(options.description_floor(18_000, 3), 6_000);
+        assert_eq!(options.description_floor(18_000, 1_000), 80);
+        // 配置值低于硬下限时按硬下限处理
+        assert_eq!(
+            ToolCompressionOptions::default().description_floor(1_000, 1_000),
+            MIN_TOOL_DESCRIPTION_LENGTH
+        );
+
+        // 长短不一的大量工具：短描述占不到比例份额时被抬到下限，其余重新分摊
+        let tools: Vec<Tool> = (0..40)
+            .map(|i| {
+                let len = if i % 4 == 0 { 6000 } else { 500 };
+                tool(
+                    &format!("t{}", i),
+                    &"d".repeat(len),
+                    serde_json::json!({"type": "object"}),
+                )
+            })
+            .collect();
+        let (out, report) = compress_tools_if_needed(&tools, &options);
+        assert!(
+            report.final_size <= TOOL_COMPRESSION_TARGET_SIZE,
+            "{:?}",
+            report
+        );
+        let overhead = report.original_size
+            - tools
+                .iter()
+                .map(|t| t.tool_specification.description.len())
+                .sum::<usize>();
+        let floor =
+            options.description_floor(TOOL_COMPRESSION_TARGET_SIZE - overhead, tools.len());
+        assert!(floor > options.min_description_length);
+        for (before, after) in tools.iter().zip(&out) {
+            let len = after.tool_specification.description.len();
+            assert!(
+                len >= floor.min(before.tool_specification.description.len()),
+                "{} < {}",
+                len,
+                floor
+            );
+        }
+    }
+
+    #[test]
+    fn test_dry_run_matches_actual_compression() {
+        let options = ToolCompressionOptions::default();
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {"path": {"type": "string", "description": "x".repeat(2000)}}
+        });
+        let tools: Vec<Tool> = (0..8)
+            .map(|i| tool(&format!("t{}", i), &"d".repeat(4000), schema.clone()))
+            .collect();
+
+        let plan = compress_tools_if_needed_dry_run(&tools, &options);
+        let (out, report) = compress_tools_if_needed(&tools, &options);
+
+        assert!(plan.compressed);
+        assert_eq!(plan.original_size, calculate_tools_size(&tools));
+        assert_eq!(plan.after_description_size, report.final_size);
+        assert_eq!(plan.after_description_size, calculate_tools_size(&out));
+        assert!(plan.after_schema_size < plan.original_size);
+        assert!(plan.after_description_size < plan.after_schema_size);
+        for ((entry, before), after) in plan.tools.iter().zip(&tools).zip(&out) {
+            assert_eq!(entry.name, before.tool_specification.name);
+            assert_eq!(entry.before_chars, 4000);
+            assert_eq!(
+                entry.after_chars,
+                after.tool_specification.description.chars().count()
+            );
+        }
+        // 输入未被修改
+        assert_eq!(tools[0].tool_specification.description.len(), 4000);
+    }
+
+    #[cfg(feature = "grapheme-truncation")]
+    #[test]
+    fn test_grapheme_truncation_keeps_family_emoji_intact() {
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let description = format!("ab{}cd", family);
+
+        // 在 emoji 序列中间截断时整簇丢弃，而非留下半个序列
+        assert_eq!(truncate_description_graphemes(&description, 4), "ab");
+        assert_eq!(
+            truncate_description_graphemes(&description, 7),
+            format!("ab{}", family)
+        );
+        assert_eq!(truncate_description_graphemes("cafe\u{301}!", 4), "caf");
+        assert_eq!(truncate_description(&description, 100), description);
+    }
+}
@@ -0,0 +1,156 @@
+//! 工具数量上限
+//!
+//! Kiro 对单次请求可携带的工具数量存在实际上限，超出时上游只返回含糊的 400。
+//! 在请求转换前检查工具数量：按配置直接拒绝，或截断为前 N 个工具
+//! （截断时保留 `tool_choice` 强制指定的工具）。
+
+use crate::model::config::MaxToolsBehavior;
+
+use super::types::MessagesRequest;
+
+/// 工具数量上限配置
+#[derive(Debug, Clone, Copy)]
+pub struct ToolLimit {
+    /// 允许的最大工具数量
+    pub max_tools: usize,
+    /// 超出上限时的处理方式
+    pub behavior: MaxToolsBehavior,
+}
+
+impl ToolLimit {
+    /// 对请求应用工具数量上限
+    ///
+    /// 未超出上限时不做任何修改；超出时按 `behavior` 拒绝（返回错误信息）或原地截断。
+    pub fn apply(&self, payload: &mut MessagesRequest) -> Result<(), String> {
+        let Some(tools) = payload.tools.as_mut() else {
+            return Ok(());
+        };
+        let count = tools.len();
+        if count <= self.max_tools {
+            return Ok(());
+        }
+
+        match self.behavior {
+            MaxToolsBehavior::Reject => Err(format!(
+                "工具数量 {} 超过上限 {}（maxTools）",
+                count, self.max_tools
+            )),
+            MaxToolsBehavior::Truncate => {
+                let forced = forced_tool_name(payload.tool_choice.as_ref());
+                let forced_pos = forced.and_then(|name| tools.iter().position(|t| t.name == name));
+
+                // 保留前 N 个；强制工具不在其中时，用它替换第 N 个
+                let mut keep: Vec<bool> = (0..count).map(|i| i < self.max_tools).collect();
+                if let Some(pos) = forced_pos
+                    && pos >= self.max_tools
+                    && self.max_tools > 0
+                {
+                    keep[self.max_tools - 1] = false;
+                    keep[pos] = true;
+                }
+                let mut keep = keep.into_iter();
+                tools.retain(|_| keep.next().unwrap_or(false));
+
+                tracing::warn!(
+                    "工具数量 {} 超过上限 {}，已截断为 {} 个",
+                    count,
+                    self.max_tools,
+                    tools.len()
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+/// 解析 `tool_choice` 中强制指定的工具名（`{"type": "tool", "name": ...}`）
+fn forced_tool_name(tool_choice: Option<&serde_json::Value>) -> Option<&str> {
+    let choice = tool_choice?;
+    if choice.get("type")?.as_str()? != "tool" {
+        return None;
+    }
+    choice.get("name")?.as_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_with_tools(count: usize, tool_choice: Option<serde_json::Value>) -> MessagesRequest {
+        let tools: Vec<serde_json::Value> = (0..count)
+            .map(|i| {
+                serde_json::json!({
+                    "name": format!("tool_{}", i),
+                    "description": "test",
+                    "input_schema": {"type": "object"}
+                })
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 1024,
+            "messages": [{"role": "user", "content": "hi"}],
+            "tools": tools,
+            "tool_choice": tool_choice
+        }))
+        .unwrap()
+    }
+
+    fn tool_names(payload: &MessagesRequest) -> Vec<&str> {
+        payload
+            .tools
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_reject_when_tool_count_exceeds_limit() {
+        let limit = ToolLimit {
+            max_tools: 3,
+            behavior: MaxToolsBehavior::Reject,
+        };
+        let mut payload = request_with_tools(5, None);
+        let err = limit.apply(&mut payload).unwrap_err();
+        assert!(err.contains('5') && err.contains('3'), "{}", err);
+        assert_eq!(payload.tools.as_ref().unwrap().len(), 5);
+
+        let mut within = request_with_tools(3, None);
+        assert!(limit.apply(&mut within).is_ok());
+    }
+
+    #[test]
+    fn test_truncate_keeps_first_tools() {
+        let limit = ToolLimit {
+            max_tools: 2,
+            behavior: MaxToolsBehavior::Truncate,
+        };
+        let mut payload = request_with_tools(4, Some(serde_json::json!({"type": "auto"})));
+        limit.apply(&mut payload).unwrap();
+        assert_eq!(tool_names(&payload), vec!["tool_0", "tool_1"]);
+    }
+
+    #[test]
+    fn test_truncate_preserves_forced_tool() {
+        let limit = ToolLimit {
+            max_tools: 3,
+            behavior: MaxToolsBehavior::Truncate,
+        };
+        let mut payload = request_with_tools(
+            6,
+            Some(serde_json::json!({"type": "tool", "name": "tool_4"})),
+        );
+        limit.apply(&mut payload).unwrap();
+        assert_eq!(tool_names(&payload), vec!["tool_0", "tool_1", "tool_4"]);
+
+        // 强制工具已在前 N 个中时，按普通截断处理
+        let mut payload = request_with_tools(
+            6,
+            Some(serde_json::json!({"type": "tool", "name": "tool_1"})),
+        );
+        limit.apply(&mut payload).unwrap();
+        assert_eq!(tool_names(&payload), vec!["tool_0", "tool_1", "tool_2"]);
+    }
+}
@@ -0,0 +1,381 @@
+//! 工具输入校验
+//!
+//! 模型生成的 tool_use 参数可能不符合工具的 `input_schema`（类型错误、缺少必填字段），
+//! 客户端直接执行时会以难以理解的方式失败。启用后在 tool_use 块组装完成时按 schema 校验：
+//! - `annotate`：在 tool_use 块上附加 `validation_error` 标记
+//!   （流式响应中块内容已发出，标记附加在该块的 `content_block_stop` 事件上）
+//! - `corrective`：非流式请求以错误 tool_result 的形式把校验结果交还模型并重试一次，
+//!   重试结果仍不合法时退化为标记；流式响应同样退化为标记
+//!
+//! 只覆盖 JSON Schema 的常用子集（type / required / properties / items / enum），
+//! 未识别的关键字一律忽略：宁可漏报，不能把合法调用判为非法。
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::kiro::model::requests::conversation::{
+    AssistantMessage, HistoryAssistantMessage, HistoryUserMessage, Message, UserInputMessage,
+    UserInputMessageContext, UserMessage,
+};
+use crate::kiro::model::requests::kiro::KiroRequest;
+use crate::kiro::model::requests::tool::{ToolResult, ToolUseEntry};
+use crate::model::config::ToolInputValidation;
+
+use super::types::Tool;
+
+/// tool_use 块（或流式 content_block_stop 事件）上的校验错误标记字段
+pub const VALIDATION_ERROR_FIELD: &str = "validation_error";
+
+/// 纠正消息：要求模型按 schema 重新发起工具调用
+const CORRECTIVE_PROMPT: &str = "One or more tool calls had arguments that do not match the tool's input_schema. \
+Call the tools again with corrected arguments.";
+
+/// 同一轮中未通过校验之外的其他工具调用的说明
+const NOT_EXECUTED_NOTE: &str = "Not executed: another tool call in the same turn had invalid arguments. Call it again if still needed.";
+
+/// 工具输入校验器
+///
+/// 按原始工具名（还原映射后的名称）保存各工具的 `input_schema`。
+#[derive(Debug, Clone)]
+pub struct ToolInputValidator {
+    mode: ToolInputValidation,
+    schemas: HashMap<String, Value>,
+}
+
+impl ToolInputValidator {
+    /// 按请求中的工具定义创建校验器
+    ///
+    /// 校验未开启或没有可用 schema（如仅有 WebSearch 工具）时返回 None。
+    pub fn new(mode: ToolInputValidation, tools: Option<&[Tool]>) -> Option<Self> {
+        if mode == ToolInputValidation::Off {
+            return None;
+        }
+        let schemas: HashMap<String, Value> = tools?
+            .iter()
+            .filter(|t| !t.input_schema.is_empty())
+            .map(|t| {
+                let schema = t.input_schema.clone().into_iter().collect();
+                (t.name.clone(), Value::Object(schema))
+            })
+            .collect();
+        (!schemas.is_empty()).then_some(Self { mode, schemas })
+    }
+
+    /// 校验模式
+    pub fn mode(&self) -> ToolInputValidation {
+        self.mode
+    }
+
+    /// 校验一次工具调用，不合法时返回错误描述（未知工具视为合法）
+    pub fn check(&self, tool_name: &str, input: &Value) -> Option<String> {
+        let schema = self.schemas.get(tool_name)?;
+        let errors = validate_input(schema, input);
+        (!errors.is_empty()).then(|| errors.join("; "))
+    }
+
+    /// 为不合法的 tool_use 块附加 [`VALIDATION_ERROR_FIELD`] 标记，返回被标记的块数
+    pub fn annotate(&self, blocks: &mut [Value]) -> usize {
+        let mut annotated = 0;
+        for block in blocks.iter_mut() {
+            let Some(name) = block["name"].as_str() else {
+                continue;
+            };
+            if let Some(error) = self.check(name, &block["input"]) {
+                tracing::warn!("工具 {} 的输入不符合 input_schema: {}", name, error);
+                block[VALIDATION_ERROR_FIELD] = Value::String(error);
+                annotated += 1;
+            }
+        }
+        annotated
+    }
+}
+
+/// 按 schema 校验工具输入，返回所有违规描述（路径使用 JSON Pointer 风格）
+pub fn validate_input(schema: &Value, input: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_at(schema, input, "", &mut errors);
+    errors
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+    let location = if path.is_empty() { "/" } else { path };
+
+    if let Some(expected) = schema.get("type")
+        && !type_matches(expected, value)
+    {
+        errors.push(format!(
+            "{}: expected {}, got {}",
+            location,
+            type_label(expected),
+            json_type_name(value)
+        ));
+        // 类型不符时不再深入检查子结构
+        return;
+    }
+
+    if let Some(Value::Array(allowed)) = schema.get("enum")
+        && !allowed.contains(value)
+    {
+        errors.push(format!(
+            "{}: value is not one of the allowed enum values",
+            location
+        ));
+    }
+
+    if let Value::Object(object) = value {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for field in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(field) {
+                    errors.push(format!(
+                        "{}: missing required field \"{}\"",
+                        location, field
+                    ));
+                }
+            }
+        }
+        if let Some(Value::Object(properties)) = schema.get("properties") {
+            for (key, child) in object {
+                if let Some(child_schema) = properties.get(key) {
+                    validate_at(child_schema, child, &format!("{}/{}", path, key), errors);
+                }
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (i, item) in items.iter().enumerate() {
+            validate_at(item_schema, item, &format!("{}/{}", path, i), errors);
+        }
+    }
+}
+
+/// `type` 可以是单个类型名或类型名数组
+fn type_matches(expected: &Value, value: &Value) -> bool {
+    match expected {
+        Value::String(name) => single_type_matches(name, value),
+        Value::Array(names) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .any(|name| single_type_matches(name, value)),
+        _ => true,
+    }
+}
+
+fn single_type_matches(name: &str, value: &Value) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|f| f.fract() == 0.0)
+        }
+        // 未知类型名不做判断
+        _ => true,
+    }
+}
+
+fn type_label(expected: &Value) -> String {
+    match expected {
+        Value::Array(names) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" | "),
+        other => other.as_str().unwrap_or("?").to_string(),
+    }
+}
+
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// 构建纠正请求：把本轮 assistant 输出追加到历史，并以错误 tool_result 交还校验结果
+///
+/// - `tool_uses`：本轮所有工具调用（Kiro 格式，工具名为上游使用的名称）
+/// - `errors`：tool_use_id → 校验错误描述；不在其中的调用标记为"未执行"
+///
+/// 请求体无法解析时返回 None。
+pub fn corrective_request_body(
+    request_body: &str,
+    assistant_text: &str,
+    tool_uses: Vec<ToolUseEntry>,
+    errors: &HashMap<String, String>,
+) -> Option<String> {
+    let mut request: KiroRequest = serde_json::from_str(request_body).ok()?;
+    let state = &mut request.conversation_state;
+    let current = std::mem::take(&mut state.current_message.user_input_message);
+
+    let tool_results = tool_uses
+        .iter()
+        .map(|tool_use| match errors.get(&tool_use.tool_use_id) {
+            Some(error) => ToolResult::error(
+                &tool_use.tool_use_id,
+                format!("Invalid tool input: {}", error),
+            ),
+            None => ToolResult::error(&tool_use.tool_use_id, NOT_EXECUTED_NOTE),
+        })
+        .collect();
+
+    // 原当前消息移入历史（工具定义只保留在新的当前消息上）
+    let UserInputMessage {
+        user_input_message_context: context,
+        content,
+        model_id,
+        images,
+        origin,
+    } = current;
+    state.history.push(Message::User(HistoryUserMessage {
+        user_input_message: UserMessage {
+            content,
+            model_id: model_id.clone(),
+            origin: origin.clone(),
+            images,
+            user_input_message_context: UserInputMessageContext::new()
+                .with_tool_results(context.tool_results),
+        },
+    }));
+
+    // Kiro 要求 content 非空，仅有工具调用时使用占位符
+    let assistant_content = if assistant_text.is_empty() {
+        " "
+    } else {
+        assistant_text
+    };
+    state
+        .history
+        .push(Message::Assistant(HistoryAssistantMessage {
+            assistant_response_message: AssistantMessage::new(assistant_content)
+                .with_tool_uses(tool_uses),
+        }));
+
+    let mut corrective = UserInputMessage::new(CORRECTIVE_PROMPT, model_id).with_context(
+        UserInputMessageContext::new()
+            .with_tools(context.tools)
+            .with_tool_results(tool_results),
+    );
+    corrective.origin = origin;
+    state.current_message.user_input_message = corrective;
+
+    serde_json::to_string(&request).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn read_file_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string"},
+                "limit": {"type": "integer"},
+                "mode": {"type": "string", "enum": ["text", "binary"]},
+                "ranges": {"type": "array", "items": {"type": "integer"}}
+            },
+            "required": ["path"]
+        })
+    }
+
+    #[test]
+    fn test_validate_input_reports_missing_required_and_type_errors() {
+        let schema = read_file_schema();
+        assert!(validate_input(&schema, &json!({"path": "/a", "limit": 10})).is_empty());
+
+        let errors = validate_input(
+            &schema,
+            &json!({"limit": "ten", "mode": "hex", "ranges": [1, "2"]}),
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "/: missing required field \"path\"",
+                "/limit: expected integer, got string",
+                "/mode: value is not one of the allowed enum values",
+                "/ranges/1: expected integer, got string",
+            ]
+        );
+        // 未识别的关键字被忽略
+        assert!(
+            validate_input(&json!({"type": "object", "minProperties": 3}), &json!({})).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_corrective_request_returns_errors_as_tool_results() {
+        let request = json!({
+            "conversationState": {
+                "conversationId": "c1",
+                "currentMessage": {"userInputMessage": {
+                    "content": "read it",
+                    "modelId": "claude-sonnet-4.5",
+                    "origin": "AI_EDITOR",
+                    "userInputMessageContext": {
+                        "tools": [{"toolSpecification": {
+                            "name": "read_file",
+                            "description": "Read a file",
+                            "inputSchema": {"json": read_file_schema()}
+                        }}]
+                    }
+                }},
+                "history": []
+            }
+        });
+        let tool_uses = vec![
+            ToolUseEntry::new("t1", "read_file").with_input(json!({"limit": 1})),
+            ToolUseEntry::new("t2", "read_file").with_input(json!({"path": "/b"})),
+        ];
+        let errors = HashMap::from([(
+            "t1".to_string(),
+            "/: missing required field \"path\"".to_string(),
+        )]);
+
+        let body = corrective_request_body(&request.to_string(), "", tool_uses, &errors).unwrap();
+        let value: Value = serde_json::from_str(&body).unwrap();
+        let state = &value["conversationState"];
+
+        assert_eq!(
+            state["history"][0]["userInputMessage"]["content"],
+            "read it"
+        );
+        assert!(
+            state["history"][0]["userInputMessage"]["userInputMessageContext"]["tools"].is_null()
+        );
+        let assistant = &state["history"][1]["assistantResponseMessage"];
+        assert_eq!(assistant["content"], " ");
+        assert_eq!(assistant["toolUses"][0]["toolUseId"], "t1");
+
+        let current = &state["currentMessage"]["userInputMessage"];
+        assert_eq!(current["content"], CORRECTIVE_PROMPT);
+        assert_eq!(current["modelId"], "claude-sonnet-4.5");
+        let context = &current["userInputMessageContext"];
+        assert_eq!(
+            context["tools"][0]["toolSpecification"]["name"],
+            "read_file"
+        );
+        let results = context["toolResults"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["status"], "error");
+        assert!(
+            results[0]["content"][0]["text"]
+                .as_str()
+                .unwrap()
+                .contains("missing required field")
+        );
+        assert_eq!(results[1]["content"][0]["text"], NOT_EXECUTED_NOTE);
+    }
+}
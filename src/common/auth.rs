@@ -4,6 +4,7 @@ use axum::{
     body::Body,
     http::{Request, header},
 };
+use sha2::{Digest, Sha256};
 use subtle::ConstantTimeEq;
 
 /// 从请求中提取 API Key
@@ -35,7 +36,54 @@ pub fn extract_api_key(request: &Request<Body>) -> Option<String> {
 /// 无论字符串内容如何，比较所需的时间都是恒定的，
 /// 这可以防止攻击者通过测量响应时间来猜测 API Key。
 ///
+/// 两侧先做 SHA-256 摘要再比较固定长度的摘要，长度不同的输入也会走完整比较，
+/// 不会因提前返回泄露长度信息。
+///
 /// 使用经过安全审计的 `subtle` crate 实现
 pub fn constant_time_eq(a: &str, b: &str) -> bool {
-    a.as_bytes().ct_eq(b.as_bytes()).into()
+    digests_eq(a, b, hash_key)
+}
+
+/// 以 `digest` 摘要两侧后做常量时间比较，结果只取决于摘要，与输入长度无关
+fn digests_eq(a: &str, b: &str, digest: impl Fn(&str) -> [u8; 32]) -> bool {
+    digest(a).ct_eq(&digest(b)).into()
+}
+
+/// 计算密钥的 SHA-256 摘要（用于只保存摘要、不保留明文的场景）
+pub fn hash_key(key: &str) -> [u8; 32] {
+    Sha256::digest(key.as_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_time_eq_compares_digests() {
+        assert!(constant_time_eq("admin-secret", "admin-secret"));
+        // 等长但内容不同
+        assert!(!constant_time_eq("admin-secret", "admin-secreT"));
+        // 长度不同（包括前缀关系）
+        assert!(!constant_time_eq("admin-secret", "admin"));
+        assert!(!constant_time_eq("admin-secret", "admin-secret-extra"));
+        assert!(!constant_time_eq("", "admin-secret"));
+        assert!(constant_time_eq("", ""));
+    }
+
+    #[test]
+    fn test_different_lengths_take_the_digest_path() {
+        // 摘要不区分输入时结果为相等：若在摘要前按长度提前返回，此断言会失败
+        let calls = std::cell::Cell::new(0);
+        let same_digest = |_: &str| {
+            calls.set(calls.get() + 1);
+            [0u8; 32]
+        };
+        assert!(digests_eq("admin-secret", "admin", same_digest));
+        assert!(digests_eq("admin-secret", "admin-secreT", same_digest));
+        assert_eq!(calls.get(), 4);
+
+        // 实际比较与摘要比较一致
+        assert!(!digests_eq("admin-secret", "admin", hash_key));
+        assert!(digests_eq("admin-secret", "admin-secret", hash_key));
+    }
 }
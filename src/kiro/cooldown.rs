@@ -0,0 +1,1022 @@
+//! 凭据冷却管理
+//!
+//! 与"禁用"不同，冷却是短期、可自动恢复的不可用状态：
+//! 凭据在冷却期内被负载均衡跳过，到期后自动重新参与选择。
+//! 同一凭据重复触发冷却时，时长按次数递增（封顶于短冷却上限）；
+//! 冷却结束后凭据每保持可用一个衰减周期，累计次数减一，旧的故障不会永久放大冷却时长。
+//! 此外记录滚动窗口内的冷却区间，用于统计凭据"花在冷却上的时间"。
+//! 可选的抖动会随机拉伸/缩短计算出的冷却时长，避免同时进入冷却的凭据在同一时刻集中恢复。
+//!
+//! 可注册回调订阅凭据进入/退出冷却的事件，回调在释放内部锁之后调用，可安全地回调管理器。
+//!
+//! 模型级冷却只针对"凭据 + 模型"组合：凭据仍可服务其他模型，不计入累计冷却时长。
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::metrics;
+use crate::model::config::BackoffStrategy;
+
+/// 默认短冷却上限（秒）
+const DEFAULT_MAX_SHORT_COOLDOWN_SECS: u64 = 300;
+
+/// 默认累计冷却统计窗口（1 小时）
+const DEFAULT_BUDGET_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// 默认触发次数衰减周期（1 小时）
+const DEFAULT_DECAY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// 默认冷却时长递增倍率（指数策略下第 n 次触发为 base * 1.5^(n-1)）
+const DEFAULT_BACKOFF_BASE: f64 = 1.5;
+
+/// 参与递增计算的最大触发次数（之后的结果均已封顶）
+const MAX_BACKOFF_STEPS: u32 = 17;
+
+/// 冷却原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CooldownReason {
+    /// 上游服务端错误（5xx / 408）
+    ///
+    /// 仅在开启 `rotateOnTransientError` 时触发，默认 5xx 只按瞬态错误重试
+    ServerError,
+    /// 上游限流（429），仅在开启 `rotateOnTransientError` 时触发
+    RateLimited,
+    /// 上游返回空响应（无文本、无工具调用）
+    EmptyResponse,
+    /// 凭据级请求配额已用尽（冷却至配额窗口滚动）
+    QuotaExhausted,
+    /// 上游暂时无法在该凭据上提供所请求的模型（模型级冷却）
+    ModelUnavailable,
+    /// 同一凭据上的请求连续被上游拒绝（400），短暂冷却避免反复重放
+    RequestRejected,
+}
+
+impl CooldownReason {
+    /// 所有冷却原因
+    pub const ALL: [CooldownReason; 6] = [
+        Self::ServerError,
+        Self::RateLimited,
+        Self::EmptyResponse,
+        Self::QuotaExhausted,
+        Self::ModelUnavailable,
+        Self::RequestRejected,
+    ];
+
+    /// 从原因标识解析（与 [`as_str`](Self::as_str) 对应，大小写不敏感）
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|reason| reason.as_str().eq_ignore_ascii_case(s.trim()))
+    }
+
+    /// 默认冷却时长
+    pub fn default_duration(&self) -> Duration {
+        match self {
+            Self::ServerError => Duration::from_secs(120),
+            Self::RateLimited => Duration::from_secs(60),
+            Self::EmptyResponse => Duration::from_secs(30),
+            Self::QuotaExhausted => Duration::from_secs(60 * 60),
+            Self::ModelUnavailable => Duration::from_secs(300),
+            Self::RequestRejected => Duration::from_secs(30),
+        }
+    }
+
+    /// 人类可读的原因描述
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::ServerError => "上游服务端错误",
+            Self::RateLimited => "上游限流",
+            Self::EmptyResponse => "上游返回空响应",
+            Self::QuotaExhausted => "请求配额已用尽",
+            Self::ModelUnavailable => "模型暂不可用",
+            Self::RequestRejected => "请求连续被上游拒绝",
+        }
+    }
+
+    /// 冷却是否可自动恢复
+    ///
+    /// 可自动恢复的冷却只是对瞬态上游状况的预防性退避，状况可能已提前消失，
+    /// 所有凭据均处于冷却时可以忽略；配额冷却是硬性上限，到期前不可忽略。
+    pub fn is_auto_recoverable(&self) -> bool {
+        match self {
+            Self::ServerError
+            | Self::RateLimited
+            | Self::EmptyResponse
+            | Self::ModelUnavailable
+            | Self::RequestRejected => true,
+            Self::QuotaExhausted => false,
+        }
+    }
+
+    /// 原因标识（用于 Admin API 展示）
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ServerError => "ServerError",
+            Self::RateLimited => "RateLimited",
+            Self::EmptyResponse => "EmptyResponse",
+            Self::QuotaExhausted => "QuotaExhausted",
+            Self::ModelUnavailable => "ModelUnavailable",
+            Self::RequestRejected => "RequestRejected",
+        }
+    }
+}
+
+/// 单个凭据的冷却条目
+#[derive(Debug, Clone)]
+pub struct CooldownEntry {
+    /// 冷却到期时间
+    pub expires_at: Instant,
+    /// 最近一次触发冷却的原因
+    pub reason: CooldownReason,
+    /// 累计触发次数（用于递增冷却时长）
+    pub trigger_count: u32,
+    /// 统计窗口内的冷却区间（开始, 结束）
+    pub periods: VecDeque<(Instant, Instant)>,
+    /// 本次冷却的结束是否已通知（到期清扫或手动解除后置为 true）
+    pub exit_notified: bool,
+}
+
+/// 冷却状态变化事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CooldownEvent {
+    /// 凭据进入冷却
+    Entered {
+        credential_id: u64,
+        reason: CooldownReason,
+        duration: Duration,
+    },
+    /// 凭据退出冷却（到期或手动解除）
+    Cleared { credential_id: u64 },
+}
+
+/// 冷却事件回调
+type CooldownListener = Arc<dyn Fn(CooldownEvent) + Send + Sync>;
+
+/// 当前生效的冷却信息（用于 Admin API 展示）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CooldownInfo {
+    /// 凭据 ID
+    pub credential_id: u64,
+    /// 原因标识
+    pub reason: &'static str,
+    /// 人类可读的原因描述
+    pub description: &'static str,
+    /// 剩余冷却时长（秒）
+    pub remaining_secs: u64,
+    /// 累计触发次数
+    pub trigger_count: u32,
+}
+
+/// 全局冷却在 [`CooldownManager::get_all_cooldowns`] 中使用的凭据 ID（凭据 ID 从 1 开始分配）
+pub const GLOBAL_COOLDOWN_ID: u64 = 0;
+
+/// 冷却管理器
+///
+/// 过期条目不会立即删除：保留 trigger_count 以便下次触发时递增时长。
+pub struct CooldownManager {
+    entries: Mutex<HashMap<u64, CooldownEntry>>,
+    /// 模型级冷却到期时间：(凭据 ID, 模型) -> 到期时间
+    model_entries: Mutex<HashMap<(u64, String), Instant>>,
+    /// 按原因覆盖的基础冷却时长（运行时可修改，未覆盖时使用默认时长）
+    duration_overrides: Mutex<HashMap<CooldownReason, Duration>>,
+    /// 全局冷却：(原因, 到期时间)，生效期间所有凭据均不可用（与凭据级冷却相互独立）
+    global: Mutex<Option<(CooldownReason, Instant)>>,
+    /// 冷却事件回调
+    listeners: Mutex<Vec<CooldownListener>>,
+    /// 冷却时长上限（秒）
+    max_short_cooldown_secs: u64,
+    /// 累计冷却时长的统计窗口
+    budget_window: Duration,
+    /// 冷却时长抖动比例（0 表示不抖动，0.15 表示 ±15%）
+    jitter: f64,
+    /// 触发次数衰减周期：冷却结束后每保持可用一个周期，触发次数减一
+    decay_interval: Duration,
+    /// 冷却时长递增策略
+    backoff_strategy: BackoffStrategy,
+    /// 递增倍率（linear / exponential 使用）
+    backoff_base: f64,
+}
+
+impl Default for CooldownManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CooldownManager {
+    /// 使用默认配置创建冷却管理器
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            model_entries: Mutex::new(HashMap::new()),
+            duration_overrides: Mutex::new(HashMap::new()),
+            global: Mutex::new(None),
+            listeners: Mutex::new(Vec::new()),
+            max_short_cooldown_secs: DEFAULT_MAX_SHORT_COOLDOWN_SECS,
+            budget_window: DEFAULT_BUDGET_WINDOW,
+            jitter: 0.0,
+            decay_interval: DEFAULT_DECAY_INTERVAL,
+            backoff_strategy: BackoffStrategy::default(),
+            backoff_base: DEFAULT_BACKOFF_BASE,
+        }
+    }
+
+    /// 设置冷却时长递增策略与倍率（倍率小于 1 或非有限值时按 1 处理，即不递增）
+    pub fn with_backoff(mut self, strategy: BackoffStrategy, base: f64) -> Self {
+        self.backoff_strategy = strategy;
+        self.backoff_base = if base.is_finite() { base.max(1.0) } else { 1.0 };
+        self
+    }
+
+    /// 设置触发次数衰减周期（为 0 时不衰减）
+    pub fn with_decay_interval(mut self, interval: Duration) -> Self {
+        self.decay_interval = interval;
+        self
+    }
+
+    /// 设置冷却时长抖动比例（如 0.15 表示 ±15%，限制在 [0, 1) 内）
+    pub fn with_jitter(mut self, fraction: f64) -> Self {
+        self.jitter = if fraction.is_finite() {
+            fraction.clamp(0.0, 0.99)
+        } else {
+            0.0
+        };
+        self
+    }
+
+    /// 设置累计冷却时长的统计窗口
+    pub fn with_budget_window(mut self, window: Duration) -> Self {
+        self.budget_window = window;
+        self
+    }
+
+    /// 累计冷却时长的统计窗口
+    pub fn budget_window(&self) -> Duration {
+        self.budget_window
+    }
+
+    /// 冷却时长上限（秒）
+    pub fn max_short_cooldown_secs(&self) -> u64 {
+        self.max_short_cooldown_secs
+    }
+
+    /// 覆盖指定原因的基础冷却时长
+    pub fn set_reason_duration(&self, reason: CooldownReason, duration: Duration) {
+        self.duration_overrides.lock().insert(reason, duration);
+    }
+
+    /// 清除指定原因的基础时长覆盖，返回此前是否存在覆盖
+    pub fn clear_reason_duration(&self, reason: CooldownReason) -> bool {
+        self.duration_overrides.lock().remove(&reason).is_some()
+    }
+
+    /// 指定原因的基础时长覆盖（未覆盖时为 None）
+    pub fn reason_duration_override(&self, reason: CooldownReason) -> Option<Duration> {
+        self.duration_overrides.lock().get(&reason).copied()
+    }
+
+    /// 指定原因的基础冷却时长（优先使用覆盖值）
+    pub fn base_duration(&self, reason: CooldownReason) -> Duration {
+        self.reason_duration_override(reason)
+            .unwrap_or_else(|| reason.default_duration())
+    }
+
+    /// 计算冷却时长：按递增策略随触发次数增长，并封顶于 `max_short_cooldown_secs`
+    pub fn calculate_cooldown_duration(&self, reason: CooldownReason, trigger_count: u32) -> Duration {
+        let base = self.base_duration(reason);
+        let secs = base.as_secs_f64() * self.backoff_multiplier(trigger_count);
+        Duration::from_secs_f64(secs.min(self.max_short_cooldown_secs as f64))
+    }
+
+    /// 第 `trigger_count` 次触发相对基础时长的倍数（首次为 1）
+    fn backoff_multiplier(&self, trigger_count: u32) -> f64 {
+        let steps = trigger_count.saturating_sub(1).min(MAX_BACKOFF_STEPS - 1);
+        match self.backoff_strategy {
+            BackoffStrategy::Linear => 1.0 + steps as f64 * (self.backoff_base - 1.0),
+            BackoffStrategy::Exponential => self.backoff_base.powi(steps as i32),
+            BackoffStrategy::Fibonacci => {
+                let (mut current, mut next) = (1u64, 1u64);
+                for _ in 0..steps {
+                    (current, next) = (next, current + next);
+                }
+                current as f64
+            }
+        }
+    }
+
+    /// 对计算出的冷却时长施加随机抖动（结果仍封顶于 `max_short_cooldown_secs`）
+    fn apply_jitter(&self, duration: Duration) -> Duration {
+        if self.jitter <= 0.0 {
+            return duration;
+        }
+        let factor = 1.0 + self.jitter * (fastrand::f64() * 2.0 - 1.0);
+        let secs = duration.as_secs_f64() * factor;
+        Duration::from_secs_f64(secs.min(self.max_short_cooldown_secs as f64))
+    }
+
+    /// 按上次冷却结束后保持可用的时长衰减触发次数（每满一个衰减周期减一）
+    fn decayed_trigger_count(&self, entry: &CooldownEntry, now: Instant) -> u32 {
+        if self.decay_interval.is_zero() {
+            return entry.trigger_count;
+        }
+        let idle = now.saturating_duration_since(entry.expires_at);
+        let periods = idle.as_secs_f64() / self.decay_interval.as_secs_f64();
+        entry
+            .trigger_count
+            .saturating_sub(periods.floor().min(u32::MAX as f64) as u32)
+    }
+
+    /// 以指定时间为"当前时间"使凭据进入冷却，返回实际生效的冷却时长
+    pub fn set_cooldown_at(&self, credential_id: u64, reason: CooldownReason, now: Instant) -> Duration {
+        let window_start = now.checked_sub(self.budget_window);
+        let mut entries = self.entries.lock();
+        let entry = entries.entry(credential_id).or_insert_with(|| CooldownEntry {
+            expires_at: now,
+            reason,
+            trigger_count: 0,
+            periods: VecDeque::new(),
+            exit_notified: true,
+        });
+        let trigger_count = self.decayed_trigger_count(entry, now).saturating_add(1);
+        let duration = self.apply_jitter(self.calculate_cooldown_duration(reason, trigger_count));
+
+        entry.expires_at = now + duration;
+        entry.reason = reason;
+        entry.trigger_count = trigger_count;
+        entry.exit_notified = false;
+        entry
+            .periods
+            .retain(|&(_, end)| window_start.is_none_or(|ws| end > ws));
+        // 冷却期间再次触发：新的到期时间取代旧的，与上一段合并为一段，避免重叠部分重复计入
+        match entry.periods.back_mut() {
+            Some(last) if last.1 >= now => last.1 = now + duration,
+            _ => entry.periods.push_back((now, now + duration)),
+        }
+        drop(entries);
+
+        tracing::warn!(
+            credential_id,
+            reason = ?reason,
+            duration_secs = duration.as_secs(),
+            "凭据 #{} 进入冷却（{}，第 {} 次）",
+            credential_id,
+            reason.description(),
+            trigger_count
+        );
+        self.emit(CooldownEvent::Entered {
+            credential_id,
+            reason,
+            duration,
+        });
+        duration
+    }
+
+    /// 使凭据冷却至指定时间（如配额窗口的滚动时刻）
+    ///
+    /// 不递增触发次数，也不计入累计冷却时长：到期时间由外部确定，并非凭据不健康。
+    /// 显式指定的到期时间不施加抖动。
+    pub fn set_cooldown_until(&self, credential_id: u64, reason: CooldownReason, until: Instant) {
+        let mut entries = self.entries.lock();
+        let entry = entries.entry(credential_id).or_insert_with(|| CooldownEntry {
+            expires_at: until,
+            reason,
+            trigger_count: 0,
+            periods: VecDeque::new(),
+            exit_notified: true,
+        });
+        let extended = until >= entry.expires_at;
+        if extended {
+            entry.expires_at = until;
+            entry.reason = reason;
+            entry.exit_notified = false;
+        }
+        drop(entries);
+
+        tracing::debug!(
+            credential_id,
+            reason = ?reason,
+            "凭据 #{} 暂停使用（{}）",
+            credential_id,
+            reason.description()
+        );
+        if extended {
+            self.emit(CooldownEvent::Entered {
+                credential_id,
+                reason,
+                duration: until.saturating_duration_since(Instant::now()),
+            });
+        }
+    }
+
+    /// 以指定时间为"当前时间"使凭据上的某个模型进入冷却，返回冷却时长
+    pub fn set_model_cooldown_at(&self, credential_id: u64, model: &str, now: Instant) -> Duration {
+        let duration = self.base_duration(CooldownReason::ModelUnavailable);
+        self.model_entries
+            .lock()
+            .insert((credential_id, model.to_string()), now + duration);
+
+        tracing::warn!(
+            credential_id,
+            model,
+            duration_secs = duration.as_secs(),
+            "凭据 #{} 上的模型 {} 进入冷却（{}）",
+            credential_id,
+            model,
+            CooldownReason::ModelUnavailable.description()
+        );
+        duration
+    }
+
+    /// 凭据在指定时间是否可用于该模型（未处于模型级冷却中）
+    pub fn is_model_available_at(&self, credential_id: u64, model: &str, now: Instant) -> bool {
+        self.model_entries
+            .lock()
+            .get(&(credential_id, model.to_string()))
+            .is_none_or(|&expires_at| expires_at <= now)
+    }
+
+    /// 统计最近一个窗口内（`[now - window, now]`）实际处于冷却中的时长
+    ///
+    /// 各冷却区间先裁剪到窗口内（尚未结束的冷却只计入已经过的部分），
+    /// 重叠的区间合并后只计一次。
+    pub fn cooldown_time_in_window(&self, credential_id: u64, now: Instant) -> Duration {
+        let window_start = now.checked_sub(self.budget_window);
+        let mut periods: Vec<(Instant, Instant)> = match self.entries.lock().get(&credential_id) {
+            Some(e) => e
+                .periods
+                .iter()
+                .map(|&(start, end)| (window_start.map_or(start, |ws| start.max(ws)), end.min(now)))
+                .filter(|&(start, end)| start < end)
+                .collect(),
+            None => return Duration::ZERO,
+        };
+        periods.sort_unstable_by_key(|&(start, _)| start);
+
+        let mut total = Duration::ZERO;
+        let mut merged: Option<(Instant, Instant)> = None;
+        for (start, end) in periods {
+            merged = match merged {
+                Some((s, e)) if start <= e => Some((s, e.max(end))),
+                Some((s, e)) => {
+                    total += e - s;
+                    Some((start, end))
+                }
+                None => Some((start, end)),
+            };
+        }
+        total + merged.map_or(Duration::ZERO, |(s, e)| e - s)
+    }
+
+    /// 指定时间处于冷却中的所有凭据：(凭据 ID, 原因, 剩余时长)，按凭据 ID 排序
+    pub fn get_all_cooldowns_at(&self, now: Instant) -> Vec<(u64, CooldownReason, Duration)> {
+        let mut active: Vec<_> = self
+            .entries
+            .lock()
+            .iter()
+            .filter(|(_, e)| e.expires_at > now)
+            .map(|(&id, e)| (id, e.reason, e.expires_at - now))
+            .collect();
+        active.sort_by_key(|&(id, _, _)| id);
+        active
+    }
+
+    /// 凭据在指定时间的冷却原因（未处于冷却中时为 None，不含全局冷却）
+    pub fn cooldown_reason_at(&self, credential_id: u64, now: Instant) -> Option<CooldownReason> {
+        self.entries
+            .lock()
+            .get(&credential_id)
+            .filter(|e| e.expires_at > now)
+            .map(|e| e.reason)
+    }
+
+    /// 当前处于冷却中的所有凭据，按凭据 ID 排序
+    ///
+    /// 全局冷却生效时，以 [`GLOBAL_COOLDOWN_ID`] 作为凭据 ID 排在首位。
+    pub fn get_all_cooldowns(&self) -> Vec<CooldownInfo> {
+        let now = Instant::now();
+        let global = self.global_cooldown_at(now).map(|(reason, remaining)| CooldownInfo {
+            credential_id: GLOBAL_COOLDOWN_ID,
+            reason: reason.as_str(),
+            description: reason.description(),
+            remaining_secs: remaining.as_secs(),
+            trigger_count: 0,
+        });
+        let mut active: Vec<CooldownInfo> = self
+            .entries
+            .lock()
+            .iter()
+            .filter(|(_, e)| e.expires_at > now)
+            .map(|(&id, e)| CooldownInfo {
+                credential_id: id,
+                reason: e.reason.as_str(),
+                description: e.reason.description(),
+                remaining_secs: (e.expires_at - now).as_secs(),
+                trigger_count: e.trigger_count,
+            })
+            .collect();
+        active.sort_by_key(|info| info.credential_id);
+        active.splice(0..0, global);
+        active
+    }
+
+    /// 设置全局冷却：持续期间所有凭据均不可用，重复设置时以新的到期时间为准
+    pub fn set_global_cooldown(&self, reason: CooldownReason, duration: Duration) {
+        self.set_global_cooldown_at(reason, duration, Instant::now());
+    }
+
+    /// 以指定时间为起点设置全局冷却
+    pub fn set_global_cooldown_at(&self, reason: CooldownReason, duration: Duration, now: Instant) {
+        *self.global.lock() = Some((reason, now + duration));
+        tracing::warn!("全局冷却 {} 秒（{}）", duration.as_secs(), reason.description());
+    }
+
+    /// 全局冷却的原因与剩余时长（未生效时返回 None）
+    pub fn global_cooldown(&self) -> Option<(CooldownReason, Duration)> {
+        self.global_cooldown_at(Instant::now())
+    }
+
+    /// 指定时间全局冷却的原因与剩余时长
+    pub fn global_cooldown_at(&self, now: Instant) -> Option<(CooldownReason, Duration)> {
+        self.global
+            .lock()
+            .filter(|&(_, until)| until > now)
+            .map(|(reason, until)| (reason, until - now))
+    }
+
+    /// 全局冷却剩余时长（未生效时返回 None）
+    pub fn global_cooldown_remaining(&self) -> Option<Duration> {
+        self.global_cooldown().map(|(_, remaining)| remaining)
+    }
+
+    /// 立即解除全局冷却，返回解除前是否生效
+    pub fn clear_global_cooldown(&self) -> bool {
+        let now = Instant::now();
+        self.global
+            .lock()
+            .take()
+            .is_some_and(|(_, until)| until > now)
+    }
+
+    /// 立即解除凭据的冷却（含模型级冷却）并重置递增次数，返回解除前是否处于冷却中
+    ///
+    /// 已花费的冷却时长（截至当前时间）仍计入统计窗口。
+    pub fn clear_cooldown(&self, credential_id: u64) -> bool {
+        let now = Instant::now();
+        let was_active = {
+            let mut entries = self.entries.lock();
+            match entries.get_mut(&credential_id) {
+                Some(entry) => {
+                    let was_active = entry.expires_at > now;
+                    entry.expires_at = entry.expires_at.min(now);
+                    entry.trigger_count = 0;
+                    entry.exit_notified = true;
+                    for period in entry.periods.iter_mut() {
+                        period.1 = period.1.min(now);
+                    }
+                    was_active
+                }
+                None => false,
+            }
+        };
+        self.model_entries
+            .lock()
+            .retain(|(id, _), _| *id != credential_id);
+        if was_active {
+            self.emit(CooldownEvent::Cleared { credential_id });
+        }
+        was_active
+    }
+
+    /// 注册冷却事件回调（进入冷却、到期、手动解除时触发）
+    ///
+    /// 回调在释放内部锁之后同步调用，可在回调内再次调用管理器。
+    pub fn on_cooldown_change(&self, callback: Box<dyn Fn(CooldownEvent) + Send + Sync>) {
+        self.listeners.lock().push(Arc::from(callback));
+    }
+
+    /// 清扫已到期但尚未通知的冷却，对每个凭据触发一次 [`CooldownEvent::Cleared`]
+    pub fn sweep_expired(&self) -> Vec<u64> {
+        self.sweep_expired_at(Instant::now())
+    }
+
+    /// 以指定时间为"当前时间"清扫到期冷却，返回本次通知的凭据 ID（按 ID 排序）
+    pub fn sweep_expired_at(&self, now: Instant) -> Vec<u64> {
+        let mut expired: Vec<u64> = self
+            .entries
+            .lock()
+            .iter_mut()
+            .filter(|(_, e)| !e.exit_notified && e.expires_at <= now)
+            .map(|(&id, e)| {
+                e.exit_notified = true;
+                id
+            })
+            .collect();
+        expired.sort_unstable();
+        for &credential_id in &expired {
+            self.emit(CooldownEvent::Cleared { credential_id });
+        }
+        expired
+    }
+
+    /// 调用所有回调（调用方不得持有任何内部锁）
+    fn emit(&self, event: CooldownEvent) {
+        if let CooldownEvent::Entered { reason, .. } = &event {
+            metrics::registry().cooldown_entries.inc(reason.as_str());
+        }
+        let listeners = self.listeners.lock().clone();
+        for listener in listeners {
+            listener(event.clone());
+        }
+    }
+
+    /// 凭据当前是否可用（未处于冷却中）
+    pub fn is_available(&self, credential_id: u64) -> bool {
+        self.is_available_at(credential_id, Instant::now())
+    }
+
+    /// 凭据在指定时间是否可用（全局冷却生效时所有凭据均不可用）
+    pub fn is_available_at(&self, credential_id: u64, now: Instant) -> bool {
+        self.global_cooldown_at(now).is_none()
+            && self
+                .entries
+                .lock()
+                .get(&credential_id)
+                .is_none_or(|e| e.expires_at <= now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_cooldown_makes_credential_unavailable() {
+        let manager = CooldownManager::new();
+        assert!(manager.is_available(1));
+
+        manager.set_cooldown_at(1, CooldownReason::ServerError, Instant::now());
+        assert!(!manager.is_available(1));
+        assert!(manager.is_available(2));
+    }
+
+    #[test]
+    fn test_cooldown_duration_increases_and_is_capped() {
+        let manager = CooldownManager::new();
+        let first = manager.set_cooldown_at(1, CooldownReason::ServerError, Instant::now());
+        let second = manager.set_cooldown_at(1, CooldownReason::ServerError, Instant::now());
+        assert_eq!(first, Duration::from_secs(120));
+        assert_eq!(second, Duration::from_secs(180));
+
+        let capped = manager.calculate_cooldown_duration(CooldownReason::ServerError, 20);
+        assert_eq!(capped, Duration::from_secs(DEFAULT_MAX_SHORT_COOLDOWN_SECS));
+    }
+
+    #[test]
+    fn test_backoff_strategies_growth_curves_are_capped() {
+        let curve = |strategy, base| {
+            let manager = CooldownManager::new().with_backoff(strategy, base);
+            (1..=7)
+                .map(|n| {
+                    manager
+                        .calculate_cooldown_duration(CooldownReason::EmptyResponse, n)
+                        .as_secs()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            curve(BackoffStrategy::Linear, 3.0),
+            vec![30, 90, 150, 210, 270, 300, 300]
+        );
+        assert_eq!(
+            curve(BackoffStrategy::Exponential, 2.0),
+            vec![30, 60, 120, 240, 300, 300, 300]
+        );
+        assert_eq!(
+            curve(BackoffStrategy::Fibonacci, 2.0),
+            vec![30, 30, 60, 90, 150, 240, 300]
+        );
+        // 倍率小于 1 时不递增
+        assert_eq!(curve(BackoffStrategy::Exponential, 0.5), vec![30; 7]);
+        // 触发次数很大时不溢出
+        let manager = CooldownManager::new().with_backoff(BackoffStrategy::Fibonacci, 1.0);
+        assert_eq!(
+            manager.calculate_cooldown_duration(CooldownReason::EmptyResponse, u32::MAX),
+            Duration::from_secs(DEFAULT_MAX_SHORT_COOLDOWN_SECS)
+        );
+    }
+
+    #[test]
+    fn test_jittered_cooldown_stays_within_band_and_cap() {
+        let manager = CooldownManager::new().with_jitter(0.15);
+        let mut seen = std::collections::HashSet::new();
+        for id in 0..200 {
+            let duration = manager.set_cooldown_at(id, CooldownReason::ServerError, Instant::now());
+            assert!(duration >= Duration::from_secs_f64(120.0 * 0.85), "{:?}", duration);
+            assert!(duration <= Duration::from_secs_f64(120.0 * 1.15), "{:?}", duration);
+            seen.insert(duration);
+        }
+        // 同时进入冷却的凭据不会在同一时刻恢复
+        assert!(seen.len() > 1);
+
+        // 已达上限的时长抖动后不超过上限
+        let max = Duration::from_secs(DEFAULT_MAX_SHORT_COOLDOWN_SECS);
+        for _ in 0..200 {
+            let capped = manager.calculate_cooldown_duration(CooldownReason::ServerError, 20);
+            let jittered = manager.apply_jitter(capped);
+            assert!(jittered <= max, "{:?}", jittered);
+            assert!(jittered >= max.mul_f64(0.85), "{:?}", jittered);
+        }
+
+        // 显式到期时间不受抖动影响
+        let until = Instant::now() + Duration::from_secs(3600);
+        manager.set_cooldown_until(999, CooldownReason::QuotaExhausted, until);
+        let now = Instant::now();
+        let (_, _, remaining) = manager
+            .get_all_cooldowns_at(now)
+            .into_iter()
+            .find(|&(id, _, _)| id == 999)
+            .unwrap();
+        assert_eq!(remaining, until - now);
+    }
+
+    #[test]
+    fn test_clear_cooldown_restores_availability_and_resets_escalation() {
+        let manager = CooldownManager::new();
+        let now = Instant::now();
+        manager.set_cooldown_at(1, CooldownReason::ServerError, now);
+        manager.set_cooldown_at(1, CooldownReason::ServerError, now);
+        manager.set_model_cooldown_at(1, "claude-opus-4.5", now);
+
+        let active = manager.get_all_cooldowns();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].credential_id, 1);
+        assert_eq!(active[0].reason, "ServerError");
+        assert_eq!(active[0].description, "上游服务端错误");
+        assert_eq!(active[0].trigger_count, 2);
+
+        assert!(manager.clear_cooldown(1));
+        assert!(manager.is_available(1));
+        assert!(manager.is_model_available_at(1, "claude-opus-4.5", Instant::now()));
+        assert!(manager.get_all_cooldowns().is_empty());
+        assert!(!manager.clear_cooldown(1));
+
+        // 递增次数已重置：再次触发时为基础时长
+        let next = manager.set_cooldown_at(1, CooldownReason::ServerError, Instant::now());
+        assert_eq!(next, Duration::from_secs(120));
+    }
+
+    #[test]
+    fn test_global_cooldown_blocks_all_credentials_independently() {
+        let manager = CooldownManager::new();
+        manager.set_cooldown_at(2, CooldownReason::ServerError, Instant::now());
+        manager.set_global_cooldown(CooldownReason::QuotaExhausted, Duration::from_secs(30));
+
+        assert!(!manager.is_available(1));
+        assert!(!manager.is_available(3));
+        let remaining = manager.global_cooldown_remaining().unwrap();
+        assert!(remaining > Duration::from_secs(29) && remaining <= Duration::from_secs(30));
+
+        let active = manager.get_all_cooldowns();
+        assert_eq!(active.len(), 2);
+        assert_eq!(active[0].credential_id, GLOBAL_COOLDOWN_ID);
+        assert_eq!(active[0].reason, "QuotaExhausted");
+        assert_eq!(active[1].credential_id, 2);
+
+        // 解除凭据级冷却不影响全局冷却
+        assert!(manager.clear_cooldown(2));
+        assert!(!manager.is_available(2));
+
+        assert!(manager.clear_global_cooldown());
+        assert!(manager.is_available(1));
+        assert!(manager.is_available(2));
+        assert!(manager.global_cooldown_remaining().is_none());
+        assert!(!manager.clear_global_cooldown());
+
+        // 到期后自动失效
+        let now = Instant::now();
+        manager.set_global_cooldown_at(CooldownReason::ServerError, Duration::from_secs(5), now);
+        assert!(!manager.is_available_at(1, now + Duration::from_secs(4)));
+        assert!(manager.is_available_at(1, now + Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_cooldown_events_fire_on_enter_sweep_and_clear() {
+        let manager = Arc::new(CooldownManager::new());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        {
+            let events = events.clone();
+            let weak = Arc::downgrade(&manager);
+            manager.on_cooldown_change(Box::new(move |event| {
+                // 回调内再次访问管理器不应死锁
+                let manager = weak.upgrade().unwrap();
+                let _ = manager.get_all_cooldowns();
+                events.lock().push(event);
+            }));
+        }
+
+        let now = Instant::now();
+        let duration = manager.set_cooldown_at(1, CooldownReason::EmptyResponse, now);
+        manager.set_cooldown_at(2, CooldownReason::ServerError, now);
+        assert_eq!(manager.sweep_expired_at(now + duration), vec![1]);
+        // 已通知过的到期不会重复通知
+        assert!(manager.sweep_expired_at(now + duration).is_empty());
+        assert!(manager.clear_cooldown(2));
+        assert!(manager.sweep_expired_at(now + Duration::from_secs(3600)).is_empty());
+
+        assert_eq!(
+            *events.lock(),
+            vec![
+                CooldownEvent::Entered {
+                    credential_id: 1,
+                    reason: CooldownReason::EmptyResponse,
+                    duration,
+                },
+                CooldownEvent::Entered {
+                    credential_id: 2,
+                    reason: CooldownReason::ServerError,
+                    duration: Duration::from_secs(120),
+                },
+                CooldownEvent::Cleared { credential_id: 1 },
+                CooldownEvent::Cleared { credential_id: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trigger_count_decays_after_long_idle() {
+        let manager = CooldownManager::new().with_decay_interval(Duration::from_secs(60 * 60));
+        let t0 = Instant::now();
+        let mut now = t0;
+        for _ in 0..5 {
+            let duration = manager.set_cooldown_at(1, CooldownReason::ServerError, now);
+            now += duration;
+        }
+        assert_eq!(
+            manager.set_cooldown_at(1, CooldownReason::ServerError, now),
+            Duration::from_secs(DEFAULT_MAX_SHORT_COOLDOWN_SECS)
+        );
+        now += Duration::from_secs(DEFAULT_MAX_SHORT_COOLDOWN_SECS);
+
+        // 冷却结束后闲置一天：6 次累计全部衰减，回到基础时长
+        let later = now + Duration::from_secs(24 * 60 * 60);
+        assert_eq!(
+            manager.set_cooldown_at(1, CooldownReason::ServerError, later),
+            Duration::from_secs(120)
+        );
+
+        // 仅闲置一个周期只衰减一次
+        let manager = CooldownManager::new();
+        let first = manager.set_cooldown_at(2, CooldownReason::ServerError, t0);
+        let second = manager.set_cooldown_at(2, CooldownReason::ServerError, t0 + first);
+        let after_idle = t0 + first + second + Duration::from_secs(60 * 60 + 1);
+        assert_eq!(
+            manager.set_cooldown_at(2, CooldownReason::ServerError, after_idle),
+            Duration::from_secs(180)
+        );
+    }
+
+    #[test]
+    fn test_auto_recoverable_reasons() {
+        assert!(CooldownReason::EmptyResponse.is_auto_recoverable());
+        assert!(CooldownReason::ServerError.is_auto_recoverable());
+        assert!(CooldownReason::RateLimited.is_auto_recoverable());
+        assert!(!CooldownReason::QuotaExhausted.is_auto_recoverable());
+
+        let manager = CooldownManager::new();
+        let now = Instant::now();
+        let duration = manager.set_cooldown_at(1, CooldownReason::EmptyResponse, now);
+        assert_eq!(
+            manager.cooldown_reason_at(1, now),
+            Some(CooldownReason::EmptyResponse)
+        );
+        assert_eq!(manager.cooldown_reason_at(1, now + duration), None);
+        assert_eq!(manager.cooldown_reason_at(2, now), None);
+    }
+
+    #[test]
+    fn test_reason_duration_override_scales_and_is_capped() {
+        let manager = CooldownManager::new();
+        manager.set_reason_duration(CooldownReason::ServerError, Duration::from_secs(20));
+        assert_eq!(
+            manager.calculate_cooldown_duration(CooldownReason::ServerError, 1),
+            Duration::from_secs(20)
+        );
+        // 递增倍率作用于覆盖值
+        assert_eq!(
+            manager.calculate_cooldown_duration(CooldownReason::ServerError, 3),
+            Duration::from_secs(45)
+        );
+        // 其他原因不受影响
+        assert_eq!(
+            manager.calculate_cooldown_duration(CooldownReason::EmptyResponse, 1),
+            Duration::from_secs(30)
+        );
+
+        // 覆盖值超过上限时仍封顶
+        manager.set_reason_duration(CooldownReason::ServerError, Duration::from_secs(3600));
+        assert_eq!(
+            manager.calculate_cooldown_duration(CooldownReason::ServerError, 1),
+            Duration::from_secs(DEFAULT_MAX_SHORT_COOLDOWN_SECS)
+        );
+
+        assert!(manager.clear_reason_duration(CooldownReason::ServerError));
+        assert!(!manager.clear_reason_duration(CooldownReason::ServerError));
+        assert_eq!(
+            manager.calculate_cooldown_duration(CooldownReason::ServerError, 1),
+            Duration::from_secs(120)
+        );
+        assert_eq!(
+            CooldownReason::parse("servererror"),
+            Some(CooldownReason::ServerError)
+        );
+        assert_eq!(CooldownReason::parse("nope"), None);
+    }
+
+    #[test]
+    fn test_cooldown_time_in_window_drops_expired_periods() {
+        let manager = CooldownManager::new().with_budget_window(Duration::from_secs(600));
+        let t0 = Instant::now();
+        manager.set_cooldown_at(1, CooldownReason::ServerError, t0);
+        // 尚未结束的冷却只计入已经过的部分
+        assert_eq!(manager.cooldown_time_in_window(1, t0), Duration::ZERO);
+        assert_eq!(manager.cooldown_time_in_window(1, t0 + Duration::from_secs(60)), Duration::from_secs(60));
+        assert_eq!(manager.cooldown_time_in_window(1, t0 + Duration::from_secs(300)), Duration::from_secs(120));
+
+        // 窗口起点越过第一段冷却的中点，只计入剩余部分
+        let t1 = t0 + Duration::from_secs(660);
+        assert_eq!(manager.cooldown_time_in_window(1, t1), Duration::from_secs(60));
+
+        // 第一段完全滑出窗口后不再计入
+        let t2 = t0 + Duration::from_secs(800);
+        manager.set_cooldown_at(1, CooldownReason::ServerError, t2);
+        assert_eq!(manager.cooldown_time_in_window(1, t2 + Duration::from_secs(180)), Duration::from_secs(180));
+        assert_eq!(manager.cooldown_time_in_window(2, t2), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_overlapping_cooldowns_are_counted_once() {
+        let manager = CooldownManager::new();
+        let t0 = Instant::now();
+        // 120 秒冷却进行到一半时再次触发 180 秒冷却：实际冷却区间为 [t0, t0 + 240]
+        manager.set_cooldown_at(1, CooldownReason::ServerError, t0);
+        manager.set_cooldown_at(1, CooldownReason::ServerError, t0 + Duration::from_secs(60));
+        assert_eq!(manager.cooldown_time_in_window(1, t0 + Duration::from_secs(600)), Duration::from_secs(240));
+    }
+
+    #[test]
+    fn test_model_cooldown_is_scoped_to_credential_and_model() {
+        let manager = CooldownManager::new();
+        let now = Instant::now();
+        let duration = manager.set_model_cooldown_at(1, "claude-opus-4.5", now);
+
+        assert!(!manager.is_model_available_at(1, "claude-opus-4.5", now));
+        assert!(manager.is_model_available_at(1, "claude-sonnet-4.5", now));
+        assert!(manager.is_model_available_at(2, "claude-opus-4.5", now));
+        assert!(manager.is_model_available_at(1, "claude-opus-4.5", now + duration));
+        // 模型级冷却不影响凭据整体可用性，也不计入累计冷却时长
+        assert!(manager.is_available_at(1, now));
+        assert_eq!(manager.cooldown_time_in_window(1, now), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_empty_response_cooldown_is_short_and_tracked_separately() {
+        assert_eq!(
+            CooldownReason::EmptyResponse.default_duration(),
+            Duration::from_secs(30)
+        );
+        assert!(
+            CooldownReason::EmptyResponse.default_duration()
+                < CooldownReason::ServerError.default_duration()
+        );
+        assert_ne!(
+            CooldownReason::EmptyResponse.description(),
+            CooldownReason::ServerError.description()
+        );
+
+        let manager = CooldownManager::new();
+        let t0 = Instant::now();
+        manager.set_cooldown_at(1, CooldownReason::EmptyResponse, t0);
+        manager.set_cooldown_at(2, CooldownReason::ServerError, t0);
+
+        let active = manager.get_all_cooldowns_at(t0 + Duration::from_secs(10));
+        assert_eq!(
+            active,
+            vec![
+                (1, CooldownReason::EmptyResponse, Duration::from_secs(20)),
+                (2, CooldownReason::ServerError, Duration::from_secs(110)),
+            ]
+        );
+
+        // 空响应冷却到期后自动恢复，服务端错误冷却仍在生效
+        let later = t0 + Duration::from_secs(31);
+        assert!(manager.is_available_at(1, later));
+        assert_eq!(
+            manager.get_all_cooldowns_at(later),
+            vec![(2, CooldownReason::ServerError, Duration::from_secs(89))]
+        );
+    }
+}
@@ -5,6 +5,9 @@
 //! - MCP: `https://q.{api_region}.amazonaws.com/mcp`
 //!
 //! 请求头使用 aws-sdk-js User-Agent 标识。请求体会在根对象上注入 `profileArn`。
+//!
+//! 可通过 `endpoints.ide.baseUrl` 覆盖基础 URL（如本地 mock 或代理网关），
+//! 此时 API / MCP 请求分别发往 `{baseUrl}/generateAssistantResponse` 与 `{baseUrl}/mcp`。
 
 use reqwest::RequestBuilder;
 use uuid::Uuid;
@@ -15,21 +18,57 @@ use super::{KiroEndpoint, RequestContext};
 pub const IDE_ENDPOINT_NAME: &str = "ide";
 
 /// Kiro IDE 端点
-pub struct IdeEndpoint;
+pub struct IdeEndpoint {
+    /// 基础 URL 覆盖（不含末尾 `/`），未设置时按 API Region 使用生产端点
+    base_url: Option<String>,
+}
 
 impl IdeEndpoint {
     pub fn new() -> Self {
-        Self
+        Self { base_url: None }
+    }
+
+    /// 从端点配置（`endpoints.ide`）构建，支持 `baseUrl` 字段
+    pub fn from_config(config: Option<&serde_json::Value>) -> Self {
+        let base_url = config
+            .and_then(|c| c.get("baseUrl"))
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.trim().is_empty());
+        match base_url {
+            Some(url) => Self::new().with_base_url(url),
+            None => Self::new(),
+        }
+    }
+
+    /// 覆盖基础 URL（如 `http://127.0.0.1:9000`）
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Some(base_url.trim().trim_end_matches('/').to_string());
+        self
     }
 
     fn api_region<'a>(&self, ctx: &'a RequestContext<'_>) -> &'a str {
         ctx.credentials.effective_api_region(ctx.config)
     }
 
-    fn host(&self, ctx: &RequestContext<'_>) -> String {
+    fn base_url(&self, ctx: &RequestContext<'_>) -> String {
+        match &self.base_url {
+            Some(url) => url.clone(),
+            None => format!("https://{}", self.production_host(ctx)),
+        }
+    }
+
+    fn production_host(&self, ctx: &RequestContext<'_>) -> String {
         format!("q.{}.amazonaws.com", self.api_region(ctx))
     }
 
+    /// 显式设置的 host 头；覆盖基础 URL 时由 HTTP 客户端按 URL 自动填写
+    fn with_host(&self, req: RequestBuilder, ctx: &RequestContext<'_>) -> RequestBuilder {
+        match self.base_url {
+            Some(_) => req,
+            None => req.header("host", self.production_host(ctx)),
+        }
+    }
+
     fn x_amz_user_agent(&self, ctx: &RequestContext<'_>) -> String {
         format!(
             "aws-sdk-js/1.0.34 KiroIDE-{}-{}",
@@ -40,8 +79,8 @@ impl IdeEndpoint {
     fn user_agent(&self, ctx: &RequestContext<'_>) -> String {
         format!(
             "aws-sdk-js/1.0.34 ua/2.1 os/{} lang/js md/nodejs#{} api/codewhispererstreaming#1.0.34 m/E KiroIDE-{}-{}",
-            ctx.config.system_version,
-            ctx.config.node_version,
+            ctx.system_version(),
+            ctx.node_version(),
             ctx.config.kiro_version,
             ctx.machine_id
         )
@@ -60,23 +99,20 @@ impl KiroEndpoint for IdeEndpoint {
     }
 
     fn api_url(&self, ctx: &RequestContext<'_>) -> String {
-        format!(
-            "https://q.{}.amazonaws.com/generateAssistantResponse",
-            self.api_region(ctx)
-        )
+        format!("{}/generateAssistantResponse", self.base_url(ctx))
     }
 
     fn mcp_url(&self, ctx: &RequestContext<'_>) -> String {
-        format!("https://q.{}.amazonaws.com/mcp", self.api_region(ctx))
+        format!("{}/mcp", self.base_url(ctx))
     }
 
     fn decorate_api(&self, req: RequestBuilder, ctx: &RequestContext<'_>) -> RequestBuilder {
-        let mut req = req
+        let mut req = self
+            .with_host(req, ctx)
             .header("x-amzn-codewhisperer-optout", "true")
             .header("x-amzn-kiro-agent-mode", "vibe")
             .header("x-amz-user-agent", self.x_amz_user_agent(ctx))
             .header("user-agent", self.user_agent(ctx))
-            .header("host", self.host(ctx))
             .header("amz-sdk-invocation-id", Uuid::new_v4().to_string())
             .header("amz-sdk-request", "attempt=1; max=3")
             .header("Authorization", format!("Bearer {}", ctx.token));
@@ -88,10 +124,10 @@ impl KiroEndpoint for IdeEndpoint {
     }
 
     fn decorate_mcp(&self, req: RequestBuilder, ctx: &RequestContext<'_>) -> RequestBuilder {
-        let mut req = req
+        let mut req = self
+            .with_host(req, ctx)
             .header("x-amz-user-agent", self.x_amz_user_agent(ctx))
             .header("user-agent", self.user_agent(ctx))
-            .header("host", self.host(ctx))
             .header("amz-sdk-invocation-id", Uuid::new_v4().to_string())
             .header("amz-sdk-request", "attempt=1; max=3")
             .header("Authorization", format!("Bearer {}", ctx.token));
@@ -125,8 +161,30 @@ fn inject_profile_arn(request_body: &str, profile_arn: &Option<String>) -> Strin
 
 #[cfg(test)]
 mod tests {
-    use super::inject_profile_arn;
+    use super::{IdeEndpoint, inject_profile_arn};
+    use crate::kiro::parser::frame::encode_event_frame;
+    use crate::kiro::provider::CallOptions;
+    use crate::kiro::test_support::{provider_with_endpoint, spawn_mock_upstream};
+    use crate::model::config::Config;
     use serde_json::Value;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_base_url_override_routes_to_local_mock() {
+        let frame = encode_event_frame("assistantResponseEvent", r#"{"content":"hi"}"#);
+        let (url, hits) = spawn_mock_upstream(vec![frame.clone()]).await;
+        let base_url = url.trim_end_matches("/generateAssistantResponse");
+        let config = serde_json::json!({ "baseUrl": format!("{}/", base_url) });
+        let endpoint = IdeEndpoint::from_config(Some(&config));
+        let provider = provider_with_endpoint(Arc::new(endpoint), Config::default(), 1);
+
+        let response = provider
+            .call_api("{}", &CallOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.bytes().await.unwrap().as_ref(), frame.as_slice());
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
 
     #[test]
     fn test_inject_profile_arn_with_some() {
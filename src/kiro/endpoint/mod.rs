@@ -8,6 +8,7 @@
 
 use reqwest::RequestBuilder;
 
+use crate::kiro::fingerprint::Fingerprint;
 use crate::kiro::model::credentials::KiroCredentials;
 use crate::model::config::Config;
 
@@ -54,6 +55,11 @@ pub trait KiroEndpoint: Send + Sync {
     fn is_bearer_token_invalid(&self, body: &str) -> bool {
         default_is_bearer_token_invalid(body)
     }
+
+    /// 判断响应体是否表示"所请求的模型暂不可用"（触发模型级冷却）
+    fn is_model_unavailable(&self, body: &str) -> bool {
+        default_is_model_unavailable(body)
+    }
 }
 
 /// 装饰请求时可用的上下文
@@ -68,6 +74,24 @@ pub struct RequestContext<'a> {
     pub machine_id: &'a str,
     /// 全局配置
     pub config: &'a Config,
+    /// 请求级指纹覆盖（存在时取代全局配置中的系统/Node 版本）
+    pub fingerprint: Option<&'a Fingerprint>,
+}
+
+impl RequestContext<'_> {
+    /// 有效的系统版本（如 `darwin#24.6.0`）
+    pub fn system_version(&self) -> String {
+        self.fingerprint
+            .map(|fp| fp.system_version())
+            .unwrap_or_else(|| self.config.system_version.clone())
+    }
+
+    /// 有效的 Node.js 版本
+    pub fn node_version(&self) -> &str {
+        self.fingerprint
+            .map(|fp| fp.node_version.as_str())
+            .unwrap_or(&self.config.node_version)
+    }
 }
 
 /// 默认的 MONTHLY_REQUEST_COUNT 判断逻辑
@@ -96,6 +120,11 @@ pub fn default_is_monthly_request_limit(body: &str) -> bool {
         .is_some_and(|v| v == "MONTHLY_REQUEST_COUNT")
 }
 
+/// 默认的模型暂不可用判断逻辑
+pub fn default_is_model_unavailable(body: &str) -> bool {
+    body.contains("INSUFFICIENT_MODEL_CAPACITY") || body.contains("MODEL_TEMPORARILY_UNAVAILABLE")
+}
+
 /// 默认的 bearer token 失效判断逻辑
 pub fn default_is_bearer_token_invalid(body: &str) -> bool {
     body.contains("The bearer token included in the request is invalid")
@@ -130,4 +159,11 @@ mod tests {
         ));
         assert!(!default_is_bearer_token_invalid("unrelated error"));
     }
+
+    #[test]
+    fn test_default_model_unavailable() {
+        let body = r#"{"message":"I am experiencing high traffic","reason":"INSUFFICIENT_MODEL_CAPACITY"}"#;
+        assert!(default_is_model_unavailable(body));
+        assert!(!default_is_model_unavailable(r#"{"reason":"MONTHLY_REQUEST_COUNT"}"#));
+    }
 }
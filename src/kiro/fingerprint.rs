@@ -0,0 +1,274 @@
+//! 客户端指纹
+//!
+//! 描述一次上游请求所呈现的客户端环境（machineId、操作系统、Node 版本），
+//! 用于构建 User-Agent 等请求头。同一种子始终生成同一指纹。
+//!
+//! 指纹可导出为 JSON 并固定到凭据上（见 [`Fingerprint::from_json`]），
+//! 确保已验证可用的指纹不会随种子迁移而变化。
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// 操作系统类型池
+const OS_TYPES: &[&str] = &["darwin", "win32", "linux"];
+
+/// darwin 内核版本池
+const DARWIN_VERSIONS: &[&str] = &["23.6.0", "24.5.0", "24.6.0"];
+
+/// win32 系统版本池
+const WIN32_VERSIONS: &[&str] = &["10.0.19045", "10.0.22631", "10.0.26100"];
+
+/// linux 内核版本池
+const LINUX_VERSIONS: &[&str] = &["6.8.0", "6.11.0", "6.14.0"];
+
+/// Node.js 版本池
+const NODE_VERSIONS: &[&str] = &["20.18.1", "22.12.0", "22.22.0"];
+
+/// 操作系统类型对应的系统版本池（未知类型返回 None）
+fn os_versions(os_type: &str) -> Option<&'static [&'static str]> {
+    match os_type {
+        "darwin" => Some(DARWIN_VERSIONS),
+        "win32" => Some(WIN32_VERSIONS),
+        "linux" => Some(LINUX_VERSIONS),
+        _ => None,
+    }
+}
+
+/// 客户端指纹
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fingerprint {
+    /// 64 字符十六进制 machineId
+    pub machine_id: String,
+    /// 操作系统类型（darwin / win32 / linux）
+    pub os_type: String,
+    /// 操作系统版本
+    pub os_version: String,
+    /// Node.js 版本
+    pub node_version: String,
+}
+
+/// 指纹校验错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FingerprintError {
+    /// JSON 无法解析为指纹
+    InvalidJson(String),
+    /// machineId 不是 64 字符十六进制
+    InvalidMachineId,
+    /// 未知的操作系统类型
+    UnknownOsType(String),
+    /// 系统版本与操作系统类型不匹配
+    OsVersionMismatch { os_type: String, os_version: String },
+}
+
+impl FingerprintError {
+    /// 出错的字段名（camelCase，与 JSON 字段一致）
+    pub fn field(&self) -> &'static str {
+        match self {
+            Self::InvalidJson(_) => "fingerprint",
+            Self::InvalidMachineId => "machineId",
+            Self::UnknownOsType(_) => "osType",
+            Self::OsVersionMismatch { .. } => "osVersion",
+        }
+    }
+}
+
+impl fmt::Display for FingerprintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidJson(e) => write!(f, "指纹 JSON 无效: {}", e),
+            Self::InvalidMachineId => write!(f, "machineId 必须是 64 字符十六进制"),
+            Self::UnknownOsType(os) => {
+                write!(f, "未知的操作系统类型 {}，可选值: {}", os, OS_TYPES.join(", "))
+            }
+            Self::OsVersionMismatch {
+                os_type,
+                os_version,
+            } => write!(
+                f,
+                "osVersion {} 与操作系统类型 {} 不匹配，可选值: {}",
+                os_version,
+                os_type,
+                os_versions(os_type).unwrap_or_default().join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FingerprintError {}
+
+impl Fingerprint {
+    /// 根据种子确定性地生成指纹
+    pub fn generate_from_seed(seed: &str) -> Self {
+        let digest = Sha256::digest(format!("KiroFingerprint/{}", seed).as_bytes());
+        let pick = |pool: &[&'static str], byte: u8| pool[byte as usize % pool.len()];
+
+        let os_type = pick(OS_TYPES, digest[0]);
+        let versions = os_versions(os_type).unwrap_or(LINUX_VERSIONS);
+
+        Self {
+            machine_id: hex::encode(Sha256::digest(
+                format!("KiroFingerprintMachine/{}", seed).as_bytes(),
+            )),
+            os_type: os_type.to_string(),
+            os_version: pick(versions, digest[1]).to_string(),
+            node_version: pick(NODE_VERSIONS, digest[2]).to_string(),
+        }
+    }
+
+    /// 根据种子生成指纹，但使用指定的 machineId（而非由种子派生）
+    ///
+    /// 用于账号最初在真实设备上注册、需保持原 machineId 的凭据；其余字段与
+    /// [`Fingerprint::generate_from_seed`] 相同。machineId 必须是 64 字符十六进制。
+    pub fn generate_from_seed_with_machine_id(
+        seed: &str,
+        machine_id: &str,
+    ) -> Result<Self, FingerprintError> {
+        if machine_id.len() != 64 || !machine_id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(FingerprintError::InvalidMachineId);
+        }
+        Ok(Self {
+            machine_id: machine_id.to_string(),
+            ..Self::generate_from_seed(seed)
+        })
+    }
+
+    /// 从导出的 JSON 解析指纹并校验
+    pub fn from_json(json: &str) -> Result<Self, FingerprintError> {
+        let fingerprint: Self =
+            serde_json::from_str(json).map_err(|e| FingerprintError::InvalidJson(e.to_string()))?;
+        fingerprint.validate()?;
+        Ok(fingerprint)
+    }
+
+    /// 校验字段取值是否合理（固定指纹前调用）
+    pub fn validate(&self) -> Result<(), FingerprintError> {
+        if self.machine_id.len() != 64 || !self.machine_id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(FingerprintError::InvalidMachineId);
+        }
+
+        // 系统版本必须来自对应操作系统的版本池（如 win32 不能搭配 darwin 内核版本）
+        let versions = os_versions(&self.os_type)
+            .ok_or_else(|| FingerprintError::UnknownOsType(self.os_type.clone()))?;
+        if !versions.contains(&self.os_version.as_str()) {
+            return Err(FingerprintError::OsVersionMismatch {
+                os_type: self.os_type.clone(),
+                os_version: self.os_version.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// User-Agent 中使用的系统版本（如 `darwin#24.6.0`）
+    pub fn system_version(&self) -> String {
+        format!("{}#{}", self.os_type, self.os_version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_from_seed_is_deterministic() {
+        let a = Fingerprint::generate_from_seed("seed-a");
+        let b = Fingerprint::generate_from_seed("seed-a");
+        assert_eq!(a, b);
+        assert_eq!(a.machine_id.len(), 64);
+        assert!(a.machine_id.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn test_generate_with_machine_id_keeps_other_fields() {
+        let pinned = "ab".repeat(32);
+        let fp = Fingerprint::generate_from_seed_with_machine_id("seed-a", &pinned).unwrap();
+        assert_eq!(fp.machine_id, pinned);
+        assert_eq!(
+            Fingerprint {
+                machine_id: pinned.clone(),
+                ..Fingerprint::generate_from_seed("seed-a")
+            },
+            fp
+        );
+
+        for invalid in ["abc", &"g".repeat(64), &"a".repeat(65)] {
+            assert_eq!(
+                Fingerprint::generate_from_seed_with_machine_id("seed-a", invalid),
+                Err(FingerprintError::InvalidMachineId)
+            );
+        }
+    }
+
+    #[test]
+    fn test_different_seeds_produce_different_machine_ids() {
+        let a = Fingerprint::generate_from_seed("seed-a");
+        let b = Fingerprint::generate_from_seed("seed-b");
+        assert_ne!(a.machine_id, b.machine_id);
+    }
+
+    #[test]
+    fn test_os_version_matches_os_type() {
+        for i in 0..50 {
+            let fp = Fingerprint::generate_from_seed(&format!("seed-{}", i));
+            let pool = match fp.os_type.as_str() {
+                "darwin" => DARWIN_VERSIONS,
+                "win32" => WIN32_VERSIONS,
+                _ => LINUX_VERSIONS,
+            };
+            assert!(pool.contains(&fp.os_version.as_str()));
+            assert_eq!(fp.system_version(), format!("{}#{}", fp.os_type, fp.os_version));
+        }
+    }
+
+    #[test]
+    fn test_generated_fingerprint_round_trips_through_json() {
+        for i in 0..50 {
+            let fp = Fingerprint::generate_from_seed(&format!("seed-{}", i));
+            assert_eq!(fp.validate(), Ok(()));
+            let json = serde_json::to_string(&fp).unwrap();
+            assert_eq!(Fingerprint::from_json(&json).unwrap(), fp);
+        }
+    }
+
+    #[test]
+    fn test_validate_rejects_inconsistent_fields() {
+        let base = Fingerprint::generate_from_seed("seed-a");
+
+        let mut fp = base.clone();
+        fp.machine_id = "abc".to_string();
+        assert_eq!(fp.validate(), Err(FingerprintError::InvalidMachineId));
+
+        let mut fp = base.clone();
+        fp.os_type = "win32".to_string();
+        fp.os_version = "24.6.0".to_string();
+        assert_eq!(fp.validate().unwrap_err().field(), "osVersion");
+
+        assert_eq!(
+            Fingerprint::from_json(r#"{"machineId":"x"}"#).unwrap_err().field(),
+            "fingerprint"
+        );
+    }
+
+    #[test]
+    fn test_validate_os_version_against_pool() {
+        let base = Fingerprint::generate_from_seed("seed-a");
+        let cases = [
+            ("darwin", "24.6.0", "10.0.22631"),
+            ("win32", "10.0.22631", "24.6.0"),
+            ("linux", "6.8.0", "10.0.19045"),
+        ];
+        for (os_type, valid, invalid) in cases {
+            let mut fp = base.clone();
+            fp.os_type = os_type.to_string();
+            fp.os_version = valid.to_string();
+            assert_eq!(fp.validate(), Ok(()), "{} {}", os_type, valid);
+
+            fp.os_version = invalid.to_string();
+            let err = fp.validate().unwrap_err();
+            assert_eq!(err.field(), "osVersion");
+            assert!(err.to_string().contains(valid), "{}", err);
+        }
+    }
+}
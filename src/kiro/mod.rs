@@ -1,8 +1,16 @@
 //! Kiro API 客户端模块
 
+pub mod cooldown;
 pub mod endpoint;
+pub mod fingerprint;
 pub mod machine_id;
 pub mod model;
 pub mod parser;
 pub mod provider;
+pub mod quota;
+pub mod slow_probe;
 pub mod token_manager;
+pub mod warmup;
+
+#[cfg(test)]
+pub(crate) mod test_support;
@@ -8,6 +8,7 @@ use std::fs;
 use std::path::Path;
 
 use crate::http_client::ProxyConfig;
+use crate::kiro::fingerprint::Fingerprint;
 use crate::model::config::Config;
 
 /// Kiro OAuth 凭证
@@ -109,6 +110,35 @@ pub struct KiroCredentials {
     /// 端点名必须在启动时注册的端点 registry 中存在。
     #[serde(skip_serializing_if = "Option::is_none")]
     pub endpoint: Option<String>,
+
+    /// 凭据级请求配额（可选）
+    ///
+    /// 每项为一个滚动窗口内的请求数上限，超出后凭据暂停使用直到窗口滚动。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub request_quotas: Vec<RequestQuota>,
+
+    /// 自由格式标签（可选，如 "tier:premium"、"region:us"）
+    ///
+    /// 用于在 Admin API 中按标签筛选和批量操作凭据。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// 固定的客户端指纹（可选）
+    ///
+    /// 设置后该凭据的请求始终使用此指纹（machineId、系统版本、Node 版本），
+    /// 不再由配置或 machineId 生成规则推导；请求级指纹覆盖仍优先。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<Fingerprint>,
+}
+
+/// 滚动窗口请求配额
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestQuota {
+    /// 窗口长度（秒），如 3600 表示每小时、86400 表示每天
+    pub window_secs: u64,
+    /// 窗口内允许的最大请求数
+    pub max_requests: u32,
 }
 
 /// 判断是否为零（用于跳过序列化）
@@ -116,6 +146,22 @@ fn is_zero(value: &u32) -> bool {
     *value == 0
 }
 
+/// 规范化标签列表：去除首尾空白、丢弃空标签并按首次出现顺序去重
+pub fn normalize_tags<I, S>(tags: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.as_ref().trim();
+        if !tag.is_empty() && !normalized.iter().any(|t| t == tag) {
+            normalized.push(tag.to_string());
+        }
+    }
+    normalized
+}
+
 fn canonicalize_auth_method_value(value: &str) -> &str {
     if value.eq_ignore_ascii_case("builder-id") || value.eq_ignore_ascii_case("iam") {
         "idc"
@@ -343,6 +389,9 @@ mod tests {
             disabled: false,
             kiro_api_key: None,
             endpoint: None,
+            request_quotas: Vec::new(),
+            tags: Vec::new(),
+            fingerprint: None,
         };
 
         let json = creds.to_pretty_json().unwrap();
@@ -461,6 +510,9 @@ mod tests {
             disabled: false,
             kiro_api_key: None,
             endpoint: None,
+            request_quotas: Vec::new(),
+            tags: Vec::new(),
+            fingerprint: None,
         };
 
         let json = creds.to_pretty_json().unwrap();
@@ -492,6 +544,9 @@ mod tests {
             disabled: false,
             kiro_api_key: None,
             endpoint: None,
+            request_quotas: Vec::new(),
+            tags: Vec::new(),
+            fingerprint: None,
         };
 
         let json = creds.to_pretty_json().unwrap();
@@ -606,6 +661,9 @@ mod tests {
             disabled: false,
             kiro_api_key: None,
             endpoint: None,
+            request_quotas: Vec::new(),
+            tags: Vec::new(),
+            fingerprint: None,
         };
 
         let json = original.to_pretty_json().unwrap();
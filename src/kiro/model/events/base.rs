@@ -4,6 +4,7 @@
 
 use crate::kiro::parser::error::{ParseError, ParseResult};
 use crate::kiro::parser::frame::Frame;
+use crate::kiro::parser::registry;
 
 /// 事件类型枚举
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -16,20 +17,20 @@ pub enum EventType {
     Metering,
     /// 上下文使用率事件
     ContextUsage,
+    /// 代码引用（许可证归属）事件
+    CodeReference,
+    /// 消息元数据（响应结束帧）事件
+    MessageMetadata,
+    /// 后续提示（建议提问）事件
+    FollowupPrompt,
     /// 未知事件类型
     Unknown,
 }
 
 impl EventType {
-    /// 从事件类型字符串解析
+    /// 从事件类型字符串解析（查询事件类型注册表）
     pub fn from_str(s: &str) -> Self {
-        match s {
-            "assistantResponseEvent" => Self::AssistantResponse,
-            "toolUseEvent" => Self::ToolUse,
-            "meteringEvent" => Self::Metering,
-            "contextUsageEvent" => Self::ContextUsage,
-            _ => Self::Unknown,
-        }
+        registry::lookup(s)
     }
 
     /// 转换为事件类型字符串
@@ -39,6 +40,9 @@ impl EventType {
             Self::ToolUse => "toolUseEvent",
             Self::Metering => "meteringEvent",
             Self::ContextUsage => "contextUsageEvent",
+            Self::CodeReference => "codeReferenceEvent",
+            Self::MessageMetadata => "messageMetadataEvent",
+            Self::FollowupPrompt => "followupPromptEvent",
             Self::Unknown => "unknown",
         }
     }
@@ -71,6 +75,12 @@ pub enum Event {
     Metering(()),
     /// 上下文使用率
     ContextUsage(super::ContextUsageEvent),
+    /// 代码引用
+    CodeReference(super::CodeReferenceEvent),
+    /// 消息元数据（响应结束帧）
+    MessageMetadata(super::MessageMetadataEvent),
+    /// 后续提示
+    FollowupPrompt(super::FollowupPromptEvent),
     /// 未知事件 (保留原始帧数据)
     Unknown {},
     /// 服务端错误
@@ -121,7 +131,23 @@ impl Event {
                 let payload = super::ContextUsageEvent::from_frame(&frame)?;
                 Ok(Self::ContextUsage(payload))
             }
-            EventType::Unknown => Ok(Self::Unknown {}),
+            EventType::CodeReference => {
+                let payload = super::CodeReferenceEvent::from_frame(&frame)?;
+                Ok(Self::CodeReference(payload))
+            }
+            EventType::MessageMetadata => {
+                let payload = super::MessageMetadataEvent::from_frame(&frame)?;
+                Ok(Self::MessageMetadata(payload))
+            }
+            EventType::FollowupPrompt => {
+                let payload = super::FollowupPromptEvent::from_frame(&frame)?;
+                Ok(Self::FollowupPrompt(payload))
+            }
+            EventType::Unknown => {
+                tracing::debug!("收到未知事件类型: {}", event_type_str);
+                registry::record_unknown(event_type_str);
+                Ok(Self::Unknown {})
+            }
         }
     }
 
@@ -172,6 +198,18 @@ mod tests {
             EventType::from_str("contextUsageEvent"),
             EventType::ContextUsage
         );
+        assert_eq!(
+            EventType::from_str("codeReferenceEvent"),
+            EventType::CodeReference
+        );
+        assert_eq!(
+            EventType::from_str("messageMetadataEvent"),
+            EventType::MessageMetadata
+        );
+        assert_eq!(
+            EventType::from_str("followupPromptEvent"),
+            EventType::FollowupPrompt
+        );
         assert_eq!(EventType::from_str("unknown_type"), EventType::Unknown);
     }
 
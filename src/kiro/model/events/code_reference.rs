@@ -0,0 +1,128 @@
+//! 代码引用事件
+//!
+//! 处理 codeReferenceEvent 类型的事件（生成代码的开源许可证归属信息）
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::kiro::parser::error::ParseResult;
+use crate::kiro::parser::frame::Frame;
+
+use super::base::EventPayload;
+
+/// 代码引用事件
+///
+/// 上游检测到生成内容与开源代码相似时发出，一个事件可包含多条引用
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeReferenceEvent {
+    /// 引用列表
+    #[serde(default)]
+    pub references: Vec<CodeReference>,
+}
+
+/// 单条代码引用
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeReference {
+    /// 许可证名称（如 "MIT"）
+    #[serde(default)]
+    pub license_name: Option<String>,
+    /// 来源仓库
+    #[serde(default)]
+    pub repository: Option<String>,
+    /// 来源链接
+    #[serde(default)]
+    pub url: Option<String>,
+    /// 引用内容在生成文本中的范围
+    #[serde(default)]
+    pub recommendation_content_span: Option<ContentSpan>,
+}
+
+/// 生成文本中的字符范围
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct ContentSpan {
+    /// 起始偏移
+    #[serde(default)]
+    pub start: u64,
+    /// 结束偏移
+    #[serde(default)]
+    pub end: u64,
+}
+
+impl EventPayload for CodeReferenceEvent {
+    fn from_frame(frame: &Frame) -> ParseResult<Self> {
+        frame.payload_as_json()
+    }
+}
+
+impl CodeReference {
+    /// 转换为返回给客户端的注解（snake_case 字段）
+    pub fn to_annotation(&self) -> Value {
+        let span = self.recommendation_content_span;
+        json!({
+            "repository": self.repository,
+            "license": self.license_name,
+            "url": self.url,
+            "start": span.map(|s| s.start),
+            "end": span.map(|s| s.end),
+        })
+    }
+}
+
+impl std::fmt::Display for CodeReferenceEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CodeReference[{} 条]", self.references.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::model::events::Event;
+    use crate::kiro::parser::decoder::EventStreamDecoder;
+    use crate::kiro::parser::frame::encode_event_frame;
+
+    #[test]
+    fn test_code_reference_frames_parse_and_accumulate() {
+        let mut decoder = EventStreamDecoder::new();
+        decoder
+            .feed(&encode_event_frame(
+                "codeReferenceEvent",
+                r#"{"references":[{"licenseName":"MIT","repository":"octo/left-pad","url":"https://github.com/octo/left-pad","recommendationContentSpan":{"start":12,"end":48}}]}"#,
+            ))
+            .unwrap();
+        decoder
+            .feed(&encode_event_frame(
+                "codeReferenceEvent",
+                r#"{"references":[{"licenseName":"Apache-2.0","repository":"acme/util"}]}"#,
+            ))
+            .unwrap();
+
+        let mut references = Vec::new();
+        for frame in decoder.decode_iter() {
+            match Event::from_frame(frame.unwrap()).unwrap() {
+                Event::CodeReference(event) => references.extend(event.references),
+                other => panic!("应解析为代码引用事件: {:?}", other),
+            }
+        }
+
+        assert_eq!(references.len(), 2);
+        assert_eq!(references[0].license_name.as_deref(), Some("MIT"));
+        assert_eq!(references[0].repository.as_deref(), Some("octo/left-pad"));
+        assert_eq!(
+            references[0].recommendation_content_span,
+            Some(ContentSpan { start: 12, end: 48 })
+        );
+        assert_eq!(
+            references[1].to_annotation(),
+            json!({
+                "repository": "acme/util",
+                "license": "Apache-2.0",
+                "url": null,
+                "start": null,
+                "end": null
+            })
+        );
+    }
+}
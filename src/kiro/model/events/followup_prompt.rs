@@ -0,0 +1,83 @@
+//! 后续提示事件
+//!
+//! 处理 followupPromptEvent 类型的事件（上游建议的后续提问）
+
+use serde::Deserialize;
+
+use crate::kiro::parser::error::ParseResult;
+use crate::kiro::parser::frame::Frame;
+
+use super::base::EventPayload;
+
+/// 后续提示事件
+///
+/// 上游在回答末尾给出的建议后续提问，每个事件携带一条建议
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FollowupPromptEvent {
+    /// 建议内容（缺失时视为无建议）
+    #[serde(default)]
+    pub followup_prompt: Option<FollowupPrompt>,
+}
+
+/// 单条后续提示
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FollowupPrompt {
+    /// 建议的提问文本
+    #[serde(default)]
+    pub content: String,
+}
+
+impl EventPayload for FollowupPromptEvent {
+    fn from_frame(frame: &Frame) -> ParseResult<Self> {
+        frame.payload_as_json()
+    }
+}
+
+impl FollowupPromptEvent {
+    /// 建议的提问文本（缺失或为空白时返回 None）
+    pub fn suggestion(&self) -> Option<&str> {
+        self.followup_prompt
+            .as_ref()
+            .map(|p| p.content.trim())
+            .filter(|content| !content.is_empty())
+    }
+}
+
+impl std::fmt::Display for FollowupPromptEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FollowupPrompt[{}]", self.suggestion().unwrap_or("-"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kiro::model::events::Event;
+    use crate::kiro::parser::decoder::EventStreamDecoder;
+    use crate::kiro::parser::frame::encode_event_frame;
+
+    #[test]
+    fn test_followup_prompt_frames_parse_and_skip_empty() {
+        let mut decoder = EventStreamDecoder::new();
+        for payload in [
+            r#"{"followupPrompt":{"content":"How do I add tests?","userIntent":"SUGGEST_ALTERNATE_IMPLEMENTATION"}}"#,
+            r#"{"followupPrompt":{"content":"   "}}"#,
+            r#"{}"#,
+        ] {
+            decoder
+                .feed(&encode_event_frame("followupPromptEvent", payload))
+                .unwrap();
+        }
+
+        let suggestions: Vec<String> = decoder
+            .decode_iter()
+            .filter_map(|frame| match Event::from_frame(frame.unwrap()).unwrap() {
+                Event::FollowupPrompt(event) => event.suggestion().map(str::to_string),
+                other => panic!("应解析为后续提示事件: {:?}", other),
+            })
+            .collect();
+
+        assert_eq!(suggestions, vec!["How do I add tests?".to_string()]);
+    }
+}
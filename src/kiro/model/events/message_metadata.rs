@@ -0,0 +1,97 @@
+//! 消息元数据事件
+//!
+//! 处理 messageMetadataEvent 类型的事件（响应结束帧，携带终止原因）
+
+use serde::Deserialize;
+
+use crate::kiro::parser::error::ParseResult;
+use crate::kiro::parser::frame::Frame;
+
+use super::base::EventPayload;
+
+/// 消息元数据事件
+///
+/// 上游在响应结束时发出，`stopReason` 为本次生成的终止原因；
+/// 其余字段（conversationId、utteranceId 等）不使用
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageMetadataEvent {
+    /// 终止原因（如 "END_TURN" / "TOOL_USE" / "MAX_TOKENS"）
+    #[serde(default)]
+    pub stop_reason: Option<String>,
+}
+
+impl EventPayload for MessageMetadataEvent {
+    fn from_frame(frame: &Frame) -> ParseResult<Self> {
+        frame.payload_as_json()
+    }
+}
+
+impl MessageMetadataEvent {
+    /// 映射为 Anthropic stop_reason（缺失或无法识别时返回 None，由调用方推断）
+    pub fn anthropic_stop_reason(&self) -> Option<&'static str> {
+        self.stop_reason.as_deref().and_then(map_stop_reason)
+    }
+}
+
+/// 将 Kiro 终止原因映射为 Anthropic stop_reason
+///
+/// 大小写与 `-` / `_` 不敏感，无法识别时返回 None。
+pub fn map_stop_reason(kiro: &str) -> Option<&'static str> {
+    let normalized = kiro.trim().to_ascii_uppercase().replace('-', "_");
+    match normalized.as_str() {
+        "END_TURN" | "COMPLETE" | "STOP" => Some("end_turn"),
+        "TOOL_USE" | "TOOL_CALLS" => Some("tool_use"),
+        "MAX_TOKENS" | "MAX_OUTPUT_TOKENS" | "LENGTH" => Some("max_tokens"),
+        "STOP_SEQUENCE" => Some("stop_sequence"),
+        "CONTENT_FILTERED" | "GUARDRAIL_INTERVENED" => Some("refusal"),
+        "CONTEXT_WINDOW_EXCEEDED" | "MODEL_CONTEXT_WINDOW_EXCEEDED" => {
+            Some("model_context_window_exceeded")
+        }
+        _ => None,
+    }
+}
+
+impl std::fmt::Display for MessageMetadataEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "MessageMetadata[stopReason={}]",
+            self.stop_reason.as_deref().unwrap_or("-")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_each_termination_cause_maps_to_anthropic_stop_reason() {
+        let cases = [
+            ("END_TURN", Some("end_turn")),
+            ("TOOL_USE", Some("tool_use")),
+            ("MAX_TOKENS", Some("max_tokens")),
+            ("STOP_SEQUENCE", Some("stop_sequence")),
+            ("CONTENT_FILTERED", Some("refusal")),
+            (
+                "CONTEXT_WINDOW_EXCEEDED",
+                Some("model_context_window_exceeded"),
+            ),
+            ("tool-use", Some("tool_use")),
+            ("something_new", None),
+        ];
+        for (kiro, expected) in cases {
+            let event: MessageMetadataEvent = serde_json::from_value(serde_json::json!({
+                "conversationId": "c1",
+                "stopReason": kiro
+            }))
+            .unwrap();
+            assert_eq!(event.anthropic_stop_reason(), expected, "{}", kiro);
+        }
+
+        let without: MessageMetadataEvent =
+            serde_json::from_str(r#"{"conversationId":"c1"}"#).unwrap();
+        assert_eq!(without.anthropic_stop_reason(), None);
+    }
+}
@@ -4,10 +4,16 @@
 
 mod assistant;
 mod base;
+mod code_reference;
 mod context_usage;
+mod followup_prompt;
+mod message_metadata;
 mod tool_use;
 
 pub use assistant::AssistantResponseEvent;
-pub use base::Event;
+pub use base::{Event, EventType};
+pub use code_reference::{CodeReference, CodeReferenceEvent};
 pub use context_usage::ContextUsageEvent;
+pub use followup_prompt::FollowupPromptEvent;
+pub use message_metadata::MessageMetadataEvent;
 pub use tool_use::ToolUseEvent;
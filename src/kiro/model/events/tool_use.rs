@@ -15,9 +15,11 @@ use super::base::EventPayload;
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ToolUseEvent {
-    /// 工具名称
+    /// 工具名称（中间增量可能缺失，此时为空）
+    #[serde(default)]
     pub name: String,
-    /// 工具调用 ID
+    /// 工具调用 ID（中间增量可能缺失，此时为空）
+    #[serde(default)]
     pub tool_use_id: String,
     /// 工具输入数据 (JSON 字符串，可能是流式的部分数据)
     #[serde(default)]
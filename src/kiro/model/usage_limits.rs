@@ -2,8 +2,23 @@
 //!
 //! 包含 getUsageLimits API 的响应类型定义
 
+use reqwest::header::HeaderMap;
 use serde::Deserialize;
 
+/// 剩余请求配额响应头
+///
+/// 推测值：沿用常见的 `x-ratelimit-*` 命名，未经上游文档或实际响应确认。
+const QUOTA_REMAINING_HEADER: &str = "x-ratelimit-remaining";
+/// 配额重置时间响应头（Unix 时间戳，或距今的秒数）
+///
+/// 推测值，同 [`QUOTA_REMAINING_HEADER`]。
+const QUOTA_RESET_HEADER: &str = "x-ratelimit-reset";
+/// 小于该值的重置时间按相对秒数处理
+///
+/// 启发式判断：上游取值格式未知，1e9 秒约为 2001 年的 Unix 时间戳，
+/// 更小的值不可能是有效的绝对时间，因此视为相对秒数。
+const RELATIVE_RESET_THRESHOLD: f64 = 1_000_000_000.0;
+
 /// 使用额度查询响应
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -19,6 +34,46 @@ pub struct UsageLimitsResponse {
     /// 使用量明细列表
     #[serde(default)]
     pub usage_breakdown_list: Vec<UsageBreakdown>,
+
+    /// 上游通过响应头报告的请求配额（不在响应体中）
+    #[serde(skip)]
+    pub quota: QuotaInfo,
+}
+
+/// 请求配额信息
+///
+/// 上游未报告时各字段为 None。解析所用的响应头名称与重置时间格式均为推测，
+/// 上游实际未发送这些响应头时始终为 None。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuotaInfo {
+    /// 剩余请求配额
+    pub remaining: Option<f64>,
+    /// 配额重置时间（Unix 时间戳）
+    pub reset_at: Option<f64>,
+}
+
+impl QuotaInfo {
+    /// 从上游响应头解析配额信息，`now` 为当前 Unix 时间戳
+    pub fn from_headers(headers: &HeaderMap, now: f64) -> Self {
+        let number = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<f64>().ok())
+                .filter(|v| v.is_finite() && *v >= 0.0)
+        };
+
+        Self {
+            remaining: number(QUOTA_REMAINING_HEADER),
+            reset_at: number(QUOTA_RESET_HEADER).map(|reset| {
+                if reset < RELATIVE_RESET_THRESHOLD {
+                    now + reset
+                } else {
+                    reset
+                }
+            }),
+        }
+    }
 }
 
 /// 订阅信息
@@ -153,10 +153,49 @@ pub fn parse_frame(buffer: &[u8]) -> ParseResult<Option<(Frame, usize)>> {
     Ok(Some((Frame { headers, payload }, total_length)))
 }
 
+/// 编码一个 event 类型的消息帧（测试用，模拟上游响应）
+#[cfg(test)]
+pub(crate) fn encode_event_frame(event_type: &str, payload: &str) -> Vec<u8> {
+    let mut headers = Vec::new();
+    for (name, value) in [
+        (":message-type", "event"),
+        (":event-type", event_type),
+        (":content-type", "application/json"),
+    ] {
+        headers.push(name.len() as u8);
+        headers.extend_from_slice(name.as_bytes());
+        headers.push(7); // String
+        headers.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        headers.extend_from_slice(value.as_bytes());
+    }
+
+    let total_length = PRELUDE_SIZE + headers.len() + payload.len() + 4;
+    let mut buffer = Vec::with_capacity(total_length);
+    buffer.extend_from_slice(&(total_length as u32).to_be_bytes());
+    buffer.extend_from_slice(&(headers.len() as u32).to_be_bytes());
+    let prelude_crc = crc32(&buffer[0..8]);
+    buffer.extend_from_slice(&prelude_crc.to_be_bytes());
+    buffer.extend_from_slice(&headers);
+    buffer.extend_from_slice(payload.as_bytes());
+    let message_crc = crc32(&buffer);
+    buffer.extend_from_slice(&message_crc.to_be_bytes());
+    buffer
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_encode_event_frame_roundtrip() {
+        let buffer = encode_event_frame("assistantResponseEvent", r#"{"content":"hi"}"#);
+        let (frame, consumed) = parse_frame(&buffer).unwrap().unwrap();
+        assert_eq!(consumed, buffer.len());
+        assert_eq!(frame.message_type(), Some("event"));
+        assert_eq!(frame.event_type(), Some("assistantResponseEvent"));
+        assert_eq!(frame.payload_as_str(), r#"{"content":"hi"}"#);
+    }
+
     #[test]
     fn test_frame_insufficient_data() {
         let buffer = [0u8; 10]; // 小于 PRELUDE_SIZE
@@ -8,3 +8,6 @@ pub mod decoder;
 pub mod error;
 pub mod frame;
 pub mod header;
+pub mod registry;
+
+pub use registry::known_event_types;
@@ -0,0 +1,108 @@
+//! 事件类型注册表
+//!
+//! 记录本构建能够解析的 Kiro 事件类型，事件分发时查询此表。
+//! 无法识别的事件名按名称计数，上游协议变更（新增事件类型）时可被监控发现，
+//! 而不是静默丢失内容。
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::LazyLock;
+
+use parking_lot::Mutex;
+
+use crate::kiro::model::events::EventType;
+
+/// 已实现的事件类型（事件名 → 事件类型）
+const REGISTRY: &[(&str, EventType)] = &[
+    ("assistantResponseEvent", EventType::AssistantResponse),
+    ("toolUseEvent", EventType::ToolUse),
+    ("meteringEvent", EventType::Metering),
+    ("contextUsageEvent", EventType::ContextUsage),
+    ("codeReferenceEvent", EventType::CodeReference),
+    ("messageMetadataEvent", EventType::MessageMetadata),
+    ("followupPromptEvent", EventType::FollowupPrompt),
+];
+
+/// 未知事件名的最大记录数量，超出后计入 [`OTHER_UNKNOWN`]（防止异常上游撑爆内存）
+const MAX_TRACKED_UNKNOWN: usize = 64;
+
+/// 超出记录上限的未知事件名统一计入此键
+const OTHER_UNKNOWN: &str = "<other>";
+
+/// 未知事件名出现次数
+static UNKNOWN_COUNTS: LazyLock<Mutex<HashMap<String, u64>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// 按事件名查找事件类型（未注册时返回 [`EventType::Unknown`]）
+pub fn lookup(name: &str) -> EventType {
+    REGISTRY
+        .iter()
+        .find(|(registered, _)| *registered == name)
+        .map(|(_, event_type)| *event_type)
+        .unwrap_or(EventType::Unknown)
+}
+
+/// 本构建能够解析的事件类型名
+pub fn known_event_types() -> Vec<&'static str> {
+    REGISTRY.iter().map(|(name, _)| *name).collect()
+}
+
+/// 记录一次未知事件
+pub fn record_unknown(name: &str) {
+    let mut counts = UNKNOWN_COUNTS.lock();
+    let key = if counts.contains_key(name) || counts.len() < MAX_TRACKED_UNKNOWN {
+        name
+    } else {
+        OTHER_UNKNOWN
+    };
+    *counts.entry(key.to_string()).or_default() += 1;
+}
+
+/// 各未知事件名的累计出现次数
+pub fn unknown_event_counts() -> BTreeMap<String, u64> {
+    UNKNOWN_COUNTS
+        .lock()
+        .iter()
+        .map(|(name, count)| (name.clone(), *count))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::model::events::Event;
+    use crate::kiro::parser::decoder::EventStreamDecoder;
+    use crate::kiro::parser::frame::encode_event_frame;
+
+    #[test]
+    fn test_registry_lists_known_events_and_counts_unknown() {
+        let known = known_event_types();
+        assert_eq!(
+            known,
+            vec![
+                "assistantResponseEvent",
+                "toolUseEvent",
+                "meteringEvent",
+                "contextUsageEvent",
+                "codeReferenceEvent",
+                "messageMetadataEvent",
+                "followupPromptEvent",
+            ]
+        );
+        for name in known {
+            assert_eq!(lookup(name).as_str(), name);
+        }
+
+        let name = "registryTestUnseenEvent";
+        let before = unknown_event_counts().get(name).copied().unwrap_or(0);
+        let mut decoder = EventStreamDecoder::new();
+        decoder.feed(&encode_event_frame(name, "{}")).unwrap();
+        decoder.feed(&encode_event_frame(name, "{}")).unwrap();
+        for frame in decoder.decode_iter() {
+            assert!(matches!(
+                Event::from_frame(frame.unwrap()).unwrap(),
+                Event::Unknown {}
+            ));
+        }
+        assert_eq!(unknown_event_counts()[name], before + 2);
+    }
+}
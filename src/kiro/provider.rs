@@ -5,25 +5,217 @@
 //! 支持多凭据故障转移和重试
 //! 支持按凭据级 endpoint 切换不同 Kiro API 端点
 
+use bytes::Bytes;
+use std::borrow::Cow;
+use futures::StreamExt;
 use reqwest::Client;
+use reqwest::header::{HeaderValue, RETRY_AFTER};
 use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
 
 use crate::http_client::{ProxyConfig, build_client};
+use crate::kiro::cooldown::CooldownReason;
 use crate::kiro::endpoint::{KiroEndpoint, RequestContext};
+use crate::kiro::fingerprint::Fingerprint;
 use crate::kiro::machine_id;
 use crate::kiro::model::credentials::KiroCredentials;
-use crate::kiro::token_manager::MultiTokenManager;
-use crate::model::config::TlsBackend;
+use crate::kiro::model::events::Event;
+use crate::kiro::parser::decoder::EventStreamDecoder;
+use crate::kiro::token_manager::{ModelUnavailableError, MultiTokenManager};
+use crate::metrics::{self, LatencyPhase};
+use crate::model::config::{Config, EmptyResponsePolicy, TlsBackend};
 use parking_lot::Mutex;
 
 /// 每个凭据的最大重试次数
 const MAX_RETRIES_PER_CREDENTIAL: usize = 3;
 
-/// 总重试次数硬上限（避免无限重试）
-const MAX_TOTAL_RETRIES: usize = 3;
+/// 解析 `Retry-After` 的上限（防止异常或恶意的超长值，如一周）
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// 在当前凭据上遵循 `Retry-After` 原地等待的最长时间（防止挂起请求）
+const MAX_RETRY_AFTER_WAIT: Duration = Duration::from_secs(60);
+
+/// 单次 API 调用的请求级选项
+///
+/// 由 Anthropic 层根据请求头等信息构造，默认值即全局行为。
+#[derive(Debug, Clone, Default)]
+pub struct CallOptions {
+    /// 指纹种子覆盖：存在时使用由该种子生成的指纹，而非凭据自身的稳定身份
+    pub fingerprint_seed: Option<String>,
+    /// 固定使用指定凭据（不参与负载均衡）
+    pub credential_id: Option<u64>,
+    /// 客户端指定的请求超时（覆盖整个上游调用，含重试与响应体传输）
+    pub timeout: Option<Duration>,
+}
+
+impl CallOptions {
+    /// 按种子生成请求级指纹（未指定种子时返回 None）
+    pub fn fingerprint(&self) -> Option<Fingerprint> {
+        self.fingerprint_seed
+            .as_deref()
+            .map(Fingerprint::generate_from_seed)
+    }
+
+    /// 按种子为指定凭据生成请求级指纹
+    ///
+    /// 凭据配置了合法的 `machineId` 时保留该值，其余字段仍由种子决定。
+    pub fn fingerprint_for(&self, credentials: &KiroCredentials) -> Option<Fingerprint> {
+        let seed = self.fingerprint_seed.as_deref()?;
+        let pinned = credentials
+            .machine_id
+            .as_deref()
+            .and_then(machine_id::normalize_machine_id);
+        Some(match pinned {
+            Some(id) => Fingerprint::generate_from_seed_with_machine_id(seed, &id)
+                .unwrap_or_else(|_| Fingerprint::generate_from_seed(seed)),
+            None => Fingerprint::generate_from_seed(seed),
+        })
+    }
+}
+
+/// 解析 `Retry-After` 响应头（秒数或 HTTP-date），结果封顶于 [`MAX_RETRY_AFTER`]
+///
+/// 无法解析时返回 None；已过去的日期视为无需等待。
+pub fn parse_retry_after(value: &HeaderValue) -> Option<Duration> {
+    parse_retry_after_at(value, chrono::Utc::now())
+}
+
+/// 以指定时间为"当前时间"解析 `Retry-After`
+fn parse_retry_after_at(
+    value: &HeaderValue,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<Duration> {
+    let value = value.to_str().ok()?.trim();
+    let delay = if let Ok(secs) = value.parse::<u64>() {
+        Duration::from_secs(secs)
+    } else {
+        let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+        (at.with_timezone(&chrono::Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO)
+    };
+    Some(delay.min(MAX_RETRY_AFTER))
+}
+
+/// 凭据使用的 machineId：固定了指纹时取指纹中的值，否则按凭据生成
+fn credential_machine_id(credentials: &KiroCredentials, config: &Config) -> String {
+    match &credentials.fingerprint {
+        Some(fp) => fp.machine_id.clone(),
+        None => machine_id::generate_from_credentials(credentials, config),
+    }
+}
+
+/// 请求内容指纹，用于识别客户端反复重放的同一请求
+///
+/// 忽略每次转换都会重新生成的 `conversationId` / `agentContinuationId`；
+/// 请求体无法解析为 JSON 时直接对原文取哈希。
+fn request_replay_key(request_body: &str) -> u64 {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    match serde_json::from_str::<serde_json::Value>(request_body) {
+        Ok(mut value) => {
+            if let Some(state) = value
+                .get_mut("conversationState")
+                .and_then(|s| s.as_object_mut())
+            {
+                state.remove("conversationId");
+                state.remove("agentContinuationId");
+            }
+            value.to_string().hash(&mut hasher);
+        }
+        Err(_) => request_body.hash(&mut hasher),
+    }
+    hasher.finish()
+}
+
+/// 一次成功上游调用的耗时分解
+///
+/// 附加在 [`KiroProvider::call_api`] 等返回的 Response extensions 中，
+/// 只统计最终成功的那次尝试。
+#[derive(Debug, Clone, Copy)]
+pub struct UpstreamTiming {
+    /// 凭据选择耗时（不含 Token 刷新）
+    pub credential_selection: Duration,
+    /// Token 刷新耗时（本次未刷新时为 None）
+    pub token_refresh: Option<Duration>,
+    /// 请求发出到收到上游响应头的耗时
+    pub first_byte: Duration,
+    /// 实际处理请求的凭据 ID
+    pub credential_id: u64,
+    /// 成功前的重试次数（含切换凭据、空响应重试）
+    pub retries: usize,
+}
+
+/// 一次成功上游调用的端到端耗时（从请求发出起算）
+///
+/// 在响应体读取完毕时记录到延迟指标（`first_frame` / `total` 阶段），并输出 debug 日志。
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestTiming {
+    /// 请求发出 → 收到首个响应体数据（首个事件帧）；响应体为空时为 None
+    pub ttfb: Option<Duration>,
+    /// 请求发出 → 响应体读取完毕
+    pub total: Duration,
+    /// 实际处理请求的凭据 ID
+    pub credential_id: u64,
+    /// 模型（无法识别时为 `unknown`）
+    pub model: String,
+}
+
+impl RequestTiming {
+    /// 记录到延迟指标并输出 debug 日志
+    fn record(&self) {
+        if let Some(ttfb) = self.ttfb {
+            metrics::latency().record(
+                LatencyPhase::FirstFrame,
+                self.credential_id,
+                &self.model,
+                ttfb,
+            );
+        }
+        metrics::latency().record(
+            LatencyPhase::Total,
+            self.credential_id,
+            &self.model,
+            self.total,
+        );
+        tracing::debug!(
+            credential_id = self.credential_id,
+            model = %self.model,
+            ttfb_ms = self.ttfb.map(|d| d.as_millis() as u64),
+            total_ms = self.total.as_millis() as u64,
+            "上游请求耗时"
+        );
+    }
+}
+
+/// 上游调用超过客户端指定的超时时间
+#[derive(Debug)]
+pub struct RequestTimeoutError {
+    pub timeout: Duration,
+}
+
+impl std::fmt::Display for RequestTimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "请求超过客户端指定的超时时间（{} ms）", self.timeout.as_millis())
+    }
+}
+
+impl std::error::Error for RequestTimeoutError {}
+
+/// 回退后实际使用的模型（Kiro 模型 ID）
+///
+/// 仅当所请求模型不可用、按 `modelFallbacks` 改用备用模型时附加在 Response extensions 中。
+#[derive(Debug, Clone)]
+pub struct FallbackModel(pub String);
+
+/// 本次调用依次尝试过的凭据 ID（按尝试顺序，最后一个为实际处理请求的凭据）
+///
+/// 附加在成功返回的 Response extensions 中。
+#[derive(Debug, Clone)]
+pub struct CredentialAttempts(pub Vec<u64>);
 
 /// Kiro API Provider
 ///
@@ -81,6 +273,11 @@ impl KiroProvider {
         }
     }
 
+    /// 获取全局配置
+    pub fn config(&self) -> &Config {
+        self.token_manager.config()
+    }
+
     /// 根据凭据的代理配置获取（或创建并缓存）对应的 reqwest::Client
     fn client_for(&self, credentials: &KiroCredentials) -> anyhow::Result<Client> {
         let effective = credentials.effective_proxy(self.global_proxy.as_ref());
@@ -93,6 +290,84 @@ impl KiroProvider {
         Ok(client)
     }
 
+    /// 对指定凭据发送最小探测请求
+    ///
+    /// 绕过负载均衡与重试，不更新成功/失败计数；上游返回 2xx 即视为凭据可用。
+    pub async fn probe_credential(&self, id: u64) -> anyhow::Result<()> {
+        let ctx = self.token_manager.acquire_context_for(id).await?;
+        let config = self.token_manager.config();
+        let machine_id = credential_machine_id(&ctx.credentials, config);
+        let endpoint = self.endpoint_for(&ctx.credentials)?;
+
+        let rctx = RequestContext {
+            credentials: &ctx.credentials,
+            token: &ctx.token,
+            machine_id: &machine_id,
+            config,
+            fingerprint: ctx.credentials.fingerprint.as_ref(),
+        };
+
+        let probe_body = serde_json::json!({
+            "conversationState": {
+                "conversationId": format!("probe-{}", uuid::Uuid::new_v4()),
+                "currentMessage": {
+                    "userInputMessage": {
+                        "content": "Hi",
+                        "modelId": "claude-opus-4.6",
+                        "userInputMessageContext": {
+                            "toolResults": [],
+                            "tools": []
+                        },
+                        "origin": "AI_EDITOR"
+                    }
+                },
+                "chatTriggerType": "MANUAL",
+                "agentTaskType": "vibe"
+            }
+        })
+        .to_string();
+
+        let url = endpoint.api_url(&rctx);
+        let body = endpoint.transform_api_body(&probe_body, &rctx);
+        let base = self
+            .client_for(&ctx.credentials)?
+            .post(&url)
+            .body(body)
+            .header("content-type", "application/json")
+            .header("Connection", "close");
+        let response = endpoint.decorate_api(base, &rctx).send().await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            anyhow::bail!("探测请求失败: HTTP {}", status.as_u16());
+        }
+        Ok(())
+    }
+
+    /// 执行一轮慢速探测：逐个探测认证类禁用的凭据，成功则重新启用
+    ///
+    /// 返回本轮重新启用的凭据数量。
+    pub async fn run_slow_probe_pass(&self) -> usize {
+        let candidates = self.token_manager.probe_candidates();
+        if candidates.is_empty() {
+            return 0;
+        }
+
+        tracing::info!("开始慢速探测 {} 个已禁用凭据: {:?}", candidates.len(), candidates);
+        let mut restored = 0;
+        for id in candidates {
+            match self.probe_credential(id).await {
+                Ok(()) => {
+                    if self.token_manager.restore_probed_credential(id) {
+                        restored += 1;
+                    }
+                }
+                Err(e) => tracing::info!("凭据 #{} 慢速探测仍失败: {}", id, e),
+            }
+        }
+        restored
+    }
+
     /// 根据凭据选择 endpoint 实现
     fn endpoint_for(
         &self,
@@ -111,13 +386,21 @@ impl KiroProvider {
     /// 发送非流式 API 请求
     ///
     /// 支持多凭据故障转移（见 [`Self::call_api_with_retry`]）
-    pub async fn call_api(&self, request_body: &str) -> anyhow::Result<reqwest::Response> {
-        self.call_api_with_retry(request_body, false).await
+    pub async fn call_api(
+        &self,
+        request_body: &str,
+        options: &CallOptions,
+    ) -> anyhow::Result<reqwest::Response> {
+        self.call_api_with_retry(request_body, false, options).await
     }
 
     /// 发送流式 API 请求
-    pub async fn call_api_stream(&self, request_body: &str) -> anyhow::Result<reqwest::Response> {
-        self.call_api_with_retry(request_body, true).await
+    pub async fn call_api_stream(
+        &self,
+        request_body: &str,
+        options: &CallOptions,
+    ) -> anyhow::Result<reqwest::Response> {
+        self.call_api_with_retry(request_body, true, options).await
     }
 
     /// 发送 MCP API 请求（WebSearch 等工具调用）
@@ -125,10 +408,16 @@ impl KiroProvider {
         self.call_mcp_with_retry(request_body).await
     }
 
+    /// 单次请求的最大尝试次数：min(凭据数量 × 每凭据重试次数, 配置的上限)
+    fn max_retries(&self) -> usize {
+        let total_credentials = self.token_manager.total_count();
+        (total_credentials * MAX_RETRIES_PER_CREDENTIAL)
+            .min(self.token_manager.config().upstream_max_retries)
+    }
+
     /// 内部方法：带重试逻辑的 MCP API 调用
     async fn call_mcp_with_retry(&self, request_body: &str) -> anyhow::Result<reqwest::Response> {
-        let total_credentials = self.token_manager.total_count();
-        let max_retries = (total_credentials * MAX_RETRIES_PER_CREDENTIAL).min(MAX_TOTAL_RETRIES);
+        let max_retries = self.max_retries();
         let mut last_error: Option<anyhow::Error> = None;
         let mut force_refreshed: HashSet<u64> = HashSet::new();
 
@@ -143,7 +432,7 @@ impl KiroProvider {
             };
 
             let config = self.token_manager.config();
-            let machine_id = machine_id::generate_from_credentials(&ctx.credentials, config);
+            let machine_id = credential_machine_id(&ctx.credentials, config);
 
             let endpoint = match self.endpoint_for(&ctx.credentials) {
                 Ok(e) => e,
@@ -160,6 +449,7 @@ impl KiroProvider {
                 token: &ctx.token,
                 machine_id: &machine_id,
                 config,
+                fingerprint: ctx.credentials.fingerprint.as_ref(),
             };
 
             let url = endpoint.mcp_url(&rctx);
@@ -274,34 +564,104 @@ impl KiroProvider {
     ///
     /// 重试策略：
     /// - 每个凭据最多重试 MAX_RETRIES_PER_CREDENTIAL 次
-    /// - 总重试次数 = min(凭据数量 × 每凭据重试次数, MAX_TOTAL_RETRIES)
-    /// - 硬上限 9 次，避免无限重试
+    /// - 总重试次数 = min(凭据数量 × 每凭据重试次数, `upstreamMaxRetries`)
+    /// - 408/429/5xx 默认在同一凭据上退避重试；开启 `rotateOnTransientError` 时冷却当前凭据并换用下一个
     async fn call_api_with_retry(
         &self,
         request_body: &str,
         is_stream: bool,
+        options: &CallOptions,
     ) -> anyhow::Result<reqwest::Response> {
-        let total_credentials = self.token_manager.total_count();
-        let max_retries = (total_credentials * MAX_RETRIES_PER_CREDENTIAL).min(MAX_TOTAL_RETRIES);
+        let max_retries = self.max_retries();
         let mut last_error: Option<anyhow::Error> = None;
         let mut force_refreshed: HashSet<u64> = HashSet::new();
+        let mut empty_retries = 0usize;
         let api_type = if is_stream { "流式" } else { "非流式" };
 
         // 尝试从请求体中提取模型信息
-        let model = Self::extract_model_from_request(request_body);
+        let mut model = Self::extract_model_from_request(request_body);
+        metrics::registry()
+            .requests
+            .inc(model.as_deref().unwrap_or("unknown"));
+
+        // 按模型的回退链：所请求模型在所有凭据上均处于模型级冷却时依次改用
+        let config = self.token_manager.config();
+        let fallback_chain: &[String] = model
+            .as_deref()
+            .and_then(|m| config.model_fallbacks.get(m))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mut next_fallback = 0usize;
+        let mut request_body = Cow::Borrowed(request_body);
+
+        // 请求级指纹覆盖（与凭据无关，整个重试过程共用）
+        let fingerprint = options.fingerprint();
+        if let Some(fp) = &fingerprint {
+            tracing::debug!(system_version = %fp.system_version(), "使用请求级指纹覆盖");
+        }
+        let mut attempted_credentials = Vec::new();
+
+        // 客户端指定的超时：换算为绝对截止时间，每次尝试只使用剩余时间
+        let deadline = options.timeout.map(|t| (t, Instant::now() + t));
+        let timed_out = |timeout: Duration| anyhow::Error::from(RequestTimeoutError { timeout });
 
         for attempt in 0..max_retries {
+            let remaining = match deadline {
+                Some((timeout, at)) => match at.checked_duration_since(Instant::now()) {
+                    Some(remaining) if !remaining.is_zero() => Some(remaining),
+                    _ => return Err(timed_out(timeout)),
+                },
+                None => None,
+            };
+
             // 获取调用上下文（绑定 index、credentials、token）
-            let ctx = match self.token_manager.acquire_context(model.as_deref()).await {
+            let acquire_started = Instant::now();
+            let ctx = loop {
+                let result = match options.credential_id {
+                    Some(id) => self.token_manager.acquire_context_for(id).await,
+                    None => self.token_manager.acquire_context(model.as_deref()).await,
+                };
+                let Err(e) = result else {
+                    break result;
+                };
+                if e.downcast_ref::<ModelUnavailableError>().is_none() {
+                    break Err(e);
+                }
+                let Some(next) = fallback_chain.get(next_fallback) else {
+                    break Err(e);
+                };
+                next_fallback += 1;
+                let Some(body) = Self::replace_model_id(&request_body, next) else {
+                    break Err(e);
+                };
+                tracing::warn!(
+                    "模型 {} 在所有可用凭据上均暂不可用，回退到 {}",
+                    model.as_deref().unwrap_or("unknown"),
+                    next
+                );
+                request_body = Cow::Owned(body);
+                model = Some(next.clone());
+            };
+            let ctx = match ctx {
                 Ok(c) => c,
                 Err(e) => {
                     last_error = Some(e);
                     continue;
                 }
             };
+            let acquire_elapsed = acquire_started.elapsed();
+            attempted_credentials.push(ctx.id);
 
             let config = self.token_manager.config();
-            let machine_id = machine_id::generate_from_credentials(&ctx.credentials, config);
+            // 请求级指纹覆盖优先（保留凭据固定的 machineId），其次为凭据上固定的指纹
+            let seeded_fingerprint = options.fingerprint_for(&ctx.credentials);
+            let effective_fingerprint = seeded_fingerprint
+                .as_ref()
+                .or(ctx.credentials.fingerprint.as_ref());
+            let machine_id = match effective_fingerprint {
+                Some(fp) => fp.machine_id.clone(),
+                None => machine_id::generate_from_credentials(&ctx.credentials, config),
+            };
 
             let endpoint = match self.endpoint_for(&ctx.credentials) {
                 Ok(e) => e,
@@ -317,10 +677,11 @@ impl KiroProvider {
                 token: &ctx.token,
                 machine_id: &machine_id,
                 config,
+                fingerprint: effective_fingerprint,
             };
 
             let url = endpoint.api_url(&rctx);
-            let body = endpoint.transform_api_body(request_body, &rctx);
+            let body = endpoint.transform_api_body(&request_body, &rctx);
 
             let base = self
                 .client_for(&ctx.credentials)?
@@ -328,11 +689,22 @@ impl KiroProvider {
                 .body(body)
                 .header("content-type", "application/json")
                 .header("Connection", "close");
+            let base = match remaining {
+                Some(remaining) => base.timeout(remaining),
+                None => base,
+            };
             let request = endpoint.decorate_api(base, &rctx);
 
+            let started = Instant::now();
             let response = match request.send().await {
                 Ok(resp) => resp,
                 Err(e) => {
+                    if e.is_timeout()
+                        && let Some((timeout, _)) = deadline
+                    {
+                        tracing::warn!("API 请求超过客户端指定的超时时间，放弃重试: {}", e);
+                        return Err(timed_out(timeout));
+                    }
                     tracing::warn!(
                         "API 请求发送失败（尝试 {}/{}）: {}",
                         attempt + 1,
@@ -350,13 +722,96 @@ impl KiroProvider {
             };
 
             let status = response.status();
+            if !status.is_success() {
+                metrics::registry().upstream_errors.inc(status.as_str());
+            }
 
             // 成功响应
             if status.is_success() {
+                let timing = UpstreamTiming {
+                    credential_selection: acquire_elapsed
+                        .saturating_sub(ctx.token_refresh.unwrap_or_default()),
+                    token_refresh: ctx.token_refresh,
+                    first_byte: started.elapsed(),
+                    credential_id: ctx.id,
+                    retries: attempt,
+                };
+                let model_label = model.as_deref().unwrap_or("unknown");
+                metrics::latency().record(
+                    LatencyPhase::FirstByte,
+                    ctx.id,
+                    model_label,
+                    timing.first_byte,
+                );
+                let mut response =
+                    Self::record_completion_latency(response, ctx.id, model_label, started)?;
+                let fallback_model = model.clone().filter(|_| next_fallback > 0).map(FallbackModel);
+
+                let policy = config.empty_response_policy;
+                if policy == EmptyResponsePolicy::Passthrough {
+                    self.token_manager.report_success(ctx.id);
+                    response.extensions_mut().insert(timing);
+                    response
+                        .extensions_mut()
+                        .insert(CredentialAttempts(attempted_credentials));
+                    if let Some(fallback) = fallback_model {
+                        response.extensions_mut().insert(fallback);
+                    }
+                    return Ok(response);
+                }
+
+                // 预读响应直到出现首个内容事件，以识别"200 但无任何内容"的空响应
+                let (has_content, mut response) = match Self::peek_for_content(response).await {
+                    Ok(peeked) => peeked,
+                    Err(e) => {
+                        tracing::warn!(
+                            "读取响应流失败（尝试 {}/{}）: {}",
+                            attempt + 1,
+                            max_retries,
+                            e
+                        );
+                        last_error = Some(e);
+                        if attempt + 1 < max_retries {
+                            sleep(Self::retry_delay(attempt)).await;
+                        }
+                        continue;
+                    }
+                };
+
+                if !has_content {
+                    if empty_retries < policy.max_retries() && attempt + 1 < max_retries {
+                        empty_retries += 1;
+                        tracing::warn!(
+                            "{} API 返回空响应（无文本、无工具调用），冷却凭据 #{} 后重试（尝试 {}/{}）",
+                            api_type,
+                            ctx.id,
+                            attempt + 1,
+                            max_retries
+                        );
+                        self.token_manager
+                            .report_cooldown(ctx.id, CooldownReason::EmptyResponse);
+                        last_error = Some(anyhow::anyhow!("{} API 返回空响应", api_type));
+                        continue;
+                    }
+                    tracing::warn!("{} API 返回空响应，重试预算已用尽，原样透传", api_type);
+                }
+
                 self.token_manager.report_success(ctx.id);
+                response.extensions_mut().insert(timing);
+                response
+                    .extensions_mut()
+                    .insert(CredentialAttempts(attempted_credentials));
+                if let Some(fallback) = fallback_model {
+                    response.extensions_mut().insert(fallback);
+                }
                 return Ok(response);
             }
 
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(parse_retry_after);
+
             // 失败响应：读取 body 用于日志/错误信息
             let body = response.text().await.unwrap_or_default();
 
@@ -389,8 +844,37 @@ impl KiroProvider {
                 continue;
             }
 
+            // 模型暂不可用：仅冷却该凭据上的该模型，立即换凭据重试
+            // （所有凭据均不可用时由回退链接管）
+            if let Some(model_id) = model.as_deref()
+                && options.credential_id.is_none()
+                && endpoint.is_model_unavailable(&body)
+            {
+                tracing::warn!(
+                    "API 请求失败（模型 {} 暂不可用，尝试 {}/{}）: {} {}",
+                    model_id,
+                    attempt + 1,
+                    max_retries,
+                    status,
+                    body
+                );
+                self.token_manager.report_model_cooldown(ctx.id, model_id);
+                last_error = Some(anyhow::anyhow!(
+                    "{} API 请求失败: {} {}",
+                    api_type,
+                    status,
+                    body
+                ));
+                continue;
+            }
+
             // 400 Bad Request - 请求问题，重试/切换凭据无意义
+            // （启用时，同一请求在同一凭据上反复被拒绝则短暂冷却，见 report_request_rejected）
             if status.as_u16() == 400 {
+                if config.request_rejected_cooldown {
+                    self.token_manager
+                        .report_request_rejected(ctx.id, request_replay_key(&request_body));
+                }
                 anyhow::bail!("{} API 请求失败: {} {}", api_type, status, body);
             }
 
@@ -434,10 +918,48 @@ impl KiroProvider {
                 continue;
             }
 
-            // 429/408/5xx - 瞬态上游错误：重试但不禁用或切换凭据
+            // 429/408/5xx - 瞬态上游错误：默认重试但不禁用或切换凭据
             // （避免 429 high traffic / 502 high load 等瞬态错误把所有凭据锁死）
+            // 上游给出 Retry-After 时按其等待（封顶），否则使用默认退避
             if matches!(status.as_u16(), 408 | 429) || status.is_server_error() {
+                // 开启轮换时冷却当前凭据并立即换用下一个（仍有其他可用凭据时才冷却）
+                if config.rotate_on_transient_error
+                    && options.credential_id.is_none()
+                    && self.token_manager.has_available_besides(ctx.id)
+                {
+                    let reason = if status.as_u16() == 429 {
+                        CooldownReason::RateLimited
+                    } else {
+                        CooldownReason::ServerError
+                    };
+                    tracing::warn!(
+                        retry_after_secs = retry_after.map(|d| d.as_secs()),
+                        "API 请求失败（上游瞬态错误，冷却凭据 #{} 并切换，尝试 {}/{}）: {} {}",
+                        ctx.id,
+                        attempt + 1,
+                        max_retries,
+                        status,
+                        body
+                    );
+                    // 上游给出 Retry-After 时按其冷却（封顶于短期冷却上限），否则使用默认冷却
+                    match retry_after {
+                        Some(duration) => {
+                            self.token_manager
+                                .report_cooldown_for(ctx.id, reason, duration)
+                        }
+                        None => self.token_manager.report_cooldown(ctx.id, reason),
+                    };
+                    last_error = Some(anyhow::anyhow!(
+                        "{} API 请求失败: {} {}",
+                        api_type,
+                        status,
+                        body
+                    ));
+                    continue;
+                }
+
                 tracing::warn!(
+                    retry_after_secs = retry_after.map(|d| d.as_secs()),
                     "API 请求失败（上游瞬态错误，尝试 {}/{}）: {} {}",
                     attempt + 1,
                     max_retries,
@@ -451,7 +973,14 @@ impl KiroProvider {
                     body
                 ));
                 if attempt + 1 < max_retries {
-                    sleep(Self::retry_delay(attempt)).await;
+                    let delay = retry_after
+                        .map(|d| d.min(MAX_RETRY_AFTER_WAIT))
+                        .unwrap_or_else(|| Self::retry_delay(attempt));
+                    let delay = match deadline {
+                        Some((_, at)) => delay.min(at.saturating_duration_since(Instant::now())),
+                        None => delay,
+                    };
+                    sleep(delay).await;
                 }
                 continue;
             }
@@ -490,6 +1019,114 @@ impl KiroProvider {
         }))
     }
 
+    /// 预读成功响应，判断其中是否包含内容（非空文本或工具调用）
+    ///
+    /// 读取到首个内容事件即停止，已读取的字节与剩余响应流重新拼接为新的 Response，
+    /// 调用方可像未预读过一样继续消费。流正常结束仍无内容时返回 `false`。
+    async fn peek_for_content(
+        response: reqwest::Response,
+    ) -> anyhow::Result<(bool, reqwest::Response)> {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let mut body_stream = response.bytes_stream();
+        let mut decoder = EventStreamDecoder::new();
+        let mut buffered: Vec<Bytes> = Vec::new();
+        let mut has_content = false;
+
+        while !has_content {
+            let Some(chunk) = body_stream.next().await else {
+                break;
+            };
+            let chunk = chunk?;
+            if let Err(e) = decoder.feed(&chunk) {
+                tracing::warn!("缓冲区溢出: {}", e);
+            }
+            for frame in decoder.decode_iter().flatten() {
+                if let Ok(event) = Event::from_frame(frame) {
+                    has_content |= match &event {
+                        Event::AssistantResponse(resp) => !resp.content.is_empty(),
+                        Event::ToolUse(_) => true,
+                        _ => false,
+                    };
+                }
+            }
+            buffered.push(chunk);
+        }
+
+        let prefix = futures::stream::iter(buffered.into_iter().map(Ok::<_, reqwest::Error>));
+        let response = Self::rebuild_response(status, headers, prefix.chain(body_stream))?;
+        Ok((has_content, response))
+    }
+
+    /// 包装响应体流：读取完毕时记录完成阶段延迟与端到端耗时（[`RequestTiming`]）
+    ///
+    /// `started` 为请求发出的时间，首个非空数据块到达时记为 TTFB。
+    fn record_completion_latency(
+        response: reqwest::Response,
+        credential_id: u64,
+        model: &str,
+        started: Instant,
+    ) -> anyhow::Result<reqwest::Response> {
+        Self::track_request_timing(response, credential_id, model, started, |timing| {
+            timing.record()
+        })
+    }
+
+    /// [`record_completion_latency`](Self::record_completion_latency) 的实现，
+    /// 响应体读取完毕时把耗时交给 `on_complete`
+    fn track_request_timing(
+        response: reqwest::Response,
+        credential_id: u64,
+        model: &str,
+        started: Instant,
+        on_complete: impl FnOnce(RequestTiming) + Send + 'static,
+    ) -> anyhow::Result<reqwest::Response> {
+        let first_byte_at = Instant::now();
+        let model = model.to_string();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let first_frame = Arc::new(OnceLock::new());
+        let seen = first_frame.clone();
+        let body = response.bytes_stream().inspect(move |chunk| {
+            if matches!(chunk, Ok(bytes) if !bytes.is_empty()) {
+                seen.get_or_init(|| started.elapsed());
+            }
+        });
+        let done = futures::stream::once(async move {
+            metrics::latency().record(
+                LatencyPhase::Completion,
+                credential_id,
+                &model,
+                first_byte_at.elapsed(),
+            );
+            on_complete(RequestTiming {
+                ttfb: first_frame.get().copied(),
+                total: started.elapsed(),
+                credential_id,
+                model,
+            });
+        })
+        .filter_map(|()| async { None::<Result<Bytes, reqwest::Error>> });
+        Self::rebuild_response(status, headers, body.chain(done))
+    }
+
+    /// 以给定的状态码、响应头和字节流重新构建 Response
+    fn rebuild_response<S>(
+        status: reqwest::StatusCode,
+        headers: reqwest::header::HeaderMap,
+        stream: S,
+    ) -> anyhow::Result<reqwest::Response>
+    where
+        S: futures::Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
+    {
+        let body = reqwest::Body::wrap_stream(stream);
+        let mut builder = http::Response::builder().status(status);
+        if let Some(h) = builder.headers_mut() {
+            *h = headers;
+        }
+        Ok(reqwest::Response::from(builder.body(body)?))
+    }
+
     /// 从请求体中提取模型信息
     ///
     /// 尝试解析 JSON 请求体，提取 conversationState.currentMessage.userInputMessage.modelId
@@ -506,6 +1143,27 @@ impl KiroProvider {
             .map(|s| s.to_string())
     }
 
+    /// 替换请求体中的模型 ID（当前消息及历史中的 userInputMessage.modelId）
+    ///
+    /// 请求体无法解析或缺少当前消息的 modelId 时返回 None。
+    fn replace_model_id(request_body: &str, model_id: &str) -> Option<String> {
+        use serde_json::Value;
+
+        let mut json: Value = serde_json::from_str(request_body).ok()?;
+        let state = json.get_mut("conversationState")?;
+        *state.pointer_mut("/currentMessage/userInputMessage/modelId")? =
+            Value::String(model_id.to_string());
+        if let Some(Value::Array(history)) = state.get_mut("history") {
+            for slot in history
+                .iter_mut()
+                .filter_map(|m| m.pointer_mut("/userInputMessage/modelId"))
+            {
+                *slot = Value::String(model_id.to_string());
+            }
+        }
+        serde_json::to_string(&json).ok()
+    }
+
     fn retry_delay(attempt: usize) -> Duration {
         // 指数退避 + 少量抖动，避免上游抖动时放大故障
         const BASE_MS: u64 = 200;
@@ -517,3 +1175,284 @@ impl KiroProvider {
         Duration::from_millis(backoff.saturating_add(jitter))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::parser::frame::encode_event_frame;
+    use crate::kiro::test_support::{
+        self, mock_provider_with_credentials, spawn_chunked_mock_upstream, spawn_mock_upstream,
+        spawn_mock_upstream_with_headers, spawn_mock_upstream_with_status,
+    };
+    use crate::model::config::Config;
+    use std::sync::atomic::Ordering;
+
+    fn mock_provider(url: &str, policy: EmptyResponsePolicy) -> KiroProvider {
+        let mut config = Config::default();
+        config.empty_response_policy = policy;
+        test_support::mock_provider(url, config)
+    }
+
+    #[test]
+    fn test_request_replay_key_ignores_conversation_ids() {
+        let a = r#"{"conversationState":{"conversationId":"a","agentContinuationId":"x","history":[1]}}"#;
+        let b = r#"{"conversationState":{"conversationId":"b","agentContinuationId":"y","history":[1]}}"#;
+        let c = r#"{"conversationState":{"conversationId":"a","agentContinuationId":"x","history":[2]}}"#;
+        assert_eq!(request_replay_key(a), request_replay_key(b));
+        assert_ne!(request_replay_key(a), request_replay_key(c));
+    }
+
+    #[test]
+    fn test_parse_retry_after_seconds_and_http_date() {
+        let now = chrono::DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let parse = |v: &'static str| parse_retry_after_at(&HeaderValue::from_static(v), now);
+
+        assert_eq!(parse("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse(" 5 "), Some(Duration::from_secs(5)));
+        assert_eq!(
+            parse("Wed, 21 Oct 2015 07:28:30 GMT"),
+            Some(Duration::from_secs(30))
+        );
+        // 已过去的日期无需等待；超长值封顶
+        assert_eq!(parse("Wed, 21 Oct 2015 07:00:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse("604800"), Some(MAX_RETRY_AFTER));
+        assert_eq!(
+            parse("Wed, 28 Oct 2015 07:28:00 GMT"),
+            Some(MAX_RETRY_AFTER)
+        );
+
+        for malformed in ["", "-1", "1.5", "soon", "Wed, 99 Oct 2015 07:28:00 GMT"] {
+            assert_eq!(parse(malformed), None, "{:?}", malformed);
+        }
+    }
+
+    #[test]
+    fn test_seeded_fingerprint_keeps_credential_machine_id() {
+        let options = CallOptions {
+            fingerprint_seed: Some("seed".to_string()),
+            ..Default::default()
+        };
+        let mut credentials = KiroCredentials::default();
+        let derived = options.fingerprint_for(&credentials).unwrap();
+        assert_eq!(derived, Fingerprint::generate_from_seed("seed"));
+
+        credentials.machine_id = Some("2582956e-cc88-4669-b546-07adbffcb894".to_string());
+        let pinned = options.fingerprint_for(&credentials).unwrap();
+        assert_eq!(
+            pinned.machine_id,
+            "2582956ecc884669b54607adbffcb8942582956ecc884669b54607adbffcb894"
+        );
+        assert_eq!(pinned.system_version(), derived.system_version());
+        assert!(
+            CallOptions::default()
+                .fingerprint_for(&credentials)
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_empty_response_retry_once_then_passthrough() {
+        let (url, hits) = spawn_mock_upstream(vec![Vec::new()]).await;
+        let provider = mock_provider(&url, EmptyResponsePolicy::RetryOnce);
+
+        let response = provider.call_api_stream("{}", &CallOptions::default()).await.unwrap();
+        assert!(response.status().is_success());
+        assert!(response.bytes().await.unwrap().is_empty());
+
+        assert_eq!(hits.load(Ordering::SeqCst), 2, "应重试一次后透传空响应");
+        assert!(!provider.token_manager.cooldowns().is_available(1));
+    }
+
+    #[tokio::test]
+    async fn test_empty_response_retry_returns_content_from_second_attempt() {
+        let frame = encode_event_frame("assistantResponseEvent", r#"{"content":"hello"}"#);
+        let (url, hits) = spawn_mock_upstream(vec![Vec::new(), frame.clone()]).await;
+        let provider = mock_provider(&url, EmptyResponsePolicy::RetryOnce);
+
+        let response = provider.call_api("{}", &CallOptions::default()).await.unwrap();
+        assert_eq!(response.bytes().await.unwrap().as_ref(), frame.as_slice());
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_empty_response_passthrough_does_not_retry() {
+        let (url, hits) = spawn_mock_upstream(vec![Vec::new()]).await;
+        let provider = mock_provider(&url, EmptyResponsePolicy::Passthrough);
+
+        let response = provider.call_api_stream("{}", &CallOptions::default()).await.unwrap();
+        assert!(response.bytes().await.unwrap().is_empty());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        assert!(provider.token_manager.cooldowns().is_available(1));
+    }
+
+    #[tokio::test]
+    async fn test_empty_response_retry_uses_full_budget() {
+        let (url, hits) = spawn_mock_upstream(vec![Vec::new()]).await;
+        let provider = mock_provider(&url, EmptyResponsePolicy::Retry);
+
+        let response = provider.call_api_stream("{}", &CallOptions::default()).await.unwrap();
+        assert!(response.bytes().await.unwrap().is_empty());
+        assert_eq!(
+            hits.load(Ordering::SeqCst),
+            Config::default().upstream_max_retries
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_probe_reenables_credential_once_upstream_recovers() {
+        let (url, hits) = spawn_mock_upstream_with_status(vec![
+            (axum::http::StatusCode::FORBIDDEN, b"denied".to_vec()),
+            (axum::http::StatusCode::OK, Vec::new()),
+        ])
+        .await;
+        let provider = mock_provider(&url, EmptyResponsePolicy::Passthrough);
+
+        // 模拟连续认证失败导致的自动禁用
+        for _ in 0..3 {
+            provider.token_manager.report_failure(1);
+        }
+        assert_eq!(provider.token_manager.available_count(), 0);
+        assert_eq!(provider.token_manager.probe_candidates(), vec![1]);
+
+        // 第一轮：上游仍拒绝，凭据保持禁用
+        assert_eq!(provider.run_slow_probe_pass().await, 0);
+        assert_eq!(provider.token_manager.available_count(), 0);
+
+        // 第二轮：上游恢复，凭据被重新启用
+        assert_eq!(provider.run_slow_probe_pass().await, 1);
+        assert_eq!(provider.token_manager.available_count(), 1);
+        assert!(provider.token_manager.probe_candidates().is_empty());
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_transient_errors_rotate_credentials_until_success() {
+        let frame = encode_event_frame("assistantResponseEvent", r#"{"content":"hello"}"#);
+        let (url, hits) = spawn_mock_upstream_with_status(vec![
+            (
+                axum::http::StatusCode::SERVICE_UNAVAILABLE,
+                b"busy".to_vec(),
+            ),
+            (
+                axum::http::StatusCode::TOO_MANY_REQUESTS,
+                b"slow down".to_vec(),
+            ),
+            (axum::http::StatusCode::OK, frame.clone()),
+        ])
+        .await;
+        let mut config = Config::default();
+        config.rotate_on_transient_error = true;
+        let provider = mock_provider_with_credentials(&url, config, 3);
+
+        let response = provider
+            .call_api("{}", &CallOptions::default())
+            .await
+            .unwrap();
+        let attempts = response.extensions().get::<CredentialAttempts>().unwrap();
+        assert_eq!(attempts.0, vec![1, 2, 3]);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), frame.as_slice());
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+        let mut reasons: Vec<(u64, CooldownReason)> = provider
+            .token_manager
+            .cooldowns()
+            .get_all_cooldowns_at(Instant::now())
+            .into_iter()
+            .map(|(id, reason, _)| (id, reason))
+            .collect();
+        reasons.sort_by_key(|(id, _)| *id);
+        assert_eq!(
+            reasons,
+            vec![
+                (1, CooldownReason::ServerError),
+                (2, CooldownReason::RateLimited)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_sets_rotated_credential_cooldown() {
+        let frame = encode_event_frame("assistantResponseEvent", r#"{"content":"ok"}"#);
+        let (url, _) = spawn_mock_upstream_with_headers(vec![
+            (
+                axum::http::StatusCode::TOO_MANY_REQUESTS,
+                vec![("retry-after", "120")],
+                b"slow down".to_vec(),
+            ),
+            (axum::http::StatusCode::OK, Vec::new(), frame),
+        ])
+        .await;
+        let mut config = Config::default();
+        config.rotate_on_transient_error = true;
+        let provider = mock_provider_with_credentials(&url, config, 2);
+
+        provider
+            .call_api("{}", &CallOptions::default())
+            .await
+            .unwrap();
+        let cooldowns = provider
+            .token_manager
+            .cooldowns()
+            .get_all_cooldowns_at(Instant::now());
+        assert_eq!(cooldowns.len(), 1);
+        let (id, reason, remaining) = cooldowns[0];
+        assert_eq!((id, reason), (1, CooldownReason::RateLimited));
+        // 按 Retry-After 冷却，而非 RateLimited 的默认时长
+        assert!(
+            remaining > Duration::from_secs(115) && remaining <= Duration::from_secs(120),
+            "{:?}",
+            remaining
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bad_request_is_not_retried_with_rotation() {
+        let (url, hits) = spawn_mock_upstream_with_status(vec![(
+            axum::http::StatusCode::BAD_REQUEST,
+            b"bad".to_vec(),
+        )])
+        .await;
+        let mut config = Config::default();
+        config.rotate_on_transient_error = true;
+        let provider = mock_provider_with_credentials(&url, config, 3);
+
+        assert!(
+            provider
+                .call_api("{}", &CallOptions::default())
+                .await
+                .is_err()
+        );
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        assert!(provider.token_manager.cooldowns().is_available(1));
+    }
+
+    #[tokio::test]
+    async fn test_request_timing_ttfb_precedes_total() {
+        let url = spawn_chunked_mock_upstream(
+            vec![b"first".to_vec(), b"second".to_vec()],
+            Duration::from_millis(200),
+        )
+        .await;
+        let started = Instant::now();
+        let response = Client::new().post(&url).send().await.unwrap();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let response =
+            KiroProvider::track_request_timing(response, 7, "timing-test", started, move |t| {
+                let _ = tx.send(t);
+            })
+            .unwrap();
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"firstsecond");
+
+        let timing = rx.await.unwrap();
+        let ttfb = timing.ttfb.unwrap();
+        assert_eq!(timing.credential_id, 7);
+        assert_eq!(timing.model, "timing-test");
+        assert!(
+            timing.total >= ttfb + Duration::from_millis(150),
+            "{:?}",
+            timing
+        );
+    }
+}
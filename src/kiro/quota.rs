@@ -0,0 +1,251 @@
+//! 凭据请求配额
+//!
+//! 在速率与冷却之外，为凭据设置硬性的请求数上限（如每天 1000 次），
+//! 以避免触及账号级的软限制。每个凭据可配置多个滚动窗口，
+//! 任一窗口内的请求数达到上限即视为配额用尽，直到最早的请求滑出窗口。
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::kiro::model::credentials::RequestQuota;
+
+/// 单个配额窗口的使用情况（用于 Admin API 展示）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaUsage {
+    /// 窗口长度（秒）
+    pub window_secs: u64,
+    /// 窗口内允许的最大请求数
+    pub max_requests: u32,
+    /// 窗口内已发出的请求数
+    pub used: u32,
+    /// 剩余可用请求数
+    pub remaining: u32,
+    /// 配额用尽时距离恢复的秒数（未用尽时为 None）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resets_in_secs: Option<u64>,
+}
+
+/// 配额是否有效（窗口或上限为 0 的配额被忽略）
+fn is_effective(quota: &RequestQuota) -> bool {
+    quota.window_secs > 0 && quota.max_requests > 0
+}
+
+/// 请求配额跟踪器
+///
+/// 按凭据记录请求时间戳，只保留最长配额窗口内的记录。
+#[derive(Default)]
+pub struct QuotaTracker {
+    requests: Mutex<HashMap<u64, VecDeque<Instant>>>,
+}
+
+impl QuotaTracker {
+    /// 创建配额跟踪器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 以指定时间为"当前时间"检查配额并记录一次请求（同一把锁内完成）
+    ///
+    /// 未配置有效配额时不记录，直接放行。未用尽时记录，返回记录后的恢复时间（本次请求用完配额时为 Some）；
+    /// 已用尽时不记录，返回 `Err(恢复时间)`。并发请求不会同时通过检查而超出配额。
+    pub fn try_record_at(
+        &self,
+        credential_id: u64,
+        quotas: &[RequestQuota],
+        now: Instant,
+    ) -> Result<Option<Instant>, Instant> {
+        let Some(longest) = longest_window(quotas) else {
+            return Ok(None);
+        };
+
+        let mut requests = self.requests.lock();
+        let timestamps = requests.entry(credential_id).or_default();
+        if let Some(until) = exhausted_until_in(Some(timestamps), quotas, now) {
+            return Err(until);
+        }
+        push_request(timestamps, longest, now);
+        Ok(exhausted_until_in(Some(timestamps), quotas, now))
+    }
+
+    /// 配额用尽时返回恢复时间（多个窗口同时用尽时取最晚者），未用尽返回 None
+    pub fn exhausted_until(
+        &self,
+        credential_id: u64,
+        quotas: &[RequestQuota],
+        now: Instant,
+    ) -> Option<Instant> {
+        exhausted_until_in(self.requests.lock().get(&credential_id), quotas, now)
+    }
+
+    /// 凭据在指定时间是否已用尽任一配额
+    pub fn is_exhausted(&self, credential_id: u64, quotas: &[RequestQuota], now: Instant) -> bool {
+        self.exhausted_until(credential_id, quotas, now).is_some()
+    }
+
+    /// 各配额窗口的使用情况
+    pub fn usage(
+        &self,
+        credential_id: u64,
+        quotas: &[RequestQuota],
+        now: Instant,
+    ) -> Vec<QuotaUsage> {
+        usage_in(self.requests.lock().get(&credential_id), quotas, now)
+            .into_iter()
+            .map(|(usage, _)| usage)
+            .collect()
+    }
+}
+
+/// 最长的有效配额窗口（无有效配额时为 None）
+fn longest_window(quotas: &[RequestQuota]) -> Option<Duration> {
+    quotas
+        .iter()
+        .filter(|q| is_effective(q))
+        .map(|q| Duration::from_secs(q.window_secs))
+        .max()
+}
+
+/// 追加一条请求记录，并丢弃已滑出最长窗口的记录
+fn push_request(timestamps: &mut VecDeque<Instant>, longest: Duration, now: Instant) {
+    if let Some(window_start) = now.checked_sub(longest) {
+        while timestamps.front().is_some_and(|&t| t <= window_start) {
+            timestamps.pop_front();
+        }
+    }
+    timestamps.push_back(now);
+}
+
+/// 配额用尽时的恢复时间（多个窗口同时用尽时取最晚者）
+fn exhausted_until_in(
+    timestamps: Option<&VecDeque<Instant>>,
+    quotas: &[RequestQuota],
+    now: Instant,
+) -> Option<Instant> {
+    usage_in(timestamps, quotas, now)
+        .into_iter()
+        .filter_map(|(_, reset_at)| reset_at)
+        .max()
+}
+
+/// 计算各有效配额的使用情况及（用尽时的）恢复时间
+fn usage_in(
+    timestamps: Option<&VecDeque<Instant>>,
+    quotas: &[RequestQuota],
+    now: Instant,
+) -> Vec<(QuotaUsage, Option<Instant>)> {
+    quotas
+        .iter()
+        .filter(|q| is_effective(q))
+        .map(|quota| {
+            let window = Duration::from_secs(quota.window_secs);
+            let in_window: Vec<Instant> = timestamps
+                .into_iter()
+                .flatten()
+                .copied()
+                .filter(|&t| now.checked_sub(window).is_none_or(|ws| t > ws))
+                .collect();
+            let used = in_window.len().min(u32::MAX as usize) as u32;
+
+            // 请求数需降到上限以下才可再次使用：第 (used - max) 条（从 0 计）滑出窗口之时
+            let reset_at = (used >= quota.max_requests)
+                .then(|| in_window[(used - quota.max_requests) as usize] + window);
+
+            let usage = QuotaUsage {
+                window_secs: quota.window_secs,
+                max_requests: quota.max_requests,
+                used,
+                remaining: quota.max_requests.saturating_sub(used),
+                resets_in_secs: reset_at.map(|t| t.saturating_duration_since(now).as_secs()),
+            };
+            (usage, reset_at)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOURLY_3: RequestQuota = RequestQuota {
+        window_secs: 3600,
+        max_requests: 3,
+    };
+
+    #[test]
+    fn test_quota_exhausts_and_recovers_when_window_rolls() {
+        let tracker = QuotaTracker::new();
+        let t0 = Instant::now();
+        for i in 0..3 {
+            assert!(!tracker.is_exhausted(1, &[HOURLY_3], t0));
+            let at = t0 + Duration::from_secs(i * 60);
+            assert!(tracker.try_record_at(1, &[HOURLY_3], at).is_ok());
+        }
+
+        let now = t0 + Duration::from_secs(180);
+        assert_eq!(
+            tracker.exhausted_until(1, &[HOURLY_3], now),
+            Some(t0 + Duration::from_secs(3600))
+        );
+        let usage = tracker.usage(1, &[HOURLY_3], now);
+        assert_eq!(usage[0].used, 3);
+        assert_eq!(usage[0].remaining, 0);
+        assert_eq!(usage[0].resets_in_secs, Some(3420));
+
+        // 第一条请求滑出窗口后恢复一次额度
+        let rolled = t0 + Duration::from_secs(3600);
+        assert!(!tracker.is_exhausted(1, &[HOURLY_3], rolled));
+        assert_eq!(tracker.usage(1, &[HOURLY_3], rolled)[0].remaining, 1);
+        assert!(!tracker.is_exhausted(2, &[HOURLY_3], now));
+    }
+
+    #[test]
+    fn test_try_record_rejects_without_recording_once_exhausted() {
+        let tracker = QuotaTracker::new();
+        let t0 = Instant::now();
+        assert_eq!(tracker.try_record_at(1, &[HOURLY_3], t0), Ok(None));
+        assert_eq!(tracker.try_record_at(1, &[HOURLY_3], t0), Ok(None));
+        // 第三次用完配额，返回恢复时间
+        let reset = t0 + Duration::from_secs(3600);
+        assert_eq!(tracker.try_record_at(1, &[HOURLY_3], t0), Ok(Some(reset)));
+        // 已用尽：拒绝且不计入
+        assert_eq!(tracker.try_record_at(1, &[HOURLY_3], t0), Err(reset));
+        assert_eq!(tracker.usage(1, &[HOURLY_3], t0)[0].used, 3);
+        // 未配置配额时总是放行
+        assert_eq!(tracker.try_record_at(2, &[], t0), Ok(None));
+    }
+
+    #[test]
+    fn test_multiple_windows_use_latest_reset_and_ignore_invalid_quotas() {
+        let daily_4 = RequestQuota {
+            window_secs: 86400,
+            max_requests: 4,
+        };
+        let invalid = RequestQuota {
+            window_secs: 0,
+            max_requests: 1,
+        };
+        let quotas = [HOURLY_3, daily_4, invalid];
+        let tracker = QuotaTracker::new();
+        let t0 = Instant::now();
+        for i in 0..4 {
+            // 只按每日配额放行，使每小时窗口超出上限
+            let at = t0 + Duration::from_secs(i);
+            assert!(tracker.try_record_at(1, &[daily_4], at).is_ok());
+        }
+
+        // 两个窗口同时用尽时以每日窗口为准
+        let now = t0 + Duration::from_secs(10);
+        assert_eq!(
+            tracker.exhausted_until(1, &quotas, now),
+            Some(t0 + Duration::from_secs(86400))
+        );
+        assert_eq!(tracker.usage(1, &quotas, now).len(), 2);
+
+        // 每小时窗口恢复后，每日窗口仍然用尽
+        assert!(tracker.is_exhausted(1, &quotas, t0 + Duration::from_secs(7200)));
+        assert!(tracker.usage(1, &[invalid], now).is_empty());
+    }
+}
@@ -0,0 +1,36 @@
+//! 慢速探测后台任务
+//!
+//! 因认证失败等原因被自动禁用的凭据不会自愈，但底层问题（如 IAM 瞬时故障）
+//! 有时会自行恢复。本任务以很长的间隔（小时级）探测这些凭据，
+//! 探测成功即重新启用，避免频繁打扰确已失效的账号。
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior, interval_at};
+
+use crate::kiro::provider::KiroProvider;
+
+/// 最小探测间隔（1 小时）
+pub const MIN_SLOW_PROBE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// 启动慢速探测任务
+///
+/// 首轮探测在一个间隔之后执行（启动时不探测），间隔低于 [`MIN_SLOW_PROBE_INTERVAL`] 时按下限处理。
+pub fn spawn_slow_probe(provider: Arc<KiroProvider>, interval: Duration) -> JoinHandle<()> {
+    let interval = interval.max(MIN_SLOW_PROBE_INTERVAL);
+    tracing::info!("慢速探测已启用，间隔 {} 秒", interval.as_secs());
+
+    tokio::spawn(async move {
+        let mut ticker = interval_at(Instant::now() + interval, interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let restored = provider.run_slow_probe_pass().await;
+            if restored > 0 {
+                tracing::info!("慢速探测完成，已重新启用 {} 个凭据", restored);
+            }
+        }
+    })
+}
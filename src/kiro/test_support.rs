@@ -0,0 +1,197 @@
+//! 测试辅助：本地 mock 上游与对应的 Provider
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures::StreamExt;
+use reqwest::RequestBuilder;
+
+use crate::kiro::endpoint::{KiroEndpoint, RequestContext};
+use crate::kiro::model::credentials::KiroCredentials;
+use crate::kiro::provider::KiroProvider;
+use crate::kiro::token_manager::MultiTokenManager;
+use crate::model::config::Config;
+
+/// 测试用端点：将请求发往本地 mock 上游
+pub(crate) struct MockEndpoint {
+    pub url: String,
+}
+
+impl KiroEndpoint for MockEndpoint {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn api_url(&self, _ctx: &RequestContext<'_>) -> String {
+        self.url.clone()
+    }
+
+    fn mcp_url(&self, _ctx: &RequestContext<'_>) -> String {
+        self.url.clone()
+    }
+
+    fn decorate_api(&self, req: RequestBuilder, _ctx: &RequestContext<'_>) -> RequestBuilder {
+        req
+    }
+
+    fn decorate_mcp(&self, req: RequestBuilder, _ctx: &RequestContext<'_>) -> RequestBuilder {
+        req
+    }
+
+    fn transform_api_body(&self, body: &str, _ctx: &RequestContext<'_>) -> String {
+        body.to_string()
+    }
+}
+
+/// 启动 mock 上游：第 n 次请求返回 200 + `responses[n]`，超出后重复最后一个
+pub(crate) async fn spawn_mock_upstream(responses: Vec<Vec<u8>>) -> (String, Arc<AtomicUsize>) {
+    spawn_mock_upstream_with_status(
+        responses
+            .into_iter()
+            .map(|body| (axum::http::StatusCode::OK, body))
+            .collect(),
+    )
+    .await
+}
+
+/// 启动 mock 上游：第 n 次请求返回 `responses[n]`（状态码 + 响应体），超出后重复最后一个
+pub(crate) async fn spawn_mock_upstream_with_status(
+    responses: Vec<(axum::http::StatusCode, Vec<u8>)>,
+) -> (String, Arc<AtomicUsize>) {
+    spawn_mock_upstream_with_headers(
+        responses
+            .into_iter()
+            .map(|(status, body)| (status, Vec::new(), body))
+            .collect(),
+    )
+    .await
+}
+
+/// mock 上游的单个响应：状态码 + 响应头 + 响应体
+pub(crate) type MockResponse = (
+    axum::http::StatusCode,
+    Vec<(&'static str, &'static str)>,
+    Vec<u8>,
+);
+
+/// 启动 mock 上游：第 n 次请求返回 `responses[n]`，超出后重复最后一个
+pub(crate) async fn spawn_mock_upstream_with_headers(
+    responses: Vec<MockResponse>,
+) -> (String, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let responses = Arc::new(responses);
+    let handler_hits = hits.clone();
+    let app = axum::Router::new().route(
+        "/generateAssistantResponse",
+        axum::routing::post(move || {
+            let hits = handler_hits.clone();
+            let responses = responses.clone();
+            async move {
+                let n = hits.fetch_add(1, Ordering::SeqCst);
+                let (status, headers, body) = responses[n.min(responses.len() - 1)].clone();
+                let mut response = axum::response::IntoResponse::into_response((status, body));
+                for (name, value) in headers {
+                    response.headers_mut().insert(
+                        name,
+                        axum::http::HeaderValue::from_static(value),
+                    );
+                }
+                response
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}/generateAssistantResponse", addr), hits)
+}
+
+/// 启动 mock 上游：每次请求等待 `delay` 后返回 200 + `body`
+pub(crate) async fn spawn_slow_mock_upstream(
+    delay: std::time::Duration,
+    body: Vec<u8>,
+) -> (String, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let handler_hits = hits.clone();
+    let app = axum::Router::new().route(
+        "/generateAssistantResponse",
+        axum::routing::post(move || {
+            let body = body.clone();
+            handler_hits.fetch_add(1, Ordering::SeqCst);
+            async move {
+                tokio::time::sleep(delay).await;
+                body
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}/generateAssistantResponse", addr), hits)
+}
+
+/// 启动 mock 上游：每次请求返回 200，依次发送 `chunks`，相邻分块之间等待 `delay`
+pub(crate) async fn spawn_chunked_mock_upstream(
+    chunks: Vec<Vec<u8>>,
+    delay: std::time::Duration,
+) -> String {
+    let app = axum::Router::new().route(
+        "/generateAssistantResponse",
+        axum::routing::post(move || {
+            let chunks = chunks.clone();
+            async move {
+                let stream = futures::stream::iter(chunks.into_iter().enumerate()).then(
+                    move |(i, chunk)| async move {
+                        if i > 0 {
+                            tokio::time::sleep(delay).await;
+                        }
+                        Ok::<_, std::convert::Infallible>(chunk)
+                    },
+                );
+                axum::body::Body::from_stream(stream)
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}/generateAssistantResponse", addr)
+}
+
+/// 构建一个使用单个 API Key 凭据、所有请求发往 `url` 的 Provider
+pub(crate) fn mock_provider(url: &str, config: Config) -> KiroProvider {
+    mock_provider_with_credentials(url, config, 1)
+}
+
+/// 构建一个使用 `count` 个 API Key 凭据（ID 1..=count）、所有请求发往 `url` 的 Provider
+pub(crate) fn mock_provider_with_credentials(
+    url: &str,
+    config: Config,
+    count: usize,
+) -> KiroProvider {
+    let endpoint = MockEndpoint {
+        url: url.to_string(),
+    };
+    provider_with_endpoint(Arc::new(endpoint), config, count)
+}
+
+/// 构建一个使用 `count` 个 API Key 凭据（ID 1..=count）、只注册 `endpoint` 的 Provider
+pub(crate) fn provider_with_endpoint(
+    endpoint: Arc<dyn KiroEndpoint>,
+    config: Config,
+    count: usize,
+) -> KiroProvider {
+    let credentials = (1..=count)
+        .map(|i| KiroCredentials {
+            kiro_api_key: Some(format!("ksk_test_key_{}", i)),
+            auth_method: Some("api_key".to_string()),
+            ..Default::default()
+        })
+        .collect();
+    let token_manager =
+        Arc::new(MultiTokenManager::new(config, credentials, None, None, false).unwrap());
+    let name = endpoint.name().to_string();
+    let endpoints = HashMap::from([(name.clone(), endpoint)]);
+    KiroProvider::with_proxy(token_manager, None, endpoints, name)
+}
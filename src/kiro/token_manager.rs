@@ -10,19 +10,22 @@ use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use tokio::sync::Mutex as TokioMutex;
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::fmt;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration as StdDuration, Instant};
 
 use crate::http_client::{ProxyConfig, build_client};
+use crate::kiro::cooldown::{CooldownInfo, CooldownManager, CooldownReason};
+use crate::kiro::fingerprint::Fingerprint;
 use crate::kiro::machine_id;
-use crate::kiro::model::credentials::KiroCredentials;
+use crate::kiro::model::credentials::{KiroCredentials, RequestQuota, normalize_tags};
 use crate::kiro::model::token_refresh::{
     IdcRefreshRequest, IdcRefreshResponse, RefreshRequest, RefreshResponse,
 };
-use crate::kiro::model::usage_limits::UsageLimitsResponse;
+use crate::kiro::model::usage_limits::{QuotaInfo, UsageLimitsResponse};
+use crate::kiro::quota::{QuotaTracker, QuotaUsage};
 use crate::model::config::Config;
 
 /// 检查 Token 是否在指定时间内过期
@@ -103,6 +106,60 @@ impl fmt::Display for RefreshTokenInvalidError {
 
 impl std::error::Error for RefreshTokenInvalidError {}
 
+/// 模型在所有可用凭据上均处于模型级冷却
+///
+/// 凭据本身健康，仅该模型暂不可用；调用方可据此回退到备用模型。
+#[derive(Debug)]
+pub(crate) struct ModelUnavailableError {
+    pub model: String,
+}
+
+impl fmt::Display for ModelUnavailableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "模型 {} 在所有可用凭据上均暂不可用", self.model)
+    }
+}
+
+impl std::error::Error for ModelUnavailableError {}
+
+/// 全局冷却生效中，所有凭据均暂停使用
+///
+/// 与凭据级冷却不同，全局冷却不做“全部冷却时忽略冷却”的软性降级，请求直接失败。
+#[derive(Debug)]
+pub(crate) struct GlobalCooldownError {
+    pub reason: CooldownReason,
+    pub remaining: StdDuration,
+}
+
+impl fmt::Display for GlobalCooldownError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "全局冷却中（{}），剩余 {} 秒",
+            self.reason.description(),
+            self.remaining.as_secs()
+        )
+    }
+}
+
+impl std::error::Error for GlobalCooldownError {}
+
+/// 指定凭据的请求配额已用尽
+///
+/// 固定凭据的请求（canary、慢探测、Admin 测试调用等）不参与负载均衡，配额用尽时直接失败。
+#[derive(Debug)]
+pub(crate) struct QuotaExhaustedError {
+    pub credential_id: u64,
+}
+
+impl fmt::Display for QuotaExhaustedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "凭据 #{} 请求配额已用尽", self.credential_id)
+    }
+}
+
+impl std::error::Error for QuotaExhaustedError {}
+
 /// 刷新 Token
 pub(crate) async fn refresh_token(
     credentials: &KiroCredentials,
@@ -388,7 +445,9 @@ pub(crate) async fn get_usage_limits(
         bail!("{}: {} {}", error_msg, status, body_text);
     }
 
-    let data: UsageLimitsResponse = response.json().await?;
+    let quota = QuotaInfo::from_headers(response.headers(), Utc::now().timestamp() as f64);
+    let mut data: UsageLimitsResponse = response.json().await?;
+    data.quota = quota;
     Ok(data)
 }
 
@@ -431,6 +490,20 @@ enum DisabledReason {
     InvalidRefreshToken,
     /// 凭据配置无效（如 authMethod=api_key 但缺少 kiroApiKey）
     InvalidConfig,
+    /// 统计窗口内冷却时长占比过高（长期不健康，需人工复核）
+    CooldownBudgetExceeded,
+}
+
+impl DisabledReason {
+    /// 是否为认证类的自动禁用（不会自愈，需慢速探测确认恢复）
+    ///
+    /// 手动禁用、配置无效与额度用尽不参与探测。
+    fn is_probe_eligible(&self) -> bool {
+        matches!(
+            self,
+            Self::TooManyFailures | Self::TooManyRefreshFailures | Self::InvalidRefreshToken
+        )
+    }
 }
 
 /// 统计数据持久化条目
@@ -487,6 +560,16 @@ pub struct CredentialEntrySnapshot {
     /// 端点名称（未显式配置时返回 None，由 Admin 层回退到默认值）
     #[serde(skip_serializing_if = "Option::is_none")]
     pub endpoint: Option<String>,
+    /// 统计窗口内累计冷却时长（秒）
+    pub cooldown_secs_in_window: u64,
+    /// 当前冷却原因（未处于冷却中时为 None）
+    pub cooldown_reason: Option<String>,
+    /// 当前冷却剩余时长（秒）
+    pub cooldown_remaining_secs: Option<u64>,
+    /// 各请求配额窗口的使用情况（未配置配额时为空）
+    pub quota_usage: Vec<QuotaUsage>,
+    /// 凭据标签
+    pub tags: Vec<String>,
 }
 
 /// 凭据管理器状态快照
@@ -526,10 +609,32 @@ pub struct MultiTokenManager {
     last_stats_save_at: Mutex<Option<Instant>>,
     /// 统计数据是否有未落盘更新
     stats_dirty: AtomicBool,
+    /// 凭据冷却状态（短期不可用，到期自动恢复）
+    cooldowns: CooldownManager,
+    /// 凭据级请求配额使用情况
+    quotas: QuotaTracker,
+    /// 标签索引（标签 → 凭据 ID 集合），凭据增删或标签变更后重建
+    tag_index: Mutex<HashMap<String, BTreeSet<u64>>>,
+    /// 同一请求在同一凭据上被 400 拒绝的次数：(凭据 ID, 请求指纹) → 次数
+    rejected_requests: Mutex<HashMap<(u64, u64), u32>>,
+}
+
+/// 支持的负载均衡模式
+pub const LOAD_BALANCING_MODES: [&str; 3] = ["priority", "balanced", "weighted-random"];
+
+/// weighted-random 模式下凭据的选择权重
+///
+/// priority 数字越小权重越大：0 → 1，1 → 1/2，3 → 1/4
+fn selection_weight(priority: u32) -> f64 {
+    1.0 / (priority as f64 + 1.0)
 }
 
 /// 每个凭据最大 API 调用失败次数
 const MAX_FAILURES_PER_CREDENTIAL: u32 = 3;
+/// 同一请求在同一凭据上被 400 拒绝多少次后进入 `RequestRejected` 冷却
+const MAX_REJECTIONS_PER_REQUEST: u32 = 3;
+/// 被拒绝请求计数表的容量上限（超出时整体清空，防止无界增长）
+const MAX_TRACKED_REJECTIONS: usize = 1024;
 /// 统计数据持久化防抖间隔
 const STATS_SAVE_DEBOUNCE: StdDuration = StdDuration::from_secs(30);
 
@@ -545,6 +650,8 @@ pub struct CallContext {
     pub credentials: KiroCredentials,
     /// 访问 Token
     pub token: String,
+    /// 本次获取上下文时的 Token 刷新耗时（未刷新时为 None）
+    pub token_refresh: Option<StdDuration>,
 }
 
 impl MultiTokenManager {
@@ -574,6 +681,7 @@ impl MultiTokenManager {
             .into_iter()
             .map(|mut cred| {
                 cred.canonicalize_auth_method();
+                cred.tags = normalize_tags(&cred.tags);
                 let id = cred.id.unwrap_or_else(|| {
                     let id = next_id;
                     next_id += 1;
@@ -644,6 +752,14 @@ impl MultiTokenManager {
             .unwrap_or(0);
 
         let load_balancing_mode = config.load_balancing_mode.clone();
+        let cooldowns = CooldownManager::new()
+            .with_budget_window(StdDuration::from_secs(config.cooldown_budget_window_secs))
+            .with_jitter(config.cooldown_jitter)
+            .with_decay_interval(StdDuration::from_secs(config.cooldown_decay_interval_secs))
+            .with_backoff(
+                config.cooldown_backoff_strategy,
+                config.cooldown_backoff_base,
+            );
         let manager = Self {
             config,
             proxy,
@@ -655,7 +771,12 @@ impl MultiTokenManager {
             load_balancing_mode: Mutex::new(load_balancing_mode),
             last_stats_save_at: Mutex::new(None),
             stats_dirty: AtomicBool::new(false),
+            cooldowns,
+            quotas: QuotaTracker::new(),
+            tag_index: Mutex::new(HashMap::new()),
+            rejected_requests: Mutex::new(HashMap::new()),
         };
+        manager.rebuild_tag_index();
 
         // 如果有新分配的 ID 或新生成的 machineId，立即持久化到配置文件
         if has_new_ids || has_new_machine_ids {
@@ -677,6 +798,11 @@ impl MultiTokenManager {
         &self.config
     }
 
+    /// 获取冷却管理器的引用
+    pub(crate) fn cooldowns(&self) -> &CooldownManager {
+        &self.cooldowns
+    }
+
     /// 获取凭据总数
     pub fn total_count(&self) -> usize {
         self.entries.lock().len()
@@ -687,14 +813,35 @@ impl MultiTokenManager {
         self.entries.lock().iter().filter(|e| !e.disabled).count()
     }
 
+    /// 除指定凭据外是否还有可参与选择的凭据（未禁用且不在冷却中）
+    pub fn has_available_besides(&self, id: u64) -> bool {
+        self.entries
+            .lock()
+            .iter()
+            .any(|e| e.id != id && !e.disabled && self.cooldowns.is_available(e.id))
+    }
+
     /// 根据负载均衡模式选择下一个凭据
     ///
     /// - priority 模式：选择优先级最高（priority 最小）的可用凭据
     /// - balanced 模式：均衡选择可用凭据
+    /// - weighted-random 模式：按优先级加权随机选择可用凭据
     ///
     /// # 参数
     /// - `model`: 可选的模型名称，用于过滤支持该模型的凭据（如 opus 模型需要付费订阅）
     fn select_next_credential(&self, model: Option<&str>) -> Option<(u64, KiroCredentials)> {
+        self.select_next_credential_at(model, Instant::now())
+    }
+
+    /// 以指定时间为"当前时间"选择下一个凭据
+    ///
+    /// 请求配额已用尽、或所请求模型处于模型级冷却的凭据总是被跳过（硬性限制），
+    /// 冷却中的凭据仅在有其他选择时跳过。
+    fn select_next_credential_at(
+        &self,
+        model: Option<&str>,
+        now: Instant,
+    ) -> Option<(u64, KiroCredentials)> {
         let entries = self.entries.lock();
 
         // 检查是否是 opus 模型
@@ -713,7 +860,12 @@ impl MultiTokenManager {
                 if is_opus && !e.credentials.supports_opus() {
                     return false;
                 }
-                true
+                if model.is_some_and(|m| !self.cooldowns.is_model_available_at(e.id, m, now)) {
+                    return false;
+                }
+                !self
+                    .quotas
+                    .is_exhausted(e.id, &e.credentials.request_quotas, now)
             })
             .collect();
 
@@ -721,6 +873,30 @@ impl MultiTokenManager {
             return None;
         }
 
+        // 优先跳过冷却中的凭据；全部处于冷却时忽略可自动恢复的冷却
+        // （冷却是软性降级，不应直接导致请求失败；配额冷却等硬性限制仍然生效）
+        let not_cooling: Vec<_> = available
+            .iter()
+            .copied()
+            .filter(|e| self.cooldowns.is_available_at(e.id, now))
+            .collect();
+        let available = if not_cooling.is_empty() {
+            let recoverable: Vec<_> = available
+                .into_iter()
+                .filter(|e| {
+                    self.cooldowns
+                        .cooldown_reason_at(e.id, now)
+                        .is_none_or(|r| r.is_auto_recoverable())
+                })
+                .collect();
+            if recoverable.is_empty() {
+                return None;
+            }
+            recoverable
+        } else {
+            not_cooling
+        };
+
         let mode = self.load_balancing_mode.lock().clone();
         let mode = mode.as_str();
 
@@ -734,6 +910,22 @@ impl MultiTokenManager {
 
                 Some((entry.id, entry.credentials.clone()))
             }
+            "weighted-random" => {
+                // 选择概率与权重成正比，优先级高的凭据更常被选中但不独占流量
+                let total: f64 = available
+                    .iter()
+                    .map(|e| selection_weight(e.credentials.priority))
+                    .sum();
+                let mut point = fastrand::f64() * total;
+                let entry = available
+                    .iter()
+                    .find(|e| {
+                        point -= selection_weight(e.credentials.priority);
+                        point < 0.0
+                    })
+                    .or(available.last())?;
+                Some((entry.id, entry.credentials.clone()))
+            }
             _ => {
                 // priority 模式（默认）：选择优先级最高的
                 let entry = available.iter().min_by_key(|e| e.credentials.priority)?;
@@ -757,6 +949,10 @@ impl MultiTokenManager {
         let max_attempts = (total * MAX_FAILURES_PER_CREDENTIAL as usize).max(1);
         let mut attempt_count = 0;
 
+        if let Some((reason, remaining)) = self.cooldowns.global_cooldown() {
+            return Err(GlobalCooldownError { reason, remaining }.into());
+        }
+
         loop {
             if attempt_count >= max_attempts {
                 anyhow::bail!(
@@ -767,9 +963,12 @@ impl MultiTokenManager {
             }
 
             let (id, credentials) = {
-                let is_balanced = self.load_balancing_mode.lock().as_str() == "balanced";
+                let is_balanced = matches!(
+                    self.load_balancing_mode.lock().as_str(),
+                    "balanced" | "weighted-random"
+                );
 
-                // balanced 模式：每次请求都重新均衡选择，不固定 current_id
+                // balanced / weighted-random 模式：每次请求都重新选择，不固定 current_id
                 // priority 模式：优先使用 current_id 指向的凭据
                 let current_hit = if is_balanced {
                     None
@@ -778,14 +977,26 @@ impl MultiTokenManager {
                     let current_id = *self.current_id.lock();
                     entries
                         .iter()
-                        .find(|e| e.id == current_id && !e.disabled)
+                        .find(|e| {
+                            e.id == current_id
+                                && !e.disabled
+                                && self.cooldowns.is_available(e.id)
+                                && model.is_none_or(|m| {
+                                    self.cooldowns.is_model_available_at(e.id, m, Instant::now())
+                                })
+                                && !self.quotas.is_exhausted(
+                                    e.id,
+                                    &e.credentials.request_quotas,
+                                    Instant::now(),
+                                )
+                        })
                         .map(|e| (e.id, e.credentials.clone()))
                 };
 
                 if let Some(hit) = current_hit {
                     hit
                 } else {
-                    // 当前凭据不可用或 balanced 模式，根据负载均衡策略选择
+                    // 当前凭据不可用或非 priority 模式，根据负载均衡策略选择
                     let mut best = self.select_next_credential(model);
 
                     // 没有可用凭据：如果是"自动禁用导致全灭"，做一次类似重启的自愈
@@ -820,6 +1031,29 @@ impl MultiTokenManager {
                         // 因为 available_count() 会尝试获取 entries 锁，
                         // 而此时我们已经持有该锁，会导致死锁
                         let available = entries.iter().filter(|e| !e.disabled).count();
+                        let now = Instant::now();
+                        if let Some(model) = model
+                            && available > 0
+                            && entries
+                                .iter()
+                                .filter(|e| !e.disabled)
+                                .all(|e| !self.cooldowns.is_model_available_at(e.id, model, now))
+                        {
+                            return Err(ModelUnavailableError {
+                                model: model.to_string(),
+                            }
+                            .into());
+                        }
+                        if entries.iter().any(|e| {
+                            !e.disabled
+                                && self.quotas.is_exhausted(e.id, &e.credentials.request_quotas, now)
+                        }) {
+                            anyhow::bail!(
+                                "所有可用凭据均已禁用或请求配额已用尽（可用: {}/{}）",
+                                available,
+                                total
+                            );
+                        }
                         anyhow::bail!("所有凭据均已禁用（{}/{}）", available, total);
                     }
                 }
@@ -828,7 +1062,11 @@ impl MultiTokenManager {
             // 尝试获取/刷新 Token
             match self.try_ensure_token(id, &credentials).await {
                 Ok(ctx) => {
-                    return Ok(ctx);
+                    if self.try_record_request(ctx.id) {
+                        return Ok(ctx);
+                    }
+                    // 选择之后并发请求已用完该凭据的配额：凭据已进入配额冷却，重新选择
+                    tracing::debug!("凭据 #{} 请求配额已被并发请求用尽，重新选择凭据", ctx.id);
                 }
                 Err(e) => {
                     // refreshToken 永久失效 → 立即禁用，不累计重试
@@ -849,6 +1087,56 @@ impl MultiTokenManager {
         }
     }
 
+    /// 检查配额并记录一次发往上游的请求（计入凭据级请求配额）
+    fn try_record_request(&self, id: u64) -> bool {
+        self.try_record_request_at(id, Instant::now())
+    }
+
+    /// 以指定时间为"当前时间"检查配额并记录请求
+    ///
+    /// 检查与记录在配额跟踪器的同一把锁内完成，并发请求不会同时通过检查而超出配额。
+    /// 本次请求用完配额时，使凭据冷却至配额窗口滚动，期间负载均衡不再选择该凭据；
+    /// 配额已用尽（如选择凭据之后被并发请求用完）时不记录并返回 false。
+    pub(crate) fn try_record_request_at(&self, id: u64, now: Instant) -> bool {
+        let Some(quotas) = self.request_quotas(id) else {
+            return true;
+        };
+
+        match self.quotas.try_record_at(id, &quotas, now) {
+            Ok(None) => true,
+            Ok(Some(until)) => {
+                self.enter_quota_cooldown(id, until, now);
+                true
+            }
+            Err(until) => {
+                self.cooldowns
+                    .set_cooldown_until(id, CooldownReason::QuotaExhausted, until);
+                false
+            }
+        }
+    }
+
+    /// 凭据配置的请求配额（未配置时为 None）
+    fn request_quotas(&self, id: u64) -> Option<Vec<RequestQuota>> {
+        let entries = self.entries.lock();
+        entries
+            .iter()
+            .find(|e| e.id == id)
+            .map(|e| e.credentials.request_quotas.clone())
+            .filter(|quotas| !quotas.is_empty())
+    }
+
+    /// 配额用尽：冷却至配额窗口滚动
+    fn enter_quota_cooldown(&self, id: u64, until: Instant, now: Instant) {
+        tracing::warn!(
+            "凭据 #{} 请求配额已用尽，{} 秒后恢复",
+            id,
+            until.saturating_duration_since(now).as_secs()
+        );
+        self.cooldowns
+            .set_cooldown_until(id, CooldownReason::QuotaExhausted, until);
+    }
+
     /// 选择优先级最高的未禁用凭据作为当前凭据（内部方法）
     ///
     /// 纯粹按优先级选择，不排除当前凭据，用于优先级变更后立即生效
@@ -896,11 +1184,13 @@ impl MultiTokenManager {
                 id,
                 credentials: credentials.clone(),
                 token,
+                token_refresh: None,
             });
         }
 
         // 第一次检查（无锁）：快速判断是否需要刷新
         let needs_refresh = is_token_expired(credentials) || is_token_expiring_soon(credentials);
+        let refresh_started = needs_refresh.then(Instant::now);
 
         let creds = if needs_refresh {
             // 获取刷新锁，确保同一时间只有一个刷新操作
@@ -965,6 +1255,7 @@ impl MultiTokenManager {
             id,
             credentials: creds,
             token,
+            token_refresh: refresh_started.map(|t| t.elapsed()),
         })
     }
 
@@ -1252,6 +1543,154 @@ impl MultiTokenManager {
         result
     }
 
+    /// 报告指定凭据需要冷却
+    ///
+    /// 冷却不计入失败次数、不禁用凭据，仅在冷却期内让负载均衡优先跳过该凭据。
+    /// 返回实际生效的冷却时长。
+    pub fn report_cooldown(&self, id: u64, reason: CooldownReason) -> StdDuration {
+        self.report_cooldown_at(id, reason, Instant::now())
+    }
+
+    /// 按上游指定的时长冷却凭据（如 429 响应的 `Retry-After`）
+    ///
+    /// 时长封顶于短期冷却上限；到期时间由上游确定，不递增触发次数、不计入累计冷却预算。
+    /// 返回实际生效的冷却时长。
+    pub fn report_cooldown_for(
+        &self,
+        id: u64,
+        reason: CooldownReason,
+        duration: StdDuration,
+    ) -> StdDuration {
+        let max = StdDuration::from_secs(self.cooldowns.max_short_cooldown_secs());
+        let duration = duration.min(max);
+        self.cooldowns
+            .set_cooldown_until(id, reason, Instant::now() + duration);
+        duration
+    }
+
+    /// 报告凭据上的请求被上游以 400 拒绝
+    ///
+    /// 400 通常是请求本身的问题，不计入失败次数。`request_key` 为请求内容的指纹：
+    /// 同一请求在同一凭据上被拒绝 [`MAX_REJECTIONS_PER_REQUEST`] 次（客户端反复重放）时，
+    /// 以 `RequestRejected` 原因短暂冷却该凭据，不同的请求互不累计。
+    ///
+    /// 该冷却时长固定，不递增触发次数、不计入累计冷却预算。进入冷却时返回冷却时长。
+    pub fn report_request_rejected(&self, id: u64, request_key: u64) -> Option<StdDuration> {
+        if !self.entries.lock().iter().any(|e| e.id == id) {
+            return None;
+        }
+        {
+            let mut rejected = self.rejected_requests.lock();
+            if rejected.len() >= MAX_TRACKED_REJECTIONS && !rejected.contains_key(&(id, request_key)) {
+                rejected.clear();
+            }
+            let count = rejected.entry((id, request_key)).or_insert(0);
+            *count += 1;
+            if *count < MAX_REJECTIONS_PER_REQUEST {
+                return None;
+            }
+            rejected.remove(&(id, request_key));
+        }
+
+        let duration = self.cooldowns.base_duration(CooldownReason::RequestRejected);
+        tracing::warn!(
+            "凭据 #{} 上同一请求连续 {} 次被上游拒绝（400），冷却 {} 秒",
+            id,
+            MAX_REJECTIONS_PER_REQUEST,
+            duration.as_secs()
+        );
+        self.cooldowns
+            .set_cooldown_until(id, CooldownReason::RequestRejected, Instant::now() + duration);
+        Some(duration)
+    }
+
+    /// 当前处于冷却中的凭据（Admin API）
+    pub fn active_cooldowns(&self) -> Vec<CooldownInfo> {
+        self.cooldowns.sweep_expired();
+        self.cooldowns.get_all_cooldowns()
+    }
+
+    /// 解除凭据冷却（Admin API），返回解除前是否处于冷却中
+    pub fn clear_cooldown(&self, id: u64) -> anyhow::Result<bool> {
+        if !self.entries.lock().iter().any(|e| e.id == id) {
+            bail!("凭据不存在: {}", id);
+        }
+        Ok(self.cooldowns.clear_cooldown(id))
+    }
+
+    /// 上报凭据上的模型暂不可用：仅该凭据的该模型进入冷却，返回冷却时长
+    pub fn report_model_cooldown(&self, id: u64, model: &str) -> StdDuration {
+        self.cooldowns.set_model_cooldown_at(id, model, Instant::now())
+    }
+
+    /// 以指定时间为"当前时间"报告冷却（便于注入时钟）
+    ///
+    /// 统计窗口内累计冷却时长超过预算（`cooldownBudgetMaxFraction`）时，
+    /// 凭据被视为长期不健康，自动禁用并等待人工复核。
+    pub(crate) fn report_cooldown_at(
+        &self,
+        id: u64,
+        reason: CooldownReason,
+        now: Instant,
+    ) -> StdDuration {
+        let duration = self.cooldowns.set_cooldown_at(id, reason, now);
+
+        let max_fraction = self.config.cooldown_budget_max_fraction;
+        if max_fraction > 0.0 {
+            let window = self.cooldowns.budget_window();
+            let spent = self.cooldowns.cooldown_time_in_window(id, now);
+            if spent.as_secs_f64() > window.as_secs_f64() * max_fraction {
+                self.disable_for_cooldown_budget(id, spent, window);
+            }
+        }
+        duration
+    }
+
+    /// 因累计冷却超出预算禁用凭据并切换到下一个可用凭据
+    fn disable_for_cooldown_budget(&self, id: u64, spent: StdDuration, window: StdDuration) {
+        {
+            let mut entries = self.entries.lock();
+            let mut current_id = self.current_id.lock();
+
+            let entry = match entries.iter_mut().find(|e| e.id == id) {
+                Some(e) => e,
+                None => return,
+            };
+            if entry.disabled {
+                return;
+            }
+
+            entry.disabled = true;
+            entry.disabled_reason = Some(DisabledReason::CooldownBudgetExceeded);
+
+            tracing::error!(
+                credential_id = id,
+                cooldown_secs = spent.as_secs(),
+                window_secs = window.as_secs(),
+                "凭据 #{} 最近 {} 秒内累计冷却 {} 秒，超出冷却预算，已被禁用（请人工复核）",
+                id,
+                window.as_secs(),
+                spent.as_secs()
+            );
+
+            if let Some(next) = entries
+                .iter()
+                .filter(|e| !e.disabled)
+                .min_by_key(|e| e.credentials.priority)
+            {
+                *current_id = next.id;
+                tracing::info!(
+                    "已切换到凭据 #{}（优先级 {}）",
+                    next.id,
+                    next.credentials.priority
+                );
+            } else {
+                tracing::error!("所有凭据均已禁用！");
+            }
+        }
+        self.save_stats_debounced();
+    }
+
     /// 报告指定凭据刷新 Token 失败。
     ///
     /// 连续刷新失败达到阈值后禁用凭据并切换，阈值内保持当前凭据不切换，
@@ -1363,6 +1802,94 @@ impl MultiTokenManager {
         result
     }
 
+    /// 获取需要慢速探测的凭据 ID（因认证类原因被自动禁用）
+    pub fn probe_candidates(&self) -> Vec<u64> {
+        self.entries
+            .lock()
+            .iter()
+            .filter(|e| e.disabled && e.disabled_reason.is_some_and(|r| r.is_probe_eligible()))
+            .map(|e| e.id)
+            .collect()
+    }
+
+    /// 慢速探测成功后重新启用凭据
+    ///
+    /// 仅当凭据仍处于可探测的禁用状态时生效（期间被手动启用/禁用则不覆盖），
+    /// 返回是否实际重新启用。
+    pub fn restore_probed_credential(&self, id: u64) -> bool {
+        let reason = {
+            let mut entries = self.entries.lock();
+            let entry = match entries.iter_mut().find(|e| e.id == id) {
+                Some(e) => e,
+                None => return false,
+            };
+            let reason = match entry.disabled_reason {
+                Some(r) if entry.disabled && r.is_probe_eligible() => r,
+                _ => return false,
+            };
+            entry.disabled = false;
+            entry.disabled_reason = None;
+            entry.failure_count = 0;
+            entry.refresh_failure_count = 0;
+            reason
+        };
+
+        tracing::warn!(
+            "========== 凭据 #{} 慢速探测成功，已自动重新启用（原禁用原因: {:?}）==========",
+            id,
+            reason
+        );
+        if let Err(e) = self.persist_credentials() {
+            tracing::warn!("凭据重新启用后持久化失败: {}", e);
+        }
+        true
+    }
+
+    /// 获取启动预热的凭据 ID（跳过已禁用的凭据，按优先级排序，可限制数量）
+    pub fn warmup_candidates(&self, limit: Option<usize>) -> Vec<u64> {
+        let entries = self.entries.lock();
+        let mut candidates: Vec<_> = entries.iter().filter(|e| !e.disabled).collect();
+        candidates.sort_by_key(|e| (e.credentials.priority, e.id));
+        candidates
+            .into_iter()
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|e| e.id)
+            .collect()
+    }
+
+    /// 预热指定凭据：确保 Token 有效（必要时刷新）并生成设备指纹
+    ///
+    /// 不计入请求配额；刷新失败与正常请求路径一样计入失败计数，
+    /// refreshToken 永久失效时直接禁用。成功时返回该凭据的 machineId。
+    pub async fn warm_up(&self, id: u64) -> anyhow::Result<String> {
+        let credentials = {
+            let entries = self.entries.lock();
+            let entry = entries
+                .iter()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            if entry.disabled {
+                anyhow::bail!("凭据 #{} 已禁用，跳过预热", id);
+            }
+            entry.credentials.clone()
+        };
+
+        match self.try_ensure_token(id, &credentials).await {
+            Ok(ctx) => Ok(machine_id::generate_from_credentials(
+                &ctx.credentials,
+                &self.config,
+            )),
+            Err(e) => {
+                if e.downcast_ref::<RefreshTokenInvalidError>().is_some() {
+                    self.report_refresh_token_invalid(id);
+                } else {
+                    self.report_refresh_failure(id);
+                }
+                Err(e)
+            }
+        }
+    }
+
     /// 切换到优先级最高的可用凭据
     ///
     /// 返回是否成功切换
@@ -1395,9 +1922,16 @@ impl MultiTokenManager {
 
     /// 获取管理器状态快照（用于 Admin API）
     pub fn snapshot(&self) -> ManagerSnapshot {
+        let now = Instant::now();
         let entries = self.entries.lock();
         let current_id = *self.current_id.lock();
         let available = entries.iter().filter(|e| !e.disabled).count();
+        let active_cooldowns: HashMap<u64, (CooldownReason, StdDuration)> = self
+            .cooldowns
+            .get_all_cooldowns_at(now)
+            .into_iter()
+            .map(|(id, reason, remaining)| (id, (reason, remaining)))
+            .collect();
 
         ManagerSnapshot {
             entries: entries
@@ -1452,8 +1986,21 @@ impl MultiTokenManager {
                         DisabledReason::QuotaExceeded => "QuotaExceeded",
                         DisabledReason::InvalidRefreshToken => "InvalidRefreshToken",
                         DisabledReason::InvalidConfig => "InvalidConfig",
+                        DisabledReason::CooldownBudgetExceeded => "CooldownBudgetExceeded",
                     }.to_string()),
                     endpoint: e.credentials.endpoint.clone(),
+                    cooldown_secs_in_window: self
+                        .cooldowns
+                        .cooldown_time_in_window(e.id, now)
+                        .as_secs(),
+                    cooldown_reason: active_cooldowns
+                        .get(&e.id)
+                        .map(|(reason, _)| reason.as_str().to_string()),
+                    cooldown_remaining_secs: active_cooldowns
+                        .get(&e.id)
+                        .map(|(_, remaining)| remaining.as_secs()),
+                    quota_usage: self.quotas.usage(e.id, &e.credentials.request_quotas, now),
+                    tags: e.credentials.tags.clone(),
                 })
                 .collect(),
             current_id,
@@ -1487,6 +2034,7 @@ impl MultiTokenManager {
                 .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?
         };
 
+        let mut refresh_started = None;
         let token = if credentials.is_api_key_credential() {
             credentials
                 .kiro_api_key
@@ -1495,6 +2043,7 @@ impl MultiTokenManager {
         } else {
             let needs_refresh =
                 is_token_expired(&credentials) || is_token_expiring_soon(&credentials);
+            refresh_started = needs_refresh.then(Instant::now);
 
             if needs_refresh {
                 let _guard = self.refresh_lock.lock().await;
@@ -1545,10 +2094,14 @@ impl MultiTokenManager {
                 .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?
         };
 
+        if !self.try_record_request(id) {
+            return Err(QuotaExhaustedError { credential_id: id }.into());
+        }
         Ok(CallContext {
             id,
             credentials,
             token,
+            token_refresh: refresh_started.map(|t| t.elapsed()),
         })
     }
 
@@ -1595,6 +2148,94 @@ impl MultiTokenManager {
         Ok(())
     }
 
+    /// 获取凭据的 machineId 与固定的指纹（Admin API）
+    pub fn credential_fingerprint(&self, id: u64) -> anyhow::Result<(String, Option<Fingerprint>)> {
+        let entries = self.entries.lock();
+        let entry = entries
+            .iter()
+            .find(|e| e.id == id)
+            .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+        let fingerprint = entry.credentials.fingerprint.clone();
+        let machine_id = match &fingerprint {
+            Some(fp) => fp.machine_id.clone(),
+            None => machine_id::generate_from_credentials(&entry.credentials, &self.config),
+        };
+        Ok((machine_id, fingerprint))
+    }
+
+    /// 固定或清除凭据的客户端指纹（Admin API）
+    pub fn set_fingerprint(&self, id: u64, fingerprint: Option<Fingerprint>) -> anyhow::Result<()> {
+        {
+            let mut entries = self.entries.lock();
+            let entry = entries
+                .iter_mut()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            entry.credentials.fingerprint = fingerprint;
+        }
+        // 持久化更改
+        self.persist_credentials()?;
+        Ok(())
+    }
+
+    /// 添加/移除凭据标签（Admin API）
+    ///
+    /// 先合并 `add` 再移除 `remove`，返回更新后的标签列表。
+    pub fn update_tags(
+        &self,
+        id: u64,
+        add: &[String],
+        remove: &[String],
+    ) -> anyhow::Result<Vec<String>> {
+        let tags = {
+            let mut entries = self.entries.lock();
+            let entry = entries
+                .iter_mut()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            let remove = normalize_tags(remove);
+            let mut tags = normalize_tags(entry.credentials.tags.iter().chain(add));
+            tags.retain(|tag| !remove.contains(tag));
+            entry.credentials.tags = tags.clone();
+            tags
+        };
+        self.rebuild_tag_index();
+        // 持久化更改
+        self.persist_credentials()?;
+        Ok(tags)
+    }
+
+    /// 查询同时带有全部指定标签的凭据 ID（按 ID 升序，未指定标签时返回空）
+    pub fn ids_with_tags(&self, tags: &[String]) -> Vec<u64> {
+        let index = self.tag_index.lock();
+        let mut matched: Option<BTreeSet<u64>> = None;
+        for tag in normalize_tags(tags) {
+            let Some(ids) = index.get(&tag) else {
+                return Vec::new();
+            };
+            matched = Some(match matched {
+                None => ids.clone(),
+                Some(m) => m.intersection(ids).copied().collect(),
+            });
+        }
+        matched.map(|m| m.into_iter().collect()).unwrap_or_default()
+    }
+
+    /// 按当前凭据重建标签索引
+    fn rebuild_tag_index(&self) {
+        let index = {
+            let entries = self.entries.lock();
+            let mut index: HashMap<String, BTreeSet<u64>> = HashMap::new();
+            for entry in entries.iter() {
+                for tag in &entry.credentials.tags {
+                    index.entry(tag.clone()).or_default().insert(entry.id);
+                }
+            }
+            index
+        };
+        *self.tag_index.lock() = index;
+    }
+
     /// 重置凭据失败计数并重新启用（Admin API）
     pub fn reset_and_enable(&self, id: u64) -> anyhow::Result<()> {
         {
@@ -1860,6 +2501,7 @@ impl MultiTokenManager {
         validated_cred.proxy_username = new_cred.proxy_username;
         validated_cred.proxy_password = new_cred.proxy_password;
         validated_cred.kiro_api_key = new_cred.kiro_api_key;
+        validated_cred.tags = normalize_tags(&new_cred.tags);
 
         {
             let mut entries = self.entries.lock();
@@ -1874,6 +2516,7 @@ impl MultiTokenManager {
                 last_used_at: None,
             });
         }
+        self.rebuild_tag_index();
 
         // 6. 持久化
         self.persist_credentials()?;
@@ -1922,6 +2565,7 @@ impl MultiTokenManager {
 
             was_current
         };
+        self.rebuild_tag_index();
 
         // 如果删除的是当前凭据，切换到优先级最高的可用凭据
         if was_current {
@@ -2017,7 +2661,7 @@ impl MultiTokenManager {
     /// 设置负载均衡模式（Admin API）
     pub fn set_load_balancing_mode(&self, mode: String) -> anyhow::Result<()> {
         // 验证模式值
-        if mode != "priority" && mode != "balanced" {
+        if !LOAD_BALANCING_MODES.contains(&mode.as_str()) {
             anyhow::bail!("无效的负载均衡模式: {}", mode);
         }
 
@@ -2419,6 +3063,47 @@ mod tests {
         std::fs::remove_file(&config_path).unwrap();
     }
 
+    #[test]
+    fn test_weighted_random_distribution_follows_priority_weights() {
+        let mut config = Config::default();
+        config.load_balancing_mode = "weighted-random".to_string();
+        let credential = |priority| KiroCredentials {
+            priority,
+            ..Default::default()
+        };
+        let manager = MultiTokenManager::new(
+            config,
+            vec![credential(0), credential(1), credential(3), credential(0)],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        // 冷却中的凭据不参与选择
+        manager.report_cooldown(4, CooldownReason::ServerError);
+
+        const DRAWS: usize = 20_000;
+        let mut counts = HashMap::new();
+        for _ in 0..DRAWS {
+            let (id, _) = manager.select_next_credential(None).unwrap();
+            *counts.entry(id).or_insert(0usize) += 1;
+        }
+        assert!(!counts.contains_key(&4));
+
+        // 权重 1 : 1/2 : 1/4 → 概率 4/7、2/7、1/7
+        for (id, expected) in [(1, 4.0 / 7.0), (2, 2.0 / 7.0), (3, 1.0 / 7.0)] {
+            let share = counts.get(&id).copied().unwrap_or(0) as f64 / DRAWS as f64;
+            assert!(
+                (share - expected).abs() < 0.03,
+                "凭据 #{} 占比 {:.3}，期望约 {:.3}",
+                id,
+                share,
+                expected
+            );
+        }
+    }
+
     #[tokio::test]
     async fn test_multi_token_manager_acquire_context_auto_recovers_all_disabled() {
         let config = Config::default();
@@ -2537,6 +3222,237 @@ mod tests {
         assert_eq!(manager.available_count(), 0);
     }
 
+    #[test]
+    fn test_cooldown_budget_exceeded_auto_disables_credential() {
+        let mut config = Config::default();
+        config.cooldown_budget_max_fraction = 0.5;
+        let manager = MultiTokenManager::new(
+            config,
+            vec![KiroCredentials::default(), KiroCredentials::default()],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        // 每次冷却到期后立即再次触发：120+180+270+300*5 = 2070 秒，
+        // 但每次上报时只计入已经过的冷却，第 8 次上报时累计 1770 秒，仍未超过 1 小时的 50%
+        let t0 = Instant::now();
+        let mut now = t0;
+        for _ in 0..8 {
+            let duration = manager.report_cooldown_at(1, CooldownReason::ServerError, now);
+            now += duration;
+        }
+        assert_eq!(manager.available_count(), 2);
+
+        // 第 9 次上报时已实际冷却 2070 秒，超出预算，凭据被禁用并切换
+        manager.report_cooldown_at(1, CooldownReason::ServerError, now);
+        assert_eq!(manager.available_count(), 1);
+        assert_eq!(manager.cooldowns().cooldown_time_in_window(1, now), StdDuration::from_secs(2070));
+
+        let snapshot = manager.snapshot();
+        let entry = snapshot.entries.iter().find(|e| e.id == 1).unwrap();
+        assert!(entry.disabled);
+        assert_eq!(entry.disabled_reason.as_deref(), Some("CooldownBudgetExceeded"));
+        assert_eq!(snapshot.current_id, 2);
+    }
+
+    #[test]
+    fn test_cooldown_budget_is_off_by_default() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![KiroCredentials::default(), KiroCredentials::default()],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let mut now = Instant::now();
+        for _ in 0..20 {
+            now += manager.report_cooldown_at(1, CooldownReason::ServerError, now);
+        }
+        assert_eq!(manager.available_count(), 2);
+    }
+
+    #[test]
+    fn test_repeated_request_rejections_cool_down_credential() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![KiroCredentials::default(), KiroCredentials::default()],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        // 不同请求各被拒绝两次：互不累计，不触发冷却
+        for key in [10, 11, 12] {
+            assert!(manager.report_request_rejected(1, key).is_none());
+            assert!(manager.report_request_rejected(1, key).is_none());
+        }
+        // 同一请求在其他凭据上被拒绝也不累计到凭据 #1
+        assert!(manager.report_request_rejected(2, 10).is_none());
+        assert!(manager.active_cooldowns().is_empty());
+
+        // 同一请求在同一凭据上第 3 次被拒绝时进入 RequestRejected 冷却
+        let duration = manager.report_request_rejected(1, 10);
+        assert_eq!(duration, Some(CooldownReason::RequestRejected.default_duration()));
+        let cooldowns = manager.active_cooldowns();
+        assert_eq!(cooldowns.len(), 1);
+        assert_eq!(cooldowns[0].credential_id, 1);
+        assert_eq!(cooldowns[0].reason, "RequestRejected");
+        // 固定时长：不递增触发次数，不计入累计冷却预算
+        assert_eq!(cooldowns[0].trigger_count, 0);
+        let later = Instant::now() + StdDuration::from_secs(3600);
+        assert_eq!(manager.cooldowns().cooldown_time_in_window(1, later), StdDuration::ZERO);
+
+        assert!(manager.report_request_rejected(99, 10).is_none());
+    }
+
+    #[test]
+    fn test_all_cooling_ignores_only_auto_recoverable_cooldowns() {
+        let fallback = KiroCredentials {
+            priority: 1,
+            ..Default::default()
+        };
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![KiroCredentials::default(), fallback],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let now = Instant::now();
+        let cooldowns = manager.cooldowns();
+        cooldowns.set_cooldown_until(
+            1,
+            CooldownReason::QuotaExhausted,
+            now + StdDuration::from_secs(3600),
+        );
+        cooldowns.set_cooldown_at(2, CooldownReason::EmptyResponse, now);
+
+        // 全部冷却：忽略 #2 的空响应冷却，#1 的配额冷却仍然生效（即使优先级更高）
+        assert_eq!(manager.select_next_credential_at(None, now).unwrap().0, 2);
+
+        cooldowns.set_cooldown_until(
+            2,
+            CooldownReason::QuotaExhausted,
+            now + StdDuration::from_secs(3600),
+        );
+        assert!(manager.select_next_credential_at(None, now).is_none());
+    }
+
+    #[test]
+    fn test_request_quota_skips_credential_until_window_rolls() {
+        use crate::kiro::model::credentials::RequestQuota;
+
+        let limited = KiroCredentials {
+            request_quotas: vec![RequestQuota {
+                window_secs: 3600,
+                max_requests: 2,
+            }],
+            ..Default::default()
+        };
+        let fallback = KiroCredentials {
+            priority: 1,
+            ..Default::default()
+        };
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![limited, fallback],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let t0 = Instant::now();
+        assert!(manager.try_record_request_at(1, t0));
+        assert_eq!(manager.select_next_credential_at(None, t0).unwrap().0, 1);
+
+        // 第二次请求用完配额：凭据被跳过（即使其优先级更高）
+        let t1 = t0 + StdDuration::from_secs(60);
+        assert!(manager.try_record_request_at(1, t1));
+        assert_eq!(manager.select_next_credential_at(None, t1).unwrap().0, 2);
+        let snapshot = manager.snapshot();
+        let usage = &snapshot.entries.iter().find(|e| e.id == 1).unwrap().quota_usage;
+        assert_eq!(usage[0].remaining, 0);
+        assert!(usage[0].resets_in_secs.is_some());
+        assert_eq!(manager.cooldowns().cooldown_time_in_window(1, t1), StdDuration::ZERO);
+
+        // 窗口滚动（首条请求滑出）前仍被跳过，之后恢复
+        let before_reset = t0 + StdDuration::from_secs(3599);
+        assert_eq!(manager.select_next_credential_at(None, before_reset).unwrap().0, 2);
+        let after_reset = t0 + StdDuration::from_secs(3600);
+        assert_eq!(manager.select_next_credential_at(None, after_reset).unwrap().0, 1);
+    }
+
+    #[test]
+    fn test_concurrent_requests_never_exceed_request_quota() {
+        let limited = KiroCredentials {
+            request_quotas: vec![RequestQuota {
+                window_secs: 3600,
+                max_requests: 3,
+            }],
+            ..Default::default()
+        };
+        let manager =
+            MultiTokenManager::new(Config::default(), vec![limited], None, None, false).unwrap();
+
+        // 所有线程都在配额未用尽时开始，检查与记录须作为一个整体完成
+        let barrier = std::sync::Barrier::new(16);
+        let admitted = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..16)
+                .map(|_| {
+                    s.spawn(|| {
+                        barrier.wait();
+                        manager.try_record_request(1)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .filter(|&ok| ok)
+                .count()
+        });
+        assert_eq!(admitted, 3);
+
+        let snapshot = manager.snapshot();
+        let usage = &snapshot.entries[0].quota_usage;
+        assert_eq!(usage[0].used, 3);
+        assert!(!manager.cooldowns().is_available(1));
+    }
+
+    #[tokio::test]
+    async fn test_pinned_acquisition_fails_once_request_quota_is_used_up() {
+        let limited = KiroCredentials {
+            kiro_api_key: Some("ksk_limited".to_string()),
+            auth_method: Some("api_key".to_string()),
+            request_quotas: vec![RequestQuota {
+                window_secs: 3600,
+                max_requests: 2,
+            }],
+            ..Default::default()
+        };
+        let manager =
+            MultiTokenManager::new(Config::default(), vec![limited], None, None, false).unwrap();
+
+        for _ in 0..2 {
+            assert_eq!(manager.acquire_context_for(1).await.unwrap().id, 1);
+        }
+        let Err(err) = manager.acquire_context_for(1).await else {
+            panic!("配额用尽后固定凭据的请求应失败");
+        };
+        let exhausted = err.downcast_ref::<QuotaExhaustedError>().unwrap();
+        assert_eq!(exhausted.credential_id, 1);
+        // 被拒绝的请求不计入配额
+        let snapshot = manager.snapshot();
+        assert_eq!(snapshot.entries[0].quota_usage[0].used, 2);
+    }
+
     #[tokio::test]
     async fn test_multi_token_manager_quota_disabled_is_not_auto_recovered() {
         let config = Config::default();
@@ -0,0 +1,170 @@
+//! 启动预热
+//!
+//! 冷启动后的首批请求需要承担 Token 刷新的耗时，恰好落在服务刚接入负载均衡的时刻。
+//! 预热在服务开始监听之前，以有限并发预先刷新（按优先级取前 N 个的）凭据 Token
+//! 并生成设备指纹；已禁用的凭据会被跳过，预热失败不阻止启动。
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::kiro::token_manager::MultiTokenManager;
+
+/// 预热选项
+#[derive(Debug, Clone, Copy)]
+pub struct WarmupOptions {
+    /// 预热的凭据数量上限（按优先级取前 N 个，None 表示全部可用凭据）
+    pub max_credentials: Option<usize>,
+    /// 并发数（最小 1）
+    pub concurrency: usize,
+    /// 总超时
+    pub timeout: Duration,
+}
+
+/// 预热结果
+#[derive(Debug, Default)]
+pub struct WarmupReport {
+    /// 预热成功的凭据及其 machineId
+    pub fingerprints: BTreeMap<u64, String>,
+    /// 预热失败的凭据
+    pub failed: Vec<u64>,
+    /// 是否因超时放弃了剩余凭据
+    pub timed_out: bool,
+}
+
+/// 执行启动预热，全部完成或超时后返回
+pub async fn run_warmup(
+    token_manager: Arc<MultiTokenManager>,
+    options: WarmupOptions,
+) -> WarmupReport {
+    let candidates = token_manager.warmup_candidates(options.max_credentials);
+    tracing::info!(
+        "开始启动预热：{} 个凭据，并发 {}，超时 {} 秒",
+        candidates.len(),
+        options.concurrency.max(1),
+        options.timeout.as_secs()
+    );
+
+    let semaphore = Arc::new(Semaphore::new(options.concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    for id in candidates {
+        let token_manager = token_manager.clone();
+        let semaphore = semaphore.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.ok()?;
+            Some((id, token_manager.warm_up(id).await))
+        });
+    }
+
+    let mut report = WarmupReport::default();
+    let collect = async {
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok(Some((id, Ok(machine_id)))) => {
+                    report.fingerprints.insert(id, machine_id);
+                }
+                Ok(Some((id, Err(e)))) => {
+                    tracing::warn!("凭据 #{} 预热失败: {}", id, e);
+                    report.failed.push(id);
+                }
+                Ok(None) | Err(_) => {}
+            }
+        }
+    };
+    if tokio::time::timeout(options.timeout, collect)
+        .await
+        .is_err()
+    {
+        tracing::warn!("启动预热超时，放弃剩余 {} 个凭据", tasks.len());
+        tasks.abort_all();
+        report.timed_out = true;
+    }
+
+    tracing::info!(
+        "启动预热完成：成功 {}，失败 {}",
+        report.fingerprints.len(),
+        report.failed.len()
+    );
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::model::credentials::KiroCredentials;
+    use crate::model::config::Config;
+
+    #[tokio::test]
+    async fn test_warmup_ensures_tokens_and_fingerprints_before_returning() {
+        let api_key = KiroCredentials {
+            id: Some(1),
+            kiro_api_key: Some("ksk_warmup".to_string()),
+            auth_method: Some("api_key".to_string()),
+            priority: 0,
+            ..Default::default()
+        };
+        let valid_oauth = KiroCredentials {
+            id: Some(2),
+            refresh_token: Some("r".repeat(120)),
+            access_token: Some("access-2".to_string()),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            priority: 1,
+            ..Default::default()
+        };
+        let disabled = KiroCredentials {
+            id: Some(3),
+            kiro_api_key: Some("ksk_disabled".to_string()),
+            auth_method: Some("api_key".to_string()),
+            disabled: true,
+            priority: 2,
+            ..Default::default()
+        };
+        // Token 已过期且缺少 refreshToken：刷新失败，计入失败但不阻止其余凭据
+        let broken = KiroCredentials {
+            id: Some(4),
+            access_token: Some("stale".to_string()),
+            expires_at: Some((chrono::Utc::now() - chrono::Duration::hours(1)).to_rfc3339()),
+            priority: 3,
+            ..Default::default()
+        };
+        let token_manager = Arc::new(
+            MultiTokenManager::new(
+                Config::default(),
+                vec![api_key, valid_oauth, disabled, broken],
+                None,
+                None,
+                false,
+            )
+            .unwrap(),
+        );
+
+        let report = run_warmup(
+            token_manager.clone(),
+            WarmupOptions {
+                max_credentials: None,
+                concurrency: 2,
+                timeout: Duration::from_secs(5),
+            },
+        )
+        .await;
+
+        assert!(!report.timed_out);
+        assert_eq!(
+            report.fingerprints.keys().copied().collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(report.failed, vec![4]);
+        let exported = token_manager.export_credentials();
+        for (id, machine_id) in &report.fingerprints {
+            let credentials = exported.iter().find(|c| c.id == Some(*id)).unwrap();
+            assert_eq!(credentials.machine_id.as_ref(), Some(machine_id));
+        }
+
+        // 数量上限按优先级截取
+        let limited = token_manager.warmup_candidates(Some(1));
+        assert_eq!(limited, vec![1]);
+    }
+}
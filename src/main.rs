@@ -4,42 +4,41 @@ mod anthropic;
 mod common;
 mod http_client;
 mod kiro;
+mod metrics;
 mod model;
+mod shutdown;
 pub mod token;
 
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use clap::Parser;
-use kiro::endpoint::{IdeEndpoint, KiroEndpoint};
+use kiro::endpoint::{IdeEndpoint, KiroEndpoint, ide::IDE_ENDPOINT_NAME};
 use kiro::model::credentials::{CredentialsConfig, KiroCredentials};
 use kiro::provider::KiroProvider;
 use kiro::token_manager::MultiTokenManager;
 use model::arg::Args;
-use model::config::Config;
+use model::config::{Config, LogFormat};
 
 #[tokio::main]
 async fn main() {
     // 解析命令行参数
     let args = Args::parse();
 
-    // 初始化日志
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
-        )
-        .init();
-
-    // 加载配置
+    // 加载配置（日志格式由配置决定，加载失败时以默认格式输出错误）
     let config_path = args
         .config
         .unwrap_or_else(|| Config::default_config_path().to_string());
     let config = Config::load(&config_path).unwrap_or_else(|e| {
+        init_logging(LogFormat::default());
         tracing::error!("加载配置失败: {}", e);
         std::process::exit(1);
     });
 
+    // 初始化日志
+    init_logging(config.log_format);
+
     // 加载凭证（支持单对象或数组格式）
     let credentials_path = args
         .credentials
@@ -99,7 +98,7 @@ async fn main() {
     // 构建端点注册表
     let mut endpoints: HashMap<String, Arc<dyn KiroEndpoint>> = HashMap::new();
     {
-        let ide = IdeEndpoint::new();
+        let ide = IdeEndpoint::from_config(config.endpoints.get(IDE_ENDPOINT_NAME));
         endpoints.insert(ide.name().to_string(), Arc::new(ide));
     }
 
@@ -145,10 +144,49 @@ async fn main() {
     let kiro_provider = KiroProvider::with_proxy(
         token_manager.clone(),
         proxy_config.clone(),
-        endpoints,
+        endpoints.clone(),
         config.default_endpoint.clone(),
     );
 
+    // 慢速探测：定期探测认证类禁用的凭据（仅在显式开启时启动）
+    if config.slow_probe_enabled {
+        let probe_provider = Arc::new(KiroProvider::with_proxy(
+            token_manager.clone(),
+            proxy_config.clone(),
+            endpoints.clone(),
+            config.default_endpoint.clone(),
+        ));
+        kiro::slow_probe::spawn_slow_probe(
+            probe_provider,
+            Duration::from_secs(config.slow_probe_interval_secs),
+        );
+    }
+
+    // 金丝雀自检：定期端到端验证 转换 → 调用 → 解析 → 组装 流程（仅在显式开启时启动）
+    if config.canary_enabled {
+        let canary_provider = Arc::new(KiroProvider::with_proxy(
+            token_manager.clone(),
+            proxy_config.clone(),
+            endpoints,
+            config.default_endpoint.clone(),
+        ));
+        anthropic::canary::spawn_canary(
+            canary_provider,
+            Duration::from_secs(config.canary_interval_secs),
+            config.canary_credential_id,
+        );
+    }
+
+    // 初始化工具压缩选项
+    anthropic::tool_compression::init_options(anthropic::tool_compression::ToolCompressionOptions {
+        enabled: config.tool_compression_enabled,
+        collapse_whitespace: config.tool_description_collapse_whitespace,
+        min_description_length: config.tool_description_min_length,
+        target_bytes: config.tool_compression_target_bytes,
+        priorities: config.tool_compression_priorities.clone(),
+        protected_tools: config.tool_compression_protected_tools.iter().cloned().collect(),
+    });
+
     // 初始化 count_tokens 配置
     token::init_config(token::CountTokensConfig {
         api_url: config.count_tokens_api_url.clone(),
@@ -158,12 +196,53 @@ async fn main() {
         tls_backend: config.tls_backend,
     });
 
+    // 指纹种子请求头白名单（仅在显式开启时生效）
+    let fingerprint_seed_allowlist = if config.fingerprint_seed_header_enabled {
+        let ips: Vec<std::net::IpAddr> = config
+            .fingerprint_seed_allowed_ips
+            .iter()
+            .filter_map(|ip| match ip.parse() {
+                Ok(ip) => Some(ip),
+                Err(_) => {
+                    tracing::warn!("忽略无效的指纹种子白名单 IP: {}", ip);
+                    None
+                }
+            })
+            .collect();
+        tracing::warn!("已启用指纹种子请求头覆盖（白名单: {:?}），请勿在生产环境使用", ips);
+        Some(ips)
+    } else {
+        None
+    };
+
     // 构建 Anthropic API 路由（profile_arn 由 provider 层根据实际凭据动态注入）
     let anthropic_app = anthropic::create_router_with_provider(
         &api_key,
         Some(kiro_provider),
         config.extract_thinking,
         config.model_mapping.clone(),
+        config.normalize_content_block_order,
+        config.max_tools.map(|max_tools| anthropic::ToolLimit {
+            max_tools,
+            behavior: config.max_tools_behavior,
+        }),
+        fingerprint_seed_allowlist,
+    );
+
+    // Prometheus 指标（导出前刷新可用凭据数）
+    let metrics_token_manager = token_manager.clone();
+    let metrics_app = axum::Router::new().route(
+        "/metrics",
+        axum::routing::get(move || {
+            let active = metrics_token_manager
+                .snapshot()
+                .entries
+                .iter()
+                .filter(|e| !e.disabled && e.cooldown_remaining_secs.is_none())
+                .count();
+            metrics::registry().active_credentials.set(active as i64);
+            metrics::prometheus_metrics()
+        }),
     );
 
     // 构建 Admin API 路由（如果配置了非空的 admin_api_key）
@@ -177,16 +256,37 @@ async fn main() {
     let app = if let Some(admin_key) = &config.admin_api_key {
         if admin_key.trim().is_empty() {
             tracing::warn!("admin_api_key 配置为空，Admin API 未启用");
-            anthropic_app
+            unauthenticated_metrics(anthropic_app, metrics_app, &config)
         } else {
-            let admin_service =
+            let mut admin_service =
                 admin::AdminService::new(
                     token_manager.clone(),
                     endpoint_names.clone(),
                     endpoints_for_admin.clone(),
                     config.default_endpoint.clone(),
                 );
-            let admin_state = admin::AdminState::new(admin_key, admin_service);
+            if let Some(interval_secs) = config.balance_refresh_interval_secs {
+                admin_service = admin_service.with_balance_warming(admin::BalanceWarming {
+                    interval: Duration::from_secs(interval_secs.max(1)),
+                    spacing: Duration::from_millis(config.balance_refresh_spacing_ms),
+                });
+            }
+            let admin_state = admin::AdminState::new(admin_key, admin_service)
+                .expect("admin_api_key 已校验非空");
+            for (name, key) in &config.admin_api_keys {
+                if let Err(e) = admin_state.add_key(name, key) {
+                    tracing::warn!("忽略 Admin API 密钥 {}: {}", name, e);
+                }
+            }
+            admin::AdminService::spawn_balance_warmer(admin_state.service.clone());
+            let metrics_app = if config.metrics_require_admin_auth {
+                metrics_app.layer(axum::middleware::from_fn_with_state(
+                    admin_state.clone(),
+                    admin::admin_auth_middleware,
+                ))
+            } else {
+                metrics_app
+            };
             let admin_app = admin::create_admin_router(admin_state);
 
             // 创建 Admin UI 路由
@@ -195,13 +295,27 @@ async fn main() {
             tracing::info!("Admin API 已启用");
             tracing::info!("Admin UI 已启用: /admin");
             anthropic_app
+                .merge(metrics_app)
                 .nest("/api/admin", admin_app)
                 .nest("/admin", admin_ui_app)
         }
     } else {
-        anthropic_app
+        unauthenticated_metrics(anthropic_app, metrics_app, &config)
     };
 
+    // 启动预热：在开始监听之前刷新凭据 Token 并生成设备指纹（仅在显式开启时执行）
+    if config.warmup_enabled {
+        kiro::warmup::run_warmup(
+            token_manager.clone(),
+            kiro::warmup::WarmupOptions {
+                max_credentials: config.warmup_max_credentials,
+                concurrency: config.warmup_concurrency,
+                timeout: Duration::from_secs(config.warmup_timeout_secs),
+            },
+        )
+        .await;
+    }
+
     // 启动服务器
     let addr = format!("{}:{}", config.host, config.port);
     tracing::info!("启动 Anthropic API 端点: {}", addr);
@@ -210,6 +324,9 @@ async fn main() {
     tracing::info!("  GET  /v1/models");
     tracing::info!("  POST /v1/messages");
     tracing::info!("  POST /v1/messages/count_tokens");
+    if admin_key_valid || !config.metrics_require_admin_auth {
+        tracing::info!("  GET  /metrics");
+    }
     if admin_key_valid {
         tracing::info!("Admin API:");
         tracing::info!("  GET  /api/admin/credentials");
@@ -217,10 +334,74 @@ async fn main() {
         tracing::info!("  POST /api/admin/credentials/:index/priority");
         tracing::info!("  POST /api/admin/credentials/:index/reset");
         tracing::info!("  GET  /api/admin/credentials/:index/balance");
+        tracing::info!("  GET  /api/admin/stats/latency");
         tracing::info!("Admin UI:");
         tracing::info!("  GET  /admin");
     }
 
+    // 优雅停机：收到信号后拒绝新请求，进行中的请求在宽限期内继续完成
+    let shutdown = shutdown::Shutdown::new();
+    let app = app.layer(axum::middleware::from_fn_with_state(
+        shutdown.clone(),
+        shutdown::shutdown_middleware,
+    ));
+    let grace = Duration::from_secs(config.shutdown_grace_period_secs);
+
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .with_graceful_shutdown({
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown::wait_for_signal().await;
+            tracing::info!(
+                "收到停机信号，停止接受新请求，等待 {} 个进行中的请求（最多 {} 秒）",
+                shutdown.active(),
+                grace.as_secs()
+            );
+            shutdown.begin();
+        }
+    });
+
+    tokio::select! {
+        result = server => result.unwrap(),
+        drained = async {
+            shutdown.started().await;
+            shutdown.wait_idle(grace).await
+        } => {
+            if !drained {
+                tracing::warn!("停机宽限期已到，仍有 {} 个请求未完成，强制退出", shutdown.active());
+            }
+        }
+    }
+    tracing::info!("服务已停止");
+}
+
+/// 按配置的格式安装全局日志订阅器（级别由 `RUST_LOG` 控制，默认 info）
+fn init_logging(format: LogFormat) {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().flatten_event(true).init(),
+    }
+}
+
+/// 未启用 Admin API 时挂载 `/metrics`
+///
+/// 配置要求 Admin 认证时无密钥可校验，此时不暴露该端点。
+fn unauthenticated_metrics(
+    app: axum::Router,
+    metrics_app: axum::Router,
+    config: &Config,
+) -> axum::Router {
+    if config.metrics_require_admin_auth {
+        tracing::warn!("metricsRequireAdminAuth 已启用但未配置 admin_api_key，/metrics 未启用");
+        app
+    } else {
+        app.merge(metrics_app)
+    }
 }
@@ -0,0 +1,570 @@
+//! 运行指标
+//!
+//! 按凭据与模型记录上游请求的延迟分布（直方图）：
+//! - `first_byte`：请求发出 → 收到响应头
+//! - `completion`：收到响应头 → 响应体读取完毕
+//!
+//! 以及请求数、上游错误状态码、冷却、工具压缩等计数（见 [`Registry`]）。
+//!
+//! 通过 Prometheus `/metrics` 端点导出，
+//! Admin API 提供按凭据/按模型汇总的 p50/p95。
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+use axum::http::header;
+use axum::response::IntoResponse;
+use parking_lot::Mutex;
+use serde::Serialize;
+
+/// 直方图桶上界（毫秒），最后隐含一个 +Inf 桶
+const LATENCY_BUCKETS_MS: &[u64] = &[
+    50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 30_000, 60_000, 120_000,
+];
+
+/// Prometheus 指标名
+const METRIC_NAME: &str = "kiro_upstream_latency_ms";
+
+/// 延迟阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LatencyPhase {
+    /// 请求发出 → 收到响应头
+    FirstByte,
+    /// 收到响应头 → 响应体读取完毕
+    Completion,
+    /// 请求发出 → 收到首个响应体数据（首个事件帧）
+    FirstFrame,
+    /// 请求发出 → 响应体读取完毕
+    Total,
+}
+
+impl LatencyPhase {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::FirstByte => "first_byte",
+            Self::Completion => "completion",
+            Self::FirstFrame => "first_frame",
+            Self::Total => "total",
+        }
+    }
+}
+
+/// 固定桶直方图
+#[derive(Debug, Clone)]
+struct Histogram {
+    /// 各桶（非累计）计数，长度为 `LATENCY_BUCKETS_MS.len() + 1`
+    counts: Vec<u64>,
+    /// 观测值总和（毫秒）
+    sum_ms: f64,
+    /// 观测总次数
+    count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: vec![0; LATENCY_BUCKETS_MS.len() + 1],
+            sum_ms: 0.0,
+            count: 0,
+        }
+    }
+}
+
+impl Histogram {
+    fn observe(&mut self, ms: f64) {
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|&upper| ms <= upper as f64)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.counts[bucket] += 1;
+        self.sum_ms += ms;
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        for (a, b) in self.counts.iter_mut().zip(&other.counts) {
+            *a += b;
+        }
+        self.sum_ms += other.sum_ms;
+        self.count += other.count;
+    }
+
+    /// 按桶估算分位数（与 Prometheus `histogram_quantile` 相同的桶内线性插值）
+    ///
+    /// 落入 +Inf 桶时返回最大的有限桶上界。
+    fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = q * self.count as f64;
+        let mut cumulative = 0u64;
+        for (i, &n) in self.counts.iter().enumerate() {
+            let previous = cumulative;
+            cumulative += n;
+            if n == 0 || (cumulative as f64) < rank {
+                continue;
+            }
+            let Some(&upper) = LATENCY_BUCKETS_MS.get(i) else {
+                return LATENCY_BUCKETS_MS.last().map(|&b| b as f64);
+            };
+            let lower = if i == 0 { 0 } else { LATENCY_BUCKETS_MS[i - 1] };
+            let fraction = (rank - previous as f64) / n as f64;
+            return Some(lower as f64 + (upper - lower) as f64 * fraction);
+        }
+        None
+    }
+}
+
+/// 单条序列的键
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct SeriesKey {
+    phase: LatencyPhase,
+    credential_id: u64,
+    model: String,
+}
+
+/// 延迟汇总条目
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencySeriesSummary {
+    /// 凭据 ID（按凭据汇总时存在）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_id: Option<u64>,
+    /// 模型（按模型汇总时存在）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// 延迟阶段（"first_byte" / "completion"）
+    pub phase: &'static str,
+    /// 样本数
+    pub count: u64,
+    /// p50（毫秒）
+    pub p50_ms: Option<f64>,
+    /// p95（毫秒）
+    pub p95_ms: Option<f64>,
+}
+
+/// 按凭据与按模型的延迟汇总
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencySummary {
+    pub by_credential: Vec<LatencySeriesSummary>,
+    pub by_model: Vec<LatencySeriesSummary>,
+}
+
+/// 延迟记录器
+#[derive(Default)]
+pub struct LatencyRecorder {
+    series: Mutex<HashMap<SeriesKey, Histogram>>,
+}
+
+impl LatencyRecorder {
+    /// 记录一次观测
+    pub fn record(&self, phase: LatencyPhase, credential_id: u64, model: &str, elapsed: Duration) {
+        let key = SeriesKey {
+            phase,
+            credential_id,
+            model: model.to_string(),
+        };
+        self.series
+            .lock()
+            .entry(key)
+            .or_default()
+            .observe(elapsed.as_secs_f64() * 1000.0);
+    }
+
+    /// 按凭据、按模型合并直方图并计算 p50/p95
+    pub fn summary(&self) -> LatencySummary {
+        let series = self.series.lock();
+        let mut by_credential: BTreeMap<(u64, LatencyPhase), Histogram> = BTreeMap::new();
+        let mut by_model: BTreeMap<(String, LatencyPhase), Histogram> = BTreeMap::new();
+        for (key, histogram) in series.iter() {
+            by_credential
+                .entry((key.credential_id, key.phase))
+                .or_default()
+                .merge(histogram);
+            by_model
+                .entry((key.model.clone(), key.phase))
+                .or_default()
+                .merge(histogram);
+        }
+
+        let summarize =
+            |credential_id, model, phase: LatencyPhase, h: &Histogram| LatencySeriesSummary {
+                credential_id,
+                model,
+                phase: phase.as_str(),
+                count: h.count,
+                p50_ms: h.quantile(0.5),
+                p95_ms: h.quantile(0.95),
+            };
+
+        LatencySummary {
+            by_credential: by_credential
+                .iter()
+                .map(|((id, phase), h)| summarize(Some(*id), None, *phase, h))
+                .collect(),
+            by_model: by_model
+                .iter()
+                .map(|((model, phase), h)| summarize(None, Some(model.clone()), *phase, h))
+                .collect(),
+        }
+    }
+
+    /// 以 Prometheus 文本格式导出所有直方图
+    pub fn render_prometheus(&self) -> String {
+        let series = self.series.lock();
+        let mut keys: Vec<&SeriesKey> = series.keys().collect();
+        keys.sort();
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# HELP {} Upstream latency in milliseconds",
+            METRIC_NAME
+        );
+        let _ = writeln!(out, "# TYPE {} histogram", METRIC_NAME);
+        for key in keys {
+            let h = &series[key];
+            let labels = format!(
+                "phase=\"{}\",credential=\"{}\",model=\"{}\"",
+                key.phase.as_str(),
+                key.credential_id,
+                escape_label_value(&key.model)
+            );
+            let mut cumulative = 0;
+            for (i, n) in h.counts.iter().enumerate() {
+                cumulative += n;
+                let le = LATENCY_BUCKETS_MS
+                    .get(i)
+                    .map(|b| b.to_string())
+                    .unwrap_or_else(|| "+Inf".to_string());
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{{{},le=\"{}\"}} {}",
+                    METRIC_NAME, labels, le, cumulative
+                );
+            }
+            let _ = writeln!(out, "{}_sum{{{}}} {}", METRIC_NAME, labels, h.sum_ms);
+            let _ = writeln!(out, "{}_count{{{}}} {}", METRIC_NAME, labels, h.count);
+        }
+        out
+    }
+}
+
+/// 转义 Prometheus 标签值中的 `\`、`"` 与换行
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// 全局延迟记录器
+static LATENCY: LazyLock<LatencyRecorder> = LazyLock::new(LatencyRecorder::default);
+
+/// 获取全局延迟记录器
+pub fn latency() -> &'static LatencyRecorder {
+    &LATENCY
+}
+
+/// 单调递增计数器
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// 可任意设置的瞬时值
+#[derive(Debug, Default)]
+pub struct Gauge(AtomicI64);
+
+impl Gauge {
+    pub fn set(&self, value: i64) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// 按单个标签值分组的计数器
+#[derive(Debug, Default)]
+pub struct LabeledCounter(Mutex<BTreeMap<String, u64>>);
+
+impl LabeledCounter {
+    pub fn inc(&self, label: &str) {
+        *self.0.lock().entry(label.to_string()).or_default() += 1;
+    }
+}
+
+/// 计数类指标注册表
+#[derive(Debug, Default)]
+pub struct Registry {
+    /// 按模型统计的上游请求数（每次 API 调用计一次，不含重试）
+    pub requests: LabeledCounter,
+    /// 按状态码统计的上游非成功响应数（每次尝试计一次）
+    pub upstream_errors: LabeledCounter,
+    /// 按原因统计的凭据进入冷却次数
+    pub cooldown_entries: LabeledCounter,
+    /// 可参与选择的凭据数量（未禁用且未冷却，导出时刷新）
+    pub active_credentials: Gauge,
+    /// 工具定义触发压缩的次数
+    pub tool_compressions: Counter,
+}
+
+impl Registry {
+    /// 以 Prometheus 文本格式导出
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        render_labeled(
+            &mut out,
+            "kiro_requests_total",
+            "Upstream API calls by model",
+            "model",
+            &self.requests,
+        );
+        render_labeled(
+            &mut out,
+            "kiro_upstream_errors_total",
+            "Non-success upstream responses by HTTP status",
+            "status",
+            &self.upstream_errors,
+        );
+        render_labeled(
+            &mut out,
+            "kiro_cooldown_entries_total",
+            "Credential cooldown entries by reason",
+            "reason",
+            &self.cooldown_entries,
+        );
+        render_header(
+            &mut out,
+            "kiro_active_credentials",
+            "Credentials neither disabled nor cooling down",
+            "gauge",
+        );
+        let _ = writeln!(
+            out,
+            "kiro_active_credentials {}",
+            self.active_credentials.get()
+        );
+        render_header(
+            &mut out,
+            "kiro_tool_compressions_total",
+            "Requests whose tool definitions were compressed",
+            "counter",
+        );
+        let _ = writeln!(
+            out,
+            "kiro_tool_compressions_total {}",
+            self.tool_compressions.get()
+        );
+        out
+    }
+}
+
+fn render_header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn render_labeled(out: &mut String, name: &str, help: &str, label: &str, counter: &LabeledCounter) {
+    render_header(out, name, help, "counter");
+    for (value, count) in counter.0.lock().iter() {
+        let _ = writeln!(
+            out,
+            "{}{{{}=\"{}\"}} {}",
+            name,
+            label,
+            escape_label_value(value),
+            count
+        );
+    }
+}
+
+/// 全局计数注册表
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::default);
+
+/// 获取全局计数注册表
+pub fn registry() -> &'static Registry {
+    &REGISTRY
+}
+
+/// GET /metrics
+///
+/// Prometheus 指标导出（调用方应先刷新 [`Registry::active_credentials`]）
+pub async fn prometheus_metrics() -> impl IntoResponse {
+    let mut body = latency().render_prometheus();
+    body.push_str(&registry().render());
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record_ms(
+        recorder: &LatencyRecorder,
+        credential_id: u64,
+        model: &str,
+        ms: u64,
+        times: usize,
+    ) {
+        for _ in 0..times {
+            recorder.record(
+                LatencyPhase::FirstByte,
+                credential_id,
+                model,
+                Duration::from_millis(ms),
+            );
+        }
+    }
+
+    #[test]
+    fn test_quantiles_interpolate_within_buckets() {
+        let recorder = LatencyRecorder::default();
+        // 50 个落在 (0, 50]，45 个落在 (100, 250]，5 个落在 (2500, 5000]
+        record_ms(&recorder, 1, "claude-sonnet-4.5", 30, 50);
+        record_ms(&recorder, 1, "claude-sonnet-4.5", 200, 45);
+        record_ms(&recorder, 1, "claude-sonnet-4.5", 3_000, 5);
+
+        let summary = recorder.summary();
+        let item = &summary.by_credential[0];
+        assert_eq!(item.credential_id, Some(1));
+        assert_eq!(item.phase, "first_byte");
+        assert_eq!(item.count, 100);
+        assert_eq!(item.p50_ms, Some(50.0));
+        assert_eq!(item.p95_ms, Some(250.0));
+    }
+
+    #[test]
+    fn test_summary_merges_series_per_credential_and_model() {
+        let recorder = LatencyRecorder::default();
+        record_ms(&recorder, 1, "model-a", 80, 10);
+        record_ms(&recorder, 1, "model-b", 80, 10);
+        record_ms(&recorder, 2, "model-a", 80, 20);
+
+        let summary = recorder.summary();
+        let counts: Vec<(Option<u64>, u64)> = summary
+            .by_credential
+            .iter()
+            .map(|s| (s.credential_id, s.count))
+            .collect();
+        assert_eq!(counts, vec![(Some(1), 20), (Some(2), 20)]);
+
+        let counts: Vec<(Option<String>, u64)> = summary
+            .by_model
+            .iter()
+            .map(|s| (s.model.clone(), s.count))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![
+                (Some("model-a".to_string()), 30),
+                (Some("model-b".to_string()), 10)
+            ]
+        );
+        // (50, 100] 桶内均匀插值：p50 = 50 + 50 * 0.5
+        assert_eq!(summary.by_model[0].p50_ms, Some(75.0));
+    }
+
+    #[test]
+    fn test_overflow_bucket_reports_largest_finite_bound() {
+        let recorder = LatencyRecorder::default();
+        record_ms(&recorder, 1, "m", 500_000, 3);
+        let summary = recorder.summary();
+        assert_eq!(summary.by_credential[0].p95_ms, Some(120_000.0));
+    }
+
+    #[test]
+    fn test_render_prometheus_uses_cumulative_buckets() {
+        let recorder = LatencyRecorder::default();
+        record_ms(&recorder, 7, "m", 30, 2);
+        record_ms(&recorder, 7, "m", 200, 1);
+
+        let text = recorder.render_prometheus();
+        let labels = "phase=\"first_byte\",credential=\"7\",model=\"m\"";
+        assert!(text.contains("# TYPE kiro_upstream_latency_ms histogram"));
+        assert!(text.contains(&format!(
+            "kiro_upstream_latency_ms_bucket{{{},le=\"50\"}} 2",
+            labels
+        )));
+        assert!(text.contains(&format!(
+            "kiro_upstream_latency_ms_bucket{{{},le=\"250\"}} 3",
+            labels
+        )));
+        assert!(text.contains(&format!(
+            "kiro_upstream_latency_ms_bucket{{{},le=\"+Inf\"}} 3",
+            labels
+        )));
+        assert!(text.contains(&format!("kiro_upstream_latency_ms_count{{{}}} 3", labels)));
+    }
+
+    /// 粗略校验一行是否为合法的 Prometheus 文本格式
+    fn is_exposition_line(line: &str) -> bool {
+        if let Some(rest) = line
+            .strip_prefix("# HELP ")
+            .or(line.strip_prefix("# TYPE "))
+        {
+            return rest
+                .split_once(' ')
+                .is_some_and(|(name, text)| !name.is_empty() && !text.is_empty());
+        }
+        let Some((series, value)) = line.rsplit_once(' ') else {
+            return false;
+        };
+        let name_end = series.find('{').unwrap_or(series.len());
+        let name = &series[..name_end];
+        let labels_ok = name_end == series.len()
+            || (series.ends_with('}')
+                && series[name_end + 1..series.len() - 1]
+                    .split(',')
+                    .all(|pair| {
+                        pair.split_once('=').is_some_and(|(k, v)| {
+                            !k.is_empty() && v.len() >= 2 && v.starts_with('"') && v.ends_with('"')
+                        })
+                    }));
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+            && labels_ok
+            && value.parse::<f64>().is_ok()
+    }
+
+    #[test]
+    fn test_registry_renders_valid_exposition_lines() {
+        let registry = Registry::default();
+        registry.requests.inc("claude-sonnet-4.5");
+        registry.requests.inc("claude-sonnet-4.5");
+        registry.upstream_errors.inc("429");
+        registry.cooldown_entries.inc("QuotaExhausted");
+        registry.active_credentials.set(3);
+        registry.tool_compressions.inc();
+
+        let text = registry.render();
+        for line in text.lines() {
+            assert!(is_exposition_line(line), "invalid line: {}", line);
+        }
+        assert!(text.contains("kiro_requests_total{model=\"claude-sonnet-4.5\"} 2"));
+        assert!(text.contains("kiro_upstream_errors_total{status=\"429\"} 1"));
+        assert!(text.contains("kiro_cooldown_entries_total{reason=\"QuotaExhausted\"} 1"));
+        assert!(text.contains("kiro_active_credentials 3"));
+        assert!(text.contains("kiro_tool_compressions_total 1"));
+
+        let recorder = LatencyRecorder::default();
+        record_ms(&recorder, 1, "m", 30, 1);
+        assert!(recorder.render_prometheus().lines().all(is_exposition_line));
+    }
+}
@@ -17,6 +17,138 @@ impl Default for TlsBackend {
     }
 }
 
+/// 日志输出格式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogFormat {
+    /// 人类可读的文本格式
+    #[default]
+    Text,
+    /// 每行一个 JSON 对象（日志内容为 `message` 字段，结构化字段平铺在顶层）
+    Json,
+}
+
+/// 上游返回空响应（无文本、无工具调用）时的处理策略
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmptyResponsePolicy {
+    /// 原样透传空响应
+    Passthrough,
+    /// 冷却当前凭据并重试一次，仍为空则透传
+    #[default]
+    RetryOnce,
+    /// 冷却当前凭据并在重试预算内持续重试，耗尽后透传
+    Retry,
+}
+
+impl EmptyResponsePolicy {
+    /// 允许因空响应发起的最大重试次数（仍受 Provider 总重试上限约束）
+    pub fn max_retries(&self) -> usize {
+        match self {
+            Self::Passthrough => 0,
+            Self::RetryOnce => 1,
+            Self::Retry => usize::MAX,
+        }
+    }
+}
+
+/// 工具数量超过 `maxTools` 时的处理方式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MaxToolsBehavior {
+    /// 返回 invalid_request_error
+    #[default]
+    Reject,
+    /// 截断为前 N 个工具（保留 tool_choice 强制指定的工具）
+    Truncate,
+}
+
+/// 可按模型声明为不支持的请求特性（见 `unsupportedModelFeatures`）
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModelFeature {
+    /// 扩展思考（`thinking.type` 为 enabled / adaptive）
+    Thinking,
+    /// 结构化输出（`output_config.format` 为 json_schema）
+    StructuredOutput,
+    /// 图片输入（image 内容块）
+    Images,
+    /// 工具调用（非空 `tools`）
+    Tools,
+}
+
+/// 对话以 assistant 轮次开头时的处理策略
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LeadingAssistantStrategy {
+    /// 在最前面插入一条最简的 user 消息
+    #[default]
+    Prepend,
+    /// 丢弃第一条 user 消息之前的所有 assistant 消息
+    Drop,
+    /// 返回 invalid_request_error
+    Reject,
+}
+
+/// 系统提示词超过 `systemPromptTokenLimits` 时的处理方式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SystemPromptLimitBehavior {
+    /// 返回 invalid_request_error
+    #[default]
+    Reject,
+    /// 保留开头部分，截断超出上限的内容
+    Truncate,
+}
+
+/// `is_error: true` 的 tool_result 的转换方式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToolErrorPolicy {
+    /// 原样传递错误内容（仍标记为 error 状态）
+    #[default]
+    Passthrough,
+    /// 以统一的"工具执行失败"说明包裹错误内容，引导模型妥善处理失败
+    Framed,
+}
+
+/// 冷却时长随触发次数的递增策略
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackoffStrategy {
+    /// 线性：第 n 次为 base * (1 + (n-1) * (倍率 - 1))
+    Linear,
+    /// 指数：第 n 次为 base * 倍率^(n-1)
+    #[default]
+    Exponential,
+    /// 斐波那契：第 n 次为 base * F(n)（1, 1, 2, 3, 5, ...，不使用倍率）
+    Fibonacci,
+}
+
+/// 工具输入 schema 校验方式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToolInputValidation {
+    /// 不校验
+    #[default]
+    Off,
+    /// 在不合法的 tool_use 块上附加 `validation_error` 标记
+    Annotate,
+    /// 把校验错误交还模型并重试一次（仅非流式，流式退化为 annotate）
+    Corrective,
+}
+
+/// 移入系统提示词的工具文档相对客户端系统提示词的位置
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToolDocumentationPlacement {
+    /// 追加在客户端系统提示词之后
+    #[default]
+    Append,
+    /// 插入在客户端系统提示词之前
+    Prepend,
+}
+
 /// KNA 应用配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -87,7 +219,15 @@ pub struct Config {
     #[serde(default)]
     pub admin_api_key: Option<String>,
 
-    /// 负载均衡模式（"priority" 或 "balanced"）
+    /// 额外的具名 Admin API 密钥（名称 → 密钥），与 `admin_api_key` 同时生效
+    #[serde(default)]
+    pub admin_api_keys: HashMap<String, String>,
+
+    /// `/metrics` 是否要求 Admin API 密钥（需配置 `admin_api_key`，否则不暴露该端点）
+    #[serde(default)]
+    pub metrics_require_admin_auth: bool,
+
+    /// 负载均衡模式（"priority"、"balanced" 或 "weighted-random"）
     #[serde(default = "default_load_balancing_mode")]
     pub load_balancing_mode: String,
 
@@ -108,6 +248,289 @@ pub struct Config {
     #[serde(default)]
     pub model_mapping: HashMap<String, String>,
 
+    /// 按模型的系统提示词 token 上限（可选）
+    /// key: Kiro 模型 ID 或客户端模型名，value: 上限（按 count_tokens 估算）
+    #[serde(default)]
+    pub system_prompt_token_limits: HashMap<String, u64>,
+
+    /// 系统提示词超过上限时的处理方式（"reject" / "truncate"，默认 "reject"）
+    #[serde(default)]
+    pub system_prompt_limit_behavior: SystemPromptLimitBehavior,
+
+    /// 模型名带 `-bot` 后缀时注入到系统提示词开头的内容（可选，未配置时不注入）
+    #[serde(default)]
+    pub bot_system_prompt: Option<String>,
+
+    /// 是否启用工具定义压缩（默认 false）
+    ///
+    /// 启用后工具定义超过 `tool_compression_target_bytes` 时依次简化 schema、截断描述（有损）
+    #[serde(default)]
+    pub tool_compression_enabled: bool,
+
+    /// 工具定义需要压缩时，是否先折叠描述中的空白（默认 false）
+    ///
+    /// 无损地去除缩进、多余空行等，在截断描述之前回收体积
+    #[serde(default)]
+    pub tool_description_collapse_whitespace: bool,
+
+    /// 工具描述截断的绝对下限（字符，默认 50，低于 50 时按 50 处理）
+    ///
+    /// 实际下限为 `可用预算 / 工具数`，且不低于该值
+    #[serde(default = "default_tool_description_min_length")]
+    pub tool_description_min_length: usize,
+
+    /// 工具定义压缩目标大小（字节，默认 20480）
+    ///
+    /// 序列化后的工具定义超过该值时才会压缩；部分模型后端对工具体积更敏感时可调低
+    #[serde(default = "default_tool_compression_target_bytes")]
+    pub tool_compression_target_bytes: usize,
+
+    /// 工具压缩优先级（工具名 -> 0-255，默认空，未配置的工具为 0）
+    ///
+    /// 截断描述时优先级越高保留越多；255 的工具仅在其他工具均已截断到下限后才会被截断
+    #[serde(default)]
+    pub tool_compression_priorities: HashMap<String, u8>,
+
+    /// 受保护的工具名（默认空）
+    ///
+    /// 这些工具的 input_schema 与描述不参与压缩，删减由其余工具承担；
+    /// 其余工具压缩后仍超出目标时才作为最后手段一并压缩
+    #[serde(default)]
+    pub tool_compression_protected_tools: Vec<String>,
+
+    /// 是否把超长工具描述移入系统提示词（默认 false，超长描述直接截断）
+    #[serde(default)]
+    pub elevate_long_tool_descriptions: bool,
+
+    /// 是否把多个工具逐字共享的描述段落移入系统提示词（默认 false）
+    ///
+    /// 与超长描述共用工具文档块的标题与位置配置
+    #[serde(default)]
+    pub dedup_shared_tool_descriptions: bool,
+
+    /// 移入系统提示词的工具文档块标题（默认 "# Tool Documentation"，为空时不加标题）
+    #[serde(default = "default_tool_documentation_heading")]
+    pub tool_documentation_heading: String,
+
+    /// 工具文档块的位置（"append" / "prepend"，默认 "append"）
+    #[serde(default)]
+    pub tool_documentation_placement: ToolDocumentationPlacement,
+
+    /// 对话以 assistant 轮次开头时的处理策略（默认 prepend）
+    #[serde(default)]
+    pub leading_assistant_strategy: LeadingAssistantStrategy,
+
+    /// `is_error: true` 的 tool_result 的转换方式（默认 passthrough）
+    #[serde(default)]
+    pub tool_error_policy: ToolErrorPolicy,
+
+    /// 按模型的回退链（可选，按模型显式开启）
+    /// key: Kiro 模型 ID（如 "claude-opus-4.5"），value: 依次尝试的备用 Kiro 模型 ID
+    /// 所请求模型在所有可用凭据上均处于模型级冷却时，改用链中下一个模型
+    #[serde(default)]
+    pub model_fallbacks: HashMap<String, Vec<String>>,
+
+    /// 返回前按工具的 input_schema 校验 tool_use 输入（"off" / "annotate" / "corrective"，默认 "off"）
+    #[serde(default)]
+    pub tool_input_validation: ToolInputValidation,
+
+    /// 单次请求允许的最大工具数量（可选，未配置时不限制）
+    #[serde(default)]
+    pub max_tools: Option<usize>,
+
+    /// 工具数量超过 `maxTools` 时的处理方式（"reject" / "truncate"，默认 "reject"）
+    #[serde(default)]
+    pub max_tools_behavior: MaxToolsBehavior,
+
+    /// 按模型声明不支持的请求特性（可选）
+    ///
+    /// key: Kiro 模型 ID 或客户端模型名；请求使用了列出的特性时，在发送上游前直接返回
+    /// invalid_request_error 并列出不支持的字段
+    #[serde(default)]
+    pub unsupported_model_features: HashMap<String, Vec<ModelFeature>>,
+
+    /// 是否对缓冲模式响应的内容块做规范化排序（默认 false）
+    ///
+    /// 启用后，`/cc/v1/messages` 流式响应在流结束时按 thinking → text → tool_use
+    /// 的顺序重排内容块，兼容对块顺序要求严格的客户端。
+    #[serde(default)]
+    pub normalize_content_block_order: bool,
+
+    /// 空响应处理策略（"passthrough" / "retry-once" / "retry"，默认 "retry-once"）
+    #[serde(default)]
+    pub empty_response_policy: EmptyResponsePolicy,
+
+    /// 单次请求的上游最大尝试次数（默认 3，另受凭据数量 × 3 约束）
+    #[serde(default = "default_upstream_max_retries")]
+    pub upstream_max_retries: usize,
+
+    /// 上游瞬态错误（408/429/5xx）时是否冷却当前凭据并立即换用下一个凭据（默认 false）
+    ///
+    /// 关闭时在同一凭据上退避重试；开启后仅在仍有其他可用凭据时冷却当前凭据，
+    /// 429 使用 `RateLimited` 原因，其余使用 `ServerError` 原因
+    #[serde(default)]
+    pub rotate_on_transient_error: bool,
+
+    /// 同一请求在同一凭据上反复被上游以 400 拒绝时，是否短暂冷却该凭据（默认 false）
+    ///
+    /// 按请求内容计数，同一请求被拒绝 3 次后以 `RequestRejected` 原因冷却（不递增、不计入冷却预算）
+    #[serde(default)]
+    pub request_rejected_cooldown: bool,
+
+    /// 日志输出格式（"text" / "json"，默认 "text"）
+    #[serde(default)]
+    pub log_format: LogFormat,
+
+    /// 优雅停机宽限期（秒，默认 30）
+    ///
+    /// 收到 SIGTERM / Ctrl-C 后拒绝新请求，进行中的请求（含流式响应）最多再等待该时长
+    #[serde(default = "default_shutdown_grace_period_secs")]
+    pub shutdown_grace_period_secs: u64,
+
+    /// 是否允许通过 `X-Kiro-Fingerprint-Seed` 请求头覆盖单次请求的指纹（默认 false）
+    ///
+    /// 仅用于测试/复现环境，生产环境请保持关闭。
+    #[serde(default)]
+    pub fingerprint_seed_header_enabled: bool,
+
+    /// 允许使用指纹种子请求头的客户端 IP 白名单（默认仅本机回环地址）
+    #[serde(default = "default_fingerprint_seed_allowed_ips")]
+    pub fingerprint_seed_allowed_ips: Vec<String>,
+
+    /// 累计冷却预算的统计窗口（秒，默认 3600）
+    #[serde(default = "default_cooldown_budget_window_secs")]
+    pub cooldown_budget_window_secs: u64,
+
+    /// 冷却时长抖动比例（默认 0 表示不抖动，如 0.15 表示 ±15%）
+    #[serde(default)]
+    pub cooldown_jitter: f64,
+
+    /// 冷却触发次数衰减周期（秒，默认 3600，0 表示不衰减）
+    #[serde(default = "default_cooldown_decay_interval_secs")]
+    pub cooldown_decay_interval_secs: u64,
+
+    /// 冷却时长递增策略（默认 exponential）
+    #[serde(default)]
+    pub cooldown_backoff_strategy: BackoffStrategy,
+
+    /// 冷却时长递增倍率（linear / exponential 使用，默认 1.5，小于 1 时按 1 处理）
+    #[serde(default = "default_cooldown_backoff_base")]
+    pub cooldown_backoff_base: f64,
+
+    /// 累计冷却预算：窗口内冷却时长占比超过该值时自动禁用凭据（默认 0 表示关闭，如 0.5）
+    #[serde(default)]
+    pub cooldown_budget_max_fraction: f64,
+
+    /// 是否启用慢速探测（默认 false）
+    ///
+    /// 启用后，后台任务会定期探测因认证失败等原因被自动禁用的凭据，
+    /// 探测成功即重新启用。
+    #[serde(default)]
+    pub slow_probe_enabled: bool,
+
+    /// 慢速探测间隔（秒，默认 21600 即 6 小时，最小 3600）
+    #[serde(default = "default_slow_probe_interval_secs")]
+    pub slow_probe_interval_secs: u64,
+
+    /// 是否在启动时预热凭据（默认 false）
+    ///
+    /// 启用后，服务开始监听之前会预先刷新凭据 Token 并生成设备指纹，
+    /// 避免冷启动后的首批请求承担刷新耗时。处于禁用状态的凭据会被跳过。
+    #[serde(default)]
+    pub warmup_enabled: bool,
+
+    /// 预热的凭据数量上限（按优先级取前 N 个，未配置时预热全部可用凭据）
+    #[serde(default)]
+    pub warmup_max_credentials: Option<usize>,
+
+    /// 预热并发数（默认 4，最小 1）
+    #[serde(default = "default_warmup_concurrency")]
+    pub warmup_concurrency: usize,
+
+    /// 预热总超时（秒，默认 30）；超时后放弃剩余凭据并照常启动
+    #[serde(default = "default_warmup_timeout_secs")]
+    pub warmup_timeout_secs: u64,
+
+    /// 是否总是在响应中附带 `X-Kiro-Timing-*` 耗时分解头（默认 false）
+    ///
+    /// 关闭时 `timing_header_allowed_ips` 内的客户端仍可通过 `X-Kiro-Timing: true` 请求头按需开启
+    #[serde(default)]
+    pub timing_headers_enabled: bool,
+
+    /// 允许通过 `X-Kiro-Timing` 请求头按需开启耗时头的客户端 IP 白名单（默认空，即请求头不生效）
+    #[serde(default)]
+    pub timing_header_allowed_ips: Vec<String>,
+
+    /// 客户端通过 `X-Kiro-Timeout-Ms` 请求头指定超时时的上限（毫秒，默认 720000）
+    #[serde(default = "default_max_request_timeout_ms")]
+    pub max_request_timeout_ms: u64,
+
+    /// 是否在响应中附带处理请求的凭据 ID（`X-Kiro-Credential-Id`，默认 false）
+    ///
+    /// 仅用于调试：会暴露内部凭据拓扑，生产环境请保持关闭
+    #[serde(default)]
+    pub credential_id_header_enabled: bool,
+
+    /// 是否把上游的代码引用（许可证归属）信息返回给客户端（默认 false）
+    ///
+    /// 非流式响应附加顶层 `code_references` 字段，流式响应附加在 message_delta 事件上
+    #[serde(default)]
+    pub emit_code_references: bool,
+
+    /// 是否把上游建议的后续提问返回给客户端（默认 false）
+    ///
+    /// 非流式响应附加顶层 `followup_prompts` 字段，流式响应附加在 message_delta 事件上
+    #[serde(default)]
+    pub emit_followup_prompts: bool,
+
+    /// 流式响应中周期性发送 usage 更新的间隔（输出 tokens，可选，未配置时仅在结束时发送）
+    ///
+    /// 每累计输出约 N 个 tokens 发送一次 `message_delta`（`stop_reason` 为 null），
+    /// usage 为累计值，最终的 message_delta 不会重复计数
+    #[serde(default)]
+    pub stream_usage_update_interval: Option<u32>,
+
+    /// 慢请求日志阈值（毫秒，可选，未配置时不记录）
+    ///
+    /// 请求总耗时超过阈值时输出一条 warn 日志，包含请求 ID、模型、凭据和耗时分解
+    #[serde(default)]
+    pub slow_request_threshold_ms: Option<u64>,
+
+    /// 重复请求合并窗口（毫秒，可选，未配置时不合并）
+    ///
+    /// 同一 API Key 在窗口内提交相同的非流式请求时，复用进行中/刚完成的结果
+    #[serde(default)]
+    pub request_dedup_window_ms: Option<u64>,
+
+    /// 是否启用金丝雀自检（默认 false）
+    ///
+    /// 启用后，后台任务会定期发送固定提示词，完整走一遍
+    /// 转换 → 调用 → 解析 → 组装流程，并校验输出非空且结构正确，
+    /// 用于尽早发现上游响应格式变化导致的静默故障。
+    #[serde(default)]
+    pub canary_enabled: bool,
+
+    /// 余额后台预热周期（秒，可选，未配置时不预热）
+    ///
+    /// 启用后后台任务定期刷新过期（超过 5 分钟）的凭据余额并持久化，
+    /// Admin 余额接口优先返回缓存（重启后仍可立即返回）
+    #[serde(default)]
+    pub balance_refresh_interval_secs: Option<u64>,
+
+    /// 余额预热时相邻两次上游查询的间隔（毫秒，默认 1000）
+    #[serde(default = "default_balance_refresh_spacing_ms")]
+    pub balance_refresh_spacing_ms: u64,
+
+    /// 金丝雀自检间隔（秒，默认 1800 即 30 分钟，最小 60）
+    #[serde(default = "default_canary_interval_secs")]
+    pub canary_interval_secs: u64,
+
+    /// 金丝雀自检固定使用的凭据 ID（建议为低优先级凭据）
+    ///
+    /// 未配置时按正常负载均衡选择凭据。
+    #[serde(default)]
+    pub canary_credential_id: Option<u64>,
+
     /// 端点特定的配置
     ///
     /// 键为端点名（如 "ide" / "cli"），值为该端点自由定义的参数对象。
@@ -157,10 +580,70 @@ fn default_load_balancing_mode() -> String {
     "priority".to_string()
 }
 
+fn default_tool_description_min_length() -> usize {
+    50
+}
+
+fn default_tool_compression_target_bytes() -> usize {
+    20 * 1024
+}
+
 fn default_extract_thinking() -> bool {
     true
 }
 
+fn default_upstream_max_retries() -> usize {
+    3
+}
+
+fn default_shutdown_grace_period_secs() -> u64 {
+    30
+}
+
+fn default_fingerprint_seed_allowed_ips() -> Vec<String> {
+    vec!["127.0.0.1".to_string(), "::1".to_string()]
+}
+
+fn default_cooldown_decay_interval_secs() -> u64 {
+    3600
+}
+
+fn default_cooldown_backoff_base() -> f64 {
+    1.5
+}
+
+fn default_cooldown_budget_window_secs() -> u64 {
+    60 * 60
+}
+
+fn default_tool_documentation_heading() -> String {
+    "# Tool Documentation".to_string()
+}
+
+fn default_max_request_timeout_ms() -> u64 {
+    720_000
+}
+
+fn default_warmup_concurrency() -> usize {
+    4
+}
+
+fn default_warmup_timeout_secs() -> u64 {
+    30
+}
+
+fn default_slow_probe_interval_secs() -> u64 {
+    6 * 60 * 60
+}
+
+fn default_balance_refresh_spacing_ms() -> u64 {
+    1000
+}
+
+fn default_canary_interval_secs() -> u64 {
+    30 * 60
+}
+
 fn default_endpoint() -> String {
     crate::kiro::endpoint::ide::IDE_ENDPOINT_NAME.to_string()
 }
@@ -186,10 +669,67 @@ impl Default for Config {
             proxy_username: None,
             proxy_password: None,
             admin_api_key: None,
+            admin_api_keys: HashMap::new(),
+            metrics_require_admin_auth: false,
             load_balancing_mode: default_load_balancing_mode(),
             extract_thinking: default_extract_thinking(),
             default_endpoint: default_endpoint(),
             model_mapping: HashMap::new(),
+            bot_system_prompt: None,
+            system_prompt_token_limits: HashMap::new(),
+            system_prompt_limit_behavior: SystemPromptLimitBehavior::default(),
+            tool_compression_enabled: false,
+            tool_description_collapse_whitespace: false,
+            tool_description_min_length: default_tool_description_min_length(),
+            tool_compression_target_bytes: default_tool_compression_target_bytes(),
+            tool_compression_priorities: HashMap::new(),
+            tool_compression_protected_tools: Vec::new(),
+            elevate_long_tool_descriptions: false,
+            dedup_shared_tool_descriptions: false,
+            tool_documentation_heading: default_tool_documentation_heading(),
+            tool_documentation_placement: ToolDocumentationPlacement::default(),
+            leading_assistant_strategy: LeadingAssistantStrategy::default(),
+            tool_error_policy: ToolErrorPolicy::default(),
+            model_fallbacks: HashMap::new(),
+            tool_input_validation: ToolInputValidation::default(),
+            max_tools: None,
+            max_tools_behavior: MaxToolsBehavior::default(),
+            unsupported_model_features: HashMap::new(),
+            normalize_content_block_order: false,
+            empty_response_policy: EmptyResponsePolicy::default(),
+            upstream_max_retries: default_upstream_max_retries(),
+            rotate_on_transient_error: false,
+            request_rejected_cooldown: false,
+            log_format: LogFormat::default(),
+            shutdown_grace_period_secs: default_shutdown_grace_period_secs(),
+            fingerprint_seed_header_enabled: false,
+            fingerprint_seed_allowed_ips: default_fingerprint_seed_allowed_ips(),
+            cooldown_budget_window_secs: default_cooldown_budget_window_secs(),
+            cooldown_jitter: 0.0,
+            cooldown_decay_interval_secs: default_cooldown_decay_interval_secs(),
+            cooldown_backoff_strategy: BackoffStrategy::default(),
+            cooldown_backoff_base: default_cooldown_backoff_base(),
+            cooldown_budget_max_fraction: 0.0,
+            slow_probe_enabled: false,
+            slow_probe_interval_secs: default_slow_probe_interval_secs(),
+            warmup_enabled: false,
+            warmup_max_credentials: None,
+            warmup_concurrency: default_warmup_concurrency(),
+            warmup_timeout_secs: default_warmup_timeout_secs(),
+            timing_headers_enabled: false,
+            timing_header_allowed_ips: Vec::new(),
+            max_request_timeout_ms: default_max_request_timeout_ms(),
+            credential_id_header_enabled: false,
+            emit_code_references: false,
+            emit_followup_prompts: false,
+            stream_usage_update_interval: None,
+            slow_request_threshold_ms: None,
+            request_dedup_window_ms: None,
+            canary_enabled: false,
+            balance_refresh_interval_secs: None,
+            balance_refresh_spacing_ms: default_balance_refresh_spacing_ms(),
+            canary_interval_secs: default_canary_interval_secs(),
+            canary_credential_id: None,
             endpoints: HashMap::new(),
             config_path: None,
         }
@@ -0,0 +1,227 @@
+//! 优雅停机
+//!
+//! 收到 SIGTERM / Ctrl-C 后进入排空状态：不再接受新请求（返回 503），
+//! 进行中的请求（含流式响应，直到响应体传输完毕）在宽限期内继续完成。
+//! 宽限期结束仍未完成的请求随进程退出被中断。
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use axum::{
+    Json,
+    body::Body,
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use tokio::sync::Notify;
+
+/// 停机协调器（可廉价克隆，所有克隆共享同一状态）
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// 是否已进入排空状态
+    draining: AtomicBool,
+    /// 进行中的请求数
+    active: AtomicUsize,
+    /// 进入排空状态时通知
+    started: Notify,
+    /// 进行中的请求数归零时通知
+    idle: Notify,
+}
+
+/// 进行中请求的占位，drop 时计数减一
+pub struct InFlightGuard {
+    inner: Arc<Inner>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.inner.active.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.inner.idle.notify_waiters();
+        }
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 是否已进入排空状态
+    pub fn is_draining(&self) -> bool {
+        self.inner.draining.load(Ordering::Acquire)
+    }
+
+    /// 进行中的请求数
+    pub fn active(&self) -> usize {
+        self.inner.active.load(Ordering::Acquire)
+    }
+
+    /// 登记一个新请求；已进入排空状态时返回 None
+    pub fn track(&self) -> Option<InFlightGuard> {
+        self.inner.active.fetch_add(1, Ordering::AcqRel);
+        let guard = InFlightGuard {
+            inner: self.inner.clone(),
+        };
+        // 先计数再检查，避免与 begin() 竞争时漏计刚进入的请求
+        if self.is_draining() {
+            return None;
+        }
+        Some(guard)
+    }
+
+    /// 进入排空状态（可重复调用）
+    pub fn begin(&self) {
+        if !self.inner.draining.swap(true, Ordering::AcqRel) {
+            self.inner.started.notify_waiters();
+        }
+    }
+
+    /// 等待进入排空状态
+    pub async fn started(&self) {
+        let notified = self.inner.started.notified();
+        if self.is_draining() {
+            return;
+        }
+        notified.await;
+    }
+
+    /// 等待进行中的请求全部完成，最多等待 `grace`；返回是否已排空
+    pub async fn wait_idle(&self, grace: Duration) -> bool {
+        tokio::time::timeout(grace, async {
+            loop {
+                let notified = self.inner.idle.notified();
+                if self.active() == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+}
+
+/// 等待 Ctrl-C 或 SIGTERM
+pub async fn wait_for_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("监听 Ctrl-C 失败: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("监听 SIGTERM 失败: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// 停机中间件：排空状态下拒绝新请求，并在响应体传输完毕前持有进行中请求的占位
+pub async fn shutdown_middleware(
+    State(shutdown): State<Shutdown>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(guard) = shutdown.track() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "type": "error",
+                "error": {
+                    "type": "overloaded_error",
+                    "message": "Server is shutting down"
+                }
+            })),
+        )
+            .into_response();
+    };
+
+    let (parts, body) = next.run(request).await.into_parts();
+    let body = body.into_data_stream().map(move |chunk| {
+        let _ = &guard;
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn spawn_server(shutdown: Shutdown) -> String {
+        let app = axum::Router::new()
+            .route(
+                "/slow",
+                axum::routing::get(|| async {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    "done"
+                }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                shutdown,
+                shutdown_middleware,
+            ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/slow", addr)
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_request_completes_while_new_one_is_rejected() {
+        let shutdown = Shutdown::new();
+        let url = spawn_server(shutdown.clone()).await;
+
+        let first = tokio::spawn(reqwest::get(url.clone()));
+        // 等待首个请求进入处理
+        while shutdown.active() == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        shutdown.begin();
+        shutdown.started().await;
+
+        let rejected = reqwest::get(&url).await.unwrap();
+        assert_eq!(rejected.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let response = first.await.unwrap().unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.text().await.unwrap(), "done");
+        assert!(shutdown.wait_idle(Duration::from_secs(1)).await);
+    }
+
+    #[tokio::test]
+    async fn test_wait_idle_times_out_with_active_request() {
+        let shutdown = Shutdown::new();
+        let guard = shutdown.track().unwrap();
+        shutdown.begin();
+        assert!(shutdown.track().is_none());
+        assert!(!shutdown.wait_idle(Duration::from_millis(20)).await);
+        drop(guard);
+        assert!(shutdown.wait_idle(Duration::from_millis(20)).await);
+    }
+}